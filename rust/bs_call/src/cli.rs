@@ -9,7 +9,8 @@ use utils::log_level::init_log;
 use super::process;
 use crate::config::{BsCallConfig, BsCallFiles, ConfVar};
 
-pub fn process_cli() -> io::Result<(BsCallConfig, BsCallFiles)> {
+// Returns None if there is no calling to be performed
+pub fn process_cli() -> io::Result<Option<(BsCallConfig, BsCallFiles)>> {
 	let yaml = load_yaml!("cli/cli.yml");
     let app = App::from_yaml(yaml).version(crate_version!());
 	let mut vbuf: Vec<u8> = Vec::new();
@@ -19,6 +20,10 @@ pub fn process_cli() -> io::Result<(BsCallConfig, BsCallFiles)> {
 	// Setup logging
 	let m = app.get_matches();	
 	let _ = init_log(&m);
+	if let Some(v) = m.values_of("recalibrate_from") {
+		options::handle_recalibrate_from(&m, &v.collect::<Vec<_>>())?;
+		return Ok(None)
+	}
 	// Process arguments
 	let (mut bs_cfg, mut bs_files) = options::handle_options(&m)?;
 
@@ -30,5 +35,5 @@ pub fn process_cli() -> io::Result<(BsCallConfig, BsCallFiles)> {
 	// Write Output header
 	process::write_vcf_header(&mut bs_cfg, &mut bs_files, &source)?;
 	bs_cfg.conf_hash.set(&"bs_call_source", ConfVar::String(Some(source)));
-	Ok((bs_cfg, bs_files))	
+	Ok(Some((bs_cfg, bs_files)))	
 }
\ No newline at end of file
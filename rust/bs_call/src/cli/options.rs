@@ -5,11 +5,11 @@ use std::collections::HashMap;
 use crate::config::*;
 use crate::{process, reference, htslib, defs};
 use super::cli_utils;
+use crate::process::pileup::GC_BIN_SIZE;
 
 use clap::ArgMatches;
-use crate::dbsnp;
 
-pub const OPTS: [(&str, ConfVar);21] = [
+pub const OPTS: [(&str, ConfVar);28] = [
 	("haploid", ConfVar::Bool(false)),
 	("keep_duplicates", ConfVar::Bool(false)),
 	("keep_supplementary", ConfVar::Bool(false)),
@@ -19,18 +19,25 @@ pub const OPTS: [(&str, ConfVar);21] = [
 	("benchmark_mode", ConfVar::Bool(false)),
 	("all_positions", ConfVar::Bool(false)),
 	("filter_contigs", ConfVar::Bool(false)),
+	("mito_mode", ConfVar::Bool(false)),
+	("recalibrate", ConfVar::Bool(false)),
 	("mapq_threshold", ConfVar::Int(20)),
 	("bq_threshold", ConfVar::Int(13)),
 	("max_template_length", ConfVar::Int(1000)),
+	("cnv_bin_size", ConfVar::Int(100000)),
+	("mito_mapq_threshold", ConfVar::Int(30)),
 	("reference_bias", ConfVar::Float(2.0)),
+	("min_heteroplasmy", ConfVar::Float(0.02)),
 	("sample", ConfVar::String(None)),
 	("output", ConfVar::String(None)),
 	("reference", ConfVar::String(None)),
 	("contig_bed", ConfVar::String(None)),
+	("mito_contig", ConfVar::String(None)),
 	("contig_exclude", ConfVar::String(None)),
 	("contig_include", ConfVar::String(None)),
 	("dbsnp", ConfVar::String(None)),	
 	("report_file", ConfVar::String(None)),	
+	("recal_sites", ConfVar::String(None)),
 ];
 
 fn distribute_threads(conf_hash: &mut HashMap<&'static str, ConfVar>, in_file: &mut htslib::SamFile, out_file: &mut htslib::VcfFile) -> io::Result<()> {
@@ -73,6 +80,35 @@ pub fn get_trim_values(m: &ArgMatches, name: &str) -> io::Result<(usize, usize)>
 	}
 }
 
+// Output type - if not set we try to guess from output file name (if supplied), otherwise use VCF format
+fn get_output_type(m: &ArgMatches, output: Option<&str>) -> io::Result<OType> {
+	Ok(if let Some(x) = m.value_of("output_type") {
+		let ot = <OType>::from_str(x).map_err(|e| new_err(format!("Couldn't parse output type argument '{}' for option output_type: {}", x, e)))?;
+		if !ot.eq_u32(htslib::FT_VCF) && output.is_none() && unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 } {
+			warn!("Will not output binary and/or compressed data to terminal");
+			OType::new(htslib::FT_VCF) 
+		} else { ot }
+	} else if let Some(x) = output { 
+		if x.ends_with(".bcf") || x.ends_with(".bcf.gz") { OType::new(htslib::FT_BCF_GZ) }
+		else if x.ends_with(".vcf.gz") { OType::new(htslib::FT_VCF_GZ) }
+		else { OType::new(htslib::FT_VCF) }
+	} else { OType::new(htslib::FT_VCF) })
+}
+
+// Recalibrate an existing VCF/BCF file using the variant sites from one or more previous runs (--recalibrate-from)
+pub fn handle_recalibrate_from(m: &ArgMatches, site_files: &[&str]) -> io::Result<()> {
+	let output = m.value_of("output");
+	let otype = get_output_type(m, output)?;
+	let threads = if let ConfVar::Int(x) = cli_utils::get_option(m, "threads", ConfVar::Int(num_cpus::get()))? { x } else { 0 };
+	let recal_stats = process::vcf::recalibrate_from_sites(site_files, m.value_of("input").unwrap_or("-"), output.unwrap_or("-"), otype, threads)?;
+	// The report is written (as null) even if the model could not be trained
+	if let Some(report_file) = m.value_of("report_file") {
+		let wrt = utils::compress::open_bufwriter(report_file)?;
+		serde_json::to_writer_pretty(wrt, &recal_stats).map_err(|e| new_err(format!("Error writing recalibration report {}: {}", report_file, e)))?;
+	}
+	Ok(())
+}
+
 pub fn handle_options(m: &ArgMatches) -> io::Result<(BsCallConfig, BsCallFiles)> {
 	
 	let mut conf_hash: HashMap<&'static str, ConfVar> = HashMap::new();
@@ -85,6 +121,16 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(BsCallConfig, BsCallFiles)>
 	
 	// And now the odd options
 	
+	// CNV bins are made from the GC content bins, so the bin size must be a multiple of the GC bin size
+	if let Some(ConfVar::Int(x)) = conf_hash.get(&"cnv_bin_size") {
+		if x % (GC_BIN_SIZE as usize) != 0 { return Err(new_err(format!("CNV bin size ({}) must be a multiple of {}", x, GC_BIN_SIZE))) }
+	}
+	
+	// The heteroplasmy fraction is the minor allele fraction, so can not exceed 0.5
+	if let Some(ConfVar::Float(x)) = conf_hash.get(&"min_heteroplasmy") {
+		if !(0.0..=0.5).contains(x) { return Err(new_err(format!("Minimum heteroplasmy fraction ({}) must be between 0 and 0.5", x))) }
+	}
+	
 	// Conversion rates
 	let (under, over) = if let Some(v) = cli_utils::get_fvec(m, "conversion", 1.0e-8, 1.0 - 1.0e-8)? { (v[0], v[1]) }
 	else { (0.01, 0.05) };
@@ -99,20 +145,10 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(BsCallConfig, BsCallFiles)>
 	conf_hash.insert(&"right_trim_read_1", ConfVar::Int(x1));
 	conf_hash.insert(&"right_trim_read_2", ConfVar::Int(x2));
 	
-	// Output type - if not set we try to guess from output file name (if supplied), otherwise use VCF format
+	// Output type
 	let output = if let ConfVar::String(x) = conf_hash.get(&"output").unwrap() { x.as_deref().map(|x| x.to_owned() ) } else { panic!("String variable output not set") };
 	let ocopy = output.clone();
-	let otype = if let Some(x) = m.value_of("output_type") {
-		let ot = <OType>::from_str(x).map_err(|e| new_err(format!("Couldn't parse output type argument '{}' for option output_type: {}", x, e)))?;
-		if !ot.eq_u32(htslib::FT_VCF) && output.is_none() && unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 } {
-			warn!("Will not output binary and/or compressed data to terminal");
-			OType::new(htslib::FT_VCF) 
-		} else { ot }
-	} else if let Some(x) = output { 
-		if x.ends_with(".bcf") || x.ends_with(".bcf.gz") { OType::new(htslib::FT_BCF_GZ) }
-		else if x.ends_with(".vcf.gz") { OType::new(htslib::FT_VCF_GZ) }
-		else { OType::new(htslib::FT_VCF) }
-	} else { OType::new(htslib::FT_VCF) };
+	let otype = get_output_type(m, output.as_deref())?;
 	conf_hash.insert(&"output_type", ConfVar::OType(otype));
 	
 	// Input file
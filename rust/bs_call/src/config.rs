@@ -6,7 +6,6 @@ use std::io::{Error, ErrorKind};
 use crate::htslib;
 use crate::defs::{CtgRegion, CtgInfo};
 
-use crate::dbsnp;
 
 pub fn new_err(s: String) -> io::Error {
 	Error::new(ErrorKind::Other, s)	
@@ -117,6 +116,8 @@ impl BsCallConfig {
 	pub fn ctg_vcf_id(&self, idx: usize) -> Option<usize> { self.contigs[idx].vcf_id() }
 	pub fn ctg_ref_id(&self, idx: usize) -> Option<usize> { self.contigs[idx].ref_id() }
 	pub fn ctg_name(&self, idx: usize) -> &str { self.contigs[idx].name() }
+	pub fn ctg_is_mito(&self, idx: usize) -> bool { self.contigs[idx].is_mito() }
+	pub fn mito_tid(&self) -> Option<usize> { self.contigs.iter().position(|c| c.is_mito()) }
 }
 
 pub struct BsCallFiles {
@@ -12,11 +12,16 @@ pub struct CtgInfo {
 	vcf_id: Option<usize>,
 	name: String,
 	in_header: bool,
+	mito: bool,
 } 
 
+// Names tried for the mitochondrial contig if one is not given explicitly
+const MITO_NAMES: [&str; 4] = ["chrM", "MT", "chrMT", "M"];
+
 impl CtgInfo {
-	fn new<S: AsRef<str>>(name: S) -> Self {CtgInfo { ref_id: None, vcf_id: None, in_header: false, name: name.as_ref().to_owned()}}
+	fn new<S: AsRef<str>>(name: S) -> Self {CtgInfo { ref_id: None, vcf_id: None, in_header: false, mito: false, name: name.as_ref().to_owned()}}
 	pub fn in_header(&self) -> bool { self.in_header }
+	pub fn is_mito(&self) -> bool { self.mito }
 	pub fn vcf_id(&self) -> Option<usize> { self.vcf_id }
 	pub fn ref_id(&self) -> Option<usize> { self.ref_id }
 	pub fn name(&self) -> &str { &self.name }
@@ -154,6 +159,21 @@ fn setup_regions_from_sam_header(ctgs: &mut Vec<CtgInfo>, sam_file: &SamFile, re
 	Ok(ctg_regions)
 }
 
+// Mark the mitochondrial contig if mitochondrial mode is selected.  The contig is either given explicitly or is the first of MITO_NAMES found in the SAM header
+fn setup_mito_contig(chash: &ConfHash, ctgs: &mut [CtgInfo], sam_file: &SamFile) -> io::Result<()> {
+	let mito_contig = chash.get_str("mito_contig");
+	if chash.get_bool("mito_mode") || mito_contig.is_some() {
+		let tid = if let Some(name) = mito_contig {
+			Some(sam_file.name2tid(name).ok_or_else(|| new_err(format!("Mitochondrial contig {} not present in SAM header", name)))?)
+		} else { MITO_NAMES.iter().find_map(|s| sam_file.name2tid(s)) };
+		if let Some(i) = tid {
+			debug!("Contig {} will be processed in mitochondrial mode", sam_file.tid2name(i));
+			ctgs[i].mito = true;
+		} else { warn!("Mitochondrial mode selected but no mitochondrial contig found in SAM header") }
+	}
+	Ok(())
+}
+
 pub fn setup_contigs(chash: &ConfHash, sam_file: &SamFile, ref_idx: &Faidx) -> io::Result<(Vec<CtgInfo>, Vec<CtgRegion>)> {
 	let omit_ctgs = read_ctg_list(chash, sam_file, "exclude")?;
 	let include_ctgs = read_ctg_list(chash, sam_file, "include")?;
@@ -165,6 +185,7 @@ pub fn setup_contigs(chash: &ConfHash, sam_file: &SamFile, ref_idx: &Faidx) -> i
 	} else {
 		setup_regions_from_sam_header(&mut ctgs, sam_file, ref_idx, &omit_ctgs, &include_ctgs)?
 	};	
+	setup_mito_contig(chash, &mut ctgs, sam_file)?;
 	Ok((ctgs, ctg_regions))
 }
 
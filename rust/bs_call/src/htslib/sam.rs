@@ -70,68 +70,3 @@ impl SamFile {
 //		self.inner.itr = Some(self.index.sam_itr_regarray(&mut self.hdr, &reg_str)?);
 	}
 }
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub enum BSStrand {Unknown, StrandC2T, StrandG2A, Unconverted}
-
-impl BSStrand {
-	pub fn get_num(&self) -> u16 {
-		match self {
-			BSStrand::Unknown => 0,
-			BSStrand::StrandC2T => 1,
-			BSStrand::StrandG2A => 2,
-			BSStrand::Unconverted => 3,
-		}
-	}
-}
-
-
-#[derive(PartialEq)]
-pub enum Aligner { Unknown, GEM, Bowtie, Novoalign, BSMap, BWAMeth }
-
-pub fn get_bs_strand(b: &BamRec) -> (BSStrand, Option<&[u8]>) {
-	let mut sa_tag = None;
-	let mut strand = BSStrand::Unconverted;
-	if let Some(itr) = b.get_aux_iter() {
-		for tag in itr {
-			let aligner = {
-				if tag[0] == b'Z' {
-					if tag[1] == b'B' { Aligner::Novoalign }
-					else if tag[1] == b'S' { Aligner::BSMap }
-					else {Aligner::Unknown}
-				} else if tag[0] == b'X' {
-					if tag[1] == b'G' { Aligner::Bowtie }
-					else if tag[1] == b'B' { Aligner::GEM }
-					else { Aligner::Unknown }
-				} else if tag[0] == b'Y' && tag[1] == b'D' { Aligner::BWAMeth }
-				else { Aligner::Unknown } 
-			};
-			if aligner != Aligner::Unknown {
-				match tag[2] {
-					b'A' if aligner == Aligner::GEM => {
-						if tag[3] == b'C' { strand = BSStrand::StrandC2T }
-						else if tag[3] == b'G' { strand = BSStrand::StrandG2A }
-					},
-					b'Z' => match aligner {
-						Aligner::Bowtie | Aligner::Novoalign => {
-							if tag[3] == b'C' { strand = BSStrand::StrandC2T }
-							else if tag[3] == b'G' { strand = BSStrand::StrandG2A }						
-						},
-						Aligner::BSMap => {
-							if tag[3] == b'+' { strand = BSStrand::StrandC2T }
-							else if tag[3] == b'-' { strand = BSStrand::StrandG2A }													
-						}
-						Aligner::BWAMeth => {
-							if tag[3] == b'f' { strand = BSStrand::StrandC2T }
-							else if tag[3] == b'r' { strand = BSStrand::StrandG2A }													
-						}
-						_ => (),
-					},
-					_ => (),
-				}
-			} else if tag[0] == b'S' && tag[1] == b'A' && tag[2] == b'Z' { sa_tag = Some(&tag[3..]) }
-			if strand != BSStrand::Unconverted && sa_tag.is_some() { break }
-		}
-	}
-	(strand, sa_tag)
-}
@@ -12,7 +12,6 @@ pub mod reference;
 pub mod htslib;
 pub mod process;
 pub mod stats;
-pub mod dbsnp;
 pub mod rusage;
 
 use rusage::*;
@@ -23,7 +22,8 @@ fn main() -> Result<(), &'static str> {
 			error!("bs_call initialization failed with error: {}", e);
 			return Err("Failed");
 		},
-		Ok(x) => x,
+		Ok(Some(x)) => x,
+		Ok(None) => return Ok(()),
 	};
 
 	if let Err(e) = process::process(Arc::new(bs_cfg), bs_files) {
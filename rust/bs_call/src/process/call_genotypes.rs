@@ -8,12 +8,14 @@ use super::pileup::{Pileup, GC_BIN_SIZE};
 use crate::stats::StatJob;
 use super::vcf::{write_vcf_entry, WriteVcfJob};
 use crate::htslib::hts_err;
-use crate::dbsnp::DBSnpContig;
+use dbsnp::DBSnpContig;
 
 mod model;
+mod mito;
 pub mod fisher;
 
 use model::Model;
+use mito::MitoModel;
 use fisher::FisherTest;
 use crate::rusage::*;
 
@@ -33,6 +35,7 @@ pub struct GenotypeCall {
 	pub max_gt: u8,
 	pub ref_base: u8,
 	pub gc: u8,
+	pub hf: Option<[f32; 4]>, // Estimated base fractions (mitochondrial contig only)
 }
 
 pub struct CallBlock {
@@ -44,7 +47,7 @@ pub struct CallBlock {
 
 const BLOCK_SIZE: usize = 4096;
 
-fn call_from_pileup(mut pileup: Pileup, model: &Model, fisher: &FisherTest, write_tx: &mpsc::SyncSender<WriteVcfJob>) -> io::Result<()> {
+fn call_from_pileup(mut pileup: Pileup, model: &Model, mito_model: Option<&MitoModel>, fisher: &FisherTest, write_tx: &mpsc::SyncSender<WriteVcfJob>) -> io::Result<()> {
 	
 	let dbsnp_contig = pileup.dbsnp_contig.take();
 	let call_block = CallBlock{dbsnp_contig, start: pileup.start, sam_tid: pileup.sam_tid, prec_ref_bases: pileup.get_prec_2_bases()};
@@ -65,10 +68,16 @@ fn call_from_pileup(mut pileup: Pileup, model: &Model, fisher: &FisherTest, writ
 			});
 			let aq = cmp::min((total_qual / (total_flt as f32)).round() as usize, 255) as u8;
 			let mq = cmp::min((pp.mapq2 / (total_flt as f32)).sqrt().round() as usize, 255) as u8;
-			let (mx, gt_ll) = model.calc_gt_prob(&counts, &qual, *ref_base, None);
+			let (mx, gt_ll, hf) = if let Some(m) = mito_model {
+				let (mx, gt_ll, fract) = m.calc_gt_prob(&counts, &qual, *ref_base);
+				(mx, gt_ll, Some(fract))
+			} else { 
+				let (mx, gt_ll) = model.calc_gt_prob(&counts, &qual, *ref_base, None);
+				(mx, gt_ll, None)
+			};
 			let fisher_strand = fisher.calc_fs_stat(mx, &pp.counts);
 			let gc = pileup.gc_bins[(ix + pileup.start) / (GC_BIN_SIZE as usize) - gc_bin_start];
-			CallEntry::Call(GenotypeCall{counts, gt_ll, fisher_strand, qual, mq, aq, max_gt: mx as u8, gc, ref_base: *ref_base})
+			CallEntry::Call(GenotypeCall{counts, gt_ll, fisher_strand, qual, mq, aq, max_gt: mx as u8, gc, ref_base: *ref_base, hf})
 		} else { CallEntry::Skip(*ref_base) };
 		call_vec.push(call);
 		if call_vec.len() == BLOCK_SIZE {
@@ -95,16 +104,20 @@ pub fn call_genotypes(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<Option<Pileu
 	let ref_bias = bs_cfg.conf_hash.get_float("reference_bias");
 	let haploid = bs_cfg.conf_hash.get_bool("haploid");
 	let conversion = (bs_cfg.conf_hash.get_float("under_conversion"), bs_cfg.conf_hash.get_float("over_conversion"));
+	let mito_tid = bs_cfg.mito_tid();
+	let min_hf = bs_cfg.conf_hash.get_float("min_heteroplasmy");
 	let (write_tx, write_rx) = mpsc::sync_channel(32);
 	let write_handle = thread::spawn(move || { write_vcf_entry(Arc::clone(&bs_cfg), write_rx, bs_files, stat_tx) });
 	let model = Model::new(conversion, ref_bias, haploid, true);
+	let mito_model = mito_tid.map(|_| MitoModel::new(conversion, ref_bias, min_hf, true));
 	let fisher = FisherTest::new();
 	loop {
 		match rx.recv() {
 			Ok(None) => break,
 			Ok(Some(pileup)) => {
 				debug!("Received new pileup: {}:{}-{}", pileup.sam_tid, pileup.start, pileup.start + pileup.data.len() - 1);
-				if let Err(e) = call_from_pileup(pileup, &model, &fisher, &write_tx) {
+				let mm = if Some(pileup.sam_tid) == mito_tid { mito_model.as_ref() } else { None };
+				if let Err(e) = call_from_pileup(pileup, &model, mm, &fisher, &write_tx) {
 					error!("call_from_pileup failed with error: {}", e);
 					break;
 				}
@@ -0,0 +1,102 @@
+use std::f64::consts::LN_10;
+use libc::c_int;
+
+use super::model::Model;
+
+// Bases (A, C, G, T as bits 0-3) compatible with each of the 8 bisulfite aware count classes
+// (non-informative for methylation followed by informative for methylation)
+const CLASS_BASES: [u8; 8] = [1, 2, 4, 8, 5, 2, 4, 10];
+
+// Base for the 4 homozygous genotypes (AA, CC, GG, TT)
+const HOM_BASE: [(usize, usize); 4] = [(0, 0), (4, 1), (7, 2), (9, 3)];
+
+// Genotype index for the unordered base pair a, b
+fn gt_index(a: usize, b: usize) -> usize {
+	let (a, b) = if a <= b { (a, b) } else { (b, a) };
+	(a + 1) * (8 - a) / 2 + b - 4
+}
+
+/*********************************************************************************************
+ * Mixture model for mitochondrial calls
+ *
+ * The major allele (a) is called using the haploid model.  We then look for a second allele (b)
+ * present at a fraction f (the heteroplasmy fraction) using the counts that discriminate between
+ * a and b after allowing for bisulfite conversion.  For C/T and A/G the counts from the
+ * converted strand can not discriminate between the alleles, so only counts from the other strand
+ * are used.  The likelihood for f is binomial with an error rate taken from the average base
+ * quality, and the mixture genotype is reported if f >= min_hf and the minor allele is seen at
+ * least twice.  The likelihood ratio between the mixture and the homozygous major allele is used
+ * to calculate the posterior probabilities of the two genotypes.
+ *
+ **********************************************************************************************/
+pub struct MitoModel {
+	model: Model,
+	min_hf: f64,
+	log10: bool,
+}
+
+impl MitoModel {
+	pub fn new(conv: (f64, f64), ref_bias: f64, min_hf: f64, log10: bool) -> Self {
+		Self{model: Model::new(conv, ref_bias, true, log10), min_hf, log10}
+	}
+
+	// Returns discriminating counts and summed base qualities for alleles a and b
+	fn pair_counts(counts: &[c_int; 8], qual: &[c_int; 8], a: usize, b: usize) -> ((f64, f64), f64) {
+		let (ma, mb) = (1u8 << a, 1u8 << b);
+		let skip = match ma | mb {
+			10 => 5, // C/T - skip methylated C on the C2T strand
+			5 => 6, // A/G - skip methylated G on the G2A strand
+			_ => 8,
+		};
+		let mut n = (0.0, 0.0);
+		let mut q = 0.0;
+		for (c, (ct, ql)) in counts.iter().zip(qual.iter()).enumerate().filter(|(c, (ct, _))| *c != skip && **ct > 0) {
+			let x = *ct as f64;
+			match (CLASS_BASES[c] & ma != 0, CLASS_BASES[c] & mb != 0) {
+				(true, false) => n.0 += x,
+				(false, true) => n.1 += x,
+				_ => continue,
+			}
+			q += x * (*ql as f64);
+		}
+		(n, q)
+	}
+
+	// Returns the called genotype, the genotype log likelihoods and the estimated fraction of each base
+	pub fn calc_gt_prob(&self, counts: &[c_int; 8], qual: &[c_int; 8], ref_base: u8) -> (usize, [f64; 10], [f32; 4]) {
+		let (mx, mut ll) = self.model.calc_gt_prob(counts, qual, ref_base, None);
+		let a = HOM_BASE.iter().find(|(g, _)| *g == mx).map(|(_, x)| *x).expect("Unexpected haploid genotype");
+		let mut fract = [0.0; 4];
+		fract[a] = 1.0;
+		// Find second allele with the highest log likelihood ratio for the mixture model
+		let mut best: Option<(usize, f64, f64)> = None;
+		for b in (0..4).filter(|b| *b != a) {
+			let ((na, nb), q) = Self::pair_counts(counts, qual, a, b);
+			if nb < 2.0 { continue }
+			let e = (-0.1 * LN_10 * q / (na + nb)).exp().min(0.5);
+			let f = ((nb / (na + nb) - e / 3.0) / (1.0 - 4.0 * e / 3.0)).clamp(0.0, 1.0);
+			let l = |f: f64| {
+				let p = f * (1.0 - e) + (1.0 - f) * e / 3.0;
+				nb * p.ln() + na * (1.0 - p).ln()
+			};
+			let llr = l(f) - l(0.0);
+			if best.map(|(_, _, x)| llr > x).unwrap_or(true) { best = Some((b, f, llr)) }
+		}
+		let mut max_gt = mx;
+		if let Some((b, f, llr)) = best {
+			fract[a] = (1.0 - f) as f32;
+			fract[b] = f as f32;
+			if f >= self.min_hf {
+				// Add mixture genotype and renormalize
+				let q = if self.log10 { LN_10 } else { 1.0 };
+				let het = gt_index(a, b);
+				ll[het] = ll[mx] + llr / q;
+				let max = ll.iter().copied().fold(f64::MIN, f64::max);
+				let sum = ll.iter().filter(|x| **x > f64::MIN).fold(0.0, |s, x| s + ((x - max) * q).exp()).ln() / q;
+				ll.iter_mut().filter(|x| **x > f64::MIN).for_each(|x| *x -= max + sum);
+				if ll[het] > ll[mx] { max_gt = het }
+			}
+		}
+		(max_gt, ll, fract)
+	}
+}
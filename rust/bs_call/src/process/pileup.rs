@@ -4,11 +4,12 @@ use std::{cmp, io, slice, thread};
 use super::call_genotypes;
 use super::records::ReadEnd;
 use crate::config::{BsCallConfig, BsCallFiles};
-use crate::dbsnp::{DBSnpContig, DBSnpFile};
+use dbsnp::{DBSnpContig, DBSnpIndex};
 use crate::defs::CtgRegion;
 use crate::htslib::{hts_err, BSStrand, CigarOp, Faidx, Sequence};
 use crate::rusage::*;
 use crate::stats::{FSBaseLevelType, FSType, MethProfile, StatJob};
+use crate::stats::cnv::CnvRef;
 
 pub struct PileupRegion {
 	start: usize,
@@ -97,11 +98,11 @@ impl Pileup {
 		gc_bins: Vec<u8>,
 		sam_tid: usize,
 		cname: &str,
-		dbsnp_file: &mut Option<DBSnpFile>,
+		dbsnp_index: &Option<DBSnpIndex>,
 	) -> Self {
 		assert!(start >= ref_start);
 		assert!(start - ref_start <= 2);
-		let dbsnp_contig = if let Some(x) = dbsnp_file.as_ref() {
+		let dbsnp_contig = if let Some(x) = dbsnp_index.as_ref() {
 			x.get_dbsnp_contig(cname)
 		} else {
 			None
@@ -222,24 +223,18 @@ impl GcContent {
 	}
 }
 
+// Returns true if a new sequence was loaded
 fn check_sequence(
 	cname: &str,
 	seq: &mut Option<SeqData>,
 	ref_index: &Faidx,
-	dbsnp_file: &mut Option<DBSnpFile>,
-) -> io::Result<()> {
+) -> io::Result<bool> {
 	if let Some(seq_data) = seq {
 		if seq_data.seq.cname() != cname {
-			if let Some(dbsnp) = dbsnp_file {
-				dbsnp.unload_ctg(seq_data.seq.cname());
-			}
 			seq.take();
 		}
 	}
 	if seq.is_none() {
-		if let Some(dbsnp) = dbsnp_file {
-			dbsnp.load_ctg(cname)?;
-		}
 		info!("Loading sequence data for {}", cname);
 		let new_seq = ref_index.fetch_seq(cname)?;
 		let gc_content = GcContent::generate_bins(&new_seq);
@@ -248,8 +243,10 @@ fn check_sequence(
 			gc_content,
 		});
 		info!("Sequence data loaded");
+		Ok(true)
+	} else {
+		Ok(false)
 	}
-	Ok(())
 }
 
 const REF_TAB: [u8; 256] = [
@@ -498,8 +495,13 @@ fn handle_pileup(
 	let min_qual = bs_cfg.conf_hash.get_int("bq_threshold") as u8;
 	let seq_data = &mut pileup_data.seq_data;
 	let ref_index = &pileup_data.ref_index;
-	let dbsnp_file = &mut pileup_data.dbsnp_file;
-	check_sequence(&preg.cname, seq_data, ref_index, dbsnp_file)?;
+	let cnv_bin_size = bs_cfg.conf_hash.get_int("cnv_bin_size");
+	if check_sequence(&preg.cname, seq_data, ref_index)? {
+		let gc_bins = &seq_data.as_ref().unwrap().gc_content.bins;
+		let callable = gc_bins.iter().filter(|x| **x != 255).count() * (GC_BIN_SIZE as usize);
+		let _ = stat_tx.send(StatJob::SetContigCallable(preg.cname.clone(), callable));
+		if cnv_bin_size > 0 { let _ = stat_tx.send(StatJob::AddCnvRef(preg.cname.clone(), CnvRef::new(gc_bins, cnv_bin_size))); }
+	}
 	check_regions(&mut preg, &bs_cfg.regions)?;
 	let meth_prof = &mut pileup_data.meth_prof;
 	let (ref_seq, ref_start, gc_bins) = load_ref_seq(&mut preg, &seq_data)?;
@@ -517,7 +519,7 @@ fn handle_pileup(
 		gc_bins,
 		preg.sam_tid,
 		&preg.cname,
-		dbsnp_file,
+		&pileup_data.dbsnp_index,
 	);
 	for read in preg.reads.drain(..).flatten() {
 		let (ltrim, rtrim) = if read.read_one() {
@@ -565,7 +567,7 @@ pub struct SeqData {
 pub struct PileupData {
 	seq_data: Option<SeqData>,
 	ref_index: Faidx,
-	dbsnp_file: Option<DBSnpFile>,
+	dbsnp_index: Option<DBSnpIndex>,
 	bs_cfg: Arc<BsCallConfig>,
 	meth_prof: MethProfile,
 }
@@ -577,22 +579,12 @@ pub fn make_pileup(
 	stat_tx: mpsc::Sender<StatJob>,
 ) {
 	info!("pileup_thread starting up");
-	let dbsnp_file = if let Some(dbsnp_index) = bs_files.dbsnp_index.take() {
-		match DBSnpFile::open(dbsnp_index) {
-			Ok(db) => Some(db),
-			Err(e) => {
-				error!("Couldn't open dbSNP index file: {}", e);
-				None
-			}
-		}
-	} else {
-		None
-	};
+	let dbsnp_index = bs_files.dbsnp_index.take();
 	let min_qual = bs_cfg.conf_hash.get_int("bq_threshold") as u8;
 	let mut pileup_data = PileupData {
 		seq_data: None,
 		ref_index: bs_files.ref_index.take().unwrap(),
-		dbsnp_file,
+		dbsnp_index,
 		meth_prof: MethProfile::new(min_qual as usize),
 		bs_cfg: Arc::clone(&bs_cfg),
 	};
@@ -104,6 +104,7 @@ pub fn read_data(bs_cfg: Arc<BsCallConfig>, stat_tx: mpsc::Sender<StatJob>, mut
 	let hdr = &mut sam_input.hdr;
 
 	let keep_duplicates = cfg.conf_hash.get_bool("keep_duplicates");
+	let mito_tid = cfg.mito_tid();
 	let mut brec = BamRec::new().unwrap();
 	let mut reads: Vec<Option<ReadEnd>> = Vec::new();
 	let mut state_hash: HashMap<String, ReadState> = HashMap::new();
@@ -123,7 +124,7 @@ pub fn read_data(bs_cfg: Arc<BsCallConfig>, stat_tx: mpsc::Sender<StatJob>, mut
 			},
 			_ => panic!("Error reading record"),
 		}
-		let (read_end, read_flag) = ReadEnd::from_bam_rec(&cfg.conf_hash, hdr, &brec);
+		let (read_end, read_flag) = ReadEnd::from_bam_rec(&cfg.conf_hash, hdr, &brec, mito_tid);
 		if let Some(mut read) = read_end {
 			let map = &read.maps[0];
 			let change = curr_state.update(map, reads.len());
@@ -137,7 +137,8 @@ pub struct ReadEnd {
 }
 
 impl ReadEnd {
-	pub fn from_bam_rec(conf_hash: &ConfHash, sam_hdr: &SamHeader, brec: &BamRec) -> (Option<Self>, ReadFlag) {
+	// mito_tid is the contig ID of the mitochondrial contig if mitochondrial mode is selected
+	pub fn from_bam_rec(conf_hash: &ConfHash, sam_hdr: &SamHeader, brec: &BamRec, mito_tid: Option<usize>) -> (Option<Self>, ReadFlag) {
 		// Pull in config options
 		let keep_duplicates = conf_hash.get_bool("keep_duplicates");
 		let keep_unmatched = conf_hash.get_bool("keep_unmatched");
@@ -145,6 +146,8 @@ impl ReadEnd {
 		let ignore_duplicates = conf_hash.get_bool("ignore_duplicates");
 		let mapq_threshold = conf_hash.get_int("mapq_threshold");
 		let max_template_length = conf_hash.get_int("max_template_length");
+		let mito = mito_tid.is_some() && brec.tid() == mito_tid;
+		let mapq_threshold = if mito { conf_hash.get_int("mito_mapq_threshold") } else { mapq_threshold };
 		
 		// Check Bam record
 		let flag = brec.flag();
@@ -172,12 +175,17 @@ impl ReadEnd {
 				let mtid = if let Some(x) = brec.mtid() {x} else { return (None, ReadFlag::NoMatePosition) };
 				let mpos = if let Some(x) = brec.mpos() {x} else { return (None, ReadFlag::NoMatePosition) };
 				if mtid != tid {
+					// Mitochondrial reads with a mate on another contig are likely to come from NUMTs, so are always discarded
+					if mito { return (None, ReadFlag::MisMatchContig) }
 					if keep_unmatched { set_read_flag(ReadFlag::MisMatchContig)} else {return (None, ReadFlag::MisMatchContig)}				
 				}
-				if brec.template_len().abs() > max_template_length as isize {
+				let tlen = brec.template_len().unsigned_abs();
+				// Pairs spanning the origin of the circular mitochondrial genome are kept and treated as single ended reads
+				let circular = mito && tlen > max_template_length && tlen + max_template_length > sam_hdr.tid2len(tid);
+				if tlen > max_template_length && !circular {
 					if keep_unmatched { set_read_flag(ReadFlag::LargeInsertSize)} else {return (None, ReadFlag::LargeInsertSize)}
 				}		
-				Some(MapPos{tid: mtid as u32, pos: mpos as u32})
+				if circular { None } else { Some(MapPos{tid: mtid as u32, pos: mpos as u32}) }
 			}
 		} else { None };
 		let maps = maps_from_bam_rec(sam_hdr, brec, keep_supplementary).unwrap_or_else(|e| panic!("Couldn't get map record from BAM read (id: {}: {}", brec.qname(), e));		
@@ -204,6 +212,8 @@ impl ReadEnd {
 		false
 	}
 	pub fn check_pair(&mut self, read: &mut Self, conf_hash: &ConfHash) -> (bool, ReadFlag) {
+		// Pairs spanning the origin of the mitochondrial genome have no mate position, and are not checked or trimmed
+		if self.mate_pos.is_none() || read.mate_pos.is_none() { return (false, ReadFlag::Passed) }
 		let keep_unmatched = conf_hash.get_bool("keep_unmatched");
 		let map1 = &mut self.maps[0];
 		let map2 = &mut read.maps[0];
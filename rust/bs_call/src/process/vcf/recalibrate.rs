@@ -0,0 +1,316 @@
+use std::{io, fs};
+use std::io::{BufRead, Write};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use libc::c_int;
+
+use crate::config::{BsCallConfig, OType, new_err};
+use crate::htslib::*;
+use crate::stats::{RecalStats, RecalTranche};
+use super::CallStats;
+
+pub const RECAL_HEADERS: [&str; 4] = [
+	"##INFO=<ID=VQSLOD,Number=1,Type=Float,Description=\"Log odds ratio of being a true variant versus being false under the trained Gaussian mixture models\">",
+	"##FILTER=<ID=vqsr99.9,Description=\"Truth sensitivity tranche level 99.0 to 99.9\">",
+	"##FILTER=<ID=vqsr100,Description=\"Truth sensitivity tranche level 99.9 to 100.0\">",
+	"##FILTER=<ID=vqsr100+,Description=\"VQSLOD below that of all known (dbSNP) variants\">",
+];
+
+// Truth sensitivity tranches (as % of known sites) and the filters for variants falling below each tranche
+// Variants in the 99.0 tranche or above pass
+const TRANCHES: [f64; 4] = [90.0, 99.0, 99.9, 100.0];
+const PASS_TRANCHE: usize = 1;
+const TRANCHE_FILTERS: [&str; 3] = ["vqsr99.9", "vqsr100", "vqsr100+"];
+
+pub const N_FEATURES: usize = 6;
+
+const MIN_KNOWN: usize = 1000;
+const MAX_TRAIN: usize = 250000;
+const BAD_FRACTION: f64 = 0.05;
+const MIN_BAD: usize = 500;
+const POS_GAUSSIANS: usize = 4;
+const NEG_GAUSSIANS: usize = 2;
+const MAX_ITER: usize = 100;
+const VAR_FLOOR: f64 = 1.0e-3;
+
+// Features used for recalibration: variant quality, quality by depth, fisher strand, mapping quality,
+// log depth and the fraction of the depth from non-converted reads (which depends on the conversion context of the variant)
+pub struct RecalSite {
+	pub vcf_rid: usize,
+	pub pos: usize,
+	pub known: bool,
+	pub transition: Option<bool>,
+	x: [f32; N_FEATURES],
+}
+
+impl RecalSite {
+	pub fn new(cs: &CallStats, vcf_rid: usize, known: bool, transition: Option<bool>) -> Self {
+		let dp = (cs.dp1 + cs.d_inf) as f32;
+		let x = [cs.phred as f32, cs.qd as f32, cs.fs as f32, cs.mq as f32, (1.0 + dp).ln(), if dp > 0.0 { cs.dp1 as f32 / dp } else { 0.0 }];
+		Self{vcf_rid, pos: cs.pos, known, transition, x}
+	}
+	// Sites with undefined features (i.e., NaN) are not used for training and fail all tranches
+	fn is_finite(&self) -> bool { self.x.iter().all(|x| x.is_finite()) }
+	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		let tr = match self.transition { Some(true) => "1", Some(false) => "0", None => "." };
+		write!(w, "{}\t{}\t{}\t{}", self.vcf_rid, self.pos, self.known as u8, tr)?;
+		for x in self.x.iter() { write!(w, "\t{}", x)? }
+		writeln!(w)
+	}
+	fn from_line(line: &str) -> Option<Self> {
+		let v: Vec<_> = line.trim_end().split('\t').collect();
+		if v.len() != 4 + N_FEATURES { return None }
+		let transition = match v[3] { "1" => Some(true), "0" => Some(false), _ => None };
+		let mut x = [0.0; N_FEATURES];
+		for (a, b) in x.iter_mut().zip(v[4..].iter()) { *a = b.parse().ok()? }
+		Some(Self{vcf_rid: v[0].parse().ok()?, pos: v[1].parse().ok()?, known: v[2] == "1", transition, x})
+	}
+}
+
+// Write the features for all variant sites so that the model can be trained on the combined sites from several output files
+pub fn write_recal_sites(path: &str, sites: &[RecalSite]) -> io::Result<()> {
+	let mut wrt = utils::compress::open_bufwriter(path)?;
+	for s in sites.iter() { s.write(&mut wrt)? }
+	wrt.flush()
+}
+
+fn read_recal_sites(path: &str, sites: &mut Vec<RecalSite>) -> io::Result<()> {
+	let rdr = utils::compress::open_bufreader(path)?;
+	for (ix, line) in rdr.lines().enumerate() {
+		let line = line?;
+		sites.push(RecalSite::from_line(&line).ok_or_else(|| new_err(format!("Error parsing line {} of recalibration sites file {}", ix + 1, path)))?)
+	}
+	Ok(())
+}
+
+// Scale features to zero mean and unit variance
+struct Scaling {
+	mean: [f64; N_FEATURES],
+	sd: [f64; N_FEATURES],
+}
+
+impl Scaling {
+	fn new(sites: &[RecalSite]) -> Self {
+		let n = sites.iter().filter(|s| s.is_finite()).count() as f64;
+		let mut mean = [0.0; N_FEATURES];
+		let mut sd = [0.0; N_FEATURES];
+		for s in sites.iter().filter(|s| s.is_finite()) {
+			for (i, x) in s.x.iter().enumerate() {
+				let x = *x as f64;
+				mean[i] += x;
+				sd[i] += x * x;
+			}
+		}
+		for (m, s) in mean.iter_mut().zip(sd.iter_mut()) {
+			*m /= n;
+			*s = (*s / n - *m * *m).max(0.0).sqrt().max(1.0e-6);
+		}
+		Self{mean, sd}
+	}
+	fn scale(&self, site: &RecalSite) -> [f64; N_FEATURES] {
+		let mut v = [0.0; N_FEATURES];
+		for (i, x) in site.x.iter().enumerate() { v[i] = (*x as f64 - self.mean[i]) / self.sd[i] }
+		v
+	}
+}
+
+// Gaussian component with diagonal covariance matrix
+#[derive(Clone)]
+struct Gaussian {
+	ln_w: f64,
+	ln_k: f64,
+	mean: [f64; N_FEATURES],
+	var: [f64; N_FEATURES],
+}
+
+impl Gaussian {
+	fn new(w: f64, mean: [f64; N_FEATURES], var: [f64; N_FEATURES]) -> Self {
+		let ln_k = var.iter().map(|v| -0.5 * (2.0 * PI * v).ln()).sum();
+		Self{ln_w: w.ln(), ln_k, mean, var}
+	}
+	fn ln_dens(&self, x: &[f64; N_FEATURES]) -> f64 {
+		self.ln_w + self.ln_k - 0.5 * x.iter().zip(self.mean.iter().zip(self.var.iter())).map(|(x, (m, v))| (x - m) * (x - m) / v).sum::<f64>()
+	}
+}
+
+struct Gmm {
+	comp: Vec<Gaussian>,
+}
+
+impl Gmm {
+	fn ln_dens(&self, x: &[f64; N_FEATURES]) -> f64 {
+		let lp: Vec<_> = self.comp.iter().map(|c| c.ln_dens(x)).collect();
+		log_sum_exp(&lp)
+	}
+
+	// Fit model using EM.  Starting means are taken from the quantiles of the first feature (variant quality)
+	fn train(data: &[[f64; N_FEATURES]], k: usize) -> Self {
+		let n = data.len();
+		let k = k.min(n);
+		let mut idx: Vec<_> = (0..n).collect();
+		idx.sort_unstable_by(|a, b| data[*a][0].total_cmp(&data[*b][0]));
+		let mut comp: Vec<_> = (0..k).map(|j| Gaussian::new(1.0 / (k as f64), data[idx[(2 * j + 1) * n / (2 * k)]], [1.0; N_FEATURES])).collect();
+		let mut lp = vec![0.0; k];
+		let mut last_ll = f64::MIN;
+		for it in 0..MAX_ITER {
+			let mut acc = vec![(0.0, [0.0; N_FEATURES], [0.0; N_FEATURES]); k];
+			let mut ll = 0.0;
+			for x in data.iter() {
+				for (c, l) in comp.iter().zip(lp.iter_mut()) { *l = c.ln_dens(x) }
+				let s = log_sum_exp(&lp);
+				ll += s;
+				for (l, (r, s1, s2)) in lp.iter().zip(acc.iter_mut()) {
+					let z = (l - s).exp();
+					*r += z;
+					for (i, y) in x.iter().enumerate() {
+						s1[i] += z * y;
+						s2[i] += z * y * y;
+					}
+				}
+			}
+			for (c, (r, s1, s2)) in comp.iter_mut().zip(acc.iter()) {
+				// Components with (almost) no data are left unchanged
+				if *r < 1.0 { continue }
+				let mut mean = [0.0; N_FEATURES];
+				let mut var = [0.0; N_FEATURES];
+				for i in 0..N_FEATURES {
+					mean[i] = s1[i] / r;
+					var[i] = (s2[i] / r - mean[i] * mean[i]).max(VAR_FLOOR);
+				}
+				*c = Gaussian::new(r / (n as f64), mean, var);
+			}
+			trace!("GMM training iteration {}: log likelihood {}", it, ll);
+			if ll - last_ll < 1.0e-4 * (n as f64) {
+				debug!("GMM training with {} components converged after {} iterations", k, it + 1);
+				break
+			}
+			last_ll = ll;
+		}
+		Self{comp}
+	}
+}
+
+fn log_sum_exp(v: &[f64]) -> f64 {
+	let mx = v.iter().copied().fold(f64::MIN, f64::max);
+	mx + v.iter().fold(0.0, |s, x| s + (x - mx).exp()).ln()
+}
+
+// Take at most MAX_TRAIN evenly spaced sites for training
+fn training_set<'a, I: Iterator<Item = &'a RecalSite>>(it: I, n: usize, scaling: &Scaling) -> Vec<[f64; N_FEATURES]> {
+	let step = n.div_ceil(MAX_TRAIN);
+	it.step_by(step.max(1)).map(|s| scaling.scale(s)).collect()
+}
+
+// Thresholds decrease with increasing sensitivity, so the number of thresholds above lod gives the filter (0 is PASS)
+fn tranche_filter(lod: f32, thresholds: &[f32]) -> usize {
+	if lod.is_nan() { TRANCHE_FILTERS.len() }
+	else { thresholds[PASS_TRANCHE..].iter().filter(|t| lod < **t).count() }
+}
+
+// Train positive model on known sites, then a negative model on the sites that fit the positive model worst.
+// Returns the log odds ratio for all sites (NaN for sites with undefined features) and the minimum VQSLOD for each tranche
+fn train_models(sites: &[RecalSite]) -> Option<(Vec<f32>, Vec<f32>, usize, usize)> {
+	let n_known = sites.iter().filter(|s| s.known && s.is_finite()).count();
+	if n_known < MIN_KNOWN {
+		warn!("Too few known variants ({}) for variant recalibration (minimum {}) - hard filters will be used", n_known, MIN_KNOWN);
+		return None
+	}
+	let scaling = Scaling::new(sites);
+	let pos_model = Gmm::train(&training_set(sites.iter().filter(|s| s.known && s.is_finite()), n_known, &scaling), POS_GAUSSIANS);
+	let lp_pos: Vec<_> = sites.iter().map(|s| if s.is_finite() { pos_model.ln_dens(&scaling.scale(s)) } else { f64::NAN }).collect();
+	let mut idx: Vec<_> = (0..sites.len()).filter(|i| sites[*i].is_finite()).collect();
+	idx.sort_unstable_by(|a, b| lp_pos[*a].total_cmp(&lp_pos[*b]));
+	let n_bad = ((idx.len() as f64 * BAD_FRACTION) as usize).max(MIN_BAD).min(idx.len());
+	let neg_model = Gmm::train(&training_set(idx[..n_bad].iter().map(|i| &sites[*i]), n_bad, &scaling), NEG_GAUSSIANS);
+	let lods: Vec<_> = sites.iter().zip(lp_pos.iter()).map(|(s, lp)| if s.is_finite() { (lp - neg_model.ln_dens(&scaling.scale(s))) as f32 } else { f32::NAN }).collect();
+	let mut known_lods: Vec<_> = sites.iter().zip(lods.iter()).filter(|(s, l)| s.known && !l.is_nan()).map(|(_, l)| *l).collect();
+	known_lods.sort_unstable_by(|a, b| b.total_cmp(a));
+	let thresholds = TRANCHES.iter().map(|ts| known_lods[((ts * (n_known as f64) / 100.0).ceil() as usize).clamp(1, n_known) - 1]).collect();
+	Some((lods, thresholds, n_known, n_bad))
+}
+
+fn tranche_stats(sites: &[RecalSite], lods: &[f32], thresholds: &[f32], n_known: usize, n_bad: usize) -> RecalStats {
+	let mut tranches: Vec<_> = TRANCHES.iter().zip(thresholds.iter()).map(|(ts, t)| RecalTranche{truth_sensitivity: *ts, min_vqslod: *t as f64, known: 0, novel: 0, novel_ti: 0, novel_tv: 0}).collect();
+	for (s, lod) in sites.iter().zip(lods.iter()) {
+		for tr in tranches.iter_mut().filter(|tr| *lod >= tr.min_vqslod as f32) {
+			if s.known { tr.known += 1 }
+			else {
+				tr.novel += 1;
+				match s.transition {
+					Some(true) => tr.novel_ti += 1,
+					Some(false) => tr.novel_tv += 1,
+					None => (),
+				}
+			}
+		}
+	}
+	RecalStats{pass_sensitivity: TRANCHES[PASS_TRANCHE], variants: sites.len(), known_training_sites: n_known, bad_training_sites: n_bad, tranches}
+}
+
+// Copy VCF/BCF from input to output adding VQSLOD and the tranche filter for recalibrated sites.  The tranche filter is
+// added to any existing (hard) filters.  Sites are matched by position as the input may not be in the same order as the sites
+fn rewrite_vcf(input: &str, output: &str, otype: OType, threads: usize, sites: &[RecalSite], lods: &[f32], thresholds: &[f32]) -> io::Result<()> {
+	let mut in_file = HtsFile::new(input, "r")?;
+	let mut hdr = VcfHeader::read(&mut in_file)?;
+	let mut out_file = HtsFile::new(output, &format!("{}", otype))?;
+	if threads > 0 { out_file.set_threads(threads)? }
+	hdr.write(&mut out_file)?;
+	let mut flt_ids = Vec::with_capacity(TRANCHE_FILTERS.len() + 1);
+	flt_ids.push(0);
+	for flt in TRANCHE_FILTERS.iter() {
+		flt_ids.push(hdr.id2int(BCF_DT_ID as usize, flt).ok_or_else(|| new_err(format!("Filter {} not found in VCF header", flt)))? as c_int)
+	}
+	let site_hash: HashMap<(usize, usize), f32> = sites.iter().zip(lods.iter()).map(|(s, lod)| ((s.vcf_rid, s.pos), *lod)).collect();
+	let mut found = 0;
+	let mut brec = BcfRec::new()?;
+	loop {
+		match brec.read(&mut in_file, &hdr) {
+			BcfReadResult::Ok => (),
+			BcfReadResult::EOF => break,
+			BcfReadResult::Error => return Err(new_err(format!("Error reading from {}", input))),
+		}
+		if let Some(lod) = site_hash.get(&(brec.rid(), brec.pos())) {
+			if !lod.is_nan() { brec.update_info_f32(&hdr, "VQSLOD", &[*lod])? }
+			let flt = tranche_filter(*lod, thresholds);
+			if flt > 0 { brec.add_filter(&hdr, flt_ids[flt])? }
+			found += 1;
+		}
+		brec.write(&mut out_file, &mut hdr)?;
+	}
+	if found < sites.len() { warn!("Not all recalibrated sites were found in {} ({} of {})", input, found, sites.len()) }
+	Ok(())
+}
+
+fn train_and_rewrite(sites: &[RecalSite], input: &str, output: &str, otype: OType, threads: usize) -> io::Result<Option<RecalStats>> {
+	info!("Starting variant recalibration with {} variants", sites.len());
+	if let Some((lods, thresholds, n_known, n_bad)) = train_models(sites) {
+		for (ts, t) in TRANCHES.iter().zip(thresholds.iter()) { debug!("Truth sensitivity tranche {}: min VQSLOD {}", ts, t) }
+		rewrite_vcf(input, output, otype, threads, sites, &lods, &thresholds)?;
+		info!("Variant recalibration finished");
+		Ok(Some(tranche_stats(sites, &lods, &thresholds, n_known, n_bad)))
+	} else { Ok(None) }
+}
+
+// Recalibrate the output file in place
+pub fn recalibrate_output(bs_cfg: &BsCallConfig, sites: &[RecalSite]) -> io::Result<Option<RecalStats>> {
+	let output = bs_cfg.get_conf_str("output").ok_or_else(|| new_err("Can not recalibrate variants without an output file".to_string()))?;
+	let tmp = format!("{}.recal.tmp", output);
+	let res = train_and_rewrite(sites, output, &tmp, bs_cfg.get_conf_otype(), bs_cfg.get_conf_int("output_threads"))?;
+	if res.is_some() { fs::rename(&tmp, output)? }
+	Ok(res)
+}
+
+// Recalibrate a VCF/BCF file (typically merged from several bs_call runs) using the sites files from all runs, so that
+// a single model is trained per sample.  If the model can not be trained the input is copied unchanged.
+pub fn recalibrate_from_sites(site_files: &[&str], input: &str, output: &str, otype: OType, threads: usize) -> io::Result<Option<RecalStats>> {
+	let mut sites = Vec::new();
+	for f in site_files.iter() { read_recal_sites(f, &mut sites)? }
+	match train_and_rewrite(&sites, input, output, otype, threads)? {
+		Some(rs) => Ok(Some(rs)),
+		None => {
+			rewrite_vcf(input, output, otype, threads, &[], &[], &[])?;
+			Ok(None)
+		}
+	}
+}
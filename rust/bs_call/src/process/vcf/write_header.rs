@@ -5,9 +5,10 @@ use chrono::prelude::*;
 
 use crate::config::*;
 use crate::defs::contigs;
+use super::recalibrate::RECAL_HEADERS;
 use crate::htslib::{VcfHeader, SamFile};
 
-const FIXED_HEADERS: [&str; 20] = [
+const FIXED_HEADERS: [&str; 21] = [
 	"##INFO=<ID=CX,Number=1,Type=String,Description=\"5 base sequence context (from position -2 to +2 on the positive strand) determined from the reference\">",
 	"##FILTER=<ID=fail,Description=\"No sample passed filters\">",
 	"##FILTER=<ID=q20,Description=\"Genotype Quality below 20\">",
@@ -28,6 +29,7 @@ const FIXED_HEADERS: [&str; 20] = [
 	"##FORMAT=<ID=CG,Number=1,Type=String,Description=\"CpG Status (from genotype calls: Y/N/H/?)\">",
 	"##FORMAT=<ID=CX,Number=1,Type=String,Description=\"5 base sequence context (from position -2 to +2 on the positive strand) determined from genotype call\">",
 	"##FORMAT=<ID=FS,Number=1,Type=Integer,Description=\"Phred scaled log p-value from Fishers exact test of strand bias\"",
+	"##FORMAT=<ID=HF,Number=A,Type=Float,Description=\"Estimated heteroplasmy fraction of alternate allele (mitochondrial contig only)\">",
 ];
 
 fn find_tags<'a>(s: &'a str, tags: &[&str]) -> Vec<Option<&'a str>> {
@@ -108,6 +110,7 @@ pub fn write_vcf_header(bs_cfg: &mut BsCallConfig, bs_files: &mut BsCallFiles, s
 	let contigs = &mut bs_cfg.contigs;
 	add_seq_info(&mut hd, contigs, sam_file)?;	
 	for line in FIXED_HEADERS.iter() { hd.append(line)?; }
+	if chash.get_bool("recalibrate") { for line in RECAL_HEADERS.iter() { hd.append(line)?; } }
 	let sample = if let Some(s) = chash.get_str("sample") { s }
 	else if let Some(s) = sam_sample { s }
 	else { "SAMPLE" };
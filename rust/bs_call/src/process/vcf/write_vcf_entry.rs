@@ -6,8 +6,9 @@ use crate::htslib::*;
 use libc::{c_char, c_int};
 use crate::stats::{StatJob, collect_vcf_stats};
 use crate::process::call_genotypes::{CallBlock, GenotypeCall, CallEntry};
-use crate::dbsnp::DBSnpContig;
+use dbsnp::DBSnpContig;
 use crate::rusage::*;
+use super::recalibrate::{recalibrate_output, write_recal_sites};
 
 pub enum WriteVcfJob {
 	CallBlock(CallBlock),
@@ -17,7 +18,7 @@ pub enum WriteVcfJob {
 
 use std::f64::consts::LN_10;
 
-pub const FLT_NAMES: [&str; 16] = ["PASS", "mac1", "fail", "GT", "FT", "DP", "MQ", "GQ", "QD", "GL", "MC8", "AMQ", "CS", "CG", "CX", "FS" ];	
+pub const FLT_NAMES: [&str; 17] = ["PASS", "mac1", "fail", "GT", "FT", "DP", "MQ", "GQ", "QD", "GL", "MC8", "AMQ", "CS", "CG", "CX", "FS", "HF" ];	
 const FLT_ID_PASS: usize = 0;
 const FLT_ID_MAC1: usize = 1;
 const FLT_ID_FAIL: usize = 2;
@@ -34,6 +35,7 @@ const FLT_ID_CS: usize = 12;
 const FLT_ID_CG: usize = 13;
 const FLT_ID_CX: usize = 14;
 const FLT_ID_FS: usize = 15;
+const FLT_ID_HF: usize = 16;
 
 fn get_filter_ids(hdr: &VcfHeader) -> Vec<u8> {
 	let mut v = Vec::with_capacity(FLT_NAMES.len());
@@ -132,6 +134,7 @@ pub const CPG_STATUS_REF_CPG: u8 = 8;
 
 pub struct CallStats {
 	pub sam_tid: usize,
+	pub pos: usize,
 	pub phred: c_int,
 	pub fs: c_int,
 	pub qd: c_int,
@@ -145,6 +148,7 @@ pub struct CallStats {
 	pub ref_base: u8,
 	pub gt: u8,
 	pub mq: u8,
+	pub alt_fract: Option<f32>, // Summed fraction of alternate alleles (mitochondrial contig only)
 }
 
 fn ln_prob_2_phred(p: f64) -> c_int {
@@ -154,8 +158,9 @@ fn ln_prob_2_phred(p: f64) -> c_int {
 }
 
 fn handle_filters(call: &GenotypeCall, cs: &CallStats) -> (usize, usize) {
+	// Quality by depth is not used for mitochondrial calls as low fraction heteroplasmies at high depth have low QD
 	let flt = if cs.phred < 20 { 1 } else { 0 } 
-		| if cs.qd < 2 { 2 } else { 0 }
+		| if cs.qd < 2 && call.hf.is_none() { 2 } else { 0 }
 		| if cs.fs > 60 { 4 } else { 0 }
 		| if call.mq < 40 { 8 } else { 0 };
 		
@@ -220,6 +225,10 @@ fn get_gt_like(call: &GenotypeCall) -> Vec<f32> {
 	v
 }
 
+// Estimated fraction of each alternate allele (mitochondrial contig only)
+fn get_alt_fract(call: &GenotypeCall) -> Option<Vec<f32>> {
+	call.hf.map(|fr| ALL_IDX[call.max_gt as usize][call.ref_base as usize].iter().filter(|i| **i > 0).map(|i| fr[*i - 1]).collect())
+}
 
 // In this part we re-implement some of the htslib functions for creating VCF/BCF entries
 // so that we can write to a Rust Vec<u8> rather than a kstring, and then we copy the Vec
@@ -339,7 +348,7 @@ fn write_fixed_columns(call: &GenotypeCall, filter_ids: &[u8], v: &mut Vec<u8>,
 	Ok(())
 }
 
-fn write_format_columns(call: &GenotypeCall, filter_ids: &[u8], called_context: &[u8], v: &mut Vec<u8>, call_stats: &CallStats, bcf_rec: &mut BcfRec) -> io::Result<()> {
+fn write_format_columns(call: &GenotypeCall, filter_ids: &[u8], called_context: &[u8], alt_fract: &Option<Vec<f32>>, v: &mut Vec<u8>, call_stats: &CallStats, bcf_rec: &mut BcfRec) -> io::Result<()> {
 	let mut n_fmt = 11;
 	v.clear();
 	// GT
@@ -393,6 +402,12 @@ fn write_format_columns(call: &GenotypeCall, filter_ids: &[u8], called_context:
 		enc_int(v, call_stats.fs);
 		n_fmt += 1;		
 	}
+	// HF
+	if let Some(hf) = alt_fract.as_ref().filter(|x| !x.is_empty()) {
+		enc_u8(v, filter_ids[FLT_ID_HF]);
+		enc_vfloat(v, hf);
+		n_fmt += 1;
+	}
 	let kstr = bcf_rec.indiv();
 	kstr.putsn(v.as_slice().as_ptr() as *const c_char, v.len());
 	bcf_rec.set_n_fmt(n_fmt);
@@ -481,15 +496,16 @@ impl WriteState {
 				};
 				let flags = if skip { CALL_STATS_SKIP } else { 0 } | if rs_found { CALL_STATS_RS_FOUND } else { 0 };
 				let meth_cts = CPG_ST_CTS[call.max_gt as usize].map(|(x, y)| (call.counts[x] as usize, call.counts[y] as usize));
-				let mut call_stats = CallStats{sam_tid: self.sam_tid, phred, fs, dp1, d_inf, qd, cpg_status, flags, gc: call.gc, 
-					meth_cts, filter: 0, gt: call.max_gt, mq: call.mq, ref_base: call.ref_base};
+				let alt_fract = get_alt_fract(call);
+				let mut call_stats = CallStats{sam_tid: self.sam_tid, pos: self.curr_x, phred, fs, dp1, d_inf, qd, cpg_status, flags, gc: call.gc, 
+					meth_cts, filter: 0, gt: call.max_gt, mq: call.mq, ref_base: call.ref_base, alt_fract: alt_fract.as_ref().map(|v| v.iter().sum())};
 				if !skip {
 					let tvec = &mut self.tvec;
 					bcf_rec.clear();
 					bcf_rec.set_rid(self.vcf_rid); 
 					bcf_rec.set_pos(self.curr_x);
 					write_fixed_columns(call, filter_ids, tvec, &mut call_stats, &ref_context, &rs, bcf_rec)?;
-					write_format_columns(call, filter_ids, &called_context, tvec, &call_stats, bcf_rec)?;
+					write_format_columns(call, filter_ids, &called_context, &alt_fract, tvec, &call_stats, bcf_rec)?;
 					bcf_rec.write(&mut vcf_output.file, &mut vcf_output.hdr)?;			
 				}
 				self.call_stats.push(call_stats);
@@ -518,6 +534,7 @@ pub fn write_vcf_entry(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<WriteVcfJob
 	let filter_ids = get_filter_ids(&vcf_output.hdr);
 	let cfg = Arc::clone(&bs_cfg);
 	let (vcf_stats_tx, vcf_stats_rx) = mpsc::sync_channel(64);
+	let recal_tx = stat_tx.clone();
 	let vcf_stats_handle = thread::spawn(move || { collect_vcf_stats(Arc::clone(&bs_cfg), vcf_stats_rx, stat_tx) });
 
 	let mut write_state: Option<WriteState> = None;
@@ -558,7 +575,24 @@ pub fn write_vcf_entry(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<WriteVcfJob
 		}
 	}
 	if vcf_stats_tx.send(None).is_err() { warn!("Error trying to send QUIT signal to vcf_stats thread") }
-	if vcf_stats_handle.join().is_err() { warn!("Error waiting for vcf_stats thread to finish") }
+	let recal_sites = vcf_stats_handle.join().unwrap_or_else(|_| {
+		warn!("Error waiting for vcf_stats thread to finish");
+		None
+	});
+	// Output file must be closed before recalibration as it will be re-read and overwritten
+	drop(vcf_output);
+	if let Some(sites) = recal_sites {
+		// Sites for recalibration of the whole sample with --recalibrate-from
+		if let Some(f) = cfg.get_conf_str("recal_sites") { 
+			if let Err(e) = write_recal_sites(f, &sites) { error!("Error writing recalibration sites to {}: {}", f, e) }
+		} else { 
+			match recalibrate_output(&cfg, &sites) {
+				Ok(Some(rs)) => { let _ = recal_tx.send(StatJob::SetRecalStats(rs)); },
+				Ok(None) => (),
+				Err(e) => error!("Variant recalibration failed with error: {}", e),
+			}
+		}
+	}
 	if let Ok(ru_thread) = Rusage::get(RusageWho::RusageThread) {
 		info!("write_vcf_thread shutting down: user {} sys {}", ru_thread.utime(), ru_thread.stime());	
 	}
@@ -1,14 +1,17 @@
 use chrono::prelude::*;
 use std::sync::mpsc;
+use std::collections::HashMap;
 
 pub mod stats_json;
 pub mod meth_profile;
 pub mod vcf_stats;
+pub mod cnv;
 
 pub use stats_json::*;
 pub use meth_profile::MethProfile;
 pub use vcf_stats::{collect_vcf_stats, VcfStats, MUT_NAMES, SITE_TYPE_ALL, SITE_TYPE_VARIANT, SITE_TYPE_CPG_REF, SITE_TYPE_CPG_NON_REF};
 use crate::rusage::*;
+use cnv::{CnvRef, make_cnv_bins};
 
 pub struct Stats {
 	stats: stats_json::CallJson,
@@ -39,6 +42,9 @@ pub enum StatJob {
 	AddFSBaseLevelCounts(FSBaseLevelType, usize),
 	SetNonCpgReadProfile(Vec<[usize; 4]>),
 	AddVcfStats(VcfStats),
+	AddCnvRef(String, CnvRef),
+	SetContigCallable(String, usize),
+	SetRecalStats(RecalStats),
 	Quit,
 }
 
@@ -47,18 +53,28 @@ fn accumulate_stats(name: String, source: String, rx: mpsc::Receiver<StatJob>) {
 	let mut filter_stats = FSType::new();
 	let mut non_cpg_read_profile: Option<Vec<[usize; 4]>> = None;
 	let mut vcf_stats: Option<VcfStats> = None;
+	let mut cnv_refs = HashMap::new();
+	let mut callable = HashMap::new();
+	let mut recal_stats = None;
 	loop {
 		match rx.recv() {
 			Ok(StatJob::Quit) => {
 				debug!("stat_thread recieved quit signal - generating report");
+				let cnv = vcf_stats.as_mut().filter(|vs| vs.cnv_bin_size > 0).map(|vs| make_cnv_bins(vs.cnv_bin_size, cnv_refs, vs.cnv_counts.drain().collect()));
 				let mut stats = Stats::new(name, source, vcf_stats, filter_stats);
 				stats.stats.total_stats().methylation().non_cpg_read_profile = non_cpg_read_profile;
+				stats.stats.set_cnv(cnv);
+				stats.stats.set_contig_callable(&callable);
+				stats.stats.set_recalibration(recal_stats);
 				break;
 			},
 			Ok(StatJob::AddFSReadLevelCounts(fs_type, c)) => filter_stats.add_read_level_fs_counts(fs_type, c),
 			Ok(StatJob::AddFSBaseLevelCounts(fs_type, c)) => filter_stats.add_base_level_count(fs_type, c),
 			Ok(StatJob::SetNonCpgReadProfile(v)) => non_cpg_read_profile = Some(v),
 			Ok(StatJob::AddVcfStats(vs)) => vcf_stats = Some(vs),
+			Ok(StatJob::AddCnvRef(ctg, rf)) => { cnv_refs.insert(ctg, rf); },
+			Ok(StatJob::SetContigCallable(ctg, x)) => { callable.insert(ctg, x); },
+			Ok(StatJob::SetRecalStats(rs)) => recal_stats = Some(rs),
 			Err(e) => {
 				warn!("stat_thread recieved error: {}", e);
 				break
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::process::pileup::GC_BIN_SIZE;
+
+// Positions with an RMS mapping quality below this are counted as being in poorly mappable regions
+pub const CNV_LOW_MAPQ: u8 = 30;
+
+// Binned read depth for copy number estimation.  Bins are fixed size (a multiple of GC_BIN_SIZE) along each contig.  For
+// each bin we have the GC content (%) and number of callable (non N) bases of the reference, the summed depth over
+// the bin, the number of covered positions and of covered positions with low mapping quality, and the number of
+// passed heterozygous and homozygous alternate SNPs.  GC correction and segmentation are done when the report is made,
+// after the output from all contig pools has been combined
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CnvBins {
+	pub bin_size: usize,
+	#[serde(rename = "GC")]
+	pub gc: Vec<f32>,
+	pub callable: Vec<u32>,
+	pub depth: Vec<u64>,
+	pub covered: Vec<u32>,
+	#[serde(rename = "LowMapQ")]
+	pub low_mapq: Vec<u32>,
+	pub het: Vec<u32>,
+	pub hom_alt: Vec<u32>,
+}
+
+// Reference GC content and callable bases per bin for a contig
+pub struct CnvRef {
+	gc: Vec<f32>,
+	callable: Vec<u32>,
+}
+
+impl CnvRef {
+	// gc_bins has the GC count for each GC_BIN_SIZE bases of the contig (255 if the sub-bin contains non ACGT bases)
+	pub fn new(gc_bins: &[u8], bin_size: usize) -> Self {
+		let k = bin_size / (GC_BIN_SIZE as usize);
+		let mut gc = Vec::with_capacity(1 + gc_bins.len() / k);
+		let mut callable = Vec::with_capacity(gc.capacity());
+		for v in gc_bins.chunks(k) {
+			let (n, s) = v.iter().filter(|x| **x != 255).fold((0, 0), |(n, s), x| (n + 1, s + (*x as u32)));
+			gc.push(if n > 0 { (s as f32) / (n as f32) } else { 0.0 });
+			callable.push(n * (GC_BIN_SIZE as u32));
+		}
+		Self{gc, callable}
+	}
+}
+
+#[derive(Default)]
+pub struct CnvCounts {
+	depth: Vec<u64>,
+	covered: Vec<u32>,
+	low_mapq: Vec<u32>,
+	het: Vec<u32>,
+	hom_alt: Vec<u32>,
+}
+
+impl CnvCounts {
+	fn resize(&mut self, n: usize) {
+		self.depth.resize(n, 0);
+		self.covered.resize(n, 0);
+		self.low_mapq.resize(n, 0);
+		self.het.resize(n, 0);
+		self.hom_alt.resize(n, 0);
+	}
+	// snp is Some(true) for passed heterozygous SNPs and Some(false) for passed homozygous alternate SNPs
+	pub fn add(&mut self, pos: usize, bin_size: usize, depth: usize, mapq: u8, snp: Option<bool>) {
+		let bin = pos / bin_size;
+		if bin >= self.depth.len() { self.resize(bin + 1) }
+		self.depth[bin] += depth as u64;
+		self.covered[bin] += 1;
+		if mapq < CNV_LOW_MAPQ { self.low_mapq[bin] += 1 }
+		match snp {
+			Some(true) => self.het[bin] += 1,
+			Some(false) => self.hom_alt[bin] += 1,
+			None => (),
+		}
+	}
+}
+
+// Combine the reference data with the counts.  Contigs where no sequence was loaded are skipped
+pub fn make_cnv_bins(bin_size: usize, refs: HashMap<String, CnvRef>, mut counts: HashMap<String, CnvCounts>) -> HashMap<String, CnvBins> {
+	let mut h = HashMap::new();
+	for (ctg, rf) in refs.into_iter() {
+		let n = rf.gc.len();
+		let mut ct = counts.remove(&ctg).unwrap_or_default();
+		ct.resize(n);
+		h.insert(ctg, CnvBins{bin_size, gc: rf.gc, callable: rf.callable, depth: ct.depth, covered: ct.covered, low_mapq: ct.low_mapq, het: ct.het, hom_alt: ct.hom_alt});
+	}
+	h
+}
@@ -3,7 +3,10 @@ use std::clone::Clone;
 use std::ops::{AddAssign, Add, Sub};
 use std::io::Write;
 use serde::{Deserialize, Serialize};
+use utils::sex_stats::SexCoverage;
+pub use utils::sex_stats::SexStats;
 
+use super::cnv::CnvBins;
 use super::{VcfStats, MUT_NAMES, SITE_TYPE_ALL, SITE_TYPE_VARIANT, SITE_TYPE_CPG_REF, SITE_TYPE_CPG_NON_REF};
 use crate::process::vcf::write_vcf_entry::FLT_NAMES;
 
@@ -428,6 +431,25 @@ impl BasicStats {
 	pub fn non_ref_cpg(&self) -> &Counts { &self.non_ref_cpg }
 }
 
+// Callable (non N) reference bases, summed depth and covered sites, and passed heterozygous and homozygous alternate SNPs for a contig
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContigCoverage {
+	pub callable: usize,
+	pub depth: usize,
+	pub covered: usize,
+	#[serde(rename = "HetSNPs")]
+	pub het_snps: usize,
+	#[serde(rename = "HomAltSNPs")]
+	pub hom_alt_snps: usize,
+}
+
+impl ContigCoverage {
+	pub fn sex_coverage(&self) -> SexCoverage {
+		SexCoverage{callable: self.callable, depth: self.depth, het_snps: self.het_snps, hom_alt_snps: self.hom_alt_snps}
+	}
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct CSType { 
 	#[serde(flatten)]
@@ -438,11 +460,14 @@ pub struct CSType {
 	#[serde(rename = "dbSNPVariantSites")]
     #[serde(skip_serializing_if = "Option::is_none")]
 	pub dbsnp_variants: Option<Counts>,
+	#[serde(rename = "Coverage")]
+	#[serde(default)]
+	pub coverage: ContigCoverage,
 }
 
 impl CSType {
 	pub fn new() -> Self {
-		Self{basic_stats: BasicStats::new(), dbsnp_sites: None, dbsnp_variants: None }
+		Self{basic_stats: BasicStats::new(), dbsnp_sites: None, dbsnp_variants: None, coverage: ContigCoverage::default() }
 	}
 }
 
@@ -452,11 +477,60 @@ fn contig_stats_from_vcf_stats(vs: &mut VcfStats) -> HashMap<String, CSType> {
 		let basic_stats = BasicStats{snps: vb.snps, indels: vb.indels, multiallelic: vb.multiallelic, ref_cpg: vb.ref_cpg, non_ref_cpg: vb.non_ref_cpg};
 		let dbsnp_sites = if vb.dbsnp_sites.all > 0 { Some(vb.dbsnp_sites) } else { None }; 
 		let dbsnp_variants = if vb.dbsnp_variants.all > 0 { Some(vb.dbsnp_variants) } else { None }; 
-		cs.insert(k, CSType{basic_stats, dbsnp_sites, dbsnp_variants});
+		let coverage = ContigCoverage{callable: 0, depth: vb.depth, covered: vb.covered, het_snps: vb.het_snps, hom_alt_snps: vb.hom_alt_snps};
+		cs.insert(k, CSType{basic_stats, dbsnp_sites, dbsnp_variants, coverage});
 	}	
 	cs
 }
 
+// Passed variants on the mitochondrial contig with the summed estimated fraction of the alternate allele(s)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MitoVariant {
+	pub pos: usize,
+	#[serde(rename = "Ref")]
+	pub ref_allele: String,
+	pub alt: String,
+	pub alt_fraction: f32,
+	pub depth: usize,
+	pub qual: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MitoStats {
+	pub contig: String,
+	pub min_heteroplasmy: f64,
+	pub variants: Vec<MitoVariant>,
+}
+
+impl MitoStats {
+	pub fn new<S: AsRef<str>>(contig: S, min_heteroplasmy: f64) -> Self { Self{contig: contig.as_ref().to_owned(), min_heteroplasmy, variants: Vec::new()} }
+}
+
+// Variant recalibration: minimum VQSLOD and counts of variants at or above each truth sensitivity tranche
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RecalTranche {
+	pub truth_sensitivity: f64,
+	#[serde(rename = "MinVQSLOD")]
+	pub min_vqslod: f64,
+	pub known: usize,
+	pub novel: usize,
+	pub novel_ti: usize,
+	pub novel_tv: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RecalStats {
+	pub pass_sensitivity: f64,
+	pub variants: usize,
+	pub known_training_sites: usize,
+	pub bad_training_sites: usize,
+	pub tranches: Vec<RecalTranche>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TSType { 
 	#[serde(flatten)]
@@ -517,6 +591,18 @@ pub struct CallJson {
 	filter_stats: FSType,
 	contig_stats: HashMap<String, CSType>,
 	total_stats: TSType,
+	#[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+	cnv: Option<HashMap<String, CnvBins>>,
+	#[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+	sex_stats: Option<SexStats>,
+	#[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+	mito: Option<MitoStats>,
+	#[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+	recalibration: Option<RecalStats>,
 }
 
 impl CallJson {
@@ -527,6 +613,10 @@ impl CallJson {
 			filter_stats: FSType::new(),
 			contig_stats: HashMap::new(),
 			total_stats: TSType::new(),
+			cnv: None,
+			sex_stats: None,
+			mito: None,
+			recalibration: None,
 		}
 	}
 	pub fn to_writer<T: Write>(&self, wrt: T) -> Result<(), String> {
@@ -541,14 +631,21 @@ impl CallJson {
 	pub fn basic_stats(&self) -> &BasicStats { &self.total_stats.basic_stats }
 	pub fn vcf_filter_stats(&self) -> &HashMap<String, QCCounts> { &self.total_stats.vcf_filter_stats }
 	pub fn mutations(&self) -> &HashMap<String, MutCounts> { &self.total_stats.mutations }
+	pub fn set_cnv(&mut self, cnv: Option<HashMap<String, CnvBins>>) { self.cnv = cnv }
+	pub fn set_recalibration(&mut self, recal: Option<RecalStats>) { self.recalibration = recal }
+	// Set callable bases for contigs from the reference, and calculate the sex inference stats
+	pub fn set_contig_callable(&mut self, callable: &HashMap<String, usize>) {
+		for (ctg, cs) in self.contig_stats.iter_mut() { cs.coverage.callable = callable.get(ctg).copied().unwrap_or(0) }
+		self.sex_stats = SexStats::from_contig_coverage(self.contig_stats.iter().map(|(k, cs)| (k.as_str(), cs.coverage.sex_coverage())));
+	}
 	
 	// name, source, vcf_stats, filter_stats, non_cpg_read_profile);
 	pub fn from_stats<S: AsRef<str>, T: AsRef<str>>(source: S, date: T, mut vcf_stats: Option<VcfStats>, filter_stats: FSType) -> Self {
-		let (contig_stats, total_stats) = if let Some(mut vs) = vcf_stats.take() {
+		let (contig_stats, total_stats, mito) = if let Some(mut vs) = vcf_stats.take() {
 			let cs = contig_stats_from_vcf_stats(&mut vs);
 			let ts = TSType::from_vcf_stats(&vs);
-			(cs, ts)
-		} else { (HashMap::new(), TSType::new()) };
-		Self {source: source.as_ref().to_owned(), date: date.as_ref().to_owned(), filter_stats, contig_stats, total_stats}
+			(cs, ts, vs.mito.take())
+		} else { (HashMap::new(), TSType::new(), None) };
+		Self {source: source.as_ref().to_owned(), date: date.as_ref().to_owned(), filter_stats, contig_stats, total_stats, cnv: None, sex_stats: None, mito, recalibration: None}
 	}
 }
\ No newline at end of file
@@ -7,8 +7,11 @@ use crate::process::vcf::*;
 use crate::process::pileup::GC_BIN_SIZE;
 use crate::process::call_genotypes::fisher::FisherTest;
 use crate::rusage::*;
+use super::cnv::CnvCounts;
 
 const GT_HET: [bool; 10] = [false, true, true, true, false, true, true, false, true, false];
+const GT_ALLELES: [&str; 10] = ["A", "AC", "AG", "AT", "C", "CG", "CT", "G", "GT", "T"];
+const PBASE: &str = "NACGT";
 
 const MUT_AC: usize = 0;
 const MUT_AG: usize = 1;
@@ -111,6 +114,11 @@ pub struct VCFBasic {
 	pub non_ref_cpg: Counts,	
 	pub dbsnp_sites: Counts,
 	pub dbsnp_variants: Counts,
+	// Summed depth and covered sites, and passed heterozygous and homozygous alternate SNPs
+	pub depth: usize,
+	pub covered: usize,
+	pub het_snps: usize,
+	pub hom_alt_snps: usize,
 }
 
 pub struct VcfStats {
@@ -126,11 +134,15 @@ pub struct VcfStats {
 	pub fs_stats: HashMap<usize, [usize; 2]>,	
 	pub qd_stats: HashMap<usize, [usize; 2]>,	
 	pub mq_stats: HashMap<usize, [usize; 2]>,	
+	pub cnv_bin_size: usize,
+	pub cnv_counts: HashMap<String, CnvCounts>,
+	pub mito: Option<MitoStats>,
 }
 
 impl VcfStats {
-	fn new() -> Self {
+	fn new(cnv_bin_size: usize, mito: Option<MitoStats>) -> Self {
 		Self {
+			cnv_bin_size, cnv_counts: HashMap::new(), mito,
 			total_stats: VCFBasic::default(),
 			contig_stats: HashMap::new(), cov_stats: HashMap::new(),
 			fs_stats: HashMap::new(), qd_stats: HashMap::new(), mq_stats: HashMap::new(),
@@ -202,8 +214,46 @@ fn mutation_stats(vs: &mut VcfStats, cs: &CallStats) {
 	}	
 }
 
+fn mito_stats(ms: &mut MitoStats, call_stats: &[CallStats]) {
+	for cs in call_stats.iter().filter(|c| (c.flags & (CALL_STATS_SKIP | CALL_STATS_SNP)) == CALL_STATS_SNP && c.filter == 0) {
+		if let Some(alt_fraction) = cs.alt_fract {
+			let rc = PBASE.as_bytes()[cs.ref_base as usize] as char;
+			let alt: Vec<_> = GT_ALLELES[cs.gt as usize].chars().filter(|c| *c != rc).map(String::from).collect();
+			ms.variants.push(MitoVariant{pos: cs.pos + 1, ref_allele: rc.to_string(), alt: alt.join(","), alt_fraction, 
+				depth: (cs.dp1 + cs.d_inf) as usize, qual: cs.phred as usize});
+		}
+	}
+}
+
+fn is_transition(cs: &CallStats) -> Option<bool> {
+	match MUT_TYPE[cs.gt as usize][cs.ref_base as usize] {
+		MUT_NO => None,
+		MUT_AG | MUT_CT | MUT_GA | MUT_TC => Some(true),
+		_ => Some(false),
+	}
+}
+
+// Mitochondrial calls are not recalibrated as the heteroplasmy model gives very different feature distributions
+fn add_recal_sites(sites: &mut Vec<RecalSite>, call_stats: &[CallStats], bs_cfg: &BsCallConfig) {
+	let vcf_rid = bs_cfg.ctg_vcf_id(call_stats[0].sam_tid).expect("Contig not in VCF list");
+	for cs in call_stats.iter().filter(|c| (c.flags & (CALL_STATS_SKIP | CALL_STATS_SNP)) == CALL_STATS_SNP && c.alt_fract.is_none()) {
+		sites.push(RecalSite::new(cs, vcf_rid, (cs.flags & CALL_STATS_RS_FOUND) != 0, is_transition(cs)))
+	}
+}
+
 fn handle_stats(call_stats: &[CallStats], vcf_stats: &mut VcfStats, bin_dist: &mut BinDist, bs_cfg: &BsCallConfig) {
 	let cname = bs_cfg.ctg_name(call_stats[0].sam_tid).to_owned();
+	if bs_cfg.ctg_is_mito(call_stats[0].sam_tid) {
+		if let Some(ms) = vcf_stats.mito.as_mut() { mito_stats(ms, call_stats) }
+	}
+	let bin_size = vcf_stats.cnv_bin_size;
+	if bin_size > 0 {
+		let cnv = vcf_stats.cnv_counts.entry(cname.clone()).or_default();
+		for cs in call_stats.iter() {
+			let snp = if (cs.flags & (CALL_STATS_SKIP | CALL_STATS_MULTI)) == 0 && (cs.flags & CALL_STATS_SNP) != 0 && cs.filter == 0 { Some(GT_HET[cs.gt as usize]) } else { None };
+			cnv.add(cs.pos, bin_size, (cs.d_inf + cs.dp1) as usize, cs.mq, snp);
+		}
+	}
 	let mut ctg_stats = vcf_stats.contig_stats.entry(cname).or_insert_with(VCFBasic::default);
 	for cs in call_stats.iter() {
 		let flags = get_basic_stats(cs);
@@ -212,7 +262,12 @@ fn handle_stats(call_stats: &[CallStats], vcf_stats: &mut VcfStats, bin_dist: &m
 		gcov.all += 1;
 		if cs.gc != 255 { gcov.gc_pcent[cs.gc as usize] += 1 };
 		if (cs.cpg_status & 7) == 4 { gcov.cpg[if (cs.cpg_status & CPG_STATUS_REF_CPG) != 0 { 0 } else { 1 }] += 1 }
+		ctg_stats.depth += dp;
+		if dp > 0 { ctg_stats.covered += 1 }
 		if (cs.flags & CALL_STATS_SKIP) != 0 { continue; }
+		if (flags & BS_SNPS) != 0 && cs.filter == 0 {
+			if GT_HET[cs.gt as usize] { ctg_stats.het_snps += 1 } else { ctg_stats.hom_alt_snps += 1 }
+		}
 		let rs_found = (cs.flags & CALL_STATS_RS_FOUND) != 0;
 		add_basic_stats(&mut ctg_stats, cs.filter, rs_found, flags);
 		add_basic_stats(&mut vcf_stats.total_stats, cs.filter, rs_found, flags);
@@ -228,10 +283,13 @@ fn handle_stats(call_stats: &[CallStats], vcf_stats: &mut VcfStats, bin_dist: &m
 	}
 }
 
-pub fn collect_vcf_stats(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<Option<Vec<CallStats>>>, stat_tx: mpsc::Sender<StatJob>) {
+// Returns the feature vectors for variant sites if recalibration has been requested
+pub fn collect_vcf_stats(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<Option<Vec<CallStats>>>, stat_tx: mpsc::Sender<StatJob>) -> Option<Vec<RecalSite>> {
 	info!("collect_vcf_stats_thread starting up");
-	let mut vcf_stats = VcfStats::new();
+	let mito = bs_cfg.mito_tid().map(|tid| MitoStats::new(bs_cfg.ctg_name(tid), bs_cfg.conf_hash.get_float("min_heteroplasmy")));
+	let mut vcf_stats = VcfStats::new(bs_cfg.conf_hash.get_int("cnv_bin_size"), mito);
 	let mut bin_dist = BinDist::new(100);
+	let mut recal_sites = if bs_cfg.conf_hash.get_bool("recalibrate") { Some(Vec::new()) } else { None };
 	loop {
 		match rx.recv() {
 			Ok(None) => break,
@@ -239,6 +297,7 @@ pub fn collect_vcf_stats(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<Option<Ve
 				debug!("Received new call stats block");
 				if !call_stats.is_empty() {
 					handle_stats(&call_stats, &mut vcf_stats, &mut bin_dist, &bs_cfg);
+					if let Some(sites) = recal_sites.as_mut() { add_recal_sites(sites, &call_stats, &bs_cfg) }
 				}
 			},
 			Err(e) => {
@@ -251,5 +310,6 @@ pub fn collect_vcf_stats(bs_cfg: Arc<BsCallConfig>, rx: mpsc::Receiver<Option<Ve
 	if let Ok(ru_thread) = Rusage::get(RusageWho::RusageThread) {
 		info!("collect_vcf_stats_thread shutting down: user {} sys {}", ru_thread.utime(), ru_thread.stime());	
 	}
+	recal_sites
  }
 
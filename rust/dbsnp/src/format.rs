@@ -0,0 +1,136 @@
+// Constants and low level IO functions shared between the reader and writer
+
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+pub const IDX_MAGIC: u32 = 0xd7278434;
+pub const IDX_VERSION: u8 = 3;
+
+// Size of main index header
+pub const HEADER_SIZE: u64 = 32;
+
+// Target size of uncompressed data blocks.  Smaller blocks make random access cheaper at the cost of
+// slightly worse compression
+pub const BLOCK_SIZE: usize = 1048576;
+
+// Maximum length of allele description (REF,ALT1,ALT2...)
+pub const MAX_ALLELE_DESC_LEN: usize = 128;
+
+// Maximum number of digits in an rs number
+pub const MAX_RS_LEN: usize = 254;
+
+// rs number used for SNPs without an rs ID
+pub const NO_RS: &str = "0";
+
+pub fn new_err(s: String) -> io::Error {
+	Error::new(ErrorKind::Other, s)
+}
+
+// Length of allele record at start of buffer and number of alternate alleles
+pub fn allele_rec_len(buf: &[u8], n_pops: usize) -> Option<(usize, usize)> {
+	let l = buf.iter().position(|c| *c == 0)?;
+	let n_alt = if l == 0 { 0 } else { buf[..l].iter().filter(|c| **c == b',').count() };
+	Some((l + 1 + 4 * n_alt * n_pops, n_alt))
+}
+
+pub fn write_bin_inc<W: Write>(mut file: W, x: usize) -> io::Result<()> {
+	if x < 64 { file.write_all(&[x as u8]) }
+	else if x < 256 { file.write_all(&[64, x as u8]) }
+	else if x < 65536 {
+		file.write_all(&[128])?;
+		write_u16(file, &[x as u16])
+	} else {
+		file.write_all(&[192])?;
+		write_u32(file, &[x as u32])
+	}
+}
+
+pub fn read_bin_inc<R: Read>(file: &mut R) -> io::Result<usize> {
+	let x = read_1(file)?;
+	Ok(match x & 192 {
+		0 => x as usize,
+		64 => read_1(file)? as usize,
+		128 => {
+			let mut x16 = [0u16; 1];
+			read_u16(file, &mut x16)?;
+			x16[0] as usize
+		},
+		_ => {
+			let mut x32 = [0u32; 1];
+			read_u32(file, &mut x32)?;
+			x32[0] as usize
+		},
+	})
+}
+
+pub fn get_string(buf: &[u8]) -> io::Result<(String, &[u8])> {
+	if !buf.is_empty() {
+		let mut s = String::new();
+		for (i, c) in buf.iter().copied().enumerate() {
+			if c == 0 {
+				return Ok((s, &buf[i + 1..]));
+			} else { s.push(c as char) }
+		}
+	}
+	Err(new_err("Bad format: String terminator not found".to_string()))
+}
+
+pub fn read_1<R: Read>(reader: &mut R) -> io::Result<u8> {
+	let mut x = [0u8; 1];
+	reader.read_exact(&mut x).map(|_| x[0])
+}
+
+pub fn read_u16<R: Read>(file: &mut R, buf: &mut[u16]) -> io::Result<()> {
+	let mut p = [0u8; 2];
+	for x in buf.iter_mut() {
+		file.read_exact(&mut p)?;
+		*x = u16::from_le_bytes(p);
+	}
+	Ok(())
+}
+
+pub fn read_u32<R: Read>(file: &mut R, buf: &mut[u32]) -> io::Result<()> {
+	let mut p = [0u8; 4];
+	for x in buf.iter_mut() {
+		file.read_exact(&mut p)?;
+		*x = u32::from_le_bytes(p);
+	}
+	Ok(())
+}
+
+pub fn read_u64<R: Read>(file: &mut R, buf: &mut[u64]) -> io::Result<()> {
+	let mut p = [0u8; 8];
+	for x in buf.iter_mut() {
+		file.read_exact(&mut p)?;
+		*x = u64::from_le_bytes(p);
+	}
+	Ok(())
+}
+
+pub fn read_u128<R: Read>(file: &mut R, buf: &mut[u128]) -> io::Result<()> {
+	let mut p = [0u8; 16];
+	for x in buf.iter_mut() {
+		file.read_exact(&mut p)?;
+		*x = u128::from_le_bytes(p);
+	}
+	Ok(())
+}
+
+pub fn write_u16<W: Write>(mut file: W, buf: &[u16]) -> io::Result<()> {
+	for x in buf.iter() { file.write_all(&x.to_le_bytes())? }
+	Ok(())
+}
+
+pub fn write_u32<W: Write>(mut file: W, buf: &[u32]) -> io::Result<()> {
+	for x in buf.iter() { file.write_all(&x.to_le_bytes())? }
+	Ok(())
+}
+
+pub fn write_u64<W: Write>(mut file: W, buf: &[u64]) -> io::Result<()> {
+	for x in buf.iter() { file.write_all(&x.to_le_bytes())? }
+	Ok(())
+}
+
+pub fn write_u128<W: Write>(mut file: W, buf: &[u128]) -> io::Result<()> {
+	for x in buf.iter() { file.write_all(&x.to_le_bytes())? }
+	Ok(())
+}
@@ -0,0 +1,12 @@
+//! Reading and writing of the binary dbSNP index used by gemBS (bs_call, snpxtr, dbsnp_index).
+//! The file format is described at the top of write.rs.
+#[macro_use]
+extern crate log;
+
+mod format;
+pub mod read;
+pub mod write;
+
+pub use read::{DBSnpIndex, DBSnpContig, DBSnpEntry, SnpIter};
+pub use write::{BinBuilder, ContigEncoder, EncodedContig, CompressBlock, IndexWriter, FORMAT_VERSION};
+pub use format::{MAX_ALLELE_DESC_LEN, MAX_RS_LEN};
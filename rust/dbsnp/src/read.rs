@@ -0,0 +1,501 @@
+use std::io;
+use std::fs::File;
+use std::sync::Arc;
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::collections::HashMap;
+
+use memmap2::Mmap;
+use zstd::block::decompress_to_buffer;
+
+use crate::format::*;
+
+/// dbSNP index file.  The file is memory mapped and data blocks are decompressed on demand
+pub struct DBSnpIndex {
+	filename: String,
+	shared: Arc<SharedData>,
+	header: String,
+	populations: Vec<String>,
+	ctgs: Vec<Arc<ContigIndex>>,
+	ctg_hash: HashMap<String, usize>,
+}
+
+impl DBSnpIndex {
+	pub fn new<S: AsRef<str>>(name: S) -> io::Result<Self> {
+		let filename = name.as_ref();
+		let file = File::open(filename)?;
+		// Safety: the index file is not expected to be modified while we are using it
+		let mmap = unsafe { Mmap::map(&file)? };
+		debug!("Reading dbSNP header from {}", filename);
+		let mut p = &mmap[..];
+		let mut td = [0u32; 1];
+		read_u32(&mut p, &mut td)?;
+		if td[0] != IDX_MAGIC { return Err(new_err(format!("Invalid format: bad magic number {:x}",td[0]))) }
+		trace!("Magic number OK");
+		let mut vs = [0u8; 4];
+		io::Read::read_exact(&mut p, &mut vs)?;
+		let version = vs[0];
+		if version != 2 && version != 3 { return Err(new_err(format!("Invalid version number {} (expected 2 or 3)", version))) }
+		let mut td1 = [0u64; 3];
+		read_u64(&mut p, &mut td1)?;
+		let (hdr_off, bufsize, csize) = (td1[0] as usize, td1[1] as usize, td1[2] as usize);
+		if hdr_off + csize + 4 > mmap.len() { return Err(new_err("Invalid format: file truncated".to_string())) }
+		let cbuf = &mmap[hdr_off..hdr_off + csize];
+		let mut p = &mmap[hdr_off + csize..];
+		read_u32(&mut p, &mut td)?;
+		if td[0] != IDX_MAGIC { return Err(new_err("Invalid format: bad second magic number".to_string())) }
+		trace!("Header data read in OK");
+		let mut ubuf: Vec<u8> = vec!(0; bufsize);
+		let sz = decompress_to_buffer(cbuf, &mut ubuf)?;
+		trace!("Header data uncompressed OK ({} bytes)", sz);
+		if sz < 4 { return Err(new_err("Invalid format: short header".to_string())) }
+		let n_ctgs = u32::from_le_bytes((&ubuf[0..4]).try_into().unwrap()) as usize;
+		let mut p = &ubuf[4..sz];
+		let mut ctg_hdrs = Vec::with_capacity(n_ctgs);
+		for _ in 0..n_ctgs {
+			ctg_hdrs.push(get_ctg_header(p)?);
+			p = &p[16..];
+		}
+		let (header, mut p) = get_string(p)?;
+		let mut ctg_names = Vec::with_capacity(n_ctgs);
+		for _ in 0..n_ctgs {
+			let (s, p1) = get_string(p)?;
+			p = p1;
+			ctg_names.push(s);
+		}
+		// Version 3 indices have a list of populations with allele frequencies
+		let mut populations = Vec::new();
+		if version > 2 {
+			if p.is_empty() { return Err(new_err("Invalid format: missing population list".to_string())) }
+			let n_pops = p[0];
+			p = &p[1..];
+			for _ in 0..n_pops {
+				let (s, p1) = get_string(p)?;
+				p = p1;
+				populations.push(s);
+			}
+		}
+		if !p.is_empty() { return Err(new_err("Error with dbSNP index header - excess data".to_string())) }
+		let mut ctgs = Vec::with_capacity(n_ctgs);
+		let mut ctg_hash = HashMap::with_capacity(n_ctgs);
+		for (name, (min_bin, max_bin, offset)) in ctg_names.drain(..).zip(ctg_hdrs.drain(..)) {
+			trace!("Inserting ctg {} {}-{}", name, min_bin, max_bin);
+			let blocks = scan_blocks(&mmap, offset as usize, min_bin, max_bin)?;
+			ctg_hash.insert(name.clone(), ctgs.len());
+			ctgs.push(Arc::new(ContigIndex{name, min_bin, max_bin, blocks}));
+		}
+		trace!("Contigs read in OK");
+		info!("Read dbSNP header from {} with data on {} contigs", filename, n_ctgs);
+		info!("Header line: {}", header);
+		if !populations.is_empty() { info!("Allele frequencies available for: {}", populations.join(", ")) }
+		let shared = Arc::new(SharedData{mmap, version, n_pops: populations.len(), bufsize});
+		Ok(Self{filename: filename.to_owned(), shared, header, populations, ctgs, ctg_hash})
+	}
+	pub fn filename(&self) -> &str { &self.filename }
+	pub fn header(&self) -> &str { &self.header }
+	pub fn version(&self) -> u8 { self.shared.version }
+	pub fn populations(&self) -> &[String] { &self.populations }
+	/// Contig names in the order they are stored in the index
+	pub fn contig_names(&self) -> impl Iterator<Item = &str> { self.ctgs.iter().map(|c| c.name.as_str()) }
+	/// First and last positions (1 offset) that could have SNPs for a contig
+	pub fn contig_range<S: AsRef<str>>(&self, name: S) -> Option<(usize, usize)> {
+		self.ctg_hash.get(name.as_ref()).map(|ix| {
+			let c = &self.ctgs[*ix];
+			(c.min_bin << 8, (c.max_bin << 8) | 255)
+		})
+	}
+	pub fn get_dbsnp_contig<S: AsRef<str>>(&self, name: S) -> Option<DBSnpContig> {
+		self.ctg_hash.get(name.as_ref()).map(|ix| DBSnpContig{idx: self.ctgs[*ix].clone(), shared: self.shared.clone(), cache: RefCell::new(None)})
+	}
+	/// Iterator over SNPs in a region of a contig (1 offset, inclusive)
+	pub fn query<S: AsRef<str>>(&self, name: S, start: usize, end: usize) -> Option<SnpIter> {
+		self.get_dbsnp_contig(name).map(|c| c.into_iter_range(start, end))
+	}
+}
+
+/// dbSNP data for a contig.  Decompressed data blocks are cached, so sequential lookups are efficient.
+/// Clones share the underlying index but not the cache
+#[derive(Clone)]
+pub struct DBSnpContig {
+	idx: Arc<ContigIndex>,
+	shared: Arc<SharedData>,
+	cache: RefCell<Option<Arc<DecodedBlock>>>,
+}
+
+impl DBSnpContig {
+	pub fn name(&self) -> &str { &self.idx.name }
+
+	/// Look up rs ID and selected status for position x (0 offset).  For SNPs without an rs ID
+	/// an ID of the form chrom:pos:ref:alt is returned
+	pub fn lookup_rs(&self, x: usize) -> Option<(String, bool)> {
+		let (bin, n) = self.get_entry_idx(x + 1)?;
+		let (rs, selected) = bin.rs(n);
+		if rs[2..] == *NO_RS {
+			let e = self.mk_entry(&bin, n, x + 1);
+			Some((e.rs, selected))
+		} else { Some((rs, selected)) }
+	}
+
+	/// Look up full dbSNP entry (rs ID, alleles and frequencies) for position x (0 offset)
+	pub fn lookup(&self, x: usize) -> Option<DBSnpEntry> {
+		let (bin, n) = self.get_entry_idx(x + 1)?;
+		Some(self.mk_entry(&bin, n, x + 1))
+	}
+
+	/// Iterator over SNPs between start and end (1 offset, inclusive)
+	pub fn into_iter_range(self, start: usize, end: usize) -> SnpIter {
+		let max = (self.idx.max_bin << 8) | 255;
+		SnpIter{pos: start.max(self.idx.min_bin << 8), end: end.min(max), ctg: self}
+	}
+
+	// Get entry n from bin, generating an ID if required
+	fn mk_entry(&self, bin: &BinRef, n: usize, pos: usize) -> DBSnpEntry {
+		let mut e = bin.entry(n, pos, self.shared.n_pops);
+		if e.rs[2..] == *NO_RS {
+			e.rs = match e.ref_allele() {
+				Some(r) => format!("{}:{}:{}:{}", self.idx.name, pos, r, e.alt_alleles().join(",")),
+				None => format!("{}:{}", self.idx.name, pos),
+			}
+		}
+		e
+	}
+
+	// Get block covering bin bn if present
+	fn get_block(&self, bn: usize) -> io::Result<Option<Arc<DecodedBlock>>> {
+		if let Some(blk) = self.cache.borrow().as_ref() {
+			if blk.covers(bn) { return Ok(Some(blk.clone())) }
+		}
+		let blocks = &self.idx.blocks;
+		let i = blocks.partition_point(|b| b.first_bin <= bn);
+		if i == 0 { return Ok(None) }
+		let blk = Arc::new(self.shared.decode_block(&blocks[i - 1])?);
+		let covers = blk.covers(bn);
+		*self.cache.borrow_mut() = Some(blk.clone());
+		Ok(if covers { Some(blk) } else { None })
+	}
+
+	// Get first bin number > bn that has data in the index
+	fn next_block_start(&self, bn: usize) -> Option<usize> {
+		let blocks = &self.idx.blocks;
+		blocks.get(blocks.partition_point(|b| b.first_bin <= bn)).map(|b| b.first_bin)
+	}
+
+	// Get bin and entry number for position x (1 offset)
+	fn get_entry_idx(&self, x: usize) -> Option<(BinRef, usize)> {
+		let bn = x >> 8;
+		if bn < self.idx.min_bin || bn > self.idx.max_bin { return None }
+		let blk = match self.get_block(bn) {
+			Ok(b) => b?,
+			Err(e) => {
+				error!("Error reading dbSNP data for {}: {}", self.idx.name, e);
+				return None
+			},
+		};
+		let n = blk.bins[bn - blk.first_bin].as_ref()?.entry_idx(x & 255)?;
+		Some((BinRef{blk, ix: bn}, n))
+	}
+}
+
+/// dbSNP entry for a site.  Alleles and frequencies are only available from version 3 indices
+#[derive(Debug, Clone, PartialEq)]
+pub struct DBSnpEntry {
+	/// Position (1 offset)
+	pub pos: usize,
+	pub rs: String,
+	pub selected: bool,
+	/// Reference allele followed by the alternate alleles (empty if not known)
+	pub alleles: Vec<String>,
+	/// Alternate allele frequencies for each population (population major)
+	pub afs: Vec<Option<f32>>,
+}
+
+impl DBSnpEntry {
+	pub fn ref_allele(&self) -> Option<&str> { self.alleles.first().map(|s| s.as_str()) }
+	pub fn alt_alleles(&self) -> &[String] { if self.alleles.is_empty() { &[] } else { &self.alleles[1..] } }
+	/// Frequency of alternate allele `alt` (0 offset) in population `pop`
+	pub fn af(&self, pop: usize, alt: usize) -> Option<f32> {
+		let n_alt = self.alt_alleles().len();
+		if alt < n_alt { self.afs.get(pop * n_alt + alt).copied().flatten() } else { None }
+	}
+}
+
+/// Iterator over the SNPs in a contig region
+pub struct SnpIter {
+	ctg: DBSnpContig,
+	pos: usize,
+	end: usize,
+}
+
+impl Iterator for SnpIter {
+	type Item = io::Result<DBSnpEntry>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.pos <= self.end {
+			let bn = self.pos >> 8;
+			let blk = match self.ctg.get_block(bn) {
+				Ok(Some(b)) => b,
+				Ok(None) => {
+					// Skip to start of next block
+					match self.ctg.next_block_start(bn) {
+						Some(b) => self.pos = b << 8,
+						None => break,
+					}
+					continue
+				},
+				Err(e) => {
+					self.end = 0;
+					return Some(Err(e))
+				},
+			};
+			if let Some(bin) = &blk.bins[bn - blk.first_bin] {
+				if let Some(off) = bin.next_set((self.pos & 255) as u32) {
+					let x = (bn << 8) | (off as usize);
+					if x > self.end { break }
+					self.pos = x + 1;
+					let n = bin.entry_idx(off as usize).unwrap();
+					return Some(Ok(self.ctg.mk_entry(&BinRef{blk: blk.clone(), ix: bn}, n, x)))
+				}
+			}
+			self.pos = (bn + 1) << 8;
+		}
+		self.pos = self.end + 1;
+		None
+	}
+}
+
+//
+// Everything below is private to the module
+//
+
+struct SharedData {
+	mmap: Mmap,
+	version: u8,
+	n_pops: usize,
+	bufsize: usize,
+}
+
+impl SharedData {
+	fn decode_block(&self, bi: &BlockIndex) -> io::Result<DecodedBlock> {
+		let cbuf = &self.mmap[bi.offset..bi.offset + bi.csize];
+		let mut ubuf: Vec<u8> = vec!(0; self.bufsize);
+		let sz = decompress_to_buffer(cbuf, &mut ubuf)?;
+		trace!("Block data uncompressed OK");
+		let bins = load_bins(&ubuf[..sz], self.version, self.n_pops)?;
+		Ok(DecodedBlock{first_bin: bi.first_bin, bins})
+	}
+}
+
+struct BlockIndex {
+	first_bin: usize,
+	offset: usize,
+	csize: usize,
+}
+
+struct ContigIndex {
+	name: String,
+	min_bin: usize,
+	max_bin: usize,
+	blocks: Vec<BlockIndex>,
+}
+
+struct DecodedBlock {
+	first_bin: usize,
+	bins: Vec<Option<DBSnpBin>>,
+}
+
+impl DecodedBlock {
+	fn covers(&self, bn: usize) -> bool { bn >= self.first_bin && bn < self.first_bin + self.bins.len() }
+}
+
+// Reference to a bin within a decoded block
+struct BinRef {
+	blk: Arc<DecodedBlock>,
+	ix: usize,
+}
+
+impl BinRef {
+	fn bin(&self) -> &DBSnpBin { self.blk.bins[self.ix - self.blk.first_bin].as_ref().unwrap() }
+	fn rs(&self, n: usize) -> (String, bool) { self.bin().rs(n) }
+	fn entry(&self, n: usize, pos: usize, n_pops: usize) -> DBSnpEntry { self.bin().entry(n, pos, n_pops) }
+}
+
+// Scan the block headers for a contig
+fn scan_blocks(mmap: &Mmap, mut off: usize, min_bin: usize, max_bin: usize) -> io::Result<Vec<BlockIndex>> {
+	let mut blocks = Vec::new();
+	loop {
+		if off + 8 > mmap.len() { return Err(new_err("Invalid format: file truncated".to_string())) }
+		let csize = u64::from_le_bytes((&mmap[off..off + 8]).try_into().unwrap()) as usize;
+		if csize == 0 { break }
+		if off + 12 + csize > mmap.len() { return Err(new_err("Invalid format: file truncated".to_string())) }
+		let first_bin = u32::from_le_bytes((&mmap[off + 8..off + 12]).try_into().unwrap()) as usize;
+		if first_bin < min_bin || first_bin > max_bin || blocks.last().map(|b: &BlockIndex| b.first_bin >= first_bin).unwrap_or(false) {
+			return Err(new_err("Error: index data corrupt".to_string()))
+		}
+		blocks.push(BlockIndex{first_bin, offset: off + 12, csize});
+		off += 12 + csize;
+	}
+	Ok(blocks)
+}
+
+struct DBSnpBin {
+	mask: [u128; 2],
+	name_len: Box<[u8]>,
+	name_buf: Box<[u8]>,
+	// Allele data (version 3 only)
+	allele_buf: Option<Box<[u8]>>,
+}
+
+const DTAB: [char; 16] = [ '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '?', '?', '?', '?', '?', '?'];
+
+const WTAB: [&str; 256] = [
+	"00", "01", "02", "03", "04", "05", "06", "07", "08", "09", "0?", "0?", "0?", "0?", "0", "0",
+	"10", "11", "12", "13", "14", "15", "16", "17", "18", "19", "1?", "1?", "1?", "1?", "1", "1",
+	"20", "21", "22", "23", "24", "25", "26", "27", "28", "29", "2?", "2?", "2?", "2?", "2", "2",
+	"30", "31", "32", "33", "34", "35", "36", "37", "38", "39", "3?", "3?", "3?", "3?", "3", "3",
+	"40", "41", "42", "43", "44", "45", "46", "47", "48", "49", "4?", "4?", "4?", "4?", "4", "4",
+	"50", "51", "52", "53", "54", "55", "56", "57", "58", "59", "5?", "5?", "5?", "5?", "5", "5",
+	"60", "61", "62", "63", "64", "65", "66", "67", "68", "69", "6?", "6?", "6?", "6?", "6", "6",
+	"70", "71", "72", "73", "74", "75", "76", "77", "78", "79", "7?", "7?", "7?", "7?", "7", "7",
+	"80", "81", "82", "83", "84", "85", "86", "87", "88", "89", "8?", "8?", "8?", "8?", "8", "8",
+	"90", "91", "92", "93", "94", "95", "96", "97", "98", "99", "9?", "9?", "9?", "9?", "9", "9",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+	"?0", "?1", "?2", "?3", "?4", "?5", "?6", "?7", "?8", "?9", "??", "??", "??", "??", "?", "?",
+];
+
+impl DBSnpBin {
+	// Index of entry in bin for position ix
+	fn entry_idx(&self, ix: usize) -> Option<usize> {
+		let (k, mk) = if ix < 128 { (0, 1u128 << ix) } else { (1, 1u128 << (ix & 127)) };
+		if (self.mask[k] & mk) != 0 {
+			Some(if k == 0 {
+				(self.mask[k] & (mk - 1)).count_ones() as usize
+			} else {
+				(self.mask[0].count_ones() + (self.mask[1] & (mk - 1)).count_ones()) as usize
+			})
+		} else { None }
+	}
+
+	// Next position >= ix with an entry
+	fn next_set(&self, ix: u32) -> Option<u32> {
+		if ix < 128 {
+			let m = self.mask[0] & (!0u128 << ix);
+			if m != 0 { return Some(m.trailing_zeros()) }
+		}
+		let m = self.mask[1] & if ix > 128 { !0u128 << (ix - 128) } else { !0u128 };
+		if m != 0 { Some(128 + m.trailing_zeros()) } else { None }
+	}
+
+	// rs ID and selected status for entry n
+	fn rs(&self, n: usize) -> (String, bool) {
+		let start_x: usize = self.name_len[0..n].iter().map(|x| *x as usize).sum();
+		let mut rs = String::with_capacity(self.name_len[n] as usize + 2);
+		rs.push_str("rs");
+		let mut it = self.name_buf[start_x>>1..].iter();
+		if (start_x & 1) != 0 {	rs.push(DTAB[(it.next().expect("Short name buf") & 0xf) as usize]) }
+		let select = loop {
+			let c = it.next().expect("Short name buf");
+			if (c & 0xf0) >= 0xe0 { break (c & 0xf0) == 0xf0 }
+			rs.push_str(WTAB[*c as usize]);
+			if (c & 0xf) >= 0xe { break (c & 0xf) == 0xf }
+		};
+		(rs, select)
+	}
+
+	// Alleles and frequencies for entry n
+	fn alleles(&self, n: usize, n_pops: usize) -> Option<(Vec<String>, Vec<Option<f32>>)> {
+		let mut p = &self.allele_buf.as_ref()?[..];
+		for _ in 0..n { p = &p[allele_rec_len(p, n_pops)?.0..] }
+		let (l, _) = allele_rec_len(p, n_pops)?;
+		let slen = p.iter().position(|c| *c == 0)?;
+		if slen == 0 { return None }
+		let alleles = p[..slen].split(|c| *c == b',').map(|a| a.iter().map(|c| *c as char).collect()).collect();
+		let afs = p[slen + 1..l].chunks_exact(4).map(|v| {
+			let z = f32::from_le_bytes(v.try_into().unwrap());
+			if z.is_nan() { None } else { Some(z) }
+		}).collect();
+		Some((alleles, afs))
+	}
+
+	fn entry(&self, n: usize, pos: usize, n_pops: usize) -> DBSnpEntry {
+		let (rs, selected) = self.rs(n);
+		let (alleles, afs) = self.alleles(n, n_pops).unwrap_or_default();
+		DBSnpEntry{pos, rs, selected, alleles, afs}
+	}
+}
+
+fn load_bins(mut buf: &[u8], version: u8, n_pops: usize) -> io::Result<Vec<Option<DBSnpBin>>> {
+	let format_err = || Err(new_err("Format error".to_string()));
+	let mut bins = Vec::with_capacity(256);
+	let mut first = true;
+	let mut mask = [0u128; 2];
+	loop {
+		let bin_inc = if first {
+			first = false;
+			0
+		} else {
+			match read_bin_inc(&mut buf) {
+				Ok(x) => x,
+				Err(e) if e.kind() == io::ErrorKind::UnexpectedEof =>  break,
+				Err(e) => return Err(e),
+			}
+		};
+		for _ in 0..bin_inc { bins.push(None) }
+		read_u128(&mut buf, &mut mask)?;
+		let n = mask[0].count_ones() + mask[1].count_ones();
+		if n == 0 { return format_err() }
+		let mut name_buf = Vec::new();
+		let mut name_len = Vec::with_capacity(n as usize);
+		let mut len = 0;
+		let mut ix = 0;
+		loop {
+			let x = read_1(&mut buf)?;
+			name_buf.push(x);
+			if len == 255 { return format_err() }
+			len += 1;
+			if (x & 0xf0) >= 0xe0 {
+				name_len.push(len);
+				ix += 1;
+				if ix == n { break; }
+				len = 0;
+			}
+			if len == 255 { return format_err() }
+			len += 1;
+			if (x & 0xf) >= 0xe {
+				name_len.push(len);
+				ix += 1;
+				if ix == n { break; }
+				len = 0;
+			}
+		}
+		// Allele records (one per snp) for version 3
+		let allele_buf = if version > 2 {
+			let mut l = 0;
+			for _ in 0..n {
+				match allele_rec_len(&buf[l..], n_pops) {
+					Some((x, _)) if l + x <= buf.len() => l += x,
+					_ => return format_err(),
+				}
+			}
+			let (a, b) = buf.split_at(l);
+			buf = b;
+			Some(a.to_vec().into_boxed_slice())
+		} else { None };
+		bins.push(Some(DBSnpBin{mask, name_buf: name_buf.into_boxed_slice(), name_len: name_len.into_boxed_slice(), allele_buf}));
+	}
+	Ok(bins)
+}
+
+fn get_ctg_header(buf: &[u8]) -> io::Result<(usize, usize, u64)> {
+	if buf.len() >= 16 {
+		let min_bin = u32::from_le_bytes((&buf[0..4]).try_into().unwrap()) as usize;
+		let max_bin = u32::from_le_bytes((&buf[4..8]).try_into().unwrap()) as usize;
+		let file_offset = u64::from_le_bytes((&buf[8..16]).try_into().unwrap());
+		Ok((min_bin, max_bin, file_offset))
+	} else {
+		Err(new_err("Bad format: Failed to read in contig header".to_string()))
+	}
+}
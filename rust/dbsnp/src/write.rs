@@ -0,0 +1,433 @@
+use std::fs::File;
+use std::path::Path;
+use std::io::{self, BufWriter, Write, Seek, SeekFrom};
+
+use zstd::block::compress;
+
+use crate::format::*;
+
+// Overall format of binary dbSNP index file
+//
+// Main index
+//
+// Contig data (per contig)
+//   Compressed data blocks (multiple blocks per contig)
+//     snp bins (multiple per data block)
+//
+// Contig information + index (compressed)
+//
+//
+// Bit level format of binary dbSNP index file
+//
+// Main Index
+//
+// Name           Size         Description
+// ---------------------------------------------------------------------------
+// magic          32           Magic number (0xd7278434)
+// version        8            Version number (should be 3; version 2 files lack the allele data and population list)
+// reserved       24           For future use
+// header_idx     64           File offset of contig header
+// ubuf_size      64           Maximum size of uncompressed data block
+// cheader_size   64           Compressed contig header size
+//
+// Contig data block [ multiple blocks per contig ]
+//
+// Name           Size         Description
+// ---------------------------------------------------------------------------
+// csize          64           Compressed size of data block (size == 0 indicates last block for contig)
+// bin            32           Bin number of first bin in block
+// cdata                       compressed data block comprising multiple snp bins (see below)
+//
+//   Snp bin (multiple per data block)
+//
+//   Name            Size             Description
+//   -------------------------------------------------------------------------
+//   bin_inc         1, 2, 3 or 5     Increment in bin number from previous bin * NOT INCLUDED FOR FIRST BIN IN BLOCK (assumed zero) *
+//
+//      let x = increment
+//
+//      x < 64:
+//        bin_inc         8             x (increment)
+//      64 < x < 256:
+//        indicator       8             64
+//        bin_inc         8             x
+//      256 < 64 < 65536:
+//        indicator       8             128
+//        bin_inc         16            x
+//      x > 65536:
+//        indicator       8             192
+//        bin_inc         32            x
+//
+//   mask0           128              bit mask for snp presence (first 128 sites)
+//   mask1           128              bit mask for snp presence (second 128 sites)
+//   rs numbers                       rs numbers for individual snps stored as packed BCD with each digit taking 4 bits (nybble), with the
+//                                    first digit in the high nybble  and the second in the low nybble.
+//                                    Digits 0-9 represented by bit patterns 0000 - 1001, terminating characters encoded as 1110 or 1111 for
+//                                    snps that are preselected (1111) or not (1110).  Bit patterns 1010, 1011, 1100, 1101 are illegal and should not occur.
+//                                    The data for adjacent snps are packed together, so a snp does not need to start on a byte boundary.
+//                                    An rs number of 0 indicates a snp without an rs ID; readers generate an ID of the form chrom:pos:ref:alt.
+//   allele data                      (version 3 only) one record per snp, in the same order as the rs numbers (see below)
+//
+//     Allele record
+//
+//     Name            Size             Description
+//     -----------------------------------------------------------------------
+//     alleles                          Null terminated string with the reference and alternate alleles separated by commas
+//                                      (i.e., REF,ALT1,ALT2...).  An empty string indicates that the alleles are not known
+//     afs             32 * n * k       Alternate allele frequencies as f32 (NaN if missing) for each of the n populations and k alternate alleles
+//                                      (all alleles for the first population, then the second population etc.).  Not present if alleles not known
+//
+// Contig information (compressed)
+//
+// Name           Size         Description
+// ---------------------------------------------------------------------------
+// n_contigs       32           Number of contigs
+// contig_headers  n * 128      one header block per contig
+//
+//   Contig header block
+//
+//   Name            Size             Description
+//   ---------------------------------------------------------------------------
+//   min_bin         32               First non-zero bin
+//   max_bin         32               Last non-zero bin
+//   offset          64               File offset for start for contig data block
+//
+// desc                         Null terminated string with description of dataset
+// contig_names                 n * null terminated strings with contig names
+// n_pops          8            (version 3 only) Number of populations with allele frequencies
+// pop_names                    (version 3 only) n_pops * null terminated strings with population names
+//
+// Magic Number
+//
+// magic           32           Magic number (0xd7278434)
+
+/// Version of index files produced by the writer
+pub const FORMAT_VERSION: u8 = IDX_VERSION;
+
+/// Builder for the data for a single bin of 256 sites.  SNPs can be added in any order
+#[derive(Clone, Default, Debug)]
+pub struct BinBuilder {
+	mask: [u128; 2],
+	half_full: bool,
+	entries: Vec<u8>,
+	name_buf: Vec<u8>,
+	// Allele and frequency data for each entry (in the order of entries).  Each record is prefixed by its length (u16)
+	extra_buf: Vec<u8>,
+}
+
+impl BinBuilder {
+	pub fn mask(&self) -> &[u128; 2] { &self.mask }
+	pub fn entries(&self) -> &[u8] { &self.entries }
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+	pub fn contains(&self, off: u8) -> bool {
+		let (ix, off1) = if off < 128 { (0, off) } else { (1, off & 127) };
+		(self.mask[ix] & (1u128 << off1)) != 0
+	}
+
+	/// Add SNP at offset `off` within bin.  `rs` should be the digits of the rs number (without the 'rs' prefix)
+	/// or empty if the SNP has no rs ID,
+	/// `alleles` the comma separated list of alleles (reference first) and `afs` the alternate allele frequencies
+	/// for each population (population major).  Frequencies are only stored if the alleles are known, and the number
+	/// of frequencies must be the same for all SNPs with the same number of alternate alleles in an index.
+	/// Returns false if a SNP is already present at this offset
+	pub fn add_snp(&mut self, off: u8, rs: &str, selected: bool, alleles: Option<&str>, afs: &[Option<f32>]) -> io::Result<bool> {
+		if self.contains(off) { return Ok(false) }
+		let rs = if rs.is_empty() { NO_RS } else { rs };
+		if rs.len() > MAX_RS_LEN || !rs.bytes().all(|c| c.is_ascii_digit()) {
+			return Err(new_err(format!("Invalid rs number rs{}", rs)))
+		}
+		let alleles = alleles.unwrap_or("");
+		if alleles.len() > MAX_ALLELE_DESC_LEN || alleles.bytes().any(|c| c == 0) {
+			return Err(new_err(format!("Invalid allele description for rs{}", rs)))
+		}
+		let afs = if alleles.is_empty() { &[] } else { afs };
+		let (ix, off1) = if off < 128 { (0, off) } else { (1, off & 127) };
+		self.mask[ix] |= 1u128 << off1;
+		let term_code = if selected { 0xf } else { 0xe };
+		let nbuf = rs.as_bytes();
+		let it = if self.half_full {
+			*self.name_buf.last_mut().unwrap() |= nbuf.first().unwrap() - b'0';
+			&nbuf[1..]
+		} else { nbuf }.chunks_exact(2);
+		let rem = it.remainder();
+		for v in it { self.name_buf.push(((v[0] - b'0') << 4) | (v[1] - b'0'))}
+		if rem.is_empty() {
+			self.name_buf.push(term_code << 4);
+			self.half_full = true;
+		} else {
+			self.name_buf.push(((rem.first().unwrap() - b'0') << 4) | term_code);
+			self.half_full = false;
+		}
+		self.entries.push(off);
+		let l = alleles.len() + 1 + 4 * afs.len();
+		self.extra_buf.extend_from_slice(&(l as u16).to_le_bytes());
+		self.extra_buf.extend_from_slice(alleles.as_bytes());
+		self.extra_buf.push(0);
+		for af in afs.iter() { self.extra_buf.extend_from_slice(&af.unwrap_or(f32::NAN).to_le_bytes()) }
+		Ok(true)
+	}
+
+	// Returns a vector of (offset, start position in nybbles) for each entry sorted by offset
+	fn sort_idx(&self) -> Vec<(u8, usize)> {
+		let mut idx = Vec::with_capacity(256);
+		let mut start_ix = 0;
+		let mut left = 0;
+		let mut it = self.name_buf.iter();
+		for off in self.entries.iter() {
+			idx.push((*off, start_ix));
+			start_ix += left;
+			left = 0;
+			loop {
+				match it.next() {
+					None => break,
+					Some(x) if (x & 0xf0) >= 0xe0 => {
+						start_ix += 1;
+						left = 1;
+						break;
+					},
+					Some(x) => {
+						start_ix += 2;
+						if (x & 0xf) >= 0xe { break }
+					},
+				}
+			}
+		}
+		idx.sort_unstable_by_key(|(off, _)| *off);
+		idx
+	}
+
+	// Write names sorted by position within bin
+	fn write_names<W: Write>(&self, w: W) -> io::Result<()> {
+		let idx = self.sort_idx();
+		let mut writer = NameWriter::new(w);
+		for (_, x) in idx.iter() {
+			let mut it = self.name_buf[x>>1..].iter();
+			if (x & 1) == 1 { writer.write_u4(*it.next().expect("Short name buffer"))? }
+			loop {
+				match it.next() {
+					None => break,
+					Some(x) if (x & 0xf0) >= 0xe0 => {
+						writer.write_u4(x >> 4)?;
+						break;
+					},
+					Some(x) => {
+						writer.write_u8(*x)?;
+						if (x & 0xf) >= 0xe { break }
+					},
+				}
+			}
+		}
+		writer.finish()
+	}
+
+	// Write allele and frequency data sorted by position within bin
+	fn write_extra<W: Write>(&self, mut w: W) -> io::Result<()> {
+		let mut recs = Vec::with_capacity(self.entries.len());
+		let mut p = &self.extra_buf[..];
+		for off in self.entries.iter() {
+			let l = u16::from_le_bytes([p[0], p[1]]) as usize;
+			recs.push((*off, &p[2..2 + l]));
+			p = &p[2 + l..];
+		}
+		recs.sort_unstable_by_key(|(off, _)| *off);
+		for (_, r) in recs.iter() { w.write_all(r)? }
+		Ok(())
+	}
+
+	/// Write bin data (mask, rs numbers and allele data)
+	pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+		write_u128(&mut w, &self.mask)?;
+		self.write_names(&mut w)?;
+		self.write_extra(&mut w)
+	}
+}
+
+struct NameWriter<W: Write> {
+	writer: W,
+	buf: Option<u8>,
+}
+
+impl <W: Write>NameWriter<W> {
+	fn new(w: W) -> Self { Self{writer: w, buf: None} }
+
+	fn write_u4(&mut self, c: u8) -> io::Result<()> {
+		if let Some(x) = self.buf.take() { self.writer.write_all(&[x | (c & 0xf)]) }
+		else {
+			self.buf = Some(c << 4);
+			Ok(())
+		}
+	}
+
+	fn write_u8(&mut self, c: u8) -> io::Result<()> {
+		if let Some(x) = self.buf {
+			self.buf = Some(c << 4);
+			self.writer.write_all(&[x | (c >> 4)])
+		} else { self.writer.write_all(&[c]) }
+	}
+
+	fn finish(mut self) -> io::Result<()> {
+		if let Some(c) = self.buf.take() { self.writer.write_all(&[c]) } else { Ok(()) }
+	}
+}
+
+/// Compressed data block
+pub struct CompressBlock {
+	first_bin: u32,
+	cbuf: Vec<u8>,
+}
+
+impl CompressBlock {
+	pub fn first_bin(&self) -> u32 { self.first_bin }
+	pub fn cbuf(&self) -> &[u8] { &self.cbuf }
+}
+
+/// Compressed data for a contig
+pub struct EncodedContig {
+	bin_range: Option<(u32, u32)>,
+	blocks: Vec<CompressBlock>,
+	max_size: usize,
+}
+
+impl EncodedContig {
+	pub fn is_empty(&self) -> bool { self.bin_range.is_none() }
+	/// First and last non-empty bins
+	pub fn bin_range(&self) -> Option<(u32, u32)> { self.bin_range }
+	pub fn blocks(&self) -> &[CompressBlock] { &self.blocks }
+	/// Maximum size of uncompressed block
+	pub fn max_size(&self) -> usize { self.max_size }
+}
+
+/// Encodes and compresses bins for a contig.  Bins must be added in increasing order
+pub struct ContigEncoder {
+	ubuf: Vec<u8>,
+	block_size: usize,
+	blocks: Vec<CompressBlock>,
+	block_first_bin: Option<u32>,
+	bin_range: Option<(u32, u32)>,
+	max_size: usize,
+	total_usize: usize,
+	total_csize: usize,
+}
+
+impl Default for ContigEncoder {
+	fn default() -> Self { Self::new() }
+}
+
+impl ContigEncoder {
+	pub fn new() -> Self { Self::with_block_size(BLOCK_SIZE) }
+	
+	/// Encoder with a given target size for uncompressed blocks
+	pub fn with_block_size(block_size: usize) -> Self {
+		Self{ubuf: Vec::with_capacity(block_size + 4096), block_size, blocks: Vec::new(), block_first_bin: None, bin_range: None, max_size: 0, total_usize: 0, total_csize: 0}
+	}
+
+	pub fn add_bin(&mut self, bin: u32, bb: &BinBuilder) -> io::Result<()> {
+		if bb.is_empty() { return Ok(()) }
+		let last = match self.bin_range {
+			Some((_, last)) if bin <= last => return Err(new_err(format!("Bins added out of order ({} after {})", bin, last))),
+			Some((first, last)) => {
+				self.bin_range = Some((first, bin));
+				Some(last)
+			},
+			None => {
+				self.bin_range = Some((bin, bin));
+				None
+			},
+		};
+		match (self.block_first_bin, last) {
+			(Some(_), Some(l)) => write_bin_inc(&mut self.ubuf, (bin - l - 1) as usize)?,
+			_ => self.block_first_bin = Some(bin),
+		}
+		bb.write(&mut self.ubuf)?;
+		if self.ubuf.len() >= self.block_size { self.flush()? }
+		Ok(())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		if let Some(first_bin) = self.block_first_bin.take() {
+			let cbuf = compress(&self.ubuf, 0)?;
+			self.total_usize += self.ubuf.len();
+			self.total_csize += cbuf.len();
+			self.max_size = self.max_size.max(self.ubuf.len());
+			self.ubuf.clear();
+			self.blocks.push(CompressBlock{first_bin, cbuf});
+		}
+		Ok(())
+	}
+
+	pub fn finish(mut self) -> io::Result<EncodedContig> {
+		self.flush()?;
+		let z = if self.total_usize > 0 { (self.total_csize as f64) / (self.total_usize as f64) } else { 1.0 };
+		debug!("ubuf {}, cbuf {}, ratio: {}", self.total_usize, self.total_csize, z);
+		Ok(EncodedContig{bin_range: self.bin_range, blocks: self.blocks, max_size: self.max_size})
+	}
+}
+
+/// Writer for index file.  Contig data should be added with add_contig(), then finish() writes the contig
+/// information and the file header
+pub struct IndexWriter {
+	file: BufWriter<File>,
+	ctgs: Vec<(String, u32, u32, u64)>,
+	max_size: usize,
+}
+
+impl IndexWriter {
+	pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let mut file = BufWriter::new(File::create(path)?);
+		// Skip over header block (we will fill it in at the end);
+		file.seek(SeekFrom::Start(HEADER_SIZE))?;
+		Ok(Self{file, ctgs: Vec::new(), max_size: 0})
+	}
+
+	pub fn add_contig<S: AsRef<str>>(&mut self, name: S, ectg: &EncodedContig) -> io::Result<()> {
+		let name = name.as_ref();
+		let (min, max) = match ectg.bin_range() {
+			Some(x) => x,
+			None => {
+				warn!("No data for contig {}", name);
+				return Ok(())
+			},
+		};
+		if name.is_empty() || name.bytes().any(|c| c == 0) { return Err(new_err(format!("Invalid contig name '{}'", name))) }
+		if self.ctgs.iter().any(|(s, _, _, _)| s == name) { return Err(new_err(format!("Duplicate contig {}", name))) }
+		let pos = self.file.stream_position()?;
+		for cb in ectg.blocks() {
+			write_u64(&mut self.file, &[cb.cbuf().len() as u64])?;
+			write_u32(&mut self.file, &[cb.first_bin()])?;
+			self.file.write_all(cb.cbuf())?;
+		}
+		write_u64(&mut self.file, &[0u64])?;
+		self.ctgs.push((name.to_owned(), min, max, pos));
+		self.max_size = self.max_size.max(ectg.max_size());
+		Ok(())
+	}
+
+	pub fn finish(mut self, desc: &str, populations: &[String]) -> io::Result<()> {
+		if populations.len() > 255 { return Err(new_err("Too many populations (max. 255)".to_string())) }
+		let pos = self.file.stream_position()?;
+		let mut ubuf: Vec<u8> = Vec::new();
+		write_u32(&mut ubuf, &[self.ctgs.len() as u32])?;
+		for (_, min, max, x) in self.ctgs.iter() {
+			write_u32(&mut ubuf, &[*min, *max])?;
+			write_u64(&mut ubuf, &[*x])?;
+		}
+		for s in std::iter::once(desc).chain(self.ctgs.iter().map(|(s, _, _, _)| s.as_str())) {
+			ubuf.write_all(s.as_bytes())?;
+			ubuf.write_all(&[0])?;
+		}
+		ubuf.write_all(&[populations.len() as u8])?;
+		for p in populations.iter() {
+			ubuf.write_all(p.as_bytes())?;
+			ubuf.write_all(&[0])?;
+		}
+		let cbuf = compress(&ubuf, 0)?;
+		let max_size = self.max_size.max(ubuf.len());
+		self.file.write_all(&cbuf)?;
+		write_u32(&mut self.file, &[IDX_MAGIC])?;
+		self.file.seek(SeekFrom::Start(0))?;
+		write_u32(&mut self.file, &[IDX_MAGIC])?;
+		self.file.write_all(&[IDX_VERSION, 0, 0, 0])?;
+		write_u64(&mut self.file, &[pos, max_size as u64, cbuf.len() as u64])?;
+		self.file.flush()
+	}
+}
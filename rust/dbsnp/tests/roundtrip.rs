@@ -0,0 +1,147 @@
+// Round trip tests: SNPs written with the index writer should be recovered exactly by the reader
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use proptest::prelude::*;
+
+use dbsnp::{BinBuilder, ContigEncoder, DBSnpIndex, IndexWriter};
+
+#[derive(Debug, Clone)]
+struct TestSnp {
+	rs: u64,
+	selected: bool,
+	alleles: Option<String>,
+	afs: Vec<Option<f32>>,
+}
+
+static FILE_CTR: AtomicUsize = AtomicUsize::new(0);
+
+fn tmp_file() -> PathBuf {
+	let n = FILE_CTR.fetch_add(1, Ordering::SeqCst);
+	std::env::temp_dir().join(format!("dbsnp_roundtrip_{}_{}.idx", std::process::id(), n))
+}
+
+fn allele_strategy() -> impl Strategy<Value = Option<String>> {
+	let allele = "[ACGT]{1,4}";
+	prop_oneof![
+		Just(None),
+		(allele, proptest::collection::vec(allele, 1..3)).prop_map(|(r, a)| Some(format!("{},{}", r, a.join(",")))),
+	]
+}
+
+fn snp_strategy(n_pops: usize) -> impl Strategy<Value = TestSnp> {
+	// rs 0 is used for SNPs without an rs ID
+	(prop_oneof![Just(0u64), 1u64..10_000_000_000], any::<bool>(), allele_strategy()).prop_flat_map(move |(rs, selected, alleles)| {
+		let n_alt = alleles.as_ref().map(|s| s.split(',').count() - 1).unwrap_or(0);
+		proptest::collection::vec(proptest::option::of(0.0f32..1.0), n_alt * n_pops)
+			.prop_map(move |afs| TestSnp{rs, selected, alleles: alleles.clone(), afs})
+	})
+}
+
+// Contig name -> (position (1 offset) -> SNP)
+type TestData = BTreeMap<String, BTreeMap<usize, TestSnp>>;
+
+fn data_strategy() -> impl Strategy<Value = (usize, TestData)> {
+	(0usize..3).prop_flat_map(|n_pops| {
+		let ctg = proptest::collection::btree_map(1usize..200_000, snp_strategy(n_pops), 1..300);
+		(Just(n_pops), proptest::collection::btree_map("chr[0-9XY]{1,2}", ctg, 1..4))
+	})
+}
+
+fn write_index(path: &PathBuf, n_pops: usize, data: &TestData, block_size: usize) {
+	let mut w = IndexWriter::create(path).unwrap();
+	for (ctg, snps) in data.iter() {
+		let mut enc = ContigEncoder::with_block_size(block_size);
+		let mut curr: Option<(usize, BinBuilder)> = None;
+		for (pos, snp) in snps.iter() {
+			let bn = pos >> 8;
+			if curr.as_ref().map(|(b, _)| *b != bn).unwrap_or(true) {
+				if let Some((b, bb)) = curr.take() { enc.add_bin(b as u32, &bb).unwrap() }
+				curr = Some((bn, BinBuilder::default()));
+			}
+			let bb = &mut curr.as_mut().unwrap().1;
+			assert!(bb.add_snp((pos & 255) as u8, &snp.rs.to_string(), snp.selected, snp.alleles.as_deref(), &snp.afs).unwrap());
+		}
+		if let Some((b, bb)) = curr.take() { enc.add_bin(b as u32, &bb).unwrap() }
+		w.add_contig(ctg, &enc.finish().unwrap()).unwrap();
+	}
+	let pops: Vec<String> = (0..n_pops).map(|i| format!("POP{}", i)).collect();
+	w.finish("test index", &pops).unwrap();
+}
+
+fn expected_id(ctg: &str, pos: usize, snp: &TestSnp) -> String {
+	if snp.rs > 0 { format!("rs{}", snp.rs) }
+	else {
+		match &snp.alleles {
+			Some(s) => {
+				let (r, a) = s.split_once(',').unwrap();
+				format!("{}:{}:{}:{}", ctg, pos, r, a)
+			},
+			None => format!("{}:{}", ctg, pos),
+		}
+	}
+}
+
+fn check_entry(ctg: &str, pos: usize, snp: &TestSnp, e: &dbsnp::DBSnpEntry) {
+	assert_eq!(e.pos, pos);
+	assert_eq!(e.rs, expected_id(ctg, pos, snp));
+	assert_eq!(e.selected, snp.selected);
+	match &snp.alleles {
+		Some(s) => {
+			assert_eq!(e.alleles.join(","), *s);
+			assert_eq!(e.afs, snp.afs);
+		},
+		None => {
+			assert!(e.alleles.is_empty());
+			assert!(e.afs.is_empty());
+		},
+	}
+}
+
+proptest! {
+	#![proptest_config(ProptestConfig::with_cases(64))]
+
+	#[test]
+	fn roundtrip_lookup_and_iter((n_pops, data) in data_strategy(), block_size in prop_oneof![Just(64usize), Just(1048576usize)]) {
+		let path = tmp_file();
+		write_index(&path, n_pops, &data, block_size);
+		let idx = DBSnpIndex::new(path.to_str().unwrap()).unwrap();
+		prop_assert_eq!(idx.version(), dbsnp::FORMAT_VERSION);
+		prop_assert_eq!(idx.header(), "test index");
+		prop_assert_eq!(idx.populations().len(), n_pops);
+		prop_assert_eq!(idx.contig_names().collect::<Vec<_>>(), data.keys().map(|s| s.as_str()).collect::<Vec<_>>());
+		for (ctg, snps) in data.iter() {
+			let dctg = idx.get_dbsnp_contig(ctg).unwrap();
+			// Random access (positions for lookup are 0 offset)
+			for (pos, snp) in snps.iter() {
+				let e = dctg.lookup(pos - 1).unwrap();
+				check_entry(ctg, *pos, snp, &e);
+				prop_assert_eq!(dctg.lookup_rs(pos - 1), Some((expected_id(ctg, *pos, snp), snp.selected)));
+				if !snps.contains_key(&(pos + 1)) { prop_assert!(dctg.lookup(*pos).is_none()) }
+			}
+			// Iteration over whole contig
+			let v: Vec<_> = idx.query(ctg, 0, usize::MAX).unwrap().map(|r| r.unwrap()).collect();
+			prop_assert_eq!(v.len(), snps.len());
+			for ((pos, snp), e) in snps.iter().zip(v.iter()) { check_entry(ctg, *pos, snp, e) }
+		}
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn roundtrip_region_query((n_pops, data) in data_strategy(), start in 1usize..200_000, len in 0usize..50_000) {
+		let path = tmp_file();
+		write_index(&path, n_pops, &data, 256);
+		let idx = DBSnpIndex::new(path.to_str().unwrap()).unwrap();
+		let end = start + len;
+		for (ctg, snps) in data.iter() {
+			let v: Vec<_> = idx.query(ctg, start, end).unwrap().map(|r| r.unwrap()).collect();
+			let expected: Vec<_> = snps.range(start..=end).collect();
+			prop_assert_eq!(v.len(), expected.len());
+			for ((pos, snp), e) in expected.iter().zip(v.iter()) { check_entry(ctg, **pos, snp, e) }
+		}
+		prop_assert!(idx.query("no_such_contig", 1, 100).is_none());
+		std::fs::remove_file(&path).unwrap();
+	}
+}
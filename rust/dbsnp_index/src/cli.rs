@@ -4,9 +4,9 @@ mod cli_utils;
 use utils::log_level::init_log;
 use clap::App;
 mod options;
-use crate::config::{Config, DbInput};
+use crate::config::Command;
 
-pub fn process_cli() -> io::Result<(Config, Box<[DbInput]>)> {
+pub fn process_cli() -> io::Result<Command> {
 	let yaml = load_yaml!("cli/cli.yml");
     let app = App::from_yaml(yaml).version(crate_version!());
 	
@@ -14,5 +14,10 @@ pub fn process_cli() -> io::Result<(Config, Box<[DbInput]>)> {
 	let m = app.get_matches();	
 	let _ = init_log(&m);
 	// Process arguments
-	options::handle_options(&m)
+	match m.subcommand() {
+		("query", Some(m_sub)) => options::handle_query_options(m_sub),
+		("dump", Some(m_sub)) => options::handle_dump_options(m_sub),
+		("info", Some(m_sub)) => options::handle_info_options(m_sub),
+		_ => options::handle_options(&m),
+	}
 }
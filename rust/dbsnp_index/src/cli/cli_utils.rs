@@ -34,4 +34,12 @@ pub fn get_arg_itype<S: AsRef<str>>(m: &ArgMatches, s: S) -> io::Result<IType> {
 		}
 	} else { Ok(IType::Auto) }
 }
-
+pub fn get_arg_oformat<S: AsRef<str>>(m: &ArgMatches, s: S) -> io::Result<OFormat> { 
+	if let Some(x) = m.value_of(s.as_ref()) {
+		match x.to_lowercase().as_str() {
+			"bed" => Ok(OFormat::Bed),
+			"vcf" => Ok(OFormat::Vcf),
+			_ => Err(new_err(format!("Unrecognized output format: {}", x))),
+		}
+	} else { Ok(OFormat::Bed) }
+}
@@ -1,12 +1,15 @@
 use std::io;
+use std::str::FromStr;
 use std::fs::metadata;
 use std::collections::{HashMap, HashSet};
 use clap::ArgMatches;
 
 use utils::compress;
 use r_htslib::*;
+use dbsnp::DBSnpIndex;
 
 use crate::config::*;
+use crate::read::vcf_filter::VcfFilter;
 use super::cli_utils::*;
 
 fn read_select_file(s: &str) -> io::Result<HashSet<String>> {
@@ -69,7 +72,7 @@ fn check_for_indexed_vcf_file(file: &str, tf: &mut Vec<(DbInput, i64)>, chrom_al
 	} else { false }	
 }
 
-pub fn handle_options(m: &ArgMatches) -> io::Result<(Config, Box<[DbInput]>)> {
+pub fn handle_options(m: &ArgMatches) -> io::Result<Command> {
 	trace!("Handle command line options");
 	let threads = get_arg_usize(m, "threads")?.unwrap_or_else(num_cpus::get);
 	let jobs = get_arg_usize(m, "jobs")?.unwrap_or(1);
@@ -85,6 +88,19 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(Config, Box<[DbInput]>)> {
 		Some(s) => read_select_file(s)?,
 		None => HashSet::new(),
 	};
+	let merge_index = match m.value_of("merge") {
+		Some(s) => Some(DBSnpIndex::new(s)?),
+		None => None,
+	};
+	// If merging with an existing index, the default is to keep the populations from the existing index
+	let populations: Vec<String> = match m.value_of("populations") {
+		Some(s) => s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(|x| x.to_owned()).collect(),
+		None => match merge_index.as_ref().map(|idx| idx.populations()) {
+			Some(p) if !p.is_empty() => p.to_vec(),
+			_ => vec!("AF".to_string()),
+		},
+	};
+	if populations.len() > 255 { return Err(new_err("Too many populations (max. 255)".to_string())) }
 	let hts_log_level = unsafe {
 		let t = hts_get_log_level();
 		hts_set_log_level(htsLogLevel::HTS_LOG_OFF);
@@ -115,9 +131,82 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(Config, Box<[DbInput]>)> {
 			let f: Vec<_> = tf.drain(..).map(|(d, _)| d).collect();
 			f
 		},
-		None => vec!(DbInput::File("-".to_string())),
+		None => if merge_index.is_some() { Vec::new() } else { vec!(DbInput::File("-".to_string())) },
 	};
 	unsafe { hts_set_log_level(hts_log_level) };
 	trace!("Finished handling command line options");
-	Ok((Config::new(threads, jobs, maf_limit, output, description, input_type, chrom_alias, selected), files.into_boxed_slice()))
+	let mut conf = Config::new(threads, jobs, maf_limit, output, description, input_type, chrom_alias, selected, populations);
+	let vcf_filter = match m.value_of("filter") {
+		Some(s) => {
+			let mut filter = VcfFilter::new(s)?;
+			// Get the number of values for INFO fields from the VCF headers
+			let mut read_files = HashSet::new();
+			for f in files.iter() {
+				let file = match f {
+					DbInput::File(file) | DbInput::VcfContig(file, _, _) => file,
+				};
+				if file != "-" && read_files.insert(file.as_str()) { filter.read_header(file)? }
+			}
+			Some(filter)
+		},
+		None => None,
+	};
+	conf.set_vcf_options(get_arg_string(m, "af_tag"), vcf_filter, m.is_present("ignore_filter"));
+	if let Some(idx) = merge_index {
+		// Keep description from existing index unless a new one has been given
+		conf.cond_set_description(idx.header());
+		conf.set_merge_index(idx);
+	}
+	Ok(Command::Build(Box::new(conf), files.into_boxed_slice()))
+}
+
+fn parse_query_item(s: &str) -> io::Result<QueryItem> {
+	let parse_pos = |x: &str| <usize>::from_str(&x.replace(',', "")).map_err(|e| new_err(format!("Couldn't parse position in region {}: {}", s, e)));
+	if s.starts_with("rs") && s.len() > 2 && s[2..].bytes().all(|c| c.is_ascii_digit()) { return Ok(QueryItem::Rs(s.to_owned())) }
+	// Regions are of the form contig, contig:start or contig:start-end
+	match s.rsplit_once(':') {
+		Some((ctg, range)) if !ctg.is_empty() => {
+			let (start, end) = match range.split_once('-') {
+				Some((a, b)) => (parse_pos(a)?, parse_pos(b)?),
+				None => (parse_pos(range)?, usize::MAX),
+			};
+			if start == 0 || end < start { Err(new_err(format!("Invalid region {}", s))) }
+			else { Ok(QueryItem::Region(ctg.to_owned(), start, end)) }
+		},
+		_ => Ok(QueryItem::Region(s.to_owned(), 1, usize::MAX)),
+	}
+}
+
+pub fn handle_query_options(m: &ArgMatches) -> io::Result<Command> {
+	trace!("Handle query options");
+	let index = DBSnpIndex::new(m.value_of("index").unwrap())?;
+	let output = get_arg_string(m, "output");
+	let format = get_arg_oformat(m, "format")?;
+	let mut items = Vec::new();
+	if let Some(f) = m.value_of("file") {
+		let mut rdr = compress::open_bufreader(f)?;
+		let mut buf = String::with_capacity(256);
+		loop {
+			buf.clear();
+			if rdr.read_line(&mut buf)? == 0 { break }
+			if let Some(s) = buf.split_ascii_whitespace().next() { items.push(parse_query_item(s)?) }
+		}
+	}
+	if let Some(v) = m.values_of("items") {
+		for s in v { items.push(parse_query_item(s)?) }
+	}
+	if items.is_empty() { return Err(new_err("No rs IDs or regions to look up".to_string())) }
+	Ok(Command::Query(QueryConfig::new(index, output, format, items)))
+}
+
+pub fn handle_dump_options(m: &ArgMatches) -> io::Result<Command> {
+	trace!("Handle dump options");
+	let index = DBSnpIndex::new(m.value_of("index").unwrap())?;
+	let output = get_arg_string(m, "output");
+	let format = get_arg_oformat(m, "format")?;
+	Ok(Command::Dump(QueryConfig::new(index, output, format, Vec::new())))
+}
+
+pub fn handle_info_options(m: &ArgMatches) -> io::Result<Command> {
+	Ok(Command::Info(DBSnpIndex::new(m.value_of("index").unwrap())?))
 }
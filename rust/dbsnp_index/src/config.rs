@@ -2,7 +2,9 @@ use std::collections::{HashMap, HashSet};
 use std::io::{self, Error, ErrorKind};
 use std::sync::RwLock;
 
+use dbsnp::DBSnpIndex;
 use super::contig::*;
+use super::read::vcf_filter::VcfFilter;
 
 pub fn new_err(s: String) -> io::Error {
 	Error::new(ErrorKind::Other, s)	
@@ -19,21 +21,42 @@ pub struct Config {
 	input_type: IType,
 	selected: HashSet<String>,
 	maf_limit: Option<f64>,
+	populations: Vec<String>,
+	merge_index: Option<DBSnpIndex>,
+	af_tag: Option<String>,
+	vcf_filter: Option<VcfFilter>,
+	ignore_filter: bool,
 	ctg_hash: ContigHash,
 }
 
 impl Config {
 	pub fn new(threads: usize, jobs: usize, maf_limit: Option<f64>, output: Option<String>, description: Option<String>, input_type: IType,
-		chrom_alias: Option<HashMap<String, String>>, selected: HashSet<String>) -> Self { 
-		Self { threads, jobs, maf_limit, output, description: RwLock::new(description), input_type, selected, 
-				ctg_hash: ContigHash::new(threads * 32, chrom_alias)}
+		chrom_alias: Option<HashMap<String, String>>, selected: HashSet<String>, populations: Vec<String>) -> Self { 
+		Self { threads, jobs, maf_limit, output, description: RwLock::new(description), input_type, selected, populations,
+				merge_index: None, af_tag: None, vcf_filter: None, ignore_filter: false, ctg_hash: ContigHash::new(threads * 32, chrom_alias)}
 	}
 	pub fn threads(&self) -> usize { self.threads }
 	pub fn jobs(&self) -> usize { self.jobs }
 	pub fn maf_limit(&self) -> Option<f64> { self.maf_limit }
+	// Populations for which allele frequencies are stored
+	pub fn populations(&self) -> &[String] { &self.populations }
 	pub fn input_type(&self) -> IType { self.input_type }
 	pub fn output(&self) -> Option<&str> { self.output.as_deref()}
 	pub fn selected<S: AsRef<str>> (&self, s: S) -> bool { self.selected.contains(s.as_ref())}
+	// Existing index to be merged with the input files
+	pub fn merge_index(&self) -> Option<&DBSnpIndex> { self.merge_index.as_ref() }
+	pub fn set_merge_index(&mut self, idx: DBSnpIndex) { self.merge_index = Some(idx) }
+	// Options for VCF input
+	pub fn set_vcf_options(&mut self, af_tag: Option<String>, vcf_filter: Option<VcfFilter>, ignore_filter: bool) {
+		self.af_tag = af_tag;
+		self.vcf_filter = vcf_filter;
+		self.ignore_filter = ignore_filter;
+	}
+	// INFO tag used to calculate MAF from VCF input
+	pub fn af_tag(&self) -> Option<&str> { self.af_tag.as_deref() }
+	pub fn vcf_filter(&self) -> Option<&VcfFilter> { self.vcf_filter.as_ref() }
+	// Do not skip VCF records that have failed filters
+	pub fn ignore_filter(&self) -> bool { self.ignore_filter }
 	pub fn ctg_hash(&self) -> &ContigHash {&self.ctg_hash}
 	pub fn description(&self) -> Option<String> { (*self.description.read().unwrap()).as_ref().cloned() }
 	// Set description if not aready set.  Returns true if description has been set.
@@ -55,3 +78,36 @@ pub enum DbInput {
 	VcfContig(String, String, libc::c_int),	
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OFormat { Bed, Vcf }
+
+#[derive(Debug, Clone)]
+pub enum QueryItem {
+	Rs(String),
+	// Contig, start, end (1 offset, inclusive)
+	Region(String, usize, usize),
+}
+
+pub struct QueryConfig {
+	index: DBSnpIndex,
+	output: Option<String>,
+	format: OFormat,
+	items: Vec<QueryItem>,
+}
+
+impl QueryConfig {
+	pub fn new(index: DBSnpIndex, output: Option<String>, format: OFormat, items: Vec<QueryItem>) -> Self {
+		Self{index, output, format, items}
+	}
+	pub fn index(&self) -> &DBSnpIndex { &self.index }
+	pub fn output(&self) -> Option<&str> { self.output.as_deref() }
+	pub fn format(&self) -> OFormat { self.format }
+	pub fn items(&self) -> &[QueryItem] { &self.items }
+}
+
+pub enum Command {
+	Build(Box<Config>, Box<[DbInput]>),
+	Query(QueryConfig),
+	Dump(QueryConfig),
+	Info(DBSnpIndex),
+}
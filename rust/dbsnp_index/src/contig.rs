@@ -1,107 +1,16 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock, TryLockError, RwLockWriteGuard};
-use std::io::Write;
 
 use crossbeam_channel::{bounded, Sender, Receiver};
+use dbsnp::BinBuilder;
 use super::snp::{RawSnp, SnpBlock};
 use crate::config::Config;
 
-#[derive(Clone, Default, Debug)]
-pub struct ContigBin {
-	mask: [u128; 2],
-	half_full: bool,
-	entries: Vec<u8>,
-	name_buf: Vec<u8>,	
-}
-
-impl ContigBin {
-	pub fn name_buf(&self) -> &[u8] { &self.name_buf }
-	pub fn mask(&self) -> &[u128; 2] { &self.mask }
-	pub fn entries(&self) -> &[u8] { &self.entries }
-	fn sort_idx(&mut self) -> Vec<(u8, usize)> {
-		let mut idx = Vec::with_capacity(256);
-		let mut start_ix = 0;
-		let mut left = 0; 
-		let mut it = self.name_buf.iter();
-		for off in self.entries.iter() {
-			idx.push((*off, start_ix));
-			start_ix += left;
-			left = 0;
-			loop {
-				match it.next() {
-					None => break,
-					Some(x) if (x & 0xf0) >= 0xe0 => {
-						start_ix += 1;
-						left = 1;
-						break;
-					},
-					Some(x) => {
-						start_ix += 2;
-						if (x & 0xf) >= 0xe { break }
-					},
-				}
-			}
-		}
-		idx.sort_unstable_by_key(|(off, _)| *off);
-		idx
-	}
-	
-	// Write names sorted by position within bin
-	pub fn write_names<W: Write>(&mut self, w: W) {
-		let idx = self.sort_idx();
-		let mut writer = NameWriter::new(w);
-		for (_, x) in idx.iter() {
-			let mut it = self.name_buf[x>>1..].iter(); 
-			if (x & 1) == 1 { writer.write_u4(*it.next().expect("Short name buffer")) }
-			loop {
-				match it.next() {
-					None => break,
-					Some(x) if (x & 0xf0) >= 0xe0 => {
-						writer.write_u4(x >> 4);
-						break;
-					},
-					Some(x) => {
-						writer.write_u8(*x);
-						if (x & 0xf) >= 0xe { break }
-					},
-				}
-			}
-		}
-	}
-}
-
-struct NameWriter<W: Write> {
-	writer: W,
-	buf: Option<u8>,	
-}
-
-impl <W: Write>NameWriter<W> {
-	fn new(w: W) -> Self { Self{writer: w, buf: None} }
-	
-	fn write_u4(&mut self, c: u8) {
-		if let Some(x) = self.buf.take() { self.writer.write_all(&[x | (c & 0xf)]).expect("Write error") }
-		else { self.buf = Some(c << 4) }
-	}
-	
-	fn write_u8(&mut self, c: u8) {
-		if let Some(x) = self.buf {
-			self.writer.write_all(&[x | (c >> 4)]).expect("Write error");
-			self.buf = Some(c << 4);
-		} else { self.writer.write_all(&[c]).expect("Write error") }
-	}
-}
-
-impl <W: Write> Drop for NameWriter<W> {
-	fn drop(&mut self) {
-		if let Some(c) = self.buf.take() { self.writer.write_all(&[c]).expect("Write error") }
-	}	
-}
-
 #[derive(Debug)]
 struct ContigInnerData {
 	min_bin: usize,	
 	max_bin: usize,
-	bins: Vec<Option<ContigBin>>,
+	bins: Vec<Option<BinBuilder>>,
 }
 
 #[derive(Default, Debug, Copy, Clone)]
@@ -119,12 +28,12 @@ impl ContigStats {
 
 impl ContigInnerData {
 	fn new(min_bin: usize, max_bin: usize) -> Self {
-		let bins: Vec<Option<ContigBin>> = vec!(None; max_bin - min_bin + 1);
+		let bins: Vec<Option<BinBuilder>> = vec!(None; max_bin - min_bin + 1);
 		Self{min_bin, max_bin, bins }
 	}
 	fn check_min(&mut self, min_bin: usize) {
 		if min_bin < self.min_bin {
-			let mut v: Vec<Option<ContigBin>> = vec!(None; self.min_bin - min_bin);
+			let mut v: Vec<Option<BinBuilder>> = vec!(None; self.min_bin - min_bin);
 			v.append(&mut self.bins);
 			self.bins = v;
 			self.min_bin = min_bin;
@@ -133,7 +42,7 @@ impl ContigInnerData {
 	fn check_max(&mut self, max_bin: usize) {
 		let mb = self.min_bin + self.bins.len() - 1;
 		if max_bin > mb {
-			let mut v: Vec<Option<ContigBin>> = vec!(None; max_bin - mb);
+			let mut v: Vec<Option<BinBuilder>> = vec!(None; max_bin - mb);
 			self.bins.append(&mut v);
 			self.max_bin = max_bin;
 		}
@@ -167,46 +76,25 @@ impl ContigData {
 		};
 		let bin = idata.bins[bin_idx].as_mut().unwrap();	
 		let off = (snp.pos() & 255) as u8;
-		let (ix, off1) = if off < 128 { (0, off) } else { (1, off & 127) };
-		let mask = 1u128 << off1;
-		if (bin.mask[ix] & mask) == 0 { 
+		if !bin.contains(off) { 
 			self.stats.n_snps += 1;
-			bin.mask[ix] |= mask;
 			let name = snp.name();
-			if name.len() > 254 { panic!("The name for SNP {} is too long (Max <= 254)", name)}
-			let select = {
+			let select = snp.selected() || {
 				let s = if let Some(maf_limit) = conf.maf_limit() {
 					if let Some(maf) = snp.maf() { maf >= maf_limit as f32 }
 					else { false }
 				} else { false };
 				if !s { conf.selected(name) } else { false }
 			};
-			let term_code = if select { 
-				self.stats.n_selected_snps += 1;
-				0xf 
-			} else { 0xe };
-			let nbuf = name.as_bytes();
-			let it = if bin.half_full {
-				*bin.name_buf.last_mut().unwrap() |= nbuf.first().unwrap() - b'0';
-				&nbuf[1..]
-			} else { nbuf }.chunks_exact(2);
-			let rem = it.remainder();
-			for v in it { bin.name_buf.push(((v[0] - b'0') << 4) | (v[1] - b'0'))}
-			if rem.is_empty() {
-				bin.name_buf.push(term_code << 4);
-				bin.half_full = true;
-			} else {
-				bin.name_buf.push(((rem.first().unwrap() - b'0') << 4) | term_code);
-				bin.half_full = false;
-			}	
-			bin.entries.push(off);
+			if select { self.stats.n_selected_snps += 1 }
+			if let Err(e) = bin.add_snp(off, name, select, snp.alleles(), snp.afs()) { panic!("Couldn't add SNP rs{}: {}", name, e) }
 		}
 	}
 	pub fn stats(&self) -> &ContigStats { &self.stats }
 	pub fn min_bin(&self) -> Option<usize> { self.inner.as_ref().map(|x| x.min_bin) }
 	pub fn max_bin(&self) -> Option<usize> { self.inner.as_ref().map(|x| x.max_bin) }
 	pub fn min_max(&self) -> Option<(usize, usize)> { self.inner.as_ref().map(|x| (x.min_bin, x.max_bin)) }
-	pub fn bins(&mut self) -> Option<&mut Vec<Option<ContigBin>>> {
+	pub fn bins(&mut self) -> Option<&mut Vec<Option<BinBuilder>>> {
 		if let Some(idata) = &mut self.inner { Some(&mut idata.bins) }
 		else { None }
 	}
@@ -11,10 +11,20 @@ pub mod config;
 pub mod read;
 pub mod write;
 pub mod compress;
+pub mod merge;
+pub mod query;
+
+use config::Command;
 
 fn main()  -> Result<(), String> {
-	let (conf, files) = cli::process_cli().map_err(|e| format!("dbsnp_index initialization failed with error: {}", e))?;
-	match process::process(conf, files) {
+	let command = cli::process_cli().map_err(|e| format!("dbsnp_index initialization failed with error: {}", e))?;
+	let res = match command {
+		Command::Build(conf, files) => process::process(*conf, files),
+		Command::Query(qconf) => query::query(&qconf),
+		Command::Dump(qconf) => query::dump(&qconf),
+		Command::Info(idx) => query::info(&idx),
+	};
+	match res {
 		Ok(_) => Ok(()),
 		Err(e) => {
 			error!("dbsnp failed with error: {}", e);
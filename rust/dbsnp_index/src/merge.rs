@@ -0,0 +1,43 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::config::{Config, new_err};
+use crate::process::AtomicServer;
+use crate::read::ReaderBuf;
+use crate::snp::{SnpAlleles, SnpBuilder};
+
+// Read SNPs from an existing index and pass them on to the store threads.  This is run after
+// all input files have been read so that SNPs from the new input files take precedence
+pub fn merge_thread(conf: Arc<Config>, ctgs: Arc<AtomicServer<String>>) -> io::Result<()> {
+	let idx = conf.merge_index().ok_or_else(|| new_err("No index to merge".to_string()))?;
+	let mut rbuf = ReaderBuf::new(256);
+	let mut builder = SnpBuilder::new(&conf);
+	// Map from output populations to populations in the existing index
+	let pop_map: Vec<Option<usize>> = conf.populations().iter().map(|p| idx.populations().iter().position(|q| q == p)).collect();
+	while let Some(ctg) = ctgs.next_item() {
+		info!("Merging SNPs from {}:{}", idx.filename(), ctg);
+		let mut n = 0;
+		let iter = idx.query(ctg, 0, usize::MAX).ok_or_else(|| new_err(format!("Unknown contig {} in {}", ctg, idx.filename())))?;
+		for res in iter {
+			let e = res.map_err(|e| new_err(format!("Error reading from {}: {}", idx.filename(), e)))?;
+			let alleles = if e.alleles.is_empty() { None } else { Some(e.alleles.join(",")) };
+			let n_alt = e.alt_alleles().len();
+			let mut afs = Vec::with_capacity(n_alt * pop_map.len());
+			for p in pop_map.iter() {
+				for alt in 0..n_alt { afs.push(p.and_then(|ix| e.af(ix, alt))) }
+			}
+			let sa = SnpAlleles{alleles, afs};
+			// SNPs without rs IDs have an ID generated by the index reader
+			let snp = if e.rs.starts_with("rs") { builder.mk_snp(&e.rs, ctg, e.pos as u32, None, sa) }
+			else { builder.mk_anon_snp(ctg, e.pos as u32, None, sa) };
+			if let Some(mut snp) = snp {
+				snp.set_selected(e.selected);
+				rbuf.add_snp(snp);
+				n += 1;
+			}
+		}
+		debug!("Merged {} SNPs from {}:{}", n, idx.filename(), ctg);
+	}
+	rbuf.flush();
+	Ok(())
+}
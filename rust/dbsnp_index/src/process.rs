@@ -10,6 +10,7 @@ use super::contig::Contig;
 use super::read::{read_thread, proc_read_thread};
 use super::write::write_thread;
 use super::compress::compress_thread;
+use super::merge::merge_thread;
 
 pub struct AtomicServer<T> {
 	idx: AtomicUsize,
@@ -57,6 +58,19 @@ pub fn process(conf: Config, files: Box<[DbInput]>) -> io::Result<()> {
 	for th in readers { th.join().unwrap(); }
 	drop(global_send);
 	for th in proc_threads { th.join().unwrap(); }
+	// SNPs from an existing index are added after the input files have been read so that the new SNPs have priority
+	if let Some(idx) = conf_ref.merge_index() {
+		let ctgs: Vec<String> = idx.contig_names().map(|s| s.to_owned()).collect();
+		let n_mergers = n_proc_threads.min(ctgs.len());
+		let ctg_list = Arc::new(AtomicServer::new(ctgs.into_boxed_slice()));
+		let mut mergers = Vec::with_capacity(n_mergers);
+		for _ in 0..n_mergers {
+			let cf = conf_ref.clone();
+			let ctgs = ctg_list.clone();
+			mergers.push(thread::spawn(move || {merge_thread(cf, ctgs)}));
+		}
+		for th in mergers { th.join().map_err(|_| new_err("Error joining merge thread".to_string()))??; }
+	}
 	for (_, s) in storers.iter() { s.send(true).unwrap() }
 	for (th, _) in storers { th.join().unwrap(); }
 	let ctg_stats_vec = conf_ref.ctg_hash().get_ctg_stats();
@@ -0,0 +1,174 @@
+use std::io::{self, Write, BufWriter};
+use std::collections::HashSet;
+
+use utils::compress::open_bufwriter;
+use dbsnp::{DBSnpIndex, DBSnpEntry};
+
+use crate::config::{new_err, OFormat, QueryConfig, QueryItem};
+
+fn open_output(conf: &QueryConfig) -> io::Result<Box<dyn Write>> {
+	match conf.output() {
+		Some(f) => open_bufwriter(f),
+		None => Ok(Box::new(BufWriter::new(io::stdout()))),
+	}
+}
+
+// INFO tag used for population allele frequencies in VCF output
+fn af_tag(pop: &str) -> String {
+	if pop == "AF" || pop.starts_with("AF_") { pop.to_owned() } else { format!("AF_{}", pop) }
+}
+
+fn fmt_afs(e: &DBSnpEntry, pop: usize) -> Option<String> {
+	let n_alt = e.alt_alleles().len();
+	let v: Vec<_> = (0..n_alt).map(|alt| e.af(pop, alt)).collect();
+	if v.iter().all(|x| x.is_none()) { None }
+	else { Some(v.iter().map(|x| x.map(|f| format!("{}", f)).unwrap_or_else(|| ".".to_string())).collect::<Vec<_>>().join(",")) }
+}
+
+struct SnpWriter<'a> {
+	wrt: Box<dyn Write>,
+	format: OFormat,
+	index: &'a DBSnpIndex,
+}
+
+impl <'a> SnpWriter<'a> {
+	fn new(conf: &'a QueryConfig) -> io::Result<Self> {
+		let mut w = Self{wrt: open_output(conf)?, format: conf.format(), index: conf.index()};
+		w.write_header()?;
+		Ok(w)
+	}
+
+	// Header lines.  BED output starts with a track line so that it can be used as input to dbsnp_index
+	fn write_header(&mut self) -> io::Result<()> {
+		let idx = self.index;
+		match self.format {
+			OFormat::Bed => {
+				let hdr = idx.header();
+				if hdr.starts_with("track") { writeln!(self.wrt, "{}", hdr)? }
+				else { writeln!(self.wrt, "track name=dbSNP_index description=\"{}\"", hdr)? }
+				write!(self.wrt, "#chrom\tstart\tend\tname\talleles\tselected")?;
+				for p in idx.populations() { write!(self.wrt, "\t{}", p)? }
+				writeln!(self.wrt)
+			},
+			OFormat::Vcf => {
+				writeln!(self.wrt, "##fileformat=VCFv4.2\n##source=dbsnp_index\n##dbSNP_index=\"{}\"", idx.header().replace('"', "'"))?;
+				for ctg in idx.contig_names() { writeln!(self.wrt, "##contig=<ID={}>", ctg)? }
+				writeln!(self.wrt, "##INFO=<ID=SEL,Number=0,Type=Flag,Description=\"Selected SNP (always reported by bs_call)\">")?;
+				for p in idx.populations() {
+					writeln!(self.wrt, "##INFO=<ID={},Number=A,Type=Float,Description=\"Alternate allele frequency in {}\">", af_tag(p), p)?
+				}
+				writeln!(self.wrt, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")
+			},
+		}
+	}
+
+	fn write_entry(&mut self, ctg: &str, e: &DBSnpEntry) -> io::Result<()> {
+		let pops = self.index.populations();
+		match self.format {
+			OFormat::Bed => {
+				let alleles = if e.alleles.is_empty() { ".".to_string() } else { e.alleles.join(",") };
+				write!(self.wrt, "{}\t{}\t{}\t{}\t{}\t{}", ctg, e.pos - 1, e.pos, e.rs, alleles, if e.selected { 1 } else { 0 })?;
+				for ix in 0..pops.len() { write!(self.wrt, "\t{}", fmt_afs(e, ix).unwrap_or_else(|| ".".to_string()))? }
+				writeln!(self.wrt)
+			},
+			OFormat::Vcf => {
+				let (ref_allele, alt) = match e.ref_allele() {
+					Some(r) => (r, e.alt_alleles().join(",")),
+					None => ("N", ".".to_string()),
+				};
+				let mut info = Vec::new();
+				if e.selected { info.push("SEL".to_string()) }
+				for (ix, p) in pops.iter().enumerate() {
+					if let Some(s) = fmt_afs(e, ix) { info.push(format!("{}={}", af_tag(p), s)) }
+				}
+				let info = if info.is_empty() { ".".to_string() } else { info.join(";") };
+				writeln!(self.wrt, "{}\t{}\t{}\t{}\t{}\t.\t.\t{}", ctg, e.pos, e.rs, ref_allele, alt, info)
+			},
+		}
+	}
+
+	fn write_region(&mut self, ctg: &str, start: usize, end: usize) -> io::Result<usize> {
+		let mut n = 0;
+		if let Some(it) = self.index.query(ctg, start, end) {
+			for e in it {
+				self.write_entry(ctg, &e?)?;
+				n += 1;
+			}
+		}
+		Ok(n)
+	}
+}
+
+/// Look up SNPs by rs ID or by region.  Regions are output in the order given followed by the SNPs found
+/// by rs ID (in index order).  Looking up rs IDs requires a scan through the index
+pub fn query(conf: &QueryConfig) -> io::Result<()> {
+	let idx = conf.index();
+	let mut wrt = SnpWriter::new(conf)?;
+	let mut rs_set = HashSet::new();
+	for item in conf.items() {
+		match item {
+			QueryItem::Region(ctg, start, end) => {
+				if idx.contig_range(ctg).is_none() { warn!("Contig {} not found in index", ctg) }
+				else {
+					let n = wrt.write_region(ctg, *start, *end)?;
+					debug!("Found {} SNPs in {}:{}-{}", n, ctg, start, end);
+				}
+			},
+			QueryItem::Rs(rs) => { rs_set.insert(rs.as_str()); },
+		}
+	}
+	if !rs_set.is_empty() {
+		info!("Scanning index for {} rs IDs", rs_set.len());
+		'outer: for ctg in idx.contig_names() {
+			for e in idx.query(ctg, 0, usize::MAX).unwrap() {
+				let e = e?;
+				if rs_set.remove(e.rs.as_str()) {
+					wrt.write_entry(ctg, &e)?;
+					if rs_set.is_empty() { break 'outer }
+				}
+			}
+		}
+		for rs in rs_set.iter() { warn!("{} not found in index", rs) }
+	}
+	wrt.wrt.flush()
+}
+
+/// Write out all SNPs in index
+pub fn dump(conf: &QueryConfig) -> io::Result<()> {
+	let idx = conf.index();
+	let mut wrt = SnpWriter::new(conf)?;
+	let mut n_snps = 0;
+	for ctg in idx.contig_names() {
+		n_snps += wrt.write_region(ctg, 0, usize::MAX)?;
+	}
+	info!("Wrote out {} SNPs from {}", n_snps, idx.filename());
+	wrt.wrt.flush()
+}
+
+/// Display summary information on the index and the SNPs it contains
+pub fn info(idx: &DBSnpIndex) -> io::Result<()> {
+	let stdout = io::stdout();
+	let mut wrt = stdout.lock();
+	writeln!(wrt, "File:\t{}\nFormat version:\t{}\nDescription:\t{}", idx.filename(), idx.version(), idx.header())?;
+	let pops = idx.populations();
+	writeln!(wrt, "Populations:\t{}", if pops.is_empty() { "-".to_string() } else { pops.join(",") })?;
+	writeln!(wrt, "\nContig\tSNPs\tSelected\tWith alleles\tFirst\tLast")?;
+	let (mut tot, mut tot_sel, mut tot_all) = (0, 0, 0);
+	for ctg in idx.contig_names() {
+		let (mut n, mut n_sel, mut n_all) = (0, 0, 0);
+		let mut range: Option<(usize, usize)> = None;
+		for e in idx.query(ctg, 0, usize::MAX).ok_or_else(|| new_err(format!("Couldn't read data for contig {}", ctg)))? {
+			let e = e?;
+			n += 1;
+			if e.selected { n_sel += 1 }
+			if !e.alleles.is_empty() { n_all += 1 }
+			range = Some(range.map(|(a, _)| (a, e.pos)).unwrap_or((e.pos, e.pos)));
+		}
+		let (first, last) = range.map(|(a, b)| (a.to_string(), b.to_string())).unwrap_or_else(|| ("-".to_string(), "-".to_string()));
+		writeln!(wrt, "{}\t{}\t{}\t{}\t{}\t{}", ctg, n, n_sel, n_all, first, last)?;
+		tot += n;
+		tot_sel += n_sel;
+		tot_all += n_all;
+	}
+	writeln!(wrt, "Total\t{}\t{}\t{}\t-\t-", tot, tot_sel, tot_all)
+}
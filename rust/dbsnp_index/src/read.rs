@@ -16,6 +16,7 @@ use crate::contig::Contig;
 pub mod read_bed;
 pub mod read_json;
 pub mod read_vcf;
+pub mod vcf_filter;
 
 use read_bed::process_bed_line;
 use read_json::process_json_line;
@@ -57,13 +58,13 @@ fn check_file_type(buf: &str) -> IType {
 
 pub fn proc_read_thread(conf: Arc<Config>, r: Receiver<(Vec<String>, IType)>) {
 	let mut rbuf = ReaderBuf::new(256);
-	let mut builder = SnpBuilder::new(conf.ctg_hash());
+	let mut builder = SnpBuilder::new(&conf);
 	for (mut v, itype) in r.iter() {
 		for buf in v.drain(..) {
 			match itype {
 				IType::Bed => process_bed_line(&conf, &buf, &mut builder, &mut rbuf),
 				IType::Json => process_json_line(&buf, &mut builder, &mut rbuf),
-				IType::Vcf => process_vcf_line(&buf, &mut builder, &mut rbuf),
+				IType::Vcf => process_vcf_line(&conf, &buf, &mut builder, &mut rbuf),
 				_ => panic!("Unknown file type"),
 			}
 		}
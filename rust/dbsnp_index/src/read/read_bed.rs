@@ -13,7 +13,7 @@ fn snp_from_bed(s: &str, rb: &mut SnpBuilder) -> Option<Snp> {
 	if v.len() > 4 {
 		let x = <u32>::from_str(&v[1]).ok()?;
 		let y = <u32>::from_str(&v[2]).ok()?;
-		if y > x && y - x == 1 { return rb.mk_snp(v[3], v[0], y, None)}
+		if y > x && y - x == 1 { return rb.mk_snp(v[3], v[0], y, None, Default::default())}
 	}
 	None	
 }
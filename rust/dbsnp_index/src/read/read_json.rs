@@ -5,7 +5,7 @@ use lazy_static::lazy_static;
 use json_rs::*;
 
 use super::*;
-use crate::snp::SnpBuilder;
+use crate::snp::{SnpBuilder, SnpAlleles};
 
 enum JsKey {
 	RefsnpId, PrimarySnapshotData, PlacementsWithAllele, IsPtlp, Alleles,
@@ -72,6 +72,8 @@ struct JsonSnp<'a> {
 	inserted_sequence: Option<u8>,
 	deleted_sequence: Option<u8>,
 	alleles: [u8; 2],	
+	study: Option<&'a str>,
+	study_counts: Vec<(&'a str, u32, u32)>,
 }
 
 fn handle_json_tokens<'a>(jtxt: &'a str, jtok: &[JTok], jsnp: &mut JsonSnp<'a>, level: usize) -> usize {
@@ -148,7 +150,9 @@ fn handle_json_tokens<'a>(jtxt: &'a str, jtok: &[JTok], jsnp: &mut JsonSnp<'a>,
 											if (jsnp.mask & FREQ_FLAGS) == FREQ_FLAGS && jsnp.a <= jsnp.b {
 												jsnp.allele_count += jsnp.a;
 												jsnp.total_count += jsnp.b;
+												if let Some(st) = jsnp.study.take() { jsnp.study_counts.push((st, jsnp.a, jsnp.b)) }
 											}
+											jsnp.study = None;
 											jsnp.mask &= !FREQ_FLAGS;
 										}
 										jsnp.mask &= !IN_FREQUENCY;
@@ -243,6 +247,7 @@ fn handle_json_tokens<'a>(jtxt: &'a str, jtok: &[JTok], jsnp: &mut JsonSnp<'a>,
 								JsKey::StudyName => {
 									if level == 5 && ntok1.tok_type == JType::String && (jsnp.mask & IN_FREQUENCY) != 0 {
 										jsnp.mask |= STUDY_NAME_OK;
+										jsnp.study = Some(&jtxt[ntok1.start..ntok1.end]);
 										j += 2;
 										child_processed = true;
 									}									
@@ -300,7 +305,18 @@ fn snp_from_json(s: &str, rb: &mut SnpBuilder) -> Option<Snp> {
 		if (jsnp.mask & VALID_SNP) != 0 {
 			if let (Some(name), Some(cname), Some(pos)) = (jsnp.name, jsnp.cname, jsnp.pos) {
 //				println!("rs{} {} {} {:?}", name, cname, pos, jsnp.maf);
-				rb.build_snp(name, cname, pos, jsnp.maf)
+				// alleles[0] is the inserted (alternate) sequence and alleles[1] the deleted (reference) sequence
+				let (alt, rf) = (jsnp.alleles[0] as char, jsnp.alleles[1] as char);
+				let alleles = SnpAlleles::mk_desc(&rf.to_string(), &[&alt.to_string()]);
+				let freq = |a: u32, b: u32| if b > 0 { Some(a as f32 / b as f32) } else { None };
+				let afs = rb.populations().iter().map(|pop| {
+					if pop == "AF" { freq(jsnp.allele_count, jsnp.total_count) } 
+					else {
+						let (a, b) = jsnp.study_counts.iter().filter(|(s, _, _)| s == pop).fold((0, 0), |(a, b), (_, x, y)| (a + x, b + y));
+						freq(a, b)
+					}
+				}).collect();
+				rb.build_snp(name, cname, pos, jsnp.maf, SnpAlleles{alleles, afs})
 			} else { None }
 		} else { None }
 	} else { None }
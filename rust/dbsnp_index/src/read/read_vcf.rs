@@ -1,19 +1,80 @@
 use std::str::FromStr;
 
 use super::*;
-use crate::snp::SnpBuilder;
+use super::vcf_filter::VcfRec;
+use crate::snp::{SnpBuilder, SnpAlleles, calc_maf};
+use crate::config::Config;
 
-fn snp_from_vcf(s: &str, rb: &mut SnpBuilder) -> Option<Snp> {
+// Parse a list of allele frequencies.  Missing values ('.') give None for that allele.  Returns None
+// unless there are n values
+fn parse_afs(s: &str, n: usize) -> Option<Vec<Option<f32>>> {
+	let v: Vec<Option<f32>> = s.split(',').map(|x| <f32>::from_str(x).ok()).collect();
+	if v.len() == n { Some(v) } else { None }
+}
+
+// Get values for an INFO tag as floats
+fn get_info_afs(info: &str, tag: &str, n: usize) -> Option<Vec<Option<f32>>> {
+	let v = info.split(';').find_map(|f| f.strip_prefix(tag).and_then(|x| x.strip_prefix('=')))?;
+	parse_afs(v, n)
+}
+
+// Get alternate allele frequencies for a population from the INFO field.  AF or AF_xxx are read from the
+// corresponding INFO tag; otherwise the frequencies are taken from the dbSNP FREQ tag, which has the format
+// FREQ=Study1:f_ref,f_alt1,...|Study2:...
+fn get_pop_afs(info: &str, pop: &str, n_alt: usize) -> Option<Vec<Option<f32>>> {
+	if pop == "AF" || pop.starts_with("AF_") { get_info_afs(info, pop, n_alt) }
+	else {
+		let freq = info.split(';').find_map(|f| f.strip_prefix("FREQ="))?;
+		let v = freq.split('|').find_map(|f| f.strip_prefix(pop).and_then(|x| x.strip_prefix(':')))?;
+		// FREQ values include the reference allele, which we skip
+		parse_afs(v, n_alt + 1).map(|mut v| v.split_off(1))
+	}
+}
+
+// Records are skipped if they have failed filters (unless this check is switched off) or if none of the alternate
+// alleles pass the filter expression.  Alternate alleles that fail the filter expression or that are not simple
+// sequences (i.e., symbolic alleles or '*') are removed, and the remaining alleles with their frequencies are
+// stored in a single entry.  If the record has no rs ID an ID is generated when the index is read
+fn snp_from_vcf(s: &str, conf: &Config, rb: &mut SnpBuilder) -> Option<Snp> {
 	let v: Vec<&str> = s.split('\t').collect();
-	if v.len() > 4 && v[3].len() == 1 && v[4].len() == 1 {
-		let pos = <u32>::from_str(&v[1]).ok()?;
-		return rb.mk_snp(v[2], v[0], pos, None)
+	if v.len() > 4 {
+		let pos = <u32>::from_str(v[1]).ok()?;
+		let filter = v.get(6).copied().unwrap_or(".");
+		if !conf.ignore_filter() && filter != "PASS" && filter != "." { return None }
+		let info = v.get(7).copied().unwrap_or("");
+		let alt: Vec<&str> = v[4].split(',').collect();
+		let rec = VcfRec{chrom: v[0], pos: v[1], id: v[2], ref_allele: v[3], alt: &alt, qual: v.get(5).copied().unwrap_or("."), filter, info};
+		let simple = |s: &str| !s.is_empty() && s.bytes().all(|c| matches!(c.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'N'));
+		let keep: Vec<usize> = (0..alt.len()).filter(|i| conf.vcf_filter().map(|f| f.test(&rec, *i)).unwrap_or(true)).collect();
+		if keep.is_empty() { return None }
+		let keep: Vec<usize> = if keep.iter().all(|i| !simple(alt[*i])) { keep } else { keep.into_iter().filter(|i| simple(alt[*i])).collect() };
+		let kept_alt: Vec<&str> = keep.iter().map(|i| alt[*i]).collect();
+		let alleles = SnpAlleles::mk_desc(v[3], &kept_alt);
+		let n_alt = alt.len();
+		let afs = if alleles.is_some() {
+			let mut afs = Vec::with_capacity(keep.len() * rb.populations().len());
+			for pop in rb.populations().iter() {
+				match get_pop_afs(info, pop, n_alt) {
+					Some(x) => afs.extend(keep.iter().map(|i| x[*i])),
+					None => afs.extend(keep.iter().map(|_| None)),
+				}
+			}
+			afs
+		} else { Vec::new() };
+		// MAF is calculated from all alternate alleles, not just those that are kept
+		let maf = conf.af_tag().and_then(|tag| get_info_afs(info, tag, n_alt))
+			.and_then(|f| f.into_iter().collect::<Option<Vec<f32>>>()).map(|f| calc_maf(&f));
+		let sa = SnpAlleles{alleles, afs};
+		return match v[2].split(';').find(|x| x.starts_with("rs")) {
+			Some(id) => rb.mk_snp(id, v[0], pos, maf, sa),
+			None => rb.mk_anon_snp(v[0], pos, maf, sa),
+		}
 	}
-	None	
+	None
 }
 
-pub fn process_vcf_line(buf: &str, builder: &mut SnpBuilder, rbuf: &mut ReaderBuf) {
+pub fn process_vcf_line(conf: &Config, buf: &str, builder: &mut SnpBuilder, rbuf: &mut ReaderBuf) {
 	if !buf.starts_with('#') {
-		if let Some(snp) = snp_from_vcf(&buf, builder) { rbuf.add_snp(snp) }
-	}	
+		if let Some(snp) = snp_from_vcf(buf, conf, builder) { rbuf.add_snp(snp) }
+	}
 }
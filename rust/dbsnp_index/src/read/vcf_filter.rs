@@ -0,0 +1,281 @@
+// Simple filter expressions for VCF input
+//
+// Expressions are made from comparisons of the form FIELD OP VALUE, where OP is one of ==, !=, <, <=, > or >=, combined
+// using &&, || and ! with parentheses for grouping (e.g., AF>0.01 && FILTER==PASS).  A field name on its own is
+// true if the field is present (useful for INFO flags).  FIELD can be one of the fixed VCF fields CHROM, POS, ID, REF,
+// ALT, QUAL or FILTER, otherwise it is taken as an INFO tag (an optional INFO/ prefix is removed).  Comparisons are
+// numeric if both sides are numbers, otherwise they are string comparisons.
+//
+// Expressions are evaluated separately for each alternate allele of multi-allelic records.  INFO fields declared in
+// the VCF header with one value per alternate allele (Number=A), per allele (Number=R) or with a variable number of
+// values (Number=.) use the value corresponding to the allele being tested.  If a field is not declared in the header
+// (e.g., when reading from stdin), a field with one value more than the number of alternate alleles is taken as
+// Number=R and other multi-valued fields are indexed by allele.  INFO fields with a single value apply to all alleles.
+// For other multi-valued fields (including ID and FILTER) a comparison is true if any value matches, except for != which
+// is true if no value matches.  Missing fields or values make a comparison false.
+
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use utils::compress;
+
+use crate::config::new_err;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CmpOp { Eq, Ne, Lt, Le, Gt, Ge }
+
+impl CmpOp {
+	fn test(&self, ord: Ordering) -> bool {
+		match self {
+			CmpOp::Eq => ord == Ordering::Equal,
+			CmpOp::Ne => ord != Ordering::Equal,
+			CmpOp::Lt => ord == Ordering::Less,
+			CmpOp::Le => ord != Ordering::Greater,
+			CmpOp::Gt => ord == Ordering::Greater,
+			CmpOp::Ge => ord != Ordering::Less,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token { LParen, RParen, And, Or, Not, Op(CmpOp), Word(String) }
+
+#[derive(Debug, Clone)]
+enum Expr {
+	And(Box<Expr>, Box<Expr>),
+	Or(Box<Expr>, Box<Expr>),
+	Not(Box<Expr>),
+	Present(String),
+	Cmp(String, CmpOp, String),
+}
+
+/// Number of values of an INFO field from the VCF header
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InfoNumber { A, R, Var, Fixed }
+
+/// Fields of a VCF record
+pub struct VcfRec<'a> {
+	pub chrom: &'a str,
+	pub pos: &'a str,
+	pub id: &'a str,
+	pub ref_allele: &'a str,
+	pub alt: &'a [&'a str],
+	pub qual: &'a str,
+	pub filter: &'a str,
+	pub info: &'a str,
+}
+
+impl <'a> VcfRec<'a> {
+	fn info_value(&self, tag: &str) -> Option<Option<&'a str>> {
+		self.info.split(';').find_map(|f| {
+			let mut it = f.splitn(2, '=');
+			if it.next() == Some(tag) { Some(it.next()) } else { None }
+		})
+	}
+
+	// Values of a field for alternate allele alt_ix.  Returns None if the field is not present
+	fn values(&self, field: &str, alt_ix: usize, numbers: &HashMap<String, InfoNumber>) -> Option<Vec<&'a str>> {
+		let n_alt = self.alt.len();
+		let v = match field {
+			"CHROM" => vec!(self.chrom),
+			"POS" => vec!(self.pos),
+			"ID" => self.id.split(';').collect(),
+			"REF" => vec!(self.ref_allele),
+			"ALT" => vec!(*self.alt.get(alt_ix)?),
+			"QUAL" => vec!(self.qual),
+			"FILTER" => self.filter.split(';').collect(),
+			_ => {
+				let tag = field.strip_prefix("INFO/").unwrap_or(field);
+				match self.info_value(tag)? {
+					Some(s) => {
+						let v: Vec<&str> = s.split(',').collect();
+						let ix = match numbers.get(tag) {
+							Some(InfoNumber::A) | Some(InfoNumber::Var) => Some(alt_ix),
+							Some(InfoNumber::R) => Some(alt_ix + 1),
+							Some(InfoNumber::Fixed) => None,
+							None => if v.len() == n_alt + 1 { Some(alt_ix + 1) } else { Some(alt_ix) },
+						};
+						match ix {
+							Some(i) if v.len() > 1 => v.get(i).copied().into_iter().collect(),
+							_ => v,
+						}
+					},
+					None => Vec::new(),
+				}
+			},
+		};
+		Some(v.into_iter().filter(|s| *s != ".").collect())
+	}
+}
+
+fn compare(a: &str, b: &str) -> Ordering {
+	match (<f64>::from_str(a), <f64>::from_str(b)) {
+		(Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Less),
+		_ => a.cmp(b),
+	}
+}
+
+impl Expr {
+	fn eval(&self, rec: &VcfRec, alt_ix: usize, numbers: &HashMap<String, InfoNumber>) -> bool {
+		match self {
+			Expr::And(a, b) => a.eval(rec, alt_ix, numbers) && b.eval(rec, alt_ix, numbers),
+			Expr::Or(a, b) => a.eval(rec, alt_ix, numbers) || b.eval(rec, alt_ix, numbers),
+			Expr::Not(a) => !a.eval(rec, alt_ix, numbers),
+			Expr::Present(f) => rec.values(f, alt_ix, numbers).is_some(),
+			Expr::Cmp(f, op, val) => match rec.values(f, alt_ix, numbers) {
+				Some(v) if !v.is_empty() => {
+					if *op == CmpOp::Ne { v.iter().all(|x| op.test(compare(x, val))) }
+					else { v.iter().any(|x| op.test(compare(x, val))) }
+				},
+				_ => false,
+			},
+		}
+	}
+}
+
+// Consume next character if it matches x
+fn next_is(it: &mut Peekable<Chars>, x: char) -> bool {
+	if it.peek() == Some(&x) {
+		it.next();
+		true
+	} else { false }
+}
+
+fn tokenize(s: &str) -> io::Result<Vec<Token>> {
+	let mut tokens = Vec::new();
+	let mut it = s.chars().peekable();
+	while let Some(c) = it.next() {
+		let tok = match c {
+			' ' | '\t' => continue,
+			'(' => Token::LParen,
+			')' => Token::RParen,
+			'&' if next_is(&mut it, '&') => Token::And,
+			'|' if next_is(&mut it, '|') => Token::Or,
+			'=' if next_is(&mut it, '=') => Token::Op(CmpOp::Eq),
+			'!' => if next_is(&mut it, '=') { Token::Op(CmpOp::Ne) } else { Token::Not },
+			'<' => if next_is(&mut it, '=') { Token::Op(CmpOp::Le) } else { Token::Op(CmpOp::Lt) },
+			'>' => if next_is(&mut it, '=') { Token::Op(CmpOp::Ge) } else { Token::Op(CmpOp::Gt) },
+			'"' => {
+				let mut w = String::new();
+				loop {
+					match it.next() {
+						Some('"') => break,
+						Some(c) => w.push(c),
+						None => return Err(new_err(format!("Unterminated string in filter expression '{}'", s))),
+					}
+				}
+				Token::Word(w)
+			},
+			c if !"&|=!<>()\" \t".contains(c) => {
+				let mut w = c.to_string();
+				while let Some(c) = it.peek() {
+					if "&|=!<>()\" \t".contains(*c) { break }
+					w.push(*c);
+					it.next();
+				}
+				Token::Word(w)
+			},
+			_ => return Err(new_err(format!("Unexpected character '{}' in filter expression '{}'", c, s))),
+		};
+		tokens.push(tok);
+	}
+	Ok(tokens)
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	ix: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> { self.tokens.get(self.ix) }
+	fn next(&mut self) -> Option<Token> {
+		let t = self.tokens.get(self.ix).cloned();
+		self.ix += 1;
+		t
+	}
+	fn parse_or(&mut self) -> io::Result<Expr> {
+		let mut e = self.parse_and()?;
+		while self.peek() == Some(&Token::Or) {
+			self.ix += 1;
+			e = Expr::Or(Box::new(e), Box::new(self.parse_and()?));
+		}
+		Ok(e)
+	}
+	fn parse_and(&mut self) -> io::Result<Expr> {
+		let mut e = self.parse_unary()?;
+		while self.peek() == Some(&Token::And) {
+			self.ix += 1;
+			e = Expr::And(Box::new(e), Box::new(self.parse_unary()?));
+		}
+		Ok(e)
+	}
+	fn parse_unary(&mut self) -> io::Result<Expr> {
+		match self.next() {
+			Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+			Some(Token::LParen) => {
+				let e = self.parse_or()?;
+				if self.next() != Some(Token::RParen) { Err(new_err("Missing ')' in filter expression".to_string())) }
+				else { Ok(e) }
+			},
+			Some(Token::Word(f)) => {
+				if let Some(Token::Op(op)) = self.peek().cloned() {
+					self.ix += 1;
+					match self.next() {
+						Some(Token::Word(v)) => Ok(Expr::Cmp(f, op, v)),
+						_ => Err(new_err(format!("Missing value after comparison with {} in filter expression", f))),
+					}
+				} else { Ok(Expr::Present(f)) }
+			},
+			t => Err(new_err(format!("Unexpected token {:?} in filter expression", t))),
+		}
+	}
+}
+
+/// Filter expression for VCF records
+#[derive(Debug, Clone)]
+pub struct VcfFilter {
+	expr: Expr,
+	numbers: HashMap<String, InfoNumber>,
+}
+
+impl VcfFilter {
+	pub fn new(s: &str) -> io::Result<Self> {
+		let mut parser = Parser{tokens: tokenize(s)?, ix: 0};
+		let expr = parser.parse_or()?;
+		if parser.ix < parser.tokens.len() { return Err(new_err(format!("Unexpected trailing characters in filter expression '{}'", s))) }
+		Ok(Self{expr, numbers: HashMap::new()})
+	}
+	/// Read the Number attribute of INFO fields from the header of a VCF file
+	pub fn read_header(&mut self, file: &str) -> io::Result<()> {
+		let mut rdr = compress::open_bufreader(file)?;
+		let mut buf = String::with_capacity(256);
+		loop {
+			buf.clear();
+			if rdr.read_line(&mut buf)? == 0 || !buf.starts_with("##") { break }
+			if let Some(s) = buf.trim_end().strip_prefix("##INFO=<").and_then(|x| x.strip_suffix('>')) {
+				let mut id = None;
+				let mut number = None;
+				for f in s.split(',') {
+					if let Some(x) = f.strip_prefix("ID=") { id = Some(x) }
+					else if let Some(x) = f.strip_prefix("Number=") {
+						number = Some(match x {
+							"A" => InfoNumber::A,
+							"R" => InfoNumber::R,
+							"." => InfoNumber::Var,
+							_ => InfoNumber::Fixed,
+						})
+					}
+				}
+				if let (Some(id), Some(n)) = (id, number) { self.numbers.insert(id.to_owned(), n); }
+			}
+		}
+		Ok(())
+	}
+	/// Test alternate allele alt_ix (0 offset) of record
+	pub fn test(&self, rec: &VcfRec, alt_ix: usize) -> bool { self.expr.eval(rec, alt_ix, &self.numbers) }
+}
@@ -6,17 +6,71 @@ use crossbeam_channel::{Receiver, Select};
 use super::contig::*;
 use crate::config::Config;
 
+// Maximum length of allele description (REF,ALT1,ALT2...) stored in the index.  SNPs with longer
+// descriptions (i.e., larger indels) are stored without allele information
+pub const MAX_ALLELE_DESC_LEN: usize = 128;
+
 #[derive(Debug)]
 pub struct RawSnp {
 	name: String,
 	pos: u32,
 	maf: Option<f32>,
+	alleles: Option<String>,
+	afs: Vec<Option<f32>>,
+	selected: bool,
 }
 
 impl RawSnp {
 	pub fn name(&self) -> &str { &self.name }
+	/// SNP already flagged as selected (i.e., from an existing index)
+	pub fn selected(&self) -> bool { self.selected }
 	pub fn pos(&self) -> u32 { self.pos }
 	pub fn maf(&self) -> Option<f32> { self.maf }
+	/// Alleles as a comma separated list with the reference allele first
+	pub fn alleles(&self) -> Option<&str> { self.alleles.as_deref() }
+	/// Number of alternate alleles
+	pub fn n_alt(&self) -> usize { self.alleles.as_ref().map(|s| s.split(',').count() - 1).unwrap_or(0) }
+	/// Frequencies of alternate alleles for each population (population major, n_pops * n_alt entries)
+	pub fn afs(&self) -> &[Option<f32>] { &self.afs }
+}
+
+/// Allele and frequency information for a SNP
+#[derive(Debug, Default)]
+pub struct SnpAlleles {
+	pub alleles: Option<String>,
+	pub afs: Vec<Option<f32>>,
+}
+
+impl SnpAlleles {
+	/// Make allele description from reference and alternate alleles. Returns None if the
+	/// alleles are missing, not simple sequences or are too long to be stored
+	pub fn mk_desc(ref_allele: &str, alt: &[&str]) -> Option<String> {
+		let valid = |s: &str| !s.is_empty() && s.bytes().all(|c| matches!(c.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'N'));
+		if alt.is_empty() || !valid(ref_allele) || !alt.iter().all(|s| valid(s)) { return None }
+		let mut s = ref_allele.to_ascii_uppercase();
+		for a in alt.iter() {
+			s.push(',');
+			s.push_str(&a.to_ascii_uppercase());
+		}
+		if s.len() > MAX_ALLELE_DESC_LEN { None } else { Some(s) }
+	}
+	// Minor allele frequency from first population with frequency information
+	fn maf(&self, n_alt: usize) -> Option<f32> {
+		if n_alt == 0 { return None }
+		self.afs.chunks(n_alt).find(|v| v.iter().all(|x| x.is_some())).map(|v| {
+			let f: Vec<f32> = v.iter().map(|x| x.unwrap()).collect();
+			calc_maf(&f)
+		})
+	}
+}
+
+/// Minor allele frequency from the alternate allele frequencies.  The minor allele is taken as the
+/// second most frequent allele
+pub fn calc_maf(alt_afs: &[f32]) -> f32 {
+	let mut f = alt_afs.to_vec();
+	f.push((1.0 - f.iter().sum::<f32>()).max(0.0));
+	f.sort_unstable_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+	f[1]
 }
 
 #[derive(Debug)]
@@ -26,6 +80,7 @@ pub struct Snp {
 }
 
 impl Snp {
+	pub fn set_selected(&mut self, selected: bool) { self.raw_snp.selected = selected }
 	pub fn components(self) -> (RawSnp, Arc<Contig>) {
 		let Snp{raw_snp, contig} = self;
 		(raw_snp, contig)
@@ -52,27 +107,40 @@ impl SnpBlock {
 
 pub struct SnpBuilder<'a> {
 	ctg_lookup: ContigLookup<'a>,
+	populations: &'a [String],
 }
 
 impl <'a>SnpBuilder<'a> {
-	pub fn new(ctg_hash: &'a ContigHash) -> Self {
-		Self{ctg_lookup: ctg_hash.mk_lookup()}
+	pub fn new(conf: &'a Config) -> Self {
+		Self{ctg_lookup: conf.ctg_hash().mk_lookup(), populations: conf.populations()}
 	}
-	pub fn build_snp(&mut self, name: &str, ctg: &str, pos: u32, maf: Option<f32>) -> Option<Snp> {
+	pub fn populations(&self) -> &[String] { self.populations }
+	pub fn build_snp(&mut self, name: &str, ctg: &str, pos: u32, maf: Option<f32>, sa: SnpAlleles) -> Option<Snp> {
 		if let Some(contig) = self.ctg_lookup.get_contig(ctg) {
+			let SnpAlleles{alleles, afs} = sa;
+			let n_alt = alleles.as_ref().map(|s| s.split(',').count() - 1).unwrap_or(0);
+			// Frequencies are stored (possibly as missing) for all populations iff the alleles are known
+			let n = n_alt * self.populations.len();
+			let afs = if afs.len() == n { afs } else { vec!(None; n) };
+			let sa = SnpAlleles{alleles, afs};
+			let maf = maf.or_else(|| sa.maf(n_alt));
 			Some(Snp {
 				raw_snp: RawSnp {
 					name: name.to_owned(),
-					pos, maf
+					pos, maf, alleles: sa.alleles, afs: sa.afs, selected: false,
 				},
 				contig
 			})
 		} else { None }	
 	}
-	pub fn mk_snp(&mut self, name: &str, ctg: &str, pos: u32, maf: Option<f32>) -> Option<Snp> {		
+	/// Make SNP without an rs ID.  Readers of the index generate an ID from the position and alleles
+	pub fn mk_anon_snp(&mut self, ctg: &str, pos: u32, maf: Option<f32>, sa: SnpAlleles) -> Option<Snp> {
+		self.build_snp("", ctg, pos, maf, sa)
+	}
+	pub fn mk_snp(&mut self, name: &str, ctg: &str, pos: u32, maf: Option<f32>, sa: SnpAlleles) -> Option<Snp> {		
 		if let Some(tname) = name.strip_prefix("rs") {
 			if tname.is_empty() || tname.find(|c :char| !char::is_ascii_digit(&c)).is_some() { None }
-            else { self.build_snp(tname, ctg, pos, maf) }
+            else { self.build_snp(tname, ctg, pos, maf, sa) }
 		} else { None }
 	}
 }
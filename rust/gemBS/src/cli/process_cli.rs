@@ -104,6 +104,9 @@ pub fn process_cli(gem_bs: &mut GemBS) -> Result<(), String> {
 		("clear", Some(m_sum)) => {
 			commands::clear::clear_command(m_sum, gem_bs)
 		},
+		("config", Some(m_sum)) => {
+			commands::config::config_command(m_sum, gem_bs)
+		},
 		("completions", Some(m_sum)) => {
 			generate_completions(m_sum)
 		},
@@ -133,14 +133,26 @@ lazy_static! {
     	m.push(("ref_bias", OptionType::Global("reference_bias", VarType::Float)));
       	m.push(("strand_specific", OptionType::Global("strand_specific", VarType::Bool)));
       	m.push(("bigwig_strand_specific", OptionType::Global("bigwig_strand_specific", VarType::Bool)));
+      	m.push(("cpg_annotation", OptionType::Global("cpg_annotation", VarType::Bool)));
+      	m.push(("mask_allele_cpg", OptionType::Global("mask_allele_cpg", VarType::Bool)));
+      	m.push(("bigwig_tracks", OptionType::Global("bigwig_tracks", VarType::String)));
       	m.push(("min_inform", OptionType::Global("min_inform", VarType::Int)));
       	m.push(("min_nc", OptionType::Global("min_nc", VarType::Int)));
+      	m.push(("non_cpg_contexts", OptionType::Global("non_cpg_contexts", VarType::String)));
       	m.push(("allow_het", OptionType::Global("allow_het", VarType::Bool)));
       	m.push(("ref_bias", OptionType::Global("reference_bias", VarType::Float)));
      	m.push(("cpg", OptionType::Global("make_cpg", VarType::Bool)));
      	m.push(("non_cpg", OptionType::Global("make_non_cpg", VarType::Bool)));
      	m.push(("bed_methyl", OptionType::Global("make_bedmethyl", VarType::Bool)));
      	m.push(("snps", OptionType::Global("make_snps", VarType::Bool)));
+     	m.push(("haplotype", OptionType::Global("make_haplotype", VarType::Bool)));
+     	m.push(("allele_report", OptionType::Global("make_allele_report", VarType::Bool)));
+     	m.push(("context_summary", OptionType::Global("make_context_summary", VarType::Bool)));
+     	m.push(("context_summary_regions", OptionType::Global("context_summary_regions", VarType::String)));
+     	m.push(("context_bigwig", OptionType::Global("make_context_bigwig", VarType::Bool)));
+     	m.push(("context_bigwig_contexts", OptionType::Global("context_bigwig_contexts", VarType::String)));
+     	m.push(("haplotype_window", OptionType::Global("haplotype_window", VarType::Int)));
+     	m.push(("haplotype_min_reads", OptionType::Global("haplotype_min_reads", VarType::Int)));
     	m.push(("snp_list", OptionType::Global("snp_list", VarType::String)));
     	m.push(("snp_db", OptionType::Global("dbsnp_index", VarType::String)));
  	  	m.push(("sampling", OptionType::Global("sampling_rate", VarType::Int)));
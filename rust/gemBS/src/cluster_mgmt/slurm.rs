@@ -50,6 +50,8 @@ impl SlurmJob {
 	}
 }
 
+// Each job runs the task through gemBS, which writes the provenance and parameter records for the
+// outputs when the task completes, in the same way as for locally run tasks
 fn write_sbatch_script<T: fmt::Write>(wrt: &mut T, jv: &SlurmJob, tl: &TaskList,  options: &HashMap<&'static str, DataValue>, verbose: LogLevel) -> fmt::Result {
 	writeln!(wrt, "#!/bin/sh")?;
 	let job_array = jv.task_vec.len() > 1;
@@ -54,7 +54,7 @@ fn gen_call_command(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataValu
 	let mut assets = get_required_asset_list(gem_bs, &options)?;
 	let mut coms = HashSet::new();
 	if !options.contains_key("_no_md5") { super::md5sum::get_assets_md5_call(gem_bs, &options, &mut assets, &mut coms)?; }
-	if gem_bs.all() { [Command::Index, Command::Map, Command::MergeBams, Command::MD5SumMap, Command::Call].iter().for_each(|x| { coms.insert(*x); }) }
+	if gem_bs.all() { [Command::Index, Command::Trim, Command::Map, Command::MergeBams, Command::MD5SumMap, Command::Call].iter().for_each(|x| { coms.insert(*x); }) }
 	else if !(options.contains_key("_merge") || options.contains_key("_index") || options.contains_key("_md5")) { coms.insert(Command::Call); }
 	if !(options.contains_key("_no_merge") || options.contains_key("_index") || options.contains_key("_md5")) { 
 		coms.insert(Command::MergeBcfs); 
@@ -59,6 +59,7 @@ pub fn clear_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String> {
 			None => warn!("Unknown task {} in running list", rtask.id())
 		}
 	}
+	gem_bs.clear_partial_ref_cache()?;
 	if flock.path().exists() {
 		info!("Removing list of incomplete tasks");
 		let _ = std::fs::remove_file(flock.path());
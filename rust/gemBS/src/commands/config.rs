@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::PathBuf;
+use clap::ArgMatches;
+
+use crate::config::GemBS;
+use crate::commands::prepare::config_file::{check_config_file, make_config_schema};
+
+pub fn config_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String> {
+	match m.subcommand() {
+		("check", Some(m_sum)) => check_command(m_sum, gem_bs),
+		("schema", Some(m_sum)) => schema_command(m_sum),
+		_ => Err("Unknown config subcommand".to_string()),
+	}
+}
+
+// Check the config file without preparing the analysis (so the .gemBS directory is not touched)
+fn check_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String> {
+	let file = m.value_of("config").unwrap();
+	// If the installation root can not be found then only local include files can be used
+	let script_path = match gem_bs.find_gem_bs_root() {
+		Ok(root) => [root, PathBuf::from("etc"), PathBuf::from("config_scripts")].iter().collect(),
+		Err(e) => {
+			debug!("{}", e);
+			PathBuf::new()
+		},
+	};
+	let check = check_config_file(file, &script_path, gem_bs)?;
+	let out = |level: &str, loc: &Option<(String, usize)>, msg: &str| match loc {
+		Some((f, l)) => println!("{}: {}: line {}: {}", level, f, l, msg),
+		None => println!("{}: {}", level, msg),
+	};
+	check.errors.iter().for_each(|(loc, msg)| out("error", loc, msg));
+	check.warnings.iter().for_each(|(loc, msg)| out("warning", loc, msg));
+	println!("Config file {}: {} error(s), {} warning(s)", file, check.errors.len(), check.warnings.len());
+	if !check.errors.is_empty() { Err(format!("Errors found in config file {}", file)) }
+	else if m.is_present("strict") && !check.warnings.is_empty() { Err(format!("Warnings found in config file {}", file)) }
+	else { Ok(()) }
+}
+
+fn schema_command(m: &ArgMatches) -> Result<(), String> {
+	let schema = serde_json::to_string_pretty(&make_config_schema()).map_err(|e| format!("Error generating JSON schema: {}", e))?;
+	match m.value_of("output") {
+		Some(f) => fs::write(f, format!("{}\n", schema)).map_err(|e| format!("Couldn't write schema file {}: {}", f, e)),
+		None => {
+			println!("{}", schema);
+			Ok(())
+		},
+	}
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use clap::ArgMatches;
 use crate::cli::utils::handle_options;
-use crate::config::GemBS;
+use crate::config::{GemBS, check_extract};
 use crate::common::defs::{Section, Command, DataValue};
 use crate::common::assets::GetAsset;
 use crate::common::{dry_run, utils};
@@ -14,6 +14,12 @@ fn get_required_asset_list(gem_bs: &GemBS, options: &HashMap<&'static str, DataV
 		if gem_bs.get_config_bool(Section::Extract, "make_cpg") { asset_list.push(gem_bs.get_asset(format!("{}_cpg.txt.gz", bc).as_str()).unwrap().idx()) }
 		if gem_bs.get_config_bool(Section::Extract, "make_non_cpg") { asset_list.push(gem_bs.get_asset(format!("{}_non_cpg.txt.gz", bc).as_str()).unwrap().idx()) }
 		if gem_bs.get_config_bool(Section::Extract, "make_bedmthyl") { asset_list.push(gem_bs.get_asset(format!("{}_cpg.bed.gz", bc).as_str()).unwrap().idx()) }
+		if gem_bs.get_config_bool(Section::Extract, "make_allele_report") { asset_list.push(gem_bs.get_asset(format!("{}_allele_report.txt.gz", bc).as_str()).unwrap().idx()) }
+		if gem_bs.get_config_bool(Section::Extract, "make_context_bigwig") {
+			for ctx in check_extract::get_bw_contexts(gem_bs, bc)?.iter() { asset_list.push(gem_bs.get_asset(format!("{}_context_{}.bw", bc, ctx).as_str()).unwrap().idx()) }
+		}
+		if gem_bs.get_config_bool(Section::Extract, "make_context_summary") { asset_list.push(gem_bs.get_asset(format!("{}_context_summary.txt", bc).as_str()).unwrap().idx()) }
+		if gem_bs.get_config_bool(Section::Extract, "make_haplotype") { asset_list.push(gem_bs.get_asset(format!("{}_haplotype.txt.gz", bc).as_str()).unwrap().idx()) }
 		if gem_bs.get_config_bool(Section::Extract, "make_snps") { asset_list.push(gem_bs.get_asset(format!("{}_snps.txt.gz", bc).as_str()).unwrap().idx()) }
 	}
 	Ok(asset_list)
@@ -28,7 +34,7 @@ pub fn extract_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String>
 	let flock = utils::wait_for_lock(gem_bs.get_signal_clone(), &task_path)?; 
 	gem_bs.setup_assets_and_tasks(&flock)?;
 	let asset_ids = get_required_asset_list(gem_bs, &options)?;
-	let task_list = if gem_bs.all() { gem_bs.get_required_tasks_from_asset_list(&asset_ids, &[Command::Index, Command::Map, Command::MergeBams, Command::Call, Command::MergeBcfs, Command::MD5SumMap, Command::MD5SumCall, Command::IndexBcf, Command::Extract])} 
+	let task_list = if gem_bs.all() { gem_bs.get_required_tasks_from_asset_list(&asset_ids, &[Command::Index, Command::Trim, Command::Map, Command::MergeBams, Command::Call, Command::MergeBcfs, Command::MD5SumMap, Command::MD5SumCall, Command::IndexBcf, Command::Extract])} 
 	else { gem_bs.get_required_tasks_from_asset_list(&asset_ids, &[Command::Extract]) };
 	if gem_bs.execute_flag() { scheduler::schedule_jobs(gem_bs, &options, &task_list, &asset_ids, &[Command::Extract], flock) }	
 	else { dry_run::handle_nonexec(gem_bs, &options, &task_list) }
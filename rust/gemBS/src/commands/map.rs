@@ -13,7 +13,7 @@ fn get_required_asset_list(gem_bs: &GemBS, options: &HashMap<&'static str, DataV
 	let mut asset_ids = HashSet::new();
 	if let Some(DataValue::StringVec(dvec)) = options.get("_dataset") {
 		for dataset in dvec.iter() {
-			if let Some(asset) = gem_bs.get_asset(format!("{}.bam", dataset).as_str()).or_else(|| {
+			if let Some(asset) = gem_bs.get_asset(format!("{}.{}", dataset, suffix).as_str()).or_else(|| {
 				if let Some(DataValue::String(bc)) = gem_bs.get_sample_data_ref().get(dataset).and_then(|rf| rf.get(&Metadata::SampleBarcode)) {
 					gem_bs.get_asset(format!("{}.{}", bc, suffix).as_str())
 				} else { None }
@@ -62,8 +62,8 @@ fn gen_map_command(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataValue
 	let mut assets = get_required_asset_list(gem_bs, &options)?;
 	let mut coms = HashSet::new();
 	if !options.contains_key("_no_md5") { super::md5sum::get_assets_md5_map(gem_bs, &options, &mut assets, &mut coms)?; }
-	if gem_bs.all() { [Command::Index, Command::Map].iter().for_each(|x| {coms.insert(*x);}) }
-	else if !(options.contains_key("_merge") || options.contains_key("_md5")) { coms.insert(Command::Map); }
+	if gem_bs.all() { [Command::Index, Command::Trim, Command::Map].iter().for_each(|x| {coms.insert(*x);}) }
+	else if !(options.contains_key("_merge") || options.contains_key("_md5")) { [Command::Trim, Command::Map].iter().for_each(|x| {coms.insert(*x);}) }
 	if !options.contains_key("_no_merge") { coms.insert(Command::MergeBams); }
 	let asset_ids: Vec<_> = assets.iter().copied().collect();
 	let com_set: Vec<_> = coms.iter().copied().collect();
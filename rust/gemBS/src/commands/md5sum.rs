@@ -12,7 +12,7 @@ pub fn get_assets_md5_map(gem_bs: &mut GemBS, options: &HashMap<&'static str, Da
 		else { return Err(format!("Unknown barcode {}", bc)); }
 	}
 	coms.insert(Command::MD5SumMap);
-	if gem_bs.all() { [Command::Index, Command::Map, Command::MergeBams].iter().for_each(|x| {coms.insert(*x);}) }	
+	if gem_bs.all() { [Command::Index, Command::Trim, Command::Map, Command::MergeBams].iter().for_each(|x| {coms.insert(*x);}) }	
 	Ok(())
 }
 
@@ -24,6 +24,6 @@ pub fn get_assets_md5_call(gem_bs: &mut GemBS, options: &HashMap<&'static str, D
 		else { return Err(format!("Unknown barcode {}", bc)); }
 	}
 	coms.insert(Command::MD5SumCall);
-	if gem_bs.all() { [Command::Index, Command::Map, Command::MergeBams, Command::Call, Command::MergeBcfs].iter().for_each(|x| {coms.insert(*x);}) }	
+	if gem_bs.all() { [Command::Index, Command::Trim, Command::Map, Command::MergeBams, Command::Call, Command::MergeBcfs].iter().for_each(|x| {coms.insert(*x);}) }	
 	Ok(())
 }
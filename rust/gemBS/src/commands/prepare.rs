@@ -2,7 +2,7 @@ use clap::ArgMatches;
 use crate::config::GemBS;
 use crate::common::defs::{Section, DataValue};
 use crate::common::utils;
-mod config_file;
+pub mod config_file;
 pub mod metadata;
 
 pub fn prepare_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String> {
@@ -17,12 +17,19 @@ pub fn prepare_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String>
 	if m.is_present("populate") { gem_bs.set_config(Section::Index, "populate_cache", DataValue::Bool(true)); }
 	
 	// Process sample metadata file
-	// This can either be a cvs file or a json file
+	// This can either be a cvs file, a json file, an Illumina sample sheet or a YAML/TOML sample sheet
 	if let Some(f) = m.value_of("cvs_metadata") { 
 		metadata::process_csv::process_cvs_metatdata_file(f, gem_bs)?;
 	} else if let Some(f) = m.value_of("json_metadata") {
 		metadata::process_json::process_json_metadata_file(f, gem_bs)?;
+	} else if let Some(f) = m.value_of("illumina_samplesheet") {
+		metadata::process_samplesheet::process_samplesheet_file(f, m.value_of("fastq_dir"), gem_bs)?;
+	} else if let Some(f) = m.value_of("sample_sheet") {
+		metadata::process_sheet::process_sheet_file(f, gem_bs)?;
 	}
+	gem_bs.check_sample_config();
+	// Get the parameters used to generate any existing assets
+	gem_bs.load_asset_params();
 	let task_path = gem_bs.get_task_file_path();
 	let flock = utils::wait_for_lock(gem_bs.get_signal_clone(), &task_path)?;
 	gem_bs.setup_assets_and_tasks(&flock)?;
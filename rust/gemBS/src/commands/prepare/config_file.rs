@@ -2,13 +2,17 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::env;
 use std::path::Path;
+use serde_json::{Value, Map, json};
+use lazy_static::lazy_static;
 
 use crate::common::defs::*;
-use crate::config::GemBS;
+use crate::config::{GemBS, ParamSet};
+use crate::scheduler::aligner::{get_aligner, check_trim_qc};
 	
 mod lex;
 mod find_var;
 mod gembs_json;
+mod var_docs;
 
 use lex::{Lexer, LexToken};
 use find_var::{find_var, Segment};
@@ -18,6 +22,8 @@ struct PrepConfigVar {
 	var: DataValue,
 	vtype:VarType,
 	section: Section,
+	scope: Option<String>,
+	location: Option<(String, usize)>,
 	known: bool,
 	used: bool,
 }
@@ -25,14 +31,17 @@ struct PrepConfigVar {
 #[derive(Debug)]
 pub struct KnownVar {
 	vtype: VarType,
-	sections: Vec<Section>
+	sections: Vec<Section>,
+	output: bool, // Does the variable affect the contents of output files
 }
 
 impl KnownVar {
 	fn new(vtype: VarType, sections: Vec<Section>) -> Self {
-		KnownVar {vtype, sections}
+		KnownVar {vtype, sections, output: true}
 	}
-
+	// Mark variables that only affect how tasks are run (threads, memory etc.) or where files are placed.  These
+	// are ignored when checking for parameter changes
+	fn no_output(&mut self) { self.output = false; }
 }
 
 #[derive(Debug)]
@@ -44,24 +53,69 @@ impl KnownVarList {
 	fn new() -> Self {
 		KnownVarList{known_var: HashMap::new()}	
 	}	
-	fn add_known_var(&mut self, name: &'static str, vtype: VarType, sections: Vec<Section>) {
+	fn add_known_var(&mut self, name: &'static str, vtype: VarType, sections: Vec<Section>) -> &mut KnownVar {
 		let mut v = sections;
 		v.push(Section::Default);
-		self.known_var.insert(name, KnownVar::new(vtype, v));
+		self.known_var.entry(name).or_insert_with(|| KnownVar::new(vtype, v))
+	}
+	// Closest known variable name (preferring those valid for the section) for suggestions
+	fn suggest_var(&self, name: &str, section: Section) -> Option<&'static str> {
+		let max_dist = (name.len() / 4).max(2);
+		self.known_var.iter().map(|(k, kv)| (edit_distance(name, k), !kv.sections.contains(&section), *k))
+			.filter(|(d, _, _)| *d <= max_dist).min().map(|(_, _, k)| k)
 	}
 	pub fn check_vtype(&self, name: &str, section: Section) -> Option<VarType> {
 		let tstr = name.to_lowercase();
 		self.known_var.get(&tstr.as_str()).and_then(|v| if v.sections.contains(&section) { Some(v.vtype) } else { None })			
 	}
+	// Convert value(s) for a known variable supplied outside of the config file (i.e., from a sample sheet)
+	pub fn parse_value(&self, name: &str, section: Section, vals: &[String]) -> Result<DataValue, String> {
+		let vtype = self.check_vtype(name, section).ok_or_else(|| format!("Unknown variable {} for section {:?}", name, section))?;
+		match vtype {
+			VarType::StringVec => Ok(DataValue::StringVec(vals.to_vec())),
+			VarType::IntVec => vals.iter().map(|s| s.parse::<isize>().map_err(|_| format!("Could not parse {} as integer value", s)))
+				.collect::<Result<Vec<_>, _>>().map(DataValue::IntVec),
+			VarType::FloatVec => vals.iter().map(|s| s.parse::<f64>().map_err(|_| format!("Could not parse {} as float value", s)))
+				.collect::<Result<Vec<_>, _>>().map(DataValue::FloatVec),
+			_ if vals.len() == 1 => DataValue::from_str(&vals[0], vtype),
+			_ => Err(format!("Expected a single value for variable {}", name)),
+		}
+	}
+}
+
+// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut curr = vec!(i + 1; b.len() + 1);
+		for (j, cb) in b.iter().enumerate() {
+			curr[j + 1] = (prev[j] + if ca == *cb { 0 } else { 1 }).min(prev[j + 1] + 1).min(curr[j] + 1);
+		}
+		prev = curr;
+	}
+	prev[b.len()]
 }
 
-fn make_known_var_list() -> KnownVarList {
+lazy_static! {
+	static ref KNOWN_VARS: KnownVarList = make_known_var_list();
+}
+
+// Check whether a variable affects the contents of output files.  Unknown variables are only used for
+// interpolation, so do not affect the outputs directly
+pub fn affects_output(name: &str) -> bool { KNOWN_VARS.known_var.get(name).map(|kv| kv.output).unwrap_or(false) }
+
+pub fn make_known_var_list() -> KnownVarList {
 	let mut kv_list = KnownVarList::new();
 	kv_list.add_known_var("index", VarType::String, vec!(Section::Index));
 	kv_list.add_known_var("nonbs_index", VarType::String, vec!(Section::Index));
-	kv_list.add_known_var("index_dir", VarType::String, vec!(Section::Index));
+	kv_list.add_known_var("index_dir", VarType::String, vec!(Section::Index)).no_output();
 	kv_list.add_known_var("reference", VarType::String, vec!(Section::Index));
-	kv_list.add_known_var("extra_references", VarType::String, vec!(Section::Index));
+	kv_list.add_known_var("extra_references", VarType::StringVec, vec!(Section::Index));
+	kv_list.add_known_var("reference_name", VarType::String, vec!(Section::Index));
+	kv_list.add_known_var("extra_reference_names", VarType::StringVec, vec!(Section::Index));
+	kv_list.add_known_var("call_genomes", VarType::StringVec, vec!(Section::Index));
+	kv_list.add_known_var("xenograft_genomes", VarType::StringVec, vec!(Section::Index));
 	kv_list.add_known_var("reference_basename", VarType::String, vec!(Section::Index));
 	kv_list.add_known_var("contig_sizes", VarType::String, vec!(Section::Index));
 	kv_list.add_known_var("dbsnp_files", VarType::StringVec, vec!(Section::DbSnp));
@@ -69,37 +123,56 @@ fn make_known_var_list() -> KnownVarList {
 	kv_list.add_known_var("dbsnp_selected", VarType::String, vec!(Section::DbSnp));
 	kv_list.add_known_var("dbsnp_chrom_alias", VarType::String, vec!(Section::DbSnp));
 	kv_list.add_known_var("dbsnp_type", VarType::DbSnpFileType, vec!(Section::DbSnp));
-	kv_list.add_known_var("dbsnp_jobs", VarType::Int, vec!(Section::DbSnp));
+	kv_list.add_known_var("dbsnp_jobs", VarType::Int, vec!(Section::DbSnp)).no_output();
 	kv_list.add_known_var("sampling_rate", VarType::Int, vec!(Section::Index));
 	kv_list.add_known_var("min_contig_size", VarType::Int, vec!(Section::Index));
-	kv_list.add_known_var("populate_cache", VarType::Bool, vec!(Section::Index));
-	kv_list.add_known_var("threads", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum));
-	kv_list.add_known_var("cores", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum));
-	kv_list.add_known_var("time", VarType::JobLen, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum));
-	kv_list.add_known_var("memory", VarType::MemSize, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum));
-	kv_list.add_known_var("keep_logs", VarType::Bool, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum));
-	kv_list.add_known_var("merge_threads", VarType::Int, vec!(Section::Mapping, Section::Calling));
-	kv_list.add_known_var("merge_cores", VarType::Int, vec!(Section::Mapping, Section::Calling));
-	kv_list.add_known_var("merge_memory", VarType::MemSize, vec!(Section::Mapping, Section::Calling));
-	kv_list.add_known_var("merge_time", VarType::JobLen, vec!(Section::Mapping, Section::Calling));
-	kv_list.add_known_var("map_threads", VarType::Int, vec!(Section::Mapping));
-	kv_list.add_known_var("sort_threads", VarType::Int, vec!(Section::Mapping));
-	kv_list.add_known_var("sort_memory", VarType::MemSize, vec!(Section::Mapping));
+	kv_list.add_known_var("populate_cache", VarType::Bool, vec!(Section::Index)).no_output();
+	kv_list.add_known_var("ref_cache", VarType::String, vec!(Section::Index)).no_output();
+	kv_list.add_known_var("aligner", VarType::String, vec!(Section::Index, Section::Mapping));
+	kv_list.add_known_var("threads", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum)).no_output();
+	kv_list.add_known_var("cores", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum)).no_output();
+	kv_list.add_known_var("time", VarType::JobLen, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum)).no_output();
+	kv_list.add_known_var("memory", VarType::MemSize, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum)).no_output();
+	kv_list.add_known_var("keep_logs", VarType::Bool, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum)).no_output();
+	kv_list.add_known_var("merge_threads", VarType::Int, vec!(Section::Mapping, Section::Calling)).no_output();
+	kv_list.add_known_var("merge_cores", VarType::Int, vec!(Section::Mapping, Section::Calling)).no_output();
+	kv_list.add_known_var("merge_memory", VarType::MemSize, vec!(Section::Mapping, Section::Calling)).no_output();
+	kv_list.add_known_var("merge_time", VarType::JobLen, vec!(Section::Mapping, Section::Calling)).no_output();
+	kv_list.add_known_var("map_threads", VarType::Int, vec!(Section::Mapping)).no_output();
+	kv_list.add_known_var("sort_threads", VarType::Int, vec!(Section::Mapping)).no_output();
+	kv_list.add_known_var("sort_memory", VarType::MemSize, vec!(Section::Mapping)).no_output();
 	kv_list.add_known_var("non_stranded", VarType::Bool, vec!(Section::Mapping));
 	kv_list.add_known_var("reverse_conversion", VarType::Bool, vec!(Section::Mapping));
-	kv_list.add_known_var("remove_individual_bams", VarType::Bool, vec!(Section::Mapping));
+	kv_list.add_known_var("remove_individual_bams", VarType::Bool, vec!(Section::Mapping)).no_output();
 	kv_list.add_known_var("underconversion_sequence", VarType::String, vec!(Section::Mapping));
 	kv_list.add_known_var("overconversion_sequence", VarType::String, vec!(Section::Mapping));
-	kv_list.add_known_var("tmp_dir", VarType::String, vec!(Section::Mapping));
-	kv_list.add_known_var("bam_dir", VarType::String, vec!(Section::Mapping));
+	kv_list.add_known_var("tmp_dir", VarType::String, vec!(Section::Mapping)).no_output();
+	kv_list.add_known_var("bam_dir", VarType::String, vec!(Section::Mapping)).no_output();
 	kv_list.add_known_var("centre", VarType::String, vec!(Section::Mapping));
 	kv_list.add_known_var("center", VarType::String, vec!(Section::Mapping));
 	kv_list.add_known_var("platform", VarType::String, vec!(Section::Mapping));
-	kv_list.add_known_var("sequence_dir", VarType::String, vec!(Section::Mapping));
+	kv_list.add_known_var("sequence_dir", VarType::String, vec!(Section::Mapping)).no_output();
 	kv_list.add_known_var("benchmark_mode", VarType::Bool, vec!(Section::Mapping, Section::Calling));
 	kv_list.add_known_var("make_cram", VarType::Bool, vec!(Section::Mapping));
-	kv_list.add_known_var("jobs", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report));
-	kv_list.add_known_var("bcf_dir", VarType::String, vec!(Section::Calling));
+	kv_list.add_known_var("fastq_qc", VarType::Bool, vec!(Section::Mapping));
+	kv_list.add_known_var("genome_stats", VarType::Bool, vec!(Section::Mapping));
+	kv_list.add_known_var("disambiguate", VarType::Bool, vec!(Section::Mapping));
+	kv_list.add_known_var("trim", VarType::Bool, vec!(Section::Trimming));
+	kv_list.add_known_var("adapter1", VarType::String, vec!(Section::Trimming));
+	kv_list.add_known_var("adapter2", VarType::String, vec!(Section::Trimming));
+	kv_list.add_known_var("adapter_min_overlap", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("adapter_error_rate", VarType::Float, vec!(Section::Trimming));
+	kv_list.add_known_var("trim_quality", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("min_read_length", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("poly_g", VarType::Bool, vec!(Section::Trimming));
+	kv_list.add_known_var("poly_g_length", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("clip_preset", VarType::String, vec!(Section::Trimming));
+	kv_list.add_known_var("clip_r1_5p", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("clip_r1_3p", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("clip_r2_5p", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("clip_r2_3p", VarType::Int, vec!(Section::Trimming));
+	kv_list.add_known_var("jobs", VarType::Int, vec!(Section::Index, Section::DbSnp, Section::Mapping, Section::Calling, Section::Extract, Section::Report)).no_output();
+	kv_list.add_known_var("bcf_dir", VarType::String, vec!(Section::Calling)).no_output();
 	kv_list.add_known_var("mapq_threshold", VarType::Int, vec!(Section::Calling, Section::Report));
 	kv_list.add_known_var("qual_threshold", VarType::Int, vec!(Section::Calling));
 	kv_list.add_known_var("left_trim", VarType::IntVec, vec!(Section::Calling));
@@ -109,8 +182,8 @@ fn make_known_var_list() -> KnownVarList {
 	kv_list.add_known_var("keep_duplicates", VarType::Bool, vec!(Section::Calling));
 	kv_list.add_known_var("ignore_duplicate_flag", VarType::Bool, vec!(Section::Calling));
 	kv_list.add_known_var("keep_improper_pairs", VarType::Bool, vec!(Section::Calling));
-	kv_list.add_known_var("call_threads", VarType::Int, vec!(Section::Calling));
-	kv_list.add_known_var("remove_individual_bcfs", VarType::Bool, vec!(Section::Calling));
+	kv_list.add_known_var("call_threads", VarType::Int, vec!(Section::Calling)).no_output();
+	kv_list.add_known_var("remove_individual_bcfs", VarType::Bool, vec!(Section::Calling)).no_output();
 	kv_list.add_known_var("haploid", VarType::Bool, vec!(Section::Calling));
 	kv_list.add_known_var("reference_bias", VarType::Float, vec!(Section::Calling, Section::Extract));
 	kv_list.add_known_var("over_conversion_rate", VarType::Float, vec!(Section::Calling));
@@ -118,24 +191,45 @@ fn make_known_var_list() -> KnownVarList {
 	kv_list.add_known_var("conversion", VarType::FloatVec, vec!(Section::Calling));
 	kv_list.add_known_var("auto_conversion", VarType::Bool, vec!(Section::Calling));
 	kv_list.add_known_var("contig_list", VarType::StringVec, vec!(Section::Calling));
-	kv_list.add_known_var("contig_pool_limit", VarType::Int, vec!(Section::Calling));
-	kv_list.add_known_var("extract_dir", VarType::String, vec!(Section::Extract));
+	kv_list.add_known_var("contig_pool_limit", VarType::Int, vec!(Section::Calling)).no_output();
+	kv_list.add_known_var("cnv_bin_size", VarType::Int, vec!(Section::Calling));
+	kv_list.add_known_var("mito_mode", VarType::Bool, vec!(Section::Calling));
+	kv_list.add_known_var("mito_contig", VarType::String, vec!(Section::Calling));
+	kv_list.add_known_var("mito_mapq_threshold", VarType::Int, vec!(Section::Calling));
+	kv_list.add_known_var("min_heteroplasmy", VarType::Float, vec!(Section::Calling));
+	kv_list.add_known_var("recalibrate", VarType::Bool, vec!(Section::Calling));
+	kv_list.add_known_var("extract_dir", VarType::String, vec!(Section::Extract)).no_output();
 	kv_list.add_known_var("snp_list", VarType::String, vec!(Section::Extract));
 	kv_list.add_known_var("allow_het", VarType::Bool, vec!(Section::Extract));
 	kv_list.add_known_var("phred_threshold", VarType::Int, vec!(Section::Extract));
 	kv_list.add_known_var("min_inform", VarType::Int, vec!(Section::Extract));
-	kv_list.add_known_var("extract_threads", VarType::Int, vec!(Section::Extract));
+	kv_list.add_known_var("extract_threads", VarType::Int, vec!(Section::Extract)).no_output();
 	kv_list.add_known_var("min_nc", VarType::Int, vec!(Section::Extract));
+	kv_list.add_known_var("non_cpg_contexts", VarType::String, vec!(Section::Extract));
 	kv_list.add_known_var("make_cpg", VarType::Bool, vec!(Section::Extract));
 	kv_list.add_known_var("make_non_cpg", VarType::Bool, vec!(Section::Extract));
 	kv_list.add_known_var("make_bedmethyl", VarType::Bool, vec!(Section::Extract));
 	kv_list.add_known_var("make_snps", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("make_haplotype", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("make_allele_report", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("make_context_summary", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("context_summary_regions", VarType::String, vec!(Section::Extract));
+	kv_list.add_known_var("make_context_bigwig", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("context_bigwig_contexts", VarType::String, vec!(Section::Extract));
+	kv_list.add_known_var("extract_from_pools", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("haplotype_window", VarType::Int, vec!(Section::Extract));
+	kv_list.add_known_var("haplotype_min_reads", VarType::Int, vec!(Section::Extract));
 	kv_list.add_known_var("bigwig_strand_specific", VarType::Bool, vec!(Section::Extract));
 	kv_list.add_known_var("strand_specific", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("cpg_annotation", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("mask_allele_cpg", VarType::Bool, vec!(Section::Extract));
+	kv_list.add_known_var("bigwig_tracks", VarType::String, vec!(Section::Extract));
 	kv_list.add_known_var("project", VarType::String, vec!(Section::Report));
-	kv_list.add_known_var("report_dir", VarType::String, vec!(Section::Report));
+	kv_list.add_known_var("report_dir", VarType::String, vec!(Section::Report)).no_output();
 	kv_list.add_known_var("paper_size", VarType::PageSize, vec!(Section::Report));
 	kv_list.add_known_var("pdf", VarType::Bool, vec!(Section::Report));
+	kv_list.add_known_var("provenance", VarType::Bool, Vec::new()).no_output();
+	kv_list.add_known_var("provenance_hash", VarType::Bool, Vec::new()).no_output();
 	kv_list
 }
 
@@ -151,6 +245,7 @@ struct PrepConfig {
 	kv_list: KnownVarList,
 	var: HashMap<String, Vec<PrepConfigVar>>,
 	lexer: Lexer,
+	location: Option<(String, usize)>,
 }
 
 impl PrepConfig {
@@ -158,7 +253,8 @@ impl PrepConfig {
 		PrepConfig{
 			kv_list: make_known_var_list(), 
 			var: HashMap::new(), 
-			lexer: Lexer::new(config_script_path)
+			lexer: Lexer::new(config_script_path),
+			location: None,
 		}
 	}
 	
@@ -175,16 +271,20 @@ impl PrepConfig {
 		self.var.get_mut(&name.to_lowercase()).and_then(|v| {
 			let mut default_var = None;
 			let mut var = None;
-			for pv in v.iter_mut() {
+			// Variables from sample or group specific sections are not used for interpolation
+			for pv in v.iter_mut().filter(|pv| pv.scope.is_none()) {
 				if pv.section == section { var = Some(pv) } else if pv.section == Section::Default { default_var = Some(pv) }
 			}
 			var.or(default_var)
 		})
 	}
 	
-	fn handle_name(&self, tok: LexToken) -> Result<ParserState, String> {
+	fn handle_name(&mut self, tok: LexToken) -> Result<ParserState, String> {
 		match tok {
-			LexToken::Name(name) => Ok(ParserState::WaitingForValue(name.to_lowercase())),
+			LexToken::Name(name) => {
+				self.location = self.lexer.get_file_line();
+				Ok(ParserState::WaitingForValue(name.to_lowercase()))
+			},
 			LexToken::End => Ok(ParserState::End),
 			_ => Err("Unexpected token - waiting for variable name".to_string()),
 		}
@@ -205,7 +305,7 @@ impl PrepConfig {
 					_ => DataValue::String(val_str),
 				};
 
-				let pvar = PrepConfigVar{var: rv, vtype: vt, section, known, used: false};
+				let pvar = PrepConfigVar{var: rv, vtype: vt, section, scope: self.lexer.get_scope(), location: self.location.clone(), known, used: false};
 				Ok(ParserState::AfterValue((name, section, pvar)))
 			},
 			_ => Err(format!("Unexpected token - waiting for value after variable {}", name)),
@@ -311,7 +411,7 @@ impl PrepConfig {
 		Ok(buf)
 	}
 		
-	fn parse(&mut self, gem_bs: &mut GemBS) -> Result<(), String> {
+	fn read_vars(&mut self) -> Result<(), String> {
 		let mut state = ParserState::WaitingForName; 
 		loop {
 			let s = self.lexer.get_token()?;
@@ -329,45 +429,65 @@ impl PrepConfig {
 			}
 			if let ParserState::End = state { break; } 
 		}
-		for(name, v) in self.var.iter_mut() {
+		Ok(())
+	}
+	
+	// Convert known variables from the strings read from the config file(s) to the correct type
+	fn convert_var(pv: &PrepConfigVar) -> Result<Option<DataValue>, String> {
+		let rv = match pv.vtype {
+			VarType::String => { Some(pv.var.clone()) },
+			VarType::FloatVec => { 
+				if let DataValue::StringVec(vv) = &pv.var {
+					let mut v = Vec::new();
+					for s in vv.iter() {
+						if let DataValue::Float(val) = DataValue::from_str(s, VarType::Float)? { v.push(val); }
+					}	
+					Some(DataValue::FloatVec(v))					
+				} else { None }
+			},
+			VarType::IntVec => { 
+				if let DataValue::StringVec(vv) = &pv.var {
+					let mut v = Vec::new();
+					for s in vv.iter() {
+						if let DataValue::Int(val) = DataValue::from_str(s, VarType::Int)? { v.push(val); }
+					}	
+					Some(DataValue::IntVec(v))					
+				} else { None }
+			},
+			VarType::StringVec => { 
+				if let DataValue::StringVec(vv) = &pv.var {
+					let mut v = Vec::new();
+					for s in vv.iter() { v.push(s.clone()); }
+					Some(DataValue::StringVec(v))					
+				} else { None }
+			},
+			_ => {
+				if let DataValue::String(var_str) = &pv.var {
+					Some(DataValue::from_str(&var_str, pv.vtype)?)
+				} else { None }				
+			},
+		};
+		Ok(rv)
+	}
+	
+	fn store_var(gem_bs: &mut GemBS, name: &str, pv: &PrepConfigVar, v: DataValue) {
+		match &pv.scope {
+			Some(scope) => gem_bs.set_sample_config(scope, pv.section, name, v),
+			None => gem_bs.set_config(pv.section, name, v),
+		}
+	}
+	
+	fn parse(&mut self, gem_bs: &mut GemBS) -> Result<(), String> {
+		self.read_vars()?;
+		for(name, v) in self.var.iter() {
 			for pv in v {
 				if pv.known {
-					let rv = match pv.vtype {
-						VarType::String => { Some(pv.var.clone()) },
-						VarType::FloatVec => { 
-							if let DataValue::StringVec(vv) = &pv.var {
-								let mut v = Vec::new();
-								for s in vv.iter() {
-									if let DataValue::Float(val) = DataValue::from_str(s, VarType::Float)? { v.push(val); }
-								}	
-								Some(DataValue::FloatVec(v))					
-							} else { None }
-						},
-						VarType::IntVec => { 
-							if let DataValue::StringVec(vv) = &pv.var {
-								let mut v = Vec::new();
-								for s in vv.iter() {
-									if let DataValue::Int(val) = DataValue::from_str(s, VarType::Int)? { v.push(val); }
-								}	
-								Some(DataValue::IntVec(v))					
-							} else { None }
-						},
-						VarType::StringVec => { 
-							if let DataValue::StringVec(vv) = &pv.var {
-								let mut v = Vec::new();
-								for s in vv.iter() { v.push(s.clone()); }
-								Some(DataValue::StringVec(v))					
-							} else { None }
-						},
-						_ => {
-							if let DataValue::String(var_str) = &pv.var {
-								Some(DataValue::from_str(&var_str, pv.vtype)?)
-							} else { None }				
-						},
-					};
-					if let Some(v) = rv { gem_bs.set_config(pv.section, &name, v); }
+					if let Some(v) = Self::convert_var(pv)? { Self::store_var(gem_bs, name, pv, v) }
 				} else if !pv.used {
-					warn!("Warning: Variable '{}' in section '{:?}' not used", name, pv.section);
+					match &pv.scope {
+						Some(scope) => warn!("Warning: Variable '{}' in section '{:?}:{}' not used", name, pv.section, scope),
+						None => warn!("Warning: Variable '{}' in section '{:?}' not used", name, pv.section),
+					}
 				}
 			}
 		}
@@ -380,5 +500,213 @@ pub fn process_config_file(file_name: &str, gem_bs: &mut GemBS) -> Result<(), St
 	prep_config.start_parse(file_name)?;
 	prep_config.parse(gem_bs)?;
 	gembs_json::check_gembs_json(gem_bs, &prep_config.kv_list)?;
+	set_section_params(gem_bs, &prep_config.kv_list);
 	Ok(())
 }
+
+// Problems found when checking a config file, with the file and line where known
+#[derive(Debug, Default)]
+pub struct ConfigCheck {
+	pub errors: Vec<(Option<(String, usize)>, String)>,
+	pub warnings: Vec<(Option<(String, usize)>, String)>,
+}
+
+// Parse a config file (including include files and variable interpolation) without preparing the analysis,
+// collecting errors rather than stopping at the first one
+pub fn check_config_file(file_name: &str, config_script_path: &Path, gem_bs: &mut GemBS) -> Result<ConfigCheck, String> {
+	let mut prep_config = PrepConfig::new(config_script_path);
+	prep_config.start_parse(file_name)?;
+	prep_config.read_vars()?;
+	let mut check = ConfigCheck::default();
+	let kv_list = &prep_config.kv_list;
+	for (name, v) in prep_config.var.iter() {
+		for pv in v.iter() {
+			let sec = match &pv.scope {
+				Some(scope) => format!("[{}:{}]", section_name(pv.section), scope),
+				None => format!("[{}]", section_name(pv.section)),
+			};
+			if pv.known {
+				match PrepConfig::convert_var(pv) {
+					Ok(Some(val)) => PrepConfig::store_var(gem_bs, name, pv, val),
+					Ok(None) => (),
+					Err(e) => check.errors.push((pv.location.clone(), format!("Invalid value for '{}' in section {}: {}", name, sec, e))),
+				}
+			} else if !pv.used {
+				// Unknown variables are allowed if they are used for interpolation
+				let msg = if let Some(kv) = kv_list.known_var.get(name.as_str()) {
+					let valid: Vec<_> = kv.sections.iter().filter(|s| **s != Section::Default).map(|s| format!("[{}]", section_name(*s))).collect();
+					format!("Variable '{}' is not valid in section {} (valid sections: {})", name, sec, valid.join(", "))
+				} else if let Some(x) = kv_list.suggest_var(name, pv.section) {
+					format!("Unknown variable '{}' in section {} - did you mean '{}'?", name, sec, x)
+				} else { format!("Unknown variable '{}' in section {}", name, sec) };
+				check.errors.push((pv.location.clone(), msg));
+			}
+		}
+	}
+	check.errors.sort();
+	check.warnings.sort();
+	gembs_json::check_gembs_json(gem_bs, kv_list)?;
+	// Conflicts are checked for the global settings, then for each sample or group with specific settings.  For the
+	// latter we only report problems not already found for the global settings
+	let (errors, warnings) = check_conflicts(gem_bs, None);
+	check.errors.extend(errors.iter().map(|x| (None, x.to_owned())));
+	check.warnings.extend(warnings.iter().map(|x| (None, x.to_owned())));
+	let mut scopes = gem_bs.get_sample_config_scopes();
+	scopes.sort_unstable();
+	for scope in scopes.iter() {
+		let (e, w) = check_conflicts(gem_bs, Some(scope));
+		e.iter().filter(|x| !errors.contains(x)).for_each(|x| check.errors.push((None, format!("{} (for {})", x, scope))));
+		w.iter().filter(|x| !warnings.contains(x)).for_each(|x| check.warnings.push((None, format!("{} (for {})", x, scope))));
+	}
+	Ok(check)
+}
+
+fn section_name(section: Section) -> String { format!("{:?}", section).to_lowercase() }
+
+// Options that are ignored or incompatible given the values of other options.  Returns errors and warnings
+fn check_conflicts(gem_bs: &GemBS, scope: Option<&str>) -> (Vec<String>, Vec<String>) {
+	let is_set = |section: Section, name: &str| gem_bs.get_scope_config(scope, section, name).is_some();
+	let get_bool = |section: Section, name: &str| matches!(gem_bs.get_scope_config(scope, section, name), Some(DataValue::Bool(true)));
+	let mut errors = Vec::new();
+	let mut warnings = Vec::new();
+	let mut ignored = |section: Section, names: &[&str], needs: &str| {
+		let v: Vec<_> = names.iter().filter(|n| is_set(section, n)).map(|n| n.to_string()).collect();
+		if !v.is_empty() && !get_bool(section, needs) { warnings.push(format!("Option(s) {} ignored as {} is not set", v.join(", "), needs)) }
+	};
+	ignored(Section::Trimming, &["adapter1", "adapter2", "adapter_min_overlap", "adapter_error_rate", "trim_quality", "min_read_length", 
+		"poly_g", "poly_g_length", "clip_preset", "clip_r1_5p", "clip_r1_3p", "clip_r2_5p", "clip_r2_3p"], "trim");
+	ignored(Section::Trimming, &["poly_g_length"], "poly_g");
+	ignored(Section::Calling, &["mito_contig", "mito_mapq_threshold", "min_heteroplasmy"], "mito_mode");
+	ignored(Section::Extract, &["haplotype_window", "haplotype_min_reads"], "make_haplotype");
+	ignored(Section::Extract, &["bigwig_tracks", "bigwig_strand_specific", "mask_allele_cpg"], "make_bedmethyl");
+	if get_bool(Section::Extract, "extract_from_pools") && ["make_bedmethyl", "make_haplotype", "make_context_bigwig"].iter().any(|x| get_bool(Section::Extract, x)) {
+		warnings.push("Option extract_from_pools ignored as incompatible with make_bedmethyl, make_haplotype and make_context_bigwig".to_string());
+	}
+	if let Some(DataValue::String(s)) = gem_bs.get_scope_config(scope, Section::Trimming, "clip_preset") {
+		if !["swift", "accel", "epignome", "truseq"].contains(&s.to_lowercase().as_str()) {
+			errors.push(format!("Unknown clip_preset '{}' (possible values: swift, accel, epignome, truseq)", s));
+		}
+	}
+	if (get_bool(Section::Mapping, "genome_stats") || get_bool(Section::Mapping, "disambiguate")) && !is_set(Section::Index, "extra_references") {
+		warnings.push("Options genome_stats and disambiguate ignored as extra_references is not set".to_string());
+	} else if get_bool(Section::Mapping, "disambiguate") && !is_set(Section::Index, "xenograft_genomes") {
+		warnings.push("Option disambiguate has no effect as xenograft_genomes is not set".to_string());
+	}
+	if get_bool(Section::Calling, "recalibrate") && !(is_set(Section::DbSnp, "dbsnp_files") || is_set(Section::DbSnp, "dbsnp_index")) {
+		errors.push("Variant recalibration requires a dbSNP index (dbsnp_files or dbsnp_index)".to_string());
+	}
+	// The aligner can not be set per sample
+	match get_aligner(gem_bs) {
+		Ok(aligner) => if let Err(e) = check_trim_qc(aligner.as_ref(), get_bool(Section::Trimming, "trim"), get_bool(Section::Mapping, "fastq_qc")) { errors.push(e) },
+		Err(e) => errors.push(e),
+	}
+	(errors, warnings)
+}
+
+// Key used in the JSON Schema for the named sections
+const SCHEMA_SECTIONS_KEY: &str = "sections";
+
+// Export the known variables as a JSON Schema.  The config file has a Default section (top level) and
+// named sections, each of which is an object with the variables valid for that section.  The sections
+// are kept under their own key as some section names (i.e., index) are also variable names
+pub fn make_config_schema() -> Value {
+	let kv_list = make_known_var_list();
+	let docs: HashMap<&str, (Option<&str>, &str)> = var_docs::VAR_DOCS.iter().map(|(n, d, s)| (*n, (*d, *s))).collect();
+	let mut names: Vec<_> = kv_list.known_var.keys().copied().collect();
+	names.sort_unstable();
+	let make_props = |section: Section| -> Map<String, Value> {
+		names.iter().filter(|n| kv_list.known_var[*n].sections.contains(&section)).map(|n| {
+			let kv = &kv_list.known_var[n];
+			let mut prop = vtype_schema(kv.vtype);
+			if let Some((def, desc)) = docs.get(n) {
+				prop.insert("description".to_string(), Value::String(desc.to_string()));
+				if let Some(d) = def { prop.insert("default".to_string(), default_value(kv.vtype, d)); }
+			}
+			let secs: Vec<_> = kv.sections.iter().map(|s| Value::String(section_name(*s))).collect();
+			prop.insert("x-gembs-sections".to_string(), Value::Array(secs));
+			(n.to_string(), Value::Object(prop))
+		}).collect()
+	};
+	let mut props = make_props(Section::Default);
+	let mut sec_list = Map::new();
+	for section in [Section::Index, Section::DbSnp, Section::Trimming, Section::Mapping, Section::Calling, Section::Extract, Section::Report, Section::MD5Sum].iter() {
+		let sec_props = make_props(*section);
+		sec_list.insert(section_name(*section), json!({
+			"type": "object",
+			"description": format!("Variables for the [{}] section (also [{}:SAMPLE] or [{}:GROUP] for sample or group specific values)", 
+				section_name(*section), section_name(*section), section_name(*section)),
+			"properties": sec_props,
+			"additionalProperties": true
+		}));
+	}
+	debug_assert!(!kv_list.known_var.contains_key(SCHEMA_SECTIONS_KEY));
+	props.insert(SCHEMA_SECTIONS_KEY.to_string(), json!({
+		"type": "object",
+		"description": "Named sections of the config file ([index], [mapping] etc.)",
+		"properties": sec_list,
+		"additionalProperties": true
+	}));
+	json!({
+		"$schema": "http://json-schema.org/draft-07/schema#",
+		"title": "gemBS configuration",
+		"description": "Known variables for gemBS config files.  Variables in the top level (default) section apply to all sections, and the named sections are under the sections key.  Other variables can be used for interpolation",
+		"type": "object",
+		"properties": props,
+		"additionalProperties": true
+	})
+}
+
+fn default_value(vtype: VarType, s: &str) -> Value {
+	let conv = |x: &str, vt: VarType| match DataValue::from_str(x, vt) {
+		Ok(DataValue::Bool(b)) => json!(b),
+		Ok(DataValue::Int(i)) => json!(i),
+		Ok(DataValue::Float(f)) => json!(f),
+		_ => json!(x),
+	};
+	match vtype {
+		VarType::IntVec => Value::Array(s.split(',').map(|x| conv(x, VarType::Int)).collect()),
+		VarType::FloatVec => Value::Array(s.split(',').map(|x| conv(x, VarType::Float)).collect()),
+		VarType::StringVec => Value::Array(s.split(',').map(|x| json!(x)).collect()),
+		_ => conv(s, vtype),
+	}
+}
+
+// Values are written as strings in the config file; vectors can also be given as comma separated lists
+fn vtype_schema(vtype: VarType) -> Map<String, Value> {
+	let v = match vtype {
+		VarType::Bool => json!({"type": ["boolean", "string"]}),
+		VarType::Int => json!({"type": "integer"}),
+		VarType::Float => json!({"type": "number"}),
+		VarType::String | VarType::ReadEnd | VarType::FileType => json!({"type": "string"}),
+		VarType::StringVec => json!({"type": ["array", "string"], "items": {"type": "string"}}),
+		VarType::IntVec => json!({"type": ["array", "integer"], "items": {"type": "integer"}}),
+		VarType::FloatVec => json!({"type": ["array", "number"], "items": {"type": "number"}}),
+		VarType::JobLen => json!({"type": "string", "pattern": r"^(\d+-)?(\d+:)?(\d+:)?\d+$"}),
+		VarType::MemSize => json!({"type": "string", "pattern": r"^\d+[kKmMgG]?$"}),
+		VarType::PageSize => json!({"type": "string", "enum": ["a4", "letter", "A4", "LETTER"]}),
+		VarType::DbSnpFileType => json!({"type": "string", "enum": ["auto", "json", "bed", "vcf", "AUTO", "JSON", "BED", "VCF"]}),
+	};
+	if let Value::Object(m) = v { m } else { Map::new() }
+}
+
+// Store the effective parameters for each section so that changes can be detected when the config is next prepared
+fn set_section_params(gem_bs: &mut GemBS, kv_list: &KnownVarList) {
+	let get_params = |gb: &GemBS, section: Section| -> ParamSet {
+		kv_list.known_var.iter()
+			.filter(|(_, kv)| kv.sections.contains(&section) && kv.output)
+			.filter_map(|(name, _)| gb.get_config(section, name).map(|v| (name.to_string(), format!("{}", v))))
+			.collect()
+	};
+	for section in [Section::Index, Section::Mapping, Section::Extract, Section::Report, Section::MD5Sum].iter() {
+		let params = get_params(gem_bs, *section);
+		gem_bs.set_section_params(*section, params);
+	}
+	// Trimming is performed as part of the mapping pipeline, so the mapping parameters also apply
+	let mut params = get_params(gem_bs, Section::Mapping);
+	params.extend(get_params(gem_bs, Section::Trimming));
+	gem_bs.set_section_params(Section::Trimming, params);
+	// The dbSNP index is used for calling
+	let mut params = get_params(gem_bs, Section::DbSnp);
+	params.extend(get_params(gem_bs, Section::Calling));
+	gem_bs.set_section_params(Section::Calling, params);
+}
@@ -14,6 +14,7 @@ struct InFile {
 	line: usize, // Line in file (zero offset)
 	pos: usize, // position within line (zero offset)
 	section: Section,
+	scope: Option<String>, // Sample barcode or group for sections of the form [calling:SAMPLE]
 	bufreader: Box<dyn BufRead>,
 }
 
@@ -32,7 +33,7 @@ impl InFile {
 			Some(inode) => {
 				match compress::open_bufreader(&pb) {
 					Ok(reader) => {
-						Some(InFile{name: pname.to_string(), inode, line: 0, pos: 0, section: Section::Default, bufreader: reader})
+						Some(InFile{name: pname.to_string(), inode, line: 0, pos: 0, section: Section::Default, scope: None, bufreader: reader})
 					},
 					Err(_) => {
 						error!("Could not open config file {}", name);
@@ -212,9 +213,10 @@ impl Lexer {
 		self.in_files.pop()
 	}
 	
-	fn set_section(&mut self, section: Section) -> Result<(), &'static str> {
+	fn set_section(&mut self, section: Section, scope: Option<String>) -> Result<(), &'static str> {
 		if let Some(file) = self.in_files.last_mut() { 
 		    file.section = section;
+			file.scope = scope;
 			Ok(())
 		} else { Err("No input files for Lexer") }	
 	}
@@ -224,6 +226,15 @@ impl Lexer {
 		else { None }
 	}
 	
+	pub fn get_scope(&self) -> Option<String> {
+		self.in_files.last().and_then(|f| f.scope.clone())
+	}
+	
+	// Current file and line (one offset) for reporting
+	pub fn get_file_line(&self) -> Option<(String, usize)> {
+		self.in_files.last().map(|f| (f.name.clone(), f.line + 1))
+	}
+
 	fn get_file_pos_str(&self) -> Option<String> {
 		if let Some(file) = self.in_files.last() {
 			Some(format!("{}: line {}, pos {}", file.name, file.line + 1, file.pos + 1))
@@ -429,8 +440,14 @@ impl Lexer {
 					return self.get_token();
 				},
 				LexToken::Section(sec) => {
+					// Sections can be restricted to a sample or group of samples i.e., [calling:SAMPLE1]
+					let (sec, scope) = match sec.split_once(':') {
+						Some((s, sc)) if !sc.is_empty() => (s.to_string(), Some(sc.to_string())),
+						Some(_) => return Err(format!("Missing sample or group name for Section {} in file {}", sec, self.get_file_pos_str().unwrap())),
+						None => (sec, None),
+					};
 					if let Ok(section) = Section::from_str(&sec) {
-						if let Err(e) = self.set_section(section) { return Err(e.to_string()); }
+						if let Err(e) = self.set_section(section, scope) { return Err(e.to_string()); }
 					} else {
 						// We can use unwrap() here because if this fails then we should panic!
 						return Err(format!("Unknown Section {} in file {}", sec, self.get_file_pos_str().unwrap()))
@@ -0,0 +1,129 @@
+// Documentation for the known configuration variables (name, default, description).  Used when exporting
+// the variable table as a JSON Schema.  The default is None where there is no fixed default (i.e., the value
+// is derived from other settings or the option is simply not used if not set)
+
+pub const VAR_DOCS: &[(&str, Option<&str>, &str)] = &[
+	("index", None, "Location of the bisulfite index for the aligner (default derived from the reference)"),
+	("nonbs_index", None, "Location of the non-bisulfite index (default derived from the reference)"),
+	("index_dir", None, "Directory for index files"),
+	("reference", None, "FASTA file with the reference genome"),
+	("extra_references", None, "FASTA file(s) with additional genomes or sequences (i.e., controls) to be added to the index"),
+	("reference_name", None, "Name of the primary genome used in reports (default - reference file name)"),
+	("extra_reference_names", None, "Names of the genomes in the extra reference files (default - file names)"),
+	("call_genomes", None, "Extra genomes to include in calling and extraction (default - only the primary genome)"),
+	("xenograft_genomes", None, "Extra genomes from the xenograft host (i.e., mouse) - reads mapping to these are removed if disambiguate is set"),
+	("reference_basename", None, "Base name used for index and contig files derived from the reference"),
+	("contig_sizes", None, "File with the sizes of the reference contigs (default derived from the reference)"),
+	("dbsnp_files", None, "List of dbSNP files (JSON, BED or VCF) used to generate the dbSNP index"),
+	("dbsnp_index", None, "Location of the dbSNP index"),
+	("dbsnp_selected", None, "File with a list of SNPs that will always be selected from the dbSNP files"),
+	("dbsnp_chrom_alias", None, "File with aliases for the contig names in the dbSNP files"),
+	("dbsnp_type", Some("auto"), "Type of the dbSNP files (auto, json, bed or vcf)"),
+	("dbsnp_jobs", None, "Number of parallel jobs when generating the dbSNP index"),
+	("sampling_rate", None, "Text sampling rate for the index - increasing will decrease index size but also performance"),
+	("min_contig_size", None, "Contigs smaller than this will be filtered out during index generation"),
+	("populate_cache", Some("false"), "Populate the reference cache for CRAM files (always done if make_cram is set)"),
+	("ref_cache", None, "Directory for the reference cache used to encode and decode CRAM files (default follows htslib)"),
+	("aligner", Some("gem3"), "Aligner used for indexing and mapping (gem3 or bwa-meth)"),
+	("threads", None, "Number of threads (default - all available cores)"),
+	("cores", None, "Number of cores for a job (default - all available cores)"),
+	("time", None, "Time required for a job"),
+	("memory", None, "Memory required for a job (default - all available memory)"),
+	("keep_logs", Some("false"), "Do not remove log files after successful completion of a task"),
+	("merge_threads", None, "Number of threads for merging (default - same as threads)"),
+	("merge_cores", None, "Number of cores for a merge job (default - same as cores)"),
+	("merge_memory", None, "Memory required for a merge job"),
+	("merge_time", None, "Time required for a merge job"),
+	("map_threads", None, "Number of threads for the aligner (default - same as threads)"),
+	("sort_threads", None, "Number of threads for sorting BAMs (default - same as map_threads)"),
+	("sort_memory", None, "Memory per thread for sorting BAMs"),
+	("non_stranded", Some("false"), "Library is non-stranded"),
+	("reverse_conversion", Some("false"), "Perform G2A conversion on read 1 and C2T on read 2 rather than the reverse"),
+	("remove_individual_bams", Some("false"), "Remove individual BAMs after merging"),
+	("underconversion_sequence", None, "Name of the control sequence used to estimate the under conversion rate"),
+	("overconversion_sequence", None, "Name of the control sequence used to estimate the over conversion rate"),
+	("tmp_dir", None, "Directory for temporary files used by the aligner and sort"),
+	("bam_dir", Some("."), "Output directory for BAM files"),
+	("centre", None, "Sequencing centre added to the BAM read groups"),
+	("center", None, "Alternative spelling of centre"),
+	("platform", None, "Sequencing platform added to the BAM read groups"),
+	("sequence_dir", Some("."), "Directory with the input sequence files"),
+	("benchmark_mode", Some("false"), "Omit version and date information from outputs to allow comparison between runs"),
+	("make_cram", Some("false"), "Generate CRAM rather than BAM files"),
+	("fastq_qc", Some("true"), "Validate FASTQ input and collect read QC statistics before mapping (if trimming is off the reads are passed through read_trim in QC only mode).  Not available with the bwa-meth aligner"),
+	("genome_stats", Some("false"), "Collect read assignment statistics for each genome when extra references are used"),
+	("disambiguate", Some("false"), "Remove read pairs where either read maps to a xenograft genome"),
+	("trim", Some("false"), "Perform adapter and quality trimming before mapping"),
+	("adapter1", None, "Adapter sequence for read 1 (default - auto detect)"),
+	("adapter2", None, "Adapter sequence for read 2 (default - same as read 1)"),
+	("adapter_min_overlap", Some("1"), "Minimum overlap with adapter sequence required for trimming"),
+	("adapter_error_rate", Some("0.1"), "Maximum error rate for adapter matches"),
+	("trim_quality", Some("20"), "Quality threshold for trimming from 3' end (0 to disable)"),
+	("min_read_length", Some("20"), "Discard reads (or pairs) where a read is shorter than this after trimming"),
+	("poly_g", Some("false"), "Trim poly-G tails from reads"),
+	("poly_g_length", Some("10"), "Minimum length of poly-G tail to trim"),
+	("clip_preset", None, "Set fixed clipping for library kit (swift, accel, epignome or truseq)"),
+	("clip_r1_5p", None, "Number of bases to clip from the 5' end of read 1"),
+	("clip_r1_3p", None, "Number of bases to clip from the 3' end of read 1"),
+	("clip_r2_5p", None, "Number of bases to clip from the 5' end of read 2"),
+	("clip_r2_3p", None, "Number of bases to clip from the 3' end of read 2"),
+	("jobs", None, "Number of parallel jobs"),
+	("bcf_dir", Some("."), "Output directory for BCF files"),
+	("mapq_threshold", Some("20"), "Threshold for mapping quality of reads"),
+	("qual_threshold", Some("13"), "Threshold for base quality"),
+	("left_trim", Some("0"), "Bases to trim from the left of read (one value for both reads or one per read)"),
+	("right_trim", Some("0"), "Bases to trim from the right of read (one value for both reads or one per read)"),
+	("species", None, "Sample species added to the VCF header"),
+	("max_template_length", Some("1000"), "Maximum template length for a pair"),
+	("keep_duplicates", Some("false"), "Do not merge duplicate reads"),
+	("ignore_duplicate_flag", Some("false"), "Ignore the duplicate flag in the input BAM/CRAM"),
+	("keep_improper_pairs", Some("false"), "Do not discard reads that do not form proper pairs"),
+	("call_threads", None, "Number of threads for bs_call (default - same as threads)"),
+	("remove_individual_bcfs", Some("false"), "Remove individual BCFs after merging"),
+	("haploid", Some("false"), "Force genotype calls to be homozygous"),
+	("reference_bias", Some("2"), "Bias to reference homozygote"),
+	("over_conversion_rate", Some("0.05"), "Over conversion rate"),
+	("under_conversion_rate", Some("0.01"), "Under conversion rate"),
+	("conversion", Some("0.01,0.05"), "Under and over conversion rates"),
+	("auto_conversion", Some("false"), "Estimate conversion rates from the control sequences"),
+	("contig_list", None, "List of contigs to call (default - all contigs)"),
+	("contig_pool_limit", Some("25000000"), "Contigs smaller than this will be called together in pools"),
+	("cnv_bin_size", Some("100000"), "Bin size for copy number estimation - must be a multiple of 100, 0 disables"),
+	("mito_mode", Some("false"), "Use heteroplasmy aware calling on the mitochondrial contig"),
+	("mito_contig", None, "Name of the mitochondrial contig (default - auto detect)"),
+	("mito_mapq_threshold", Some("30"), "Threshold for mapping quality of reads on the mitochondrial contig"),
+	("min_heteroplasmy", Some("0.02"), "Minimum minor allele fraction for heteroplasmic calls on the mitochondrial contig"),
+	("recalibrate", Some("false"), "Recalibrate variant qualities using the dbSNP index"),
+	("extract_dir", Some("."), "Output directory for extracted files"),
+	("snp_list", None, "File with list of SNPs to be extracted"),
+	("allow_het", Some("false"), "Allow heterozygous sites for methylation extraction"),
+	("phred_threshold", Some("20"), "Minimum PHRED score for sites/CpGs"),
+	("min_inform", Some("1"), "Minimum number of informative reads for sites/CpGs"),
+	("extract_threads", None, "Number of threads for mextr and snpxtr (default - same as threads)"),
+	("min_nc", Some("1"), "Minimum number of non-converted bases for non CpG sites"),
+	("non_cpg_contexts", None, "Comma separated list of contexts (IUPAC codes allowed) to output in the non CpG file"),
+	("make_cpg", Some("false"), "Output CpG file (default if no other output is selected)"),
+	("make_non_cpg", Some("false"), "Output non CpG file"),
+	("make_bedmethyl", Some("false"), "Output bedMethyl and bigWig files"),
+	("make_snps", Some("false"), "Output SNP file"),
+	("make_haplotype", Some("false"), "Output CpG haplotype metrics"),
+	("make_allele_report", Some("false"), "Output report of reference CpGs lost and novel CpGs gained in each sample"),
+	("make_context_summary", Some("false"), "Output summary of methylation levels by trinucleotide context"),
+	("context_summary_regions", None, "BED file with regions for the context summary (in addition to the genome wide and per contig summaries)"),
+	("make_context_bigwig", Some("false"), "Output bigWig files of methylation levels for selected contexts"),
+	("context_bigwig_contexts", Some("CHG,CHH"), "Comma separated list of contexts (IUPAC codes allowed) for the context bigWig files.  A site is written to all matching files"),
+	("extract_from_pools", Some("false"), "Extract directly from the per pool BCFs rather than the merged BCF, so extraction does not wait for the BCF merge and index steps.  Extraction still starts only when all pools have been called"),
+	("haplotype_window", Some("4"), "Number of consecutive CpGs in each haplotype window"),
+	("haplotype_min_reads", Some("10"), "Minimum number of reads covering all CpGs in a haplotype window"),
+	("bigwig_strand_specific", Some("false"), "Output separate bigWig files for each strand"),
+	("strand_specific", Some("false"), "Output CpG sites separately for each strand"),
+	("cpg_annotation", Some("false"), "Add columns to CpG file with the per sample CpG status and the ID (i.e., dbSNP) of the site"),
+	("mask_allele_cpg", Some("false"), "Exclude sites where a SNP creates or destroys a CpG from bigWig output"),
+	("bigwig_tracks", None, "Comma separated list of additional bigWig tracks (depth, nonconv, ci_low, ci_high).  ci_low and ci_high give the 95% credible interval for methylation"),
+	("project", None, "Project name used in reports"),
+	("report_dir", Some("."), "Output directory for reports"),
+	("paper_size", Some("a4"), "Paper size for PDF reports (a4 or letter)"),
+	("pdf", Some("false"), "Generate PDF reports"),
+	("provenance", Some("false"), "Record provenance information for output files"),
+	("provenance_hash", Some("false"), "Include full MD5 checksums of files in the provenance records.  Otherwise only the size and the first and last 64KB of each file are compared, so changes in the middle of a file that do not alter its size are not detected"),
+];
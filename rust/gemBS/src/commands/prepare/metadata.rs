@@ -3,20 +3,23 @@ use serde_json::{Value, Map};
 use lazy_static::lazy_static;
 
 use crate::config::GemBS;
-use crate::common::defs::{Metadata, DataValue, ReadEnd, FileType};
+use crate::common::defs::{Metadata, DataValue, ReadEnd, FileType, Section, Sex, VarType};
 
 pub mod process_csv;
 pub mod process_json;
+pub mod process_samplesheet;
+pub mod process_sheet;
 
 #[derive(Debug, Clone)]
 struct SampleData {
 	data: HashMap<String, HashMap<Metadata, DataValue>>,
+	params: HashMap<String, Vec<(Section, String, DataValue)>>,
 }
 
 impl SampleData {
-	pub fn new() -> Self { SampleData{data: HashMap::new() }}
+	pub fn new() -> Self { SampleData{data: HashMap::new(), params: HashMap::new() }}
 	
-	pub fn add_csv_record(&mut self, hr: &mut HashMap<Metadata, DataValue>) -> Result<(), String> {
+	pub fn add_record(&mut self, hr: &mut HashMap<Metadata, DataValue>) -> Result<(), String> {
 		if let Some(ds) = hr.remove(&Metadata::Dataset) {
 			if let DataValue::String(data_set) = ds { self.check_and_store_record(hr, data_set)?; }
 			else { return Err("Internal error".to_string()); }	
@@ -34,6 +37,7 @@ impl SampleData {
 				"sample_name" => if let Some(s) = val.as_str() { Some((Metadata::SampleName, DataValue::String(s.to_string()))) } else { None },
 				"platform" => if let Some(s) = val.as_str() { Some((Metadata::Platform, DataValue::String(s.to_string()))) } else { None },
 				"centre" => if let Some(s) = val.as_str() { Some((Metadata::Centre, DataValue::String(s.to_string()))) } else { None },
+				"sex" | "gender" => if let Some(s) = val.as_str() { Some((Metadata::Sex, DataValue::String(s.to_string()))) } else { None },
 				"application" => if let Some(s) = val.as_str() { Some((Metadata::Bisulfite, DataValue::Bool(BISULFITE_APPS.contains(&(s.to_lowercase().as_str()))))) } else { None },
 				_ => None,
 			};
@@ -62,9 +66,37 @@ impl SampleData {
 		Ok(())
 	}
 	
+	// Parameter override for a sample
+	pub fn add_sample_param(&mut self, barcode: &str, section: Section, name: &str, val: DataValue) {
+		self.params.entry(barcode.to_string()).or_insert_with(Vec::new).push((section, name.to_string(), val));
+	}
+	
 	// Sanity checking of the metadata that has been read in.
 	pub fn check_and_store_sample_data(&mut self, gem_bs: &mut GemBS) -> Result<(), String> {
+		let mut sample_sex: HashMap<String, Sex> = HashMap::new();
+		let mut sample_group: HashMap<String, String> = HashMap::new();
 		for (dataset, href) in self.data.iter_mut() {
+			// Check sex is valid and consistent between datasets from the same sample.  Unknown values are removed
+			if let Some(DataValue::String(s)) = href.get(&Metadata::Sex) {
+				match s.to_lowercase().as_str() {
+					"" | "." | "u" | "unknown" | "na" | "n/a" => { href.remove(&Metadata::Sex); },
+					st => {
+						let sex = st.parse::<Sex>().map_err(|_| format!("Error with dataset {}: unrecognized value for Sex: {}", dataset, s))?;
+						href.insert(Metadata::Sex, DataValue::String(format!("{}", sex)));
+						if let Some(DataValue::String(bc)) = href.get(&Metadata::SampleBarcode) {
+							if let Some(s1) = sample_sex.insert(bc.to_owned(), sex) {
+								if s1 != sex { return Err(format!("Inconsistent Sex for sample {}", bc)) }
+							}
+						}
+					},
+				}
+			}
+			// Check groups are consistent between datasets from the same sample
+			if let (Some(DataValue::String(bc)), Some(DataValue::String(g))) = (href.get(&Metadata::SampleBarcode), href.get(&Metadata::Group)) {
+				if let Some(g1) = sample_group.insert(bc.to_owned(), g.to_owned()) {
+					if g1 != *g { return Err(format!("Inconsistent Group for sample {}", bc)) }
+				}
+			}
 			// Check for paired data
 			if href.contains_key(&Metadata::FilePath1) && href.contains_key(&Metadata::FilePath2) {
 				if let Some(ftype) = href.get(&Metadata::FileType) {
@@ -79,11 +111,37 @@ impl SampleData {
 				gem_bs.set_sample_data(dataset, *md, val.clone());
 			}
 		}
+		for (barcode, v) in self.params.drain() {
+			if !self.data.values().any(|h| matches!(h.get(&Metadata::SampleBarcode), Some(DataValue::String(bc)) if *bc == barcode)) {
+				return Err(format!("Parameters specified for unknown sample {}", barcode));
+			}
+			for (section, name, val) in v.into_iter() { gem_bs.set_sample_config(&barcode, section, &name, val) }
+		}
 		Ok(())
 	}
 }
 
 lazy_static! {
+    static ref MD_ASSOC: HashMap<Metadata, VarType> = {
+        let mut m = HashMap::new();
+        m.insert(Metadata::SampleBarcode, VarType::String);
+        m.insert(Metadata::SampleName, VarType::String);
+        m.insert(Metadata::LibraryBarcode, VarType::String);
+        m.insert(Metadata::Dataset, VarType::String);
+        m.insert(Metadata::AltDataset, VarType::String);
+        m.insert(Metadata::FileType, VarType::FileType);
+        m.insert(Metadata::FilePath, VarType::String);
+        m.insert(Metadata::FilePath1, VarType::String);
+        m.insert(Metadata::FilePath2, VarType::String);
+        m.insert(Metadata::ReadEnd, VarType::ReadEnd);
+        m.insert(Metadata::Description, VarType::String);
+        m.insert(Metadata::Centre, VarType::String);
+        m.insert(Metadata::Platform, VarType::String);
+        m.insert(Metadata::Bisulfite, VarType::Bool);
+        m.insert(Metadata::Sex, VarType::String);
+        m.insert(Metadata::Group, VarType::String);
+        m
+    };
     static ref BISULFITE_APPS: Vec<&'static str> = {
         let mut m = Vec::new();
 		m.push("wg-bs-seq");
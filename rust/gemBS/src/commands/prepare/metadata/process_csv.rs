@@ -1,10 +1,9 @@
 use std::str::FromStr;
 use std::collections::HashMap;
 use csv::{Reader, StringRecord};
-use lazy_static::lazy_static;
 use crate::config::GemBS;
-use crate::commands::prepare::metadata::SampleData;
-use crate::common::defs::{Metadata, DataValue, VarType};
+use crate::commands::prepare::metadata::{SampleData, MD_ASSOC};
+use crate::common::defs::{Metadata, DataValue};
 
 #[derive(Debug)]
 struct Header {
@@ -55,27 +54,6 @@ impl InputLine {
 	fn get_hash(&mut self) -> &mut HashMap<Metadata, DataValue> { &mut self.vals }
 }
 
-lazy_static! {
-    static ref MD_ASSOC: HashMap<Metadata, VarType> = {
-        let mut m = HashMap::new();
-        m.insert(Metadata::SampleBarcode, VarType::String);
-        m.insert(Metadata::SampleName, VarType::String);
-        m.insert(Metadata::LibraryBarcode, VarType::String);
-        m.insert(Metadata::Dataset, VarType::String);
-        m.insert(Metadata::AltDataset, VarType::String);
-        m.insert(Metadata::FileType, VarType::FileType);
-        m.insert(Metadata::FilePath, VarType::String);
-        m.insert(Metadata::FilePath1, VarType::String);
-        m.insert(Metadata::FilePath2, VarType::String);
-        m.insert(Metadata::ReadEnd, VarType::ReadEnd);
-        m.insert(Metadata::Description, VarType::String);
-        m.insert(Metadata::Centre, VarType::String);
-        m.insert(Metadata::Platform, VarType::String);
-        m.insert(Metadata::Bisulfite, VarType::Bool);
-        m
-    };
-}
-
 pub fn process_cvs_metatdata_file(file_name: &str, gem_bs: &mut GemBS) -> Result<(), String> {
 	let mut rdr = Reader::from_path(file_name).map_err(|e| format!("Error opening cvs metadata file {} for input: {}", file_name, e))?;
 	let hrecord = rdr.headers().map_err(|e| format!("Error reading header line from cvs metadata file {} for input: {}", file_name, e))?;
@@ -103,7 +81,7 @@ pub fn process_cvs_metatdata_file(file_name: &str, gem_bs: &mut GemBS) -> Result
 						}
 					}
 				}
-				if let Err(e) = sample_data.add_csv_record(input_line.get_hash()) {
+				if let Err(e) = sample_data.add_record(input_line.get_hash()) {
 					return Err(format!("Error reading record at line {} of cvs metadata file {}: {}", line, file_name, e));
 				}
 			},
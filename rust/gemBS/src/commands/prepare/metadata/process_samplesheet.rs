@@ -0,0 +1,156 @@
+// Import of Illumina (bcl2fastq / bcl-convert) sample sheets, versions 1 and 2.
+// The FASTQ files produced by the demultiplexing are found by searching the output directory
+use std::collections::{HashMap, BTreeMap};
+use std::path::{Path, PathBuf};
+use csv::ReaderBuilder;
+use regex::Regex;
+use lazy_static::lazy_static;
+use crate::config::GemBS;
+use crate::commands::prepare::metadata::SampleData;
+use crate::common::defs::{Metadata, DataValue, FileType, Section};
+
+#[derive(Debug)]
+struct SampleSheet {
+	version: usize,
+	run: Option<String>,
+	settings: HashMap<String, String>,
+	columns: HashMap<String, usize>,
+	data: Vec<Vec<String>>,
+}
+
+impl SampleSheet {
+	fn from_file(file_name: &str) -> Result<Self, String> {
+		let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).trim(csv::Trim::All).from_path(file_name)
+			.map_err(|e| format!("Error opening Illumina sample sheet {} for input: {}", file_name, e))?;
+		let mut sheet = SampleSheet{version: 1, run: None, settings: HashMap::new(), columns: HashMap::new(), data: Vec::new()};
+		let mut section = String::new();
+		for (line, rec) in rdr.records().enumerate() {
+			let rec = rec.map_err(|e| format!("Error reading line {} of Illumina sample sheet {}: {}", line + 1, file_name, e))?;
+			let fields: Vec<String> = rec.iter().map(|s| s.to_string()).collect();
+			let n = fields.iter().rposition(|s| !s.is_empty()).map(|i| i + 1).unwrap_or(0);
+			if n == 0 { continue; }
+			if fields[0].starts_with('[') {
+				section = fields[0].trim_start_matches('[').trim_end_matches(']').to_lowercase();
+				continue;
+			}
+			match section.as_str() {
+				"header" => match (fields[0].as_str(), fields.get(1)) {
+					("IEMFileVersion", _) => (),
+					("FileFormatVersion", Some(v)) => sheet.version = v.parse::<usize>().map_err(|_| format!("Illegal FileFormatVersion {} in Illumina sample sheet {}", v, file_name))?,
+					("RunName", Some(v)) | ("Experiment Name", Some(v)) if !v.is_empty() => sheet.run = Some(v.to_owned()),
+					_ => (),
+				},
+				"settings" | "bclconvert_settings" => if let Some(v) = fields.get(1) { sheet.settings.insert(fields[0].to_owned(), v.to_owned()); },
+				"data" | "bclconvert_data" => {
+					if sheet.columns.is_empty() {
+						for (ix, s) in fields[..n].iter().enumerate() { sheet.columns.insert(s.to_owned(), ix); }
+						if !sheet.columns.contains_key("Sample_ID") { return Err(format!("No Sample_ID column found in Illumina sample sheet {}", file_name)); }
+					} else { sheet.data.push(fields) }
+				},
+				_ => (),
+			}
+		}
+		if sheet.data.is_empty() { return Err(format!("No sample data found in Illumina sample sheet {}", file_name)); }
+		Ok(sheet)
+	}
+
+	fn get<'a>(&self, rec: &'a [String], col: &str) -> Option<&'a str> {
+		self.columns.get(col).and_then(|ix| rec.get(*ix)).map(|s| s.as_str()).filter(|s| !s.is_empty())
+	}
+
+	// Version 1 sheets have a single Adapter setting (possibly with multiple adapters separated by '+')
+	// or Adapter and AdapterRead2.  Version 2 sheets have AdapterRead1 and AdapterRead2
+	fn adapters(&self) -> (Option<&str>, Option<&str>) {
+		let get = |k: &str| self.settings.get(k).and_then(|s| s.split('+').next()).filter(|s| !s.is_empty());
+		(get("AdapterRead1").or_else(|| get("Adapter")), get("AdapterRead2"))
+	}
+}
+
+// FASTQ file found in the output directory, indexed on the file prefix (Sample_ID or Sample_Name)
+#[derive(Debug)]
+struct FastqFile {
+	lane: Option<usize>,
+	read: usize,
+	path: PathBuf,
+}
+
+fn find_fastq_files(dir: &Path) -> Result<HashMap<String, Vec<FastqFile>>, String> {
+	lazy_static! { static ref RE: Regex = Regex::new(r"^(.+)_S\d+(?:_L(\d{3}))?_R([12])_\d{3}\.fastq(?:\.gz)?$").unwrap(); }
+	let pattern = format!("{}/**/*.fastq*", dir.display());
+	let mut files: HashMap<String, Vec<FastqFile>> = HashMap::new();
+	for path in glob::glob(&pattern).map_err(|e| format!("Error searching for FASTQ files: {}", e))?.filter_map(Result::ok) {
+		let fname = if let Some(s) = path.file_name().and_then(|x| x.to_str()) { s.to_owned() } else { continue };
+		if let Some(cap) = RE.captures(&fname) {
+			let lane = cap.get(2).map(|x| x.as_str().parse::<usize>().unwrap());
+			let read = cap[3].parse::<usize>().unwrap();
+			let path = path.canonicalize().unwrap_or(path);
+			files.entry(cap[1].to_owned()).or_default().push(FastqFile{lane, read, path});
+		}
+	}
+	Ok(files)
+}
+
+pub fn process_samplesheet_file(file_name: &str, fastq_dir: Option<&str>, gem_bs: &mut GemBS) -> Result<(), String> {
+	let sheet = SampleSheet::from_file(file_name)?;
+	debug!("Read Illumina sample sheet {} (version {}) with {} records", file_name, sheet.version, sheet.data.len());
+	let dir = match fastq_dir {
+		Some(d) => Path::new(d).to_owned(),
+		None => Path::new(file_name).parent().map(|p| p.to_owned()).filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| PathBuf::from(".")),
+	};
+	let files = find_fastq_files(&dir)?;
+	let (adapter1, adapter2) = sheet.adapters();
+	let mut sample_data = SampleData::new();
+	let mut barcodes = Vec::new();
+	for rec in sheet.data.iter() {
+		let sample_id = sheet.get(rec, "Sample_ID").ok_or_else(|| format!("Missing Sample_ID in Illumina sample sheet {}", file_name))?;
+		let sample_name = sheet.get(rec, "Sample_Name");
+		let lane = match sheet.get(rec, "Lane") {
+			Some(s) => Some(s.parse::<usize>().map_err(|_| format!("Illegal lane {} for sample {} in Illumina sample sheet {}", s, sample_id, file_name))?),
+			None => None,
+		};
+		// bcl-convert uses the Sample_ID as the file prefix, bcl2fastq uses Sample_Name if present
+		let fq = match files.get(sample_id).or_else(|| sample_name.and_then(|s| files.get(s))) {
+			Some(v) => v,
+			None => {
+				warn!("No FASTQ files found for sample {} in {}", sample_id, dir.display());
+				continue;
+			}
+		};
+		// Collect files per lane.  If the lane is not given in the sheet, all lanes are used
+		let mut lanes: BTreeMap<Option<usize>, Vec<&FastqFile>> = BTreeMap::new();
+		for f in fq.iter().filter(|f| lane.is_none() || f.lane == lane) { lanes.entry(f.lane).or_default().push(f) }
+		for (l, v) in lanes.iter() {
+			let mut dataset = String::new();
+			if let Some(r) = &sheet.run { dataset.push_str(&format!("{}_", r)) }
+			dataset.push_str(sample_id);
+			if let Some(x) = l { dataset.push_str(&format!("_L{:03}", x)) }
+			let mut hr = HashMap::new();
+			hr.insert(Metadata::Dataset, DataValue::String(dataset.clone()));
+			hr.insert(Metadata::SampleBarcode, DataValue::String(sample_id.to_owned()));
+			hr.insert(Metadata::SampleName, DataValue::String(sample_name.unwrap_or(sample_id).to_owned()));
+			hr.insert(Metadata::Platform, DataValue::String("Illumina".to_string()));
+			if let Some(s) = sheet.get(rec, "Description") { hr.insert(Metadata::Description, DataValue::String(s.to_owned())); }
+			for f in v.iter() {
+				let md = if f.read == 1 { Metadata::FilePath1 } else { Metadata::FilePath2 };
+				if hr.insert(md, DataValue::String(f.path.to_string_lossy().to_string())).is_some() {
+					return Err(format!("Multiple FASTQ files found for read {} of dataset {}", f.read, dataset));
+				}
+			}
+			if !hr.contains_key(&Metadata::FilePath2) {
+				if let Some(fp) = hr.remove(&Metadata::FilePath1) {
+					hr.insert(Metadata::FilePath, fp);
+					hr.insert(Metadata::FileType, DataValue::FileType(FileType::Single));
+				} else { return Err(format!("No read 1 FASTQ file found for dataset {}", dataset)); }
+			}
+			sample_data.add_record(&mut hr)?;
+		}
+		if !barcodes.contains(&sample_id) { barcodes.push(sample_id) }
+	}
+	if barcodes.is_empty() { return Err(format!("No FASTQ files found for samples in Illumina sample sheet {}", file_name)); }
+	// Adapters from the sample sheet are used if read trimming is enabled
+	for bc in barcodes.iter() {
+		if let Some(a) = adapter1 { sample_data.add_sample_param(bc, Section::Trimming, "adapter1", DataValue::String(a.to_owned())) }
+		if let Some(a) = adapter2 { sample_data.add_sample_param(bc, Section::Trimming, "adapter2", DataValue::String(a.to_owned())) }
+	}
+	sample_data.check_and_store_sample_data(gem_bs)
+}
@@ -0,0 +1,163 @@
+// Import of structured sample sheets in YAML or TOML format.
+//
+// The sheet has an optional defaults map with metadata applied to all datasets, and a samples
+// entry that is either a map keyed on sample barcode or an array of maps with a barcode key.
+// Each sample can have a parameters map of sections with parameters specific to that sample,
+// and a datasets array with the metadata for each dataset.
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::path::Path;
+use serde_json::{Value, Map, Number};
+use yaml_rust::{Yaml, YamlLoader};
+use toml_edit::{Document, Item};
+use crate::config::GemBS;
+use crate::commands::prepare::metadata::{SampleData, MD_ASSOC};
+use crate::commands::prepare::config_file::{KnownVarList, make_known_var_list};
+use crate::common::defs::{Metadata, DataValue, Section};
+
+fn yaml_to_json(y: &Yaml) -> Value {
+	match y {
+		Yaml::String(s) => Value::String(s.to_owned()),
+		Yaml::Integer(x) => Value::Number((*x).into()),
+		Yaml::Real(s) => s.parse::<f64>().ok().and_then(Number::from_f64).map(Value::Number).unwrap_or_else(|| Value::String(s.to_owned())),
+		Yaml::Boolean(x) => Value::Bool(*x),
+		Yaml::Array(v) => Value::Array(v.iter().map(yaml_to_json).collect()),
+		Yaml::Hash(h) => Value::Object(h.iter().map(|(k, v)| (yaml_key(k), yaml_to_json(v))).collect()),
+		_ => Value::Null,
+	}
+}
+
+fn yaml_key(y: &Yaml) -> String {
+	match yaml_to_json(y) {
+		Value::String(s) => s,
+		v => v.to_string(),
+	}
+}
+
+fn toml_value_to_json(v: &toml_edit::Value) -> Value {
+	match v {
+		toml_edit::Value::String(s) => Value::String(s.value().to_owned()),
+		toml_edit::Value::Integer(x) => Value::Number((*x.value()).into()),
+		toml_edit::Value::Float(x) => Number::from_f64(*x.value()).map(Value::Number).unwrap_or(Value::Null),
+		toml_edit::Value::Boolean(x) => Value::Bool(*x.value()),
+		toml_edit::Value::Datetime(x) => Value::String(format!("{}", x.value())),
+		toml_edit::Value::Array(a) => Value::Array(a.iter().map(toml_value_to_json).collect()),
+		toml_edit::Value::InlineTable(t) => Value::Object(t.iter().map(|(k, v)| (k.to_owned(), toml_value_to_json(v))).collect()),
+	}
+}
+
+fn toml_to_json(item: &Item) -> Value {
+	match item {
+		Item::Value(v) => toml_value_to_json(v),
+		Item::Table(t) => Value::Object(t.iter().map(|(k, v)| (k.to_owned(), toml_to_json(v))).collect()),
+		Item::ArrayOfTables(a) => Value::Array(a.iter().map(|t| Value::Object(t.iter().map(|(k, v)| (k.to_owned(), toml_to_json(v))).collect())).collect()),
+		Item::None => Value::Null,
+	}
+}
+
+fn read_sheet(file_name: &str) -> Result<Value, String> {
+	let s = std::fs::read_to_string(file_name).map_err(|e| format!("Could not read sample sheet {}: {}", file_name, e))?;
+	let toml = Path::new(file_name).extension().map(|x| x.to_string_lossy().to_lowercase() == "toml").unwrap_or(false);
+	if toml {
+		let doc = Document::parse(s.as_str()).map_err(|e| format!("Could not parse TOML sample sheet {}: {}", file_name, e))?;
+		Ok(toml_to_json(doc.as_item()))
+	} else {
+		let docs = YamlLoader::load_from_str(&s).map_err(|e| format!("Could not parse YAML sample sheet {}: {}", file_name, e))?;
+		Ok(docs.first().map(yaml_to_json).unwrap_or(Value::Null))
+	}
+}
+
+// Scalar values as strings, arrays as vectors of strings
+fn value_to_strings(v: &Value) -> Option<Vec<String>> {
+	match v {
+		Value::String(s) => Some(vec!(s.to_owned())),
+		Value::Number(x) => Some(vec!(x.to_string())),
+		Value::Bool(x) => Some(vec!(x.to_string())),
+		Value::Array(a) => a.iter().map(|x| value_to_strings(x).filter(|v| v.len() == 1).map(|mut v| v.pop().unwrap())).collect(),
+		_ => None,
+	}
+}
+
+fn get_metadata(href: &Map<String, Value>, hr: &mut HashMap<Metadata, DataValue>, context: &str) -> Result<(), String> {
+	for (key, val) in href.iter() {
+		if key == "parameters" || key == "datasets" { continue; }
+		let md = match Metadata::from_str(key) {
+			Ok(md) => md,
+			Err(_) => {
+				warn!("Warning: key {} for {} in sample sheet not recognized", key, context);
+				continue;
+			},
+		};
+		let s = match value_to_strings(val) {
+			Some(v) if v.len() == 1 => v,
+			_ => return Err(format!("Illegal value for {} for {} in sample sheet", key, context)),
+		};
+		let dval = DataValue::from_str(&s[0], *MD_ASSOC.get(&md).unwrap()).map_err(|e| format!("Error with {} for {} in sample sheet: {}", key, context, e))?;
+		hr.insert(md, dval);
+	}
+	if hr.contains_key(&Metadata::FilePath) && (hr.contains_key(&Metadata::FilePath1) || hr.contains_key(&Metadata::FilePath2)) {
+		return Err(format!("Error with {} in sample sheet: can not have FilePath in the same record as FilePath1 or FilePath2", context));
+	}
+	Ok(())
+}
+
+fn get_parameters(href: &Map<String, Value>, barcode: &str, kv_list: &KnownVarList, sample_data: &mut SampleData) -> Result<(), String> {
+	let params = match href.get("parameters") {
+		Some(Value::Object(p)) => p,
+		Some(_) => return Err(format!("Parameters for sample {} in sample sheet should be a map of sections", barcode)),
+		None => return Ok(()),
+	};
+	for (sec, vars) in params.iter() {
+		let section = Section::from_str(sec).map_err(|_| format!("Unknown section {} in parameters for sample {}", sec, barcode))?;
+		let vars = if let Value::Object(v) = vars { v } else { return Err(format!("Parameters for section {} for sample {} should be a map", sec, barcode)) };
+		for (name, val) in vars.iter() {
+			let vals = value_to_strings(val).ok_or_else(|| format!("Illegal value for parameter {} for sample {}", name, barcode))?;
+			let dval = kv_list.parse_value(name, section, &vals).map_err(|e| format!("Error with parameters for sample {}: {}", barcode, e))?;
+			sample_data.add_sample_param(barcode, section, &name.to_lowercase(), dval);
+		}
+	}
+	Ok(())
+}
+
+pub fn process_sheet_file(file_name: &str, gem_bs: &mut GemBS) -> Result<(), String> {
+	let sheet = match read_sheet(file_name)? {
+		Value::Object(m) => m,
+		_ => return Err(format!("Unexpected structure in sample sheet {}", file_name)),
+	};
+	let mut defaults = HashMap::new();
+	if let Some(d) = sheet.get("defaults") {
+		if let Value::Object(m) = d { get_metadata(m, &mut defaults, "defaults")? }
+		else { return Err(format!("Defaults in sample sheet {} should be a map", file_name)) }
+	}
+	// Samples can be a map keyed on barcode or an array
+	let samples: Vec<(Option<&String>, &Map<String, Value>)> = match sheet.get("samples") {
+		Some(Value::Object(m)) => m.iter().map(|(bc, v)| if let Value::Object(x) = v { Ok((Some(bc), x)) } else { Err(format!("Entry for sample {} should be a map", bc)) }).collect::<Result<_, _>>()?,
+		Some(Value::Array(v)) => v.iter().map(|v| if let Value::Object(x) = v { Ok((None, x)) } else { Err("Entries in samples array should be maps".to_string()) }).collect::<Result<_, _>>()?,
+		_ => return Err(format!("No samples found in sample sheet {}", file_name)),
+	};
+	let kv_list = make_known_var_list();
+	let mut sample_data = SampleData::new();
+	for (bc, href) in samples.iter() {
+		let mut sample_md = defaults.clone();
+		if let Some(b) = bc { sample_md.insert(Metadata::SampleBarcode, DataValue::String(b.to_string())); }
+		get_metadata(href, &mut sample_md, "sample")?;
+		let barcode = if let Some(DataValue::String(b)) = sample_md.get(&Metadata::SampleBarcode) { b.to_owned() }
+		else { return Err(format!("Missing barcode for sample in sample sheet {}", file_name)) };
+		get_parameters(href, &barcode, &kv_list, &mut sample_data)?;
+		let datasets = match href.get("datasets") {
+			Some(Value::Array(v)) if !v.is_empty() => v,
+			_ => return Err(format!("No datasets found for sample {} in sample sheet {}", barcode, file_name)),
+		};
+		for (ix, ds) in datasets.iter().enumerate() {
+			let context = format!("dataset {} of sample {}", ix + 1, barcode);
+			let mut hr = sample_md.clone();
+			// Dataset level file specifications replace any from the sample or defaults
+			hr.retain(|md, _| !matches!(md, Metadata::FilePath | Metadata::FilePath1 | Metadata::FilePath2 | Metadata::Dataset));
+			if let Value::Object(m) = ds { get_metadata(m, &mut hr, &context)? }
+			else { return Err(format!("Error with {} in sample sheet: should be a map", context)) }
+			hr.entry(Metadata::Dataset).or_insert_with(|| DataValue::String(format!("{}_{}", barcode, ix + 1)));
+			sample_data.add_record(&mut hr).map_err(|e| format!("Error with {} in sample sheet {}: {}", context, file_name, e))?;
+		}
+	}
+	sample_data.check_and_store_sample_data(gem_bs)
+}
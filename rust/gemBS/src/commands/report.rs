@@ -8,6 +8,7 @@ use std::collections::HashSet;
 
 pub mod make_map_report;
 pub mod make_call_report;
+pub mod cnv;
 pub mod make_report;
 pub mod report_utils;
 
@@ -34,7 +35,7 @@ pub fn report_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String>
 	let flock = utils::wait_for_lock(gem_bs.get_signal_clone(), &task_path)?; 
 	gem_bs.setup_assets_and_tasks(&flock)?;
 	let com_set = if gem_bs.all() {
-		let mut tc = vec!(Command::Index, Command::Map);
+		let mut tc = vec!(Command::Index, Command::Trim, Command::Map);
 		if options.contains_key("_mapping") { tc.append(&mut vec!(Command::MD5SumMap, Command::MapReport)); }
 		if options.contains_key("_calling") { tc.append(&mut vec!(Command::MergeBams, Command::MD5SumCall, Command::MergeCallJsons, Command::IndexBcf, Command::Call, Command::CallReport)); }
 		if options.contains_key("_report") { tc.push(Command::Report); }		
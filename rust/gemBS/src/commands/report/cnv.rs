@@ -0,0 +1,329 @@
+// Copy number estimation from the binned read depth collected by bs_call
+//
+// Bins with too few callable (non N) reference bases or with a high proportion of covered positions with low
+// mapping quality are discarded.  The depth per callable base is corrected for GC content by dividing by the
+// median depth of autosomal bins with the same GC content, and then normalized to the autosomal median to give
+// a log2 ratio where 0 corresponds to the (assumed diploid) autosomal copy number.  Each contig is segmented using
+// circular binary segmentation, and segments are called as gains or losses from their mean log2 ratio.  Segments
+// where the proportion of heterozygous SNPs is much lower than the genome-wide value are flagged as LOH.
+
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::io::Write;
+use std::fmt;
+
+use plotters::prelude::*;
+
+use crate::common::json_call_stats::CnvBins;
+use utils::sex_stats::{is_autosome, strip_chr};
+
+// Minimum fraction of callable bases in a bin, and maximum fraction of covered positions with low MAPQ
+const MIN_CALLABLE: f64 = 0.75;
+const MAX_LOW_MAPQ: f64 = 0.5;
+// Minimum number of autosomal bins in a GC bucket to calculate a median
+const MIN_GC_BUCKET_BINS: usize = 10;
+// Segmentation parameters: minimum bins in a segment, minimum t statistic and minimum difference in log2 ratio to split
+const MIN_SEG_BINS: usize = 3;
+const MIN_SEG_T: f64 = 5.0;
+const MIN_SEG_DIFF: f64 = 0.1;
+// Thresholds on segment log2 ratios for calls
+const HOMDEL_THRESHOLD: f64 = -1.6;
+const LOSS_THRESHOLD: f64 = -0.2;
+const GAIN_THRESHOLD: f64 = 0.2;
+const AMP_THRESHOLD: f64 = 1.3;
+// LOH is called if the het SNP fraction is below LOH_RATIO * the genome-wide fraction with at least LOH_MIN_SNPS SNPs
+const LOH_RATIO: f64 = 0.25;
+const LOH_MIN_SNPS: u32 = 20;
+// Floor for log2 ratios (bins with no coverage)
+const MIN_LOG2: f64 = -6.6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CnvCall { HomDel, Loss, Neutral, Gain, Amp }
+
+impl fmt::Display for CnvCall {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			CnvCall::HomDel => "HomDel",
+			CnvCall::Loss => "Loss",
+			CnvCall::Neutral => "Neutral",
+			CnvCall::Gain => "Gain",
+			CnvCall::Amp => "Amp",
+		};
+		write!(f, "{}", s)
+	}
+}
+
+impl CnvCall {
+	fn from_log2(x: f64) -> Self {
+		if x < HOMDEL_THRESHOLD { CnvCall::HomDel }
+		else if x < LOSS_THRESHOLD { CnvCall::Loss }
+		else if x > AMP_THRESHOLD { CnvCall::Amp }
+		else if x > GAIN_THRESHOLD { CnvCall::Gain }
+		else { CnvCall::Neutral }
+	}
+}
+
+fn copy_number(log2: f64) -> f64 { 2.0 * log2.exp2() }
+
+// Sort contigs with numeric names first (in numeric order), then X, Y and the rest, with the mitochondrion last
+fn ctg_sort_key(ctg: &str) -> (usize, usize, String) {
+	let s = strip_chr(ctg);
+	if let Ok(x) = s.parse::<usize>() { (0, x, String::new()) }
+	else {
+		match s {
+			"X" => (1, 0, String::new()),
+			"Y" => (1, 1, String::new()),
+			"M" | "MT" => (3, 0, String::new()),
+			_ => (2, 0, s.to_owned()),
+		}
+	}
+}
+
+fn median(v: &mut [f64]) -> Option<f64> {
+	if v.is_empty() { return None }
+	v.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	let n = v.len();
+	Some(if n & 1 == 1 { v[n >> 1] } else { 0.5 * (v[(n >> 1) - 1] + v[n >> 1]) })
+}
+
+pub struct CnvBin {
+	pub start: usize,
+	pub log2: f64,
+	het: u32,
+	hom_alt: u32,
+}
+
+pub struct CnvSegment {
+	pub ctg: String,
+	pub start: usize,
+	pub end: usize,
+	pub n_bins: usize,
+	pub log2: f64,
+	pub het: u32,
+	pub hom_alt: u32,
+	pub call: CnvCall,
+	pub loh: bool,
+}
+
+impl CnvSegment {
+	pub fn copy_number(&self) -> f64 { copy_number(self.log2) }
+}
+
+pub struct CnvContig {
+	pub name: String,
+	pub bins: Vec<CnvBin>,
+	pub median_log2: Option<f64>,
+}
+
+impl CnvContig {
+	pub fn copy_number(&self) -> Option<f64> { self.median_log2.map(copy_number) }
+	// Autosomes where the estimated copy number (rounded) differs from 2
+	pub fn aneuploid(&self) -> bool {
+		is_autosome(&self.name) && self.copy_number().map(|x| (x.round() as i64) != 2).unwrap_or(false)
+	}
+}
+
+pub struct CnvAnalysis {
+	pub bin_size: usize,
+	pub contigs: Vec<CnvContig>,
+	pub segments: Vec<CnvSegment>,
+	// Estimated standard deviation of log2 ratios between adjacent bins
+	pub noise: f64,
+	pub het_fraction: f64,
+}
+
+// Returns the raw depth per callable base for bins that pass the filters
+fn bin_depth(b: &CnvBins, i: usize) -> Option<f64> {
+	let callable = b.callable[i] as f64;
+	let covered = b.covered[i] as f64;
+	if callable < MIN_CALLABLE * (b.bin_size as f64) || covered == 0.0 || (b.low_mapq[i] as f64) > MAX_LOW_MAPQ * covered { None }
+	else { Some((b.depth[i] as f64) / callable) }
+}
+
+// Median depth for each GC% bucket from autosomal bins.  Buckets with too few bins take the value from the nearest bucket with data
+fn gc_medians(cnv: &HashMap<String, CnvBins>) -> Option<Vec<f64>> {
+	let mut buckets: Vec<Vec<f64>> = vec!(Vec::new(); 101);
+	for b in cnv.iter().filter(|(c, _)| is_autosome(c)).map(|(_, b)| b) {
+		for i in 0..b.gc.len() {
+			if let Some(d) = bin_depth(b, i) { buckets[(b.gc[i].round() as usize).min(100)].push(d) }
+		}
+	}
+	let med: Vec<Option<f64>> = buckets.iter_mut().map(|v| if v.len() >= MIN_GC_BUCKET_BINS { median(v) } else { None }).collect();
+	if med.iter().all(|x| x.is_none()) { return None }
+	Some((0..med.len()).map(|i| {
+		(0..med.len()).find_map(|k| med.get(i + k).copied().flatten().or_else(|| if k <= i { med[i - k] } else { None })).expect("No GC medians")
+	}).collect())
+}
+
+// Circular binary segmentation of bins lo..hi (cs has the cumulative sums of the log2 ratios).  For each arc [i, j) we
+// calculate the t statistic for the difference between the mean inside and outside the arc, and if the maximum is
+// significant we split at i and j and recurse on the pieces
+fn segment(cs: &[f64], lo: usize, hi: usize, sd: f64, brk: &mut Vec<usize>) {
+	let n = hi - lo;
+	if n < 2 * MIN_SEG_BINS { return }
+	let tot = cs[hi] - cs[lo];
+	let mut best: Option<(f64, usize, usize)> = None;
+	for i in lo..hi {
+		for j in (i + MIN_SEG_BINS)..=hi {
+			let n_in = j - i;
+			let n_out = n - n_in;
+			if n_out < MIN_SEG_BINS { break }
+			let s_in = cs[j] - cs[i];
+			let diff = s_in / (n_in as f64) - (tot - s_in) / (n_out as f64);
+			if diff.abs() < MIN_SEG_DIFF { continue }
+			let t = diff.abs() / (sd * (1.0 / (n_in as f64) + 1.0 / (n_out as f64)).sqrt());
+			if best.map(|(bt, _, _)| t > bt).unwrap_or(true) { best = Some((t, i, j)) }
+		}
+	}
+	if let Some((t, i, j)) = best {
+		if t >= MIN_SEG_T {
+			let mut pts = vec!(lo);
+			if i > lo { pts.push(i) }
+			if j < hi { pts.push(j) }
+			pts.push(hi);
+			for w in pts.windows(2) {
+				if w[0] > lo { brk.push(w[0]) }
+				segment(cs, w[0], w[1], sd, brk);
+			}
+		}
+	}
+}
+
+impl CnvAnalysis {
+	pub fn new(cnv: &HashMap<String, CnvBins>) -> Option<Self> {
+		let bin_size = cnv.values().next()?.bin_size;
+		let gc_med = gc_medians(cnv)?;
+		let mut names: Vec<&String> = cnv.keys().collect();
+		names.sort_by_key(|s| ctg_sort_key(s));
+
+		// GC corrected depth for passing bins
+		let mut contigs = Vec::with_capacity(names.len());
+		for name in names.iter() {
+			let b = &cnv[*name];
+			let bins: Vec<CnvBin> = (0..b.gc.len()).filter_map(|i| bin_depth(b, i).and_then(|d| {
+				let m = gc_med[(b.gc[i].round() as usize).min(100)];
+				if m > 0.0 { Some(CnvBin{start: i * bin_size, log2: d / m, het: b.het[i], hom_alt: b.hom_alt[i]}) } else { None }
+			})).collect();
+			contigs.push(CnvContig{name: name.to_string(), bins, median_log2: None});
+		}
+
+		// Normalize to the autosomal median and convert to log2 ratios
+		let mut v: Vec<f64> = contigs.iter().filter(|c| is_autosome(&c.name)).flat_map(|c| c.bins.iter().map(|b| b.log2)).collect();
+		let norm = median(&mut v).filter(|x| *x > 0.0)?;
+		for c in contigs.iter_mut() {
+			for b in c.bins.iter_mut() { b.log2 = (b.log2 / norm).log2().max(MIN_LOG2) }
+			let mut v: Vec<f64> = c.bins.iter().map(|b| b.log2).collect();
+			c.median_log2 = median(&mut v);
+		}
+
+		// Estimate noise from the differences between adjacent bins (robust to breakpoints)
+		let mut d: Vec<f64> = contigs.iter().flat_map(|c| c.bins.windows(2).map(|w| (w[1].log2 - w[0].log2).abs())).collect();
+		let noise = (1.4826 * median(&mut d).unwrap_or(0.0) / std::f64::consts::SQRT_2).max(0.01);
+
+		let (het, hom) = contigs.iter().filter(|c| is_autosome(&c.name)).flat_map(|c| c.bins.iter()).fold((0, 0), |(a, b), x| (a + x.het as u64, b + x.hom_alt as u64));
+		let het_fraction = if het + hom > 0 { (het as f64) / ((het + hom) as f64) } else { 0.0 };
+
+		// Segment each contig
+		let mut segments = Vec::new();
+		for c in contigs.iter() {
+			let n = c.bins.len();
+			if n == 0 { continue }
+			let mut cs = Vec::with_capacity(n + 1);
+			cs.push(0.0);
+			for b in c.bins.iter() { cs.push(cs.last().unwrap() + b.log2) }
+			let mut brk = vec!(0, n);
+			segment(&cs, 0, n, noise, &mut brk);
+			brk.sort_unstable();
+			brk.dedup();
+			for w in brk.windows(2) {
+				let bins = &c.bins[w[0]..w[1]];
+				let log2 = (cs[w[1]] - cs[w[0]]) / (bins.len() as f64);
+				let (het, hom_alt) = bins.iter().fold((0, 0), |(a, b), x| (a + x.het, b + x.hom_alt));
+				let loh = het + hom_alt >= LOH_MIN_SNPS && (het as f64) < LOH_RATIO * het_fraction * ((het + hom_alt) as f64);
+				segments.push(CnvSegment{ctg: c.name.clone(), start: bins[0].start, end: bins[bins.len() - 1].start + bin_size, n_bins: bins.len(),
+					log2, het, hom_alt, call: CnvCall::from_log2(log2), loh});
+			}
+		}
+		Some(Self{bin_size, contigs, segments, noise, het_fraction})
+	}
+
+	pub fn altered_segments(&self) -> impl Iterator<Item = &CnvSegment> { self.segments.iter().filter(|s| s.call != CnvCall::Neutral || s.loh) }
+
+	// BED like file with all segments
+	pub fn write_segments(&self, path: &Path) -> Result<(), String> {
+		let mut wrt = std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("Couldn't create CNV segment file {}: {}", path.display(), e))?);
+		let mut f = || -> std::io::Result<()> {
+			writeln!(wrt, "#Contig\tStart\tEnd\tBins\tLog2Ratio\tCopyNumber\tCall\tHetSNPs\tHomAltSNPs\tLOH")?;
+			for s in self.segments.iter() {
+				writeln!(wrt, "{}\t{}\t{}\t{}\t{:.4}\t{:.2}\t{}\t{}\t{}\t{}", s.ctg, s.start, s.end, s.n_bins, s.log2, s.copy_number(), s.call, s.het, s.hom_alt, if s.loh { "LOH" } else { "." })?;
+			}
+			wrt.flush()
+		};
+		f().map_err(|e| format!("Error writing CNV segment file {}: {}", path.display(), e))
+	}
+
+	fn make_plot(&self, path: &Path, title: &str) -> Result<(), Box<dyn std::error::Error>> {
+		let root = BitMapBackend::new(&path, (1280, 480)).into_drawing_area();
+		root.fill(&WHITE)?;
+		// Genome-wide x coordinate in bins
+		let mut offsets = Vec::with_capacity(self.contigs.len());
+		let mut tot = 0;
+		for c in self.contigs.iter() {
+			offsets.push(tot);
+			tot += c.bins.last().map(|b| b.start / self.bin_size + 1).unwrap_or(0);
+		}
+		let (ymin, ymax) = (-3.0, 3.0);
+		let mut chart = ChartBuilder::on(&root)
+			.x_label_area_size(35)
+			.y_label_area_size(60)
+			.margin(5)
+			.caption(title, ("sans-serif", 22.0).into_font())
+			.build_ranged(0..tot.max(1), ymin..ymax)?;
+
+		chart
+			.configure_mesh()
+			.disable_x_mesh()
+			.line_style_1(&WHITE.mix(0.3))
+			.x_labels(0)
+			.y_desc("Log2 Copy Ratio")
+			.x_desc("Contig")
+			.y_label_formatter(&|x| format!("{:.1}", x))
+			.axis_desc_style(("sans-serif", 15).into_font())
+			.draw()?;
+
+		let clamp = |y: f64| y.max(ymin).min(ymax);
+		for (ix, (c, off)) in self.contigs.iter().zip(offsets.iter()).enumerate() {
+			let col = if ix & 1 == 0 { BLUE.mix(0.4) } else { BLACK.mix(0.3) };
+			chart.draw_series(c.bins.iter().map(|b| Circle::new((off + b.start / self.bin_size, clamp(b.log2)), 1, col.filled())))?;
+			if let Some(b) = c.bins.last() {
+				let mid = off + (b.start / self.bin_size) / 2;
+				let name = c.name.strip_prefix("chr").unwrap_or(&c.name).to_owned();
+				chart.draw_series(std::iter::once(Text::new(name, (mid, ymin + 0.15), ("sans-serif", 10).into_font())))?;
+			}
+		}
+		let off_map: HashMap<&str, usize> = self.contigs.iter().zip(offsets.iter()).map(|(c, off)| (c.name.as_str(), *off)).collect();
+		for s in self.segments.iter() {
+			let off = off_map[s.ctg.as_str()];
+			let y = clamp(s.log2);
+			chart.draw_series(std::iter::once(PathElement::new(vec!((off + s.start / self.bin_size, y), (off + s.end / self.bin_size, y)),
+				Into::<ShapeStyle>::into(&RED).stroke_width(3))))?;
+		}
+		Ok(())
+	}
+}
+
+// Perform analysis, write segments file and plot.  If there is no copy number data in the JSON file, nothing is done
+pub fn make_cnv_analysis(bc: &str, dir: &Path, cnv: Option<&HashMap<String, CnvBins>>) -> Result<Option<CnvAnalysis>, String> {
+	let cnv = match cnv.and_then(CnvAnalysis::new) {
+		Some(c) => c,
+		None => {
+			debug!("No copy number data for {}", bc);
+			return Ok(None)
+		},
+	};
+	let seg_path: PathBuf = [dir, Path::new(format!("{}_cnv_segments.txt", bc).as_str())].iter().collect();
+	cnv.write_segments(&seg_path)?;
+	let img_path: PathBuf = [dir, Path::new("images"), Path::new(format!("{}_copy_number.png", bc).as_str())].iter().collect();
+	cnv.make_plot(&img_path, "Genome-wide Copy Number Profile").map_err(|e| format!("{}", e))?;
+	Ok(Some(cnv))
+}
@@ -9,13 +9,15 @@ use plotters::prelude::*;
 
 use crate::common::utils::check_signal;
 use utils::compress;
-use crate::common::json_call_stats::{CallJson, FSReadLevelType, FSBaseLevelType, FSCounts, Counts, QCCounts, MutCounts};
+use crate::common::json_call_stats::{CallJson, FSReadLevelType, FSBaseLevelType, FSCounts, Counts, QCCounts, MutCounts, infer_sex};
 use crate::scheduler::report::CallJsonFiles;
 use super::report_utils::*;
 use super::make_map_report;
 use super::make_map_report::{make_title, make_section};
+use super::cnv::{CnvAnalysis, make_cnv_analysis};
 use crate::common::html_utils::*;
 use crate::common::latex_utils::*;
+use crate::common::defs::Sex;
 
 enum CovType { All, NonRefCpg, NonRefCpgInf, RefCpg, RefCpgInf, Variant }
 enum QualType { All, RefCpg, NonRefCpg, Variant }
@@ -636,6 +638,75 @@ fn make_vcf_filter_stats_latex_tab(json: &CallJson) -> Result<LatexContent, Stri
 	Ok(LatexContent::Table(table))		
 }
 
+fn make_sex_tab<T: Table>(table: &mut T, json: &CallJson, expected: Option<Sex>, ss: Option<&mut Option<SexSummary>>) {
+	table.add_header(vec!("X Coverage Ratio", "Y Coverage Ratio", "X Het. Rate", "Autosome Het. Rate", "Inferred Sex", "Expected Sex"));
+	let f = |x: Option<f64>| x.map(|z| format!("{:.3}", z)).unwrap_or_else(|| "n/a".to_string());
+	let fs = |x: Option<Sex>| x.map(|s| format!("{}", s)).unwrap_or_else(|| "undetermined".to_string());
+	let exp = expected.map(|s| format!("{}", s)).unwrap_or_else(|| "-".to_string());
+	match json.sex_stats() {
+		Some(st) => {
+			let inferred = infer_sex(st);
+			table.add_row(vec!(f(st.x_ratio), f(st.y_ratio), f(st.x_het_rate), f(st.autosome_het_rate), fs(inferred), exp));
+			if let Some(s) = ss { *s = Some(SexSummary{inferred}) }
+		},
+		None => { table.add_row(vec!(f(None), f(None), f(None), f(None), fs(None), exp)); },
+	}
+}
+
+// Contig names are passed through fmt_ctg so that they can be escaped for LaTeX
+fn make_cnv_contig_tab<T: Table>(table: &mut T, cnv: &CnvAnalysis, fmt_ctg: &dyn Fn(&str) -> String) {
+	table.add_header(vec!("Contig", "Bins", "Median Log2 Ratio", "Est. Copy Number", "Status"));
+	for c in cnv.contigs.iter() {
+		if let Some(x) = c.median_log2 {
+			let cn = c.copy_number().unwrap_or(0.0);
+			table.add_row(vec!(fmt_ctg(&c.name), format!("{}", c.bins.len()), format!("{:.3}", x), format!("{:.2}", cn),
+				(if c.aneuploid() { "Aneuploid" } else { "" }).to_string()));
+		}
+	}
+}
+
+fn make_cnv_segment_tab<T: Table>(table: &mut T, cnv: &CnvAnalysis, fmt_ctg: &dyn Fn(&str) -> String) {
+	table.add_header(vec!("Contig", "Start", "End", "Bins", "Log2 Ratio", "Est. Copy Number", "Call", "Het SNPs", "Hom. Alt SNPs", "LOH"));
+	for s in cnv.altered_segments() {
+		table.add_row(vec!(fmt_ctg(&s.ctg), format!("{}", s.start), format!("{}", s.end), format!("{}", s.n_bins), format!("{:.3}", s.log2),
+			format!("{:.2}", s.copy_number()), format!("{}", s.call), format!("{}", s.het), format!("{}", s.hom_alt),
+			(if s.loh { "Yes" } else { "" }).to_string()));
+	}
+}
+
+// Mitochondrial depth and copy number, with counts of passed variants
+fn make_mito_summary_tab<T: Table>(table: &mut T, json: &CallJson, fmt_ctg: &dyn Fn(&str) -> String) {
+	table.add_header(vec!("Contig", "Mean Depth", "Autosome Mean Depth", "Est. mtDNA Copy Number", "Variants", "Heteroplasmic Variants"));
+	let f = |x: Option<f64>, p: usize| x.map(|z| format!("{:.*}", p, z)).unwrap_or_else(|| "n/a".to_string());
+	if let Some(mito) = json.mito() {
+		let het = mito.variants.iter().filter(|v| mito.is_heteroplasmic(v)).count();
+		table.add_row(vec!(fmt_ctg(&mito.contig), f(json.contig_depth(&mito.contig), 1), f(json.sex_stats().map(|s| s.autosome_depth), 1),
+			f(json.mito_copy_number(), 0), format!("{}", mito.variants.len()), format!("{}", het)));
+	}
+}
+
+fn make_mito_variant_tab<T: Table>(table: &mut T, json: &CallJson) {
+	table.add_header(vec!("Position", "Ref", "Alt", "Alt Fraction", "Depth", "Quality", "Type"));
+	if let Some(mito) = json.mito() {
+		for v in mito.variants.iter() {
+			table.add_row(vec!(format!("{}", v.pos), v.ref_allele.clone(), v.alt.clone(), format!("{:.3}", v.alt_fraction), format!("{}", v.depth), 
+				format!("{}", v.qual), (if mito.is_heteroplasmic(v) { "Heteroplasmic" } else { "Homoplasmic" }).to_string()));
+		}
+	}
+}
+
+// Counts of variants at or above each truth sensitivity tranche from the variant recalibration
+fn make_recal_tranche_tab<T: Table>(table: &mut T, json: &CallJson) {
+	table.add_header(vec!("Truth Sensitivity", "Min. VQSLOD", "Known Variants", "Novel Variants", "Novel Ti / Tv", "Filter"));
+	if let Some(recal) = json.recalibration() {
+		for t in recal.tranches.iter() {
+			table.add_row(vec!(format!("{:.1}", t.truth_sensitivity), format!("{:.3}", t.min_vqslod), format!("{}", t.known), format!("{}", t.novel),
+				t.novel_ti_tv().map(|z| format!("{:.3}", z)).unwrap_or_else(|| "n/a".to_string()),
+				(if t.truth_sensitivity <= recal.pass_sensitivity { "PASS" } else { "" }).to_string()));
+		}
+	}
+}
+
 fn new_body(project: &str, bc: &str, tag: &str) -> HtmlElement {
 	let mut body = HtmlElement::new("BODY", None, true);
 	let mut path = HtmlElement::new("P", Some("id=\"path\""), true);
@@ -651,7 +722,7 @@ fn new_body(project: &str, bc: &str, tag: &str) -> HtmlElement {
 	body
 }
 
-fn create_mapping_report_body(project: &str, bc: &str, dir: &Path, json: &CallJson, msumm: &mut MapSummary) -> HtmlElement {
+fn create_mapping_report_body(project: &str, bc: &str, dir: &Path, json: &CallJson, msumm: &mut MapSummary, expected_sex: Option<Sex>, ssumm: &mut Option<SexSummary>) -> HtmlElement {
 	let mut img_dir = dir.to_owned();
 	img_dir.push("images");
 	let mut body = new_body(project, bc, "mapping_coverage");
@@ -661,6 +732,11 @@ fn create_mapping_report_body(project: &str, bc: &str, dir: &Path, json: &CallJs
 	body.push_element(make_section("Base Level Counts"));
 	body.push(make_base_level_table(json));
 	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+	body.push_element(make_section("Sex Inference"));
+	let mut table = HtmlTable::new("hor-zebra");
+	make_sex_tab(&mut table, json, expected_sex, Some(ssumm));
+	body.push(Content::Table(table));
+	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
 	body.push_element(make_section("Coverage and Quality"));
 	let get_path = |name: &str| {
 		let mut tp = img_dir.clone();
@@ -671,7 +747,7 @@ fn create_mapping_report_body(project: &str, bc: &str, dir: &Path, json: &CallJs
 		let fname = p.file_name().expect("Missing filename").to_string_lossy();
 		format!("<img src=\"images/{}\" alt=\"{}\">", fname, fname)	
 	};
-	let mut table = HtmlTable::new("hor-zebra");
+	table = HtmlTable::new("hor-zebra");
 	table.add_header(vec!("Coverage Distribution", "Quality Distribution"));
 	table.add_row(vec!(img_str(&get_path("coverage_all")), img_str(&get_path("quality_all"))));
 	body.push(Content::Table(table));
@@ -730,6 +806,24 @@ fn create_variant_report_body(project: &str, bc: &str, dir: &Path, json: &CallJs
 	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
 	body.push_element(make_section("Ti / Tv Ratio"));
 	body.push(t2);	
+	if json.recalibration().is_some() {
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Variant Recalibration"));
+		table = HtmlTable::new("green");
+		make_recal_tranche_tab(&mut table, json);
+		body.push(Content::Table(table));
+	}
+	if json.mito().is_some() {
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Mitochondria"));
+		table = HtmlTable::new("hor-zebra");
+		make_mito_summary_tab(&mut table, json, &|s| s.to_owned());
+		body.push(Content::Table(table));
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		table = HtmlTable::new("green");
+		make_mito_variant_tab(&mut table, json);
+		body.push(Content::Table(table));
+	}
 	let(t, total, _) = prep_hist_vec(&json.coverage().variant);
 	let mut tmp = 0;
 	for(ix, x) in t.iter() {
@@ -789,6 +883,32 @@ fn create_meth_report_body(project: &str, bc: &str, dir: &Path, json: &CallJson,
 	body
 }
 
+fn create_copy_number_report_body(project: &str, bc: &str, cnv: &CnvAnalysis, csumm: &mut CnvSummary) -> HtmlElement {
+	let mut body = new_body(project, bc, "copy_number");
+	body.push_element(make_section("Copy Number Profile"));
+	let mut table = HtmlTable::new("green");
+	table.add_header(vec!("Genome-wide Log2 Copy Ratio (GC corrected)"));
+	table.add_row(vec!(format!("<img src=\"images/{}_copy_number.png\" alt=\"{}_copy_number.png\">", bc, bc)));
+	body.push(Content::Table(table));
+	let mut p = HtmlElement::new("P", None, true);
+	p.push_string(format!("Bin size {} bp, noise (SD of log2 ratio) {:.3}, autosomal het SNP fraction {:.3}.  All segments are in <a class=\"link\" href=\"{}_cnv_segments.txt\">{}_cnv_segments.txt</a>",
+		cnv.bin_size, cnv.noise, cnv.het_fraction, bc, bc));
+	body.push_element(p);
+	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+	body.push_element(make_section("Contig Copy Number"));
+	let mut table = HtmlTable::new("hor-zebra");
+	make_cnv_contig_tab(&mut table, cnv, &|s| s.to_owned());
+	body.push(Content::Table(table));
+	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+	body.push_element(make_section("Copy Number Alterations and LOH"));
+	let mut table = HtmlTable::new("hor-zebra");
+	make_cnv_segment_tab(&mut table, cnv, &|s| s.to_owned());
+	body.push(Content::Table(table));
+	csumm.altered_segments = cnv.altered_segments().count();
+	csumm.aneuploid_contigs = cnv.contigs.iter().filter(|c| c.aneuploid()).map(|c| c.name.clone()).collect();
+	body
+}
+
 fn new_page(path: &Path) -> Result<HtmlPage, String> {
 	let mut html = HtmlPage::new(path)?;
 	let mut head_element = HtmlElement::new("HEAD", None, true);
@@ -807,7 +927,7 @@ fn new_page(path: &Path) -> Result<HtmlPage, String> {
 // for the output
 //
 
-fn create_mapping_latex_section(bc: &str, json: &CallJson) -> LatexSection {
+fn create_mapping_latex_section(bc: &str, json: &CallJson, expected_sex: Option<Sex>) -> LatexSection {
 	info!("Create latex mapping report for {}", bc);
 	let mut img_dir = PathBuf::from_str(bc).expect("Couldn't get Path from barcode");
 	img_dir.push("images");
@@ -817,6 +937,10 @@ fn create_mapping_latex_section(bc: &str, json: &CallJson) -> LatexSection {
 	sec.push(make_read_level_latex_tab(json));
 	sec.push_str("\\subsubsection{{Base Level Counts}}");
 	sec.push(make_base_level_latex_tab(json));
+	sec.push_str("\\subsubsection{{Sex Inference}}");
+	let mut table = LatexTable::new();
+	make_sex_tab(&mut table, json, expected_sex, None);
+	sec.push(LatexContent::Table(table));
 	sec.push_str("\\subsubsection{{Coverage Distribution}}");
 	sec.push_string(format!("\\includegraphics[width=12cm]{{{}}}", img_dir.join(format!("{}_coverage_all", bc).as_str()).display()));
 	sec.push_str("\\subsubsection{{Quality Distribution}}");
@@ -861,6 +985,22 @@ fn create_variant_latex_section(bc: &str, json: &CallJson) -> Result<LatexSectio
 	sec.push(t1);
 	sec.push_str("\\subsubsection{Ti / Tv Ratio}");
 	sec.push(t2);
+	if json.recalibration().is_some() {
+		sec.push_str("\\subsubsection{Variant Recalibration}");
+		let mut table = LatexTable::new();
+		make_recal_tranche_tab(&mut table, json);
+		sec.push(LatexContent::Table(table));
+	}
+	if json.mito().is_some() {
+		sec.push_str("\\subsubsection{Mitochondria}");
+		let mut table = LatexTable::new();
+		make_mito_summary_tab(&mut table, json, &latex_escape_str);
+		sec.push(LatexContent::Table(table));
+		sec.push_str("\\subsubsection*{Mitochondrial Variants}");
+		let mut table = LatexTable::new();
+		make_mito_variant_tab(&mut table, json);
+		sec.push(LatexContent::Table(table));
+	}
 	Ok(sec)
 }
 
@@ -890,6 +1030,25 @@ fn create_meth_latex_section(bc: &str, json: &CallJson) -> LatexSection {
 	sec
 }
 
+fn create_copy_number_latex_section(bc: &str, cnv: &CnvAnalysis) -> LatexSection {
+	info!("Create latex copy number report for {}", bc);
+	let mut img_dir = PathBuf::from_str(bc).expect("Couldn't get Path from barcode");
+	img_dir.push("images");
+	let mut sec = LatexSection::new("D");
+	sec.push_string(format!("\\subsection{{Copy Number Report for {}}}", bc));
+	sec.push_str("\\subsubsection{Copy Number Profile}");
+	sec.push_string(format!("\\includegraphics[width=16cm]{{{}}}", img_dir.join(format!("{}_copy_number", bc).as_str()).display()));
+	sec.push_str("\\subsubsection{Contig Copy Number}");
+	let mut table = LatexTable::new();
+	make_cnv_contig_tab(&mut table, cnv, &latex_escape_str);
+	sec.push(LatexContent::Table(table));
+	sec.push_str("\\subsubsection{Copy Number Alterations and LOH}");
+	let mut table = LatexTable::new();
+	make_cnv_segment_tab(&mut table, cnv, &latex_escape_str);
+	sec.push(LatexContent::Table(table));
+	sec
+}
+
 fn get_section_array_for_bc<'a>(ldoc: &'a mut LatexBare, bc: &str) -> Result<&'a mut SectionArray, String> {
 	if ldoc.find_section(bc).is_none() {
 		let mut  s = LatexSection::new(bc);
@@ -913,10 +1072,17 @@ fn create_mapping_report(bc: &str, dir: &Path, project: &str, call_json: &CallJs
 	let path: PathBuf = [dir, Path::new(format!("{}_mapping_coverage.html", bc).as_str())].iter().collect();
 	let mut html = new_page(&path)?;
 	let mut map_summ = MapSummary::new();
-	html.push_element(create_mapping_report_body(project, bc, dir, call_json, &mut map_summ));	
+	let mut sex_summ = None;
+	let expected_sex = summary.lock().expect("Couldn't lock CallSummary").get(bc).expect("Couldn't find CallSummary for sample").expected_sex;
+	html.push_element(create_mapping_report_body(project, bc, dir, call_json, &mut map_summ, expected_sex, &mut sex_summ));	
 	let mut shash = summary.lock().expect("Couldn't lock CallSummary");
-	shash.get_mut(&bc.to_owned()).expect("Couldn't find CallSummary for sample").map = Some(map_summ);
-	let sec = create_mapping_latex_section(bc, call_json);
+	let summ = shash.get_mut(&bc.to_owned()).expect("Couldn't find CallSummary for sample");
+	summ.map = Some(map_summ);
+	summ.sex = sex_summ;
+	if summ.sex_mismatch() { 
+		warn!("Sex mismatch for sample {}: inferred {}, expected {}", bc, summ.sex.as_ref().and_then(|s| s.inferred).unwrap(), summ.expected_sex.unwrap());
+	}
+	let sec = create_mapping_latex_section(bc, call_json, expected_sex);
 	if let Ok(mut ldoc) = latex_doc.lock() { 
 		let sa = get_section_array_for_bc(&mut ldoc, bc)?;
 		sa.push(sec);
@@ -956,6 +1122,28 @@ fn create_meth_report(bc: &str, dir: &Path, project: &str, call_json: &CallJson,
 	} else { Err("Couldn't obtain lock on latex doc".to_string()) }
 }
 
+// The copy number report is only made if the JSON file has copy number data
+fn create_copy_number_report(bc: &str, dir: &Path, project: &str, call_json: &CallJson, summary: Arc<Mutex<HashMap<String, CallSummary>>>, latex_doc: Arc<Mutex<LatexBare>>) -> Result<(), String> {
+	debug!("Create copy number report for {}", bc);
+	let cnv = match make_cnv_analysis(bc, dir, call_json.cnv())? {
+		Some(c) => c,
+		None => return Ok(()),
+	};
+	let path: PathBuf = [dir, Path::new(format!("{}_copy_number.html", bc).as_str())].iter().collect();
+	let mut html = new_page(&path)?;
+	let mut cnv_summ = CnvSummary{altered_segments: 0, aneuploid_contigs: Vec::new()};
+	html.push_element(create_copy_number_report_body(project, bc, &cnv, &mut cnv_summ));
+	if !cnv_summ.aneuploid_contigs.is_empty() { warn!("Possible aneuploidy for sample {}: {}", bc, cnv_summ.aneuploid_contigs.join(", ")) }
+	let mut shash = summary.lock().expect("Couldn't lock CallSummary");
+	shash.get_mut(&bc.to_owned()).expect("Couldn't find CallSummary for sample").cnv = Some(cnv_summ);
+	let sec = create_copy_number_latex_section(bc, &cnv);
+	if let Ok(mut ldoc) = latex_doc.lock() { 
+		let sa = get_section_array_for_bc(&mut ldoc, bc)?;
+		sa.push(sec);
+		Ok(())
+	} else { Err("Couldn't obtain lock on latex doc".to_string()) }
+}
+
 fn create_summary(dir: &Path, summary: Arc<Mutex<HashMap<String, CallSummary>>>, latex_doc: Arc<Mutex<LatexBare>>) -> Result<(), String> {
 	debug!("Create summary of calling report");
 	let mut path = dir.to_owned();
@@ -975,12 +1163,12 @@ fn create_summary(dir: &Path, summary: Arc<Mutex<HashMap<String, CallSummary>>>,
 		else {format!("{}", x)}
 	};
 	table.add_header(vec!(
-		"Sample", "Aligned", "Uniquely Aligned", "Passed", "GC Depth corr.", "Variants", "Passed Variants", "Med. Cov. Passed Variants",
+		"Sample", "Aligned", "Uniquely Aligned", "Passed", "GC Depth corr.", "Sex", "Variants", "Passed Variants", "Med. Cov. Passed Variants",
 		"Ti/Tv Ratio", "Med. CpG Meth.", "Med. CpG Cov.", "Passed CpGs", "Reports"));
 	let mut ltable1 = LatexTable::new();
 	let mut ltable2 = LatexTable::new();
 	let mut ltable3 = LatexTable::new();
-	ltable1.add_header(vec!("Sample", "Aligned", "Uniquely Aligned", "Passed", "GC Depth Corr."));
+	ltable1.add_header(vec!("Sample", "Aligned", "Uniquely Aligned", "Passed", "GC Depth Corr.", "Sex"));
 	ltable2.set_col_desc("|m{1.6cm}|m{3cm}|m{3cm}|m{2.5cm}|");
 	ltable2.add_header(vec!("Variants", "Passed Variants", "Median Cov. of Passed Variants", "Ti/Tv Ratio"));
 	ltable3.set_col_desc("|m{2.3cm}|m{2.2cm}|m{1.6cm}|");
@@ -1004,6 +1192,16 @@ fn create_summary(dir: &Path, summary: Arc<Mutex<HashMap<String, CallSummary>>>,
 			lrow1.push(format!("{} ({:.2} %)", f(map_summ.passed), pct(map_summ.passed, map_summ.aligned)));
 			row.push(format!("{:.2}", map_summ.gc_correlation));
 			lrow1.push(format!("{:.2}", map_summ.gc_correlation));
+			// Inferred sex, flagging discrepancies with the metadata
+			let sex = s.sex.as_ref().and_then(|x| x.inferred).map(|x| format!("{}", x)).unwrap_or_else(|| "?".to_string());
+			if s.sex_mismatch() {
+				let exp = s.expected_sex.unwrap();
+				row.push(format!("<font color=\"red\"><B>{} (expected {})</B></font>", sex, exp));
+				lrow1.push(format!("\\textbf{{{} (expected {})}}", sex, exp));
+			} else {
+				row.push(sex.clone());
+				lrow1.push(sex);
+			}
 			ltable1.add_row(lrow1);
 			row.push(format!("{:.3e}", var_summ.variants));
 			lrow2.push(format!("{:.3e}", var_summ.variants));
@@ -1026,7 +1224,11 @@ fn create_summary(dir: &Path, summary: Arc<Mutex<HashMap<String, CallSummary>>>,
 			link2.push_str("&#187 Variants");
 			let mut link3 = HtmlElement::new("a", Some(format!("class=\"link\" href=\"{}/{}_methylation.html\"", bc, bc).as_str()), true);
 			link3.push_str("&#187 Methylation");
-			row.push(format!("{}<BR>{}<BR>{}<BR>", link1, link2, link3));
+			if s.cnv.is_some() {
+				let mut link4 = HtmlElement::new("a", Some(format!("class=\"link\" href=\"{}/{}_copy_number.html\"", bc, bc).as_str()), true);
+				link4.push_str("&#187 Copy Number");
+				row.push(format!("{}<BR>{}<BR>{}<BR>{}<BR>", link1, link2, link3, link4));
+			} else { row.push(format!("{}<BR>{}<BR>{}<BR>", link1, link2, link3)); }
 			table.add_row(row);
 			ltable3.add_row(lrow3);
 		}
@@ -1075,6 +1277,7 @@ fn make_call_job(bc: &str, bc_dir: &Path, project: &str, job: MakeCallJob) -> Re
 		CallJob::MappingReport => create_mapping_report(bc, bc_dir, project, cj, job.summary, job.latex_doc),
 		CallJob::VariantReport => create_variant_report(bc, bc_dir, project, cj, job.summary, job.latex_doc),
 		CallJob::MethylationReport => create_meth_report(bc, bc_dir, project, cj, job.summary, job.latex_doc),
+		CallJob::CopyNumberReport => create_copy_number_report(bc, bc_dir, project, cj, job.summary, job.latex_doc),
 	}
 }
 
@@ -1139,11 +1342,11 @@ pub fn make_call_report(sig: Arc<AtomicUsize>, outputs: &[PathBuf], project: Opt
 	let report_tex_path = outputs.first().expect("No output files for call report");
 	let output_dir = report_tex_path.parent().expect("No parent directory found for call report");
 	// Set up worker threads	
-	// Maximum parallel jobs that we could do if there were enough cores is 18 * the number of samples (18 images per sample)
-	let n_dsets = svec.len() * 18;
+	// Maximum parallel jobs that we could do if there were enough cores is 19 * the number of samples (18 images + copy number analysis per sample)
+	let n_dsets = svec.len() * 19;
 	let n_workers = if n_cores > n_dsets { n_dsets } else { n_cores };
 	let mut shash = HashMap::new();
-	for cjson in svec.iter() { shash.insert(cjson.barcode.clone(), CallSummary::new()); }
+	for cjson in svec.iter() { shash.insert(cjson.barcode.clone(), CallSummary::new(cjson.sex)); }
 	let summary = Arc::new(Mutex::new(shash));
 	let latex_doc = Arc::new(Mutex::new(LatexBare::new(&report_tex_path)?));
 	let mut job_vec = prepare_jobs(&svec, &project, summary.clone(), latex_doc.clone());
@@ -1,5 +1,5 @@
 use std::path::{Path, PathBuf};
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, mpsc, Mutex};
 use std::{fs, thread, time};
@@ -12,6 +12,9 @@ use crate::scheduler::report::SampleJsonFiles;
 use crate::scheduler::call;
 use crate::common::utils;
 use crate::common::json_map_stats::{MapJson, MapJsonType, Counts, Count, Paired, New};
+use crate::common::json_trim_stats::{TrimJson, TrimReadStats};
+use crate::common::json_qc_stats::{QcJson, EndQc};
+use crate::common::json_genome_stats::GenomeJson;
 use crate::common::html_utils::*;
 use crate::common::latex_utils::*;
 use super::report_utils::*;
@@ -269,6 +272,231 @@ fn make_conversion_latex_tab(json: &MapJson) -> LatexContent {
 	LatexContent::Table(table)
 }
 
+fn make_trim_row(v: &[usize], total: &[usize], ends: usize, s: &str) -> Vec<String> {
+	let mut row = vec!(s.to_owned());
+	let (x, t) = (v.iter().sum(), total.iter().sum());
+	row.push(format!("{}", x));
+	row.push(format!("{:.2} %", pct(x, t)));
+	if v.len() > 1 {
+		for (a, b) in v.iter().zip(total.iter()) {
+			row.push(format!("{}", a));
+			row.push(format!("{:.2} %", pct(*a, *b)));
+		}
+	}
+	row.resize(if ends > 1 { 7 } else { 3 }, String::new());
+	row
+}
+
+fn make_trim_tab<T: Table>(table: &mut T, json: &TrimJson) {
+	let ends = json.reads.len();
+	let mut hdr = vec!("Concept", "Total", "%");
+	if ends > 1 { hdr.extend(&["Read One", "%", "Read Two", "%"]); }
+	table.add_header(hdr);
+	let unit = if json.paired { "Pairs" } else { "Reads" };
+	let mut row = vec!("Adapter Sequences".to_string(), json.adapters.join(", "));
+	row.resize(if ends > 1 { 7 } else { 3 }, String::new());
+	table.add_row(row);
+	table.add_row(make_trim_row(&[json.reads_in], &[json.reads_in], ends, format!("{} Processed", unit).as_str()));
+	table.add_row(make_trim_row(&[json.reads_out], &[json.reads_in], ends, format!("{} Written", unit).as_str()));
+	table.add_row(make_trim_row(&[json.too_short], &[json.reads_in], ends, format!("{} Too Short", unit).as_str()));
+	let get = |f: &dyn Fn(&TrimReadStats) -> usize| -> Vec<usize> { json.reads.iter().map(f).collect() };
+	let reads = vec!(json.reads_in; ends);
+	let bases = get(&|x| x.bases_in);
+	table.add_row(make_trim_row(&bases, &bases, ends, "Bases Processed"));
+	table.add_row(make_trim_row(&get(&|x| x.bases_out), &bases, ends, "Bases Written"));
+	table.add_row(make_trim_row(&get(&|x| x.adapter_reads), &reads, ends, "Reads with Adapter"));
+	table.add_row(make_trim_row(&get(&|x| x.adapter_bases), &bases, ends, "Adapter Bases"));
+	table.add_row(make_trim_row(&get(&|x| x.quality_bases), &bases, ends, "Quality Trimmed Bases"));
+	table.add_row(make_trim_row(&get(&|x| x.poly_g_reads), &reads, ends, "Reads with Poly-G Tail"));
+	table.add_row(make_trim_row(&get(&|x| x.poly_g_bases), &bases, ends, "Poly-G Bases"));
+	table.add_row(make_trim_row(&get(&|x| x.clipped_bases), &bases, ends, "Clipped Bases"));
+}
+
+fn make_trim_table(json: &TrimJson) -> Content {
+	let mut table = HtmlTable::new("green");
+	make_trim_tab(&mut table, json);
+	Content::Table(table)
+}
+
+fn make_trim_latex_tab(json: &TrimJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	make_trim_tab(&mut table, json);
+	LatexContent::Table(table)
+}
+
+fn make_qc_row(v: &[String], s: &str) -> Vec<String> {
+	let mut row = vec!(s.to_owned());
+	row.extend(v.iter().cloned());
+	row
+}
+
+fn make_qc_tab<T: Table>(table: &mut T, json: &QcJson) {
+	let mut hdr = vec!("Concept");
+	if json.ends.len() > 1 { hdr.extend(&["Read One", "Read Two"]) } else { hdr.push("Value") }
+	table.add_header(hdr);
+	let get = |f: &dyn Fn(&EndQc) -> String| -> Vec<String> { json.ends.iter().map(f).collect() };
+	let n = json.ends.len();
+	table.add_row(make_qc_row(&vec!(json.encoding.clone(); n), "Quality Encoding"));
+	table.add_row(make_qc_row(&vec!(format!("{}", json.reads); n), if json.paired { "Pairs" } else { "Reads" }));
+	table.add_row(make_qc_row(&get(&|x| format!("{}", x.bases)), "Bases"));
+	table.add_row(make_qc_row(&get(&|x| if x.min_length == x.max_length { format!("{}", x.max_length) } else { format!("{}-{}", x.min_length, x.max_length) }), "Read Length"));
+	table.add_row(make_qc_row(&get(&|x| format!("{:.2}", x.mean_quality())), "Mean Base Quality"));
+	// Overall base composition.  For bisulfite converted reads C should be strongly depleted in read 1 (and G in read 2)
+	for (ix, b) in ["A", "C", "G", "T", "N"].iter().enumerate() {
+		table.add_row(make_qc_row(&get(&|x| {
+			let (c, t) = x.base_counts.iter().fold((0, 0), |(c, t), v| (c + v[ix], t + v.iter().sum::<usize>()));
+			format!("{:.2} %", pct(c, t))
+		}), format!("% {}", b).as_str()));
+	}
+}
+
+fn make_qc_table(json: &QcJson) -> Content {
+	let mut table = HtmlTable::new("green");
+	make_qc_tab(&mut table, json);
+	Content::Table(table)
+}
+
+fn make_qc_latex_tab(json: &QcJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	make_qc_tab(&mut table, json);
+	LatexContent::Table(table)
+}
+
+// Assignment of reads to the genomes in the reference (primary alignments)
+fn make_genome_tab<T: Table>(table: &mut T, json: &GenomeJson) {
+	table.add_header(vec!("Genome", "Reads", "%"));
+	let total = json.total();
+	for g in json.genomes.iter() {
+		let name = if g.removed { format!("{} (removed)", g.name) } else { g.name.clone() };
+		table.add_row(vec!(name, format!("{}", g.reads), format!("{:.2} %", pct(g.reads, total))));
+	}
+	table.add_row(vec!("Unmapped".to_string(), format!("{}", json.unmapped), format!("{:.2} %", pct(json.unmapped, total))));
+	table.add_row(vec!("Pairs Split Between Genomes".to_string(), format!("{}", json.cross_genome), format!("{:.2} %", pct(json.cross_genome, total))));
+	if json.removed > 0 { table.add_row(vec!("Removed (Disambiguation)".to_string(), format!("{}", json.removed), format!("{:.2} %", pct(json.removed, total)))); }
+}
+
+fn make_genome_table(json: &GenomeJson) -> Content {
+	let mut table = HtmlTable::new("green");
+	make_genome_tab(&mut table, json);
+	Content::Table(table)
+}
+
+fn make_genome_latex_tab(json: &GenomeJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	make_genome_tab(&mut table, json);
+	LatexContent::Table(table)
+}
+
+// Storage statistics for a CRAM file
+struct CramStats {
+	version: String,
+	size: u64,
+	index_size: Option<u64>,
+}
+
+fn read_cram_stats(path: &Path) -> Result<CramStats, String> {
+	let mut file = fs::File::open(path).map_err(|e| format!("Couldn't open {}: {}", path.to_string_lossy(), e))?;
+	// File definition: 'CRAM' followed by the major and minor version numbers
+	let mut buf = [0u8; 6];
+	file.read_exact(&mut buf).map_err(|e| format!("Error reading from {}: {}", path.to_string_lossy(), e))?;
+	if &buf[..4] != b"CRAM" { return Err(format!("File {} is not a CRAM file", path.to_string_lossy())) }
+	let size = file.metadata().map_err(|e| format!("Couldn't get size of {}: {}", path.to_string_lossy(), e))?.len();
+	let mut idx = path.as_os_str().to_owned();
+	idx.push(".crai");
+	let index_size = fs::metadata(&idx).ok().map(|m| m.len());
+	Ok(CramStats{version: format!("{}.{}", buf[4], buf[5]), size, index_size})
+}
+
+fn fmt_size(x: u64) -> String {
+	let units = ["bytes", "KB", "MB", "GB", "TB"];
+	let mut z = x as f64;
+	let mut ix = 0;
+	while z >= 1024.0 && ix < units.len() - 1 {
+		z /= 1024.0;
+		ix += 1;
+	}
+	if ix == 0 { format!("{} bytes", x) } else { format!("{:.2} {}", z, units[ix]) }
+}
+
+fn make_cram_tab<T: Table>(table: &mut T, cram: &CramStats, json: &MapJson) {
+	table.add_header(vec!("Concept", "Value"));
+	let reads = get_total_reads(json);
+	let bases = get_total_bases(json);
+	table.add_row(vec!("CRAM Version".to_string(), cram.version.clone()));
+	table.add_row(vec!("File Size".to_string(), fmt_size(cram.size)));
+	if let Some(x) = cram.index_size { table.add_row(vec!("Index Size".to_string(), fmt_size(x))); }
+	let bpr = if reads > 0 { format!("{:.2}", cram.size as f64 / reads as f64) } else { "NA".to_string() };
+	table.add_row(vec!("Bytes per Read".to_string(), bpr));
+	let bpb = if bases > 0 { format!("{:.3}", 8.0 * cram.size as f64 / bases as f64) } else { "NA".to_string() };
+	table.add_row(vec!("Bits per Base".to_string(), bpb));
+}
+
+fn make_cram_table(cram: &CramStats, json: &MapJson) -> Content {
+	let mut table = HtmlTable::new("green");
+	make_cram_tab(&mut table, cram, json);
+	Content::Table(table)
+}
+
+fn make_cram_latex_tab(cram: &CramStats, json: &MapJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	make_cram_tab(&mut table, cram, json);
+	LatexContent::Table(table)
+}
+
+fn make_qual_dist_tab<T: Table>(table: &mut T, json: &QcJson) {
+	let mut hdr = vec!("Base Quality", "Total Bases", "%");
+	if json.ends.len() > 1 { hdr.extend(&["Read One", "%", "Read Two", "%"]) }
+	table.add_header(hdr);
+	let bins = [(0, 10, "0-9"), (10, 20, "10-19"), (20, 30, "20-29"), (30, 40, "30-39"), (40, usize::MAX, ">= 40")];
+	let totals: Vec<usize> = json.ends.iter().map(|x| x.quality_hist.iter().sum()).collect();
+	for (a, b, desc) in bins.iter() {
+		let v: Vec<usize> = json.ends.iter().map(|x| x.quality_hist.iter().enumerate().filter(|(q, _)| q >= a && q < b).map(|(_, c)| c).sum()).collect();
+		table.add_row(make_trim_row(&v, &totals, json.ends.len(), desc));
+	}
+}
+
+fn make_qual_dist_table(json: &QcJson) -> Content {
+	let mut table = HtmlTable::new("hor-zebra");
+	make_qual_dist_tab(&mut table, json);
+	Content::Table(table)
+}
+
+fn make_qual_dist_latex_tab(json: &QcJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	make_qual_dist_tab(&mut table, json);
+	LatexContent::Table(table)
+}
+
+fn make_overrep_tab<T: Table>(table: &mut T, json: &QcJson, max: usize) {
+	table.add_header(vec!("Sequence", "Read", "Count", "%"));
+	for (end, x) in json.ends.iter().enumerate() {
+		for s in x.overrepresented.iter().take(max) {
+			table.add_row(vec!(s.sequence.clone(), format!("{}", end + 1), format!("{}", s.count), format!("{:.2} %", pct(s.count, x.sampled))));
+		}
+	}
+}
+
+fn make_overrep_table(json: &QcJson) -> Content {
+	let mut table = HtmlTable::new("hor-zebra");
+	make_overrep_tab(&mut table, json, 20);
+	Content::Table(table)
+}
+
+fn make_overrep_latex_tab(json: &QcJson) -> LatexContent {
+	let mut table = LatexTable::new();
+	table.set_col_desc("|m{9.5cm}|m{1cm}|m{1.5cm}|m{1.5cm}|");
+	make_overrep_tab(&mut table, json, 10);
+	LatexContent::Table(table)
+}
+
+fn make_base_comp_table(path: &Path) -> Content {
+	let mut table = HtmlTable::new("green");
+	table.add_header(vec!("Per Position Base Composition"));
+	let fname = path.file_name().expect("Missing filename").to_string_lossy();
+	table.add_row(vec!(format!("<img src=\"images/{}\" alt=\"{}\">", fname, fname)));	
+	Content::Table(table)
+}
+
 fn make_correct_pairs_table(paired: &Paired) -> Content {
 	let mut table = HtmlTable::new("hor-zebra");
 	table.add_header(vec!("Concept", "Read Pairs"));
@@ -381,7 +609,56 @@ fn create_isize_hist(path: &Path, paired: &Paired) -> Result<(), Box<dyn std::er
 	Ok(())
 		
 }
-fn create_sample_body(project: &str, bc: &str, ds: &[&str], mapq_threshold: usize, dir: &Path, json: &MapJson, sample_report: bool) -> Result<HtmlElement, String> {
+// Per position base composition for each read end.  With bisulfite converted reads this
+// should show the depletion of C (read 1) or G (read 2) along the whole read
+fn create_base_comp_plot(path: &Path, json: &QcJson) -> Result<(), Box<dyn std::error::Error>> {
+	let root = BitMapBackend::new(path, (1024, 640)).into_drawing_area();
+	root.fill(&WHITE)?;
+	let colours = [&GREEN, &BLUE, &BLACK, &RED, &MAGENTA];
+	let names = ["A", "C", "G", "T", "N"];
+	let areas = root.split_evenly((1, json.ends.len()));
+	for (end, (area, x)) in areas.iter().zip(json.ends.iter()).enumerate() {
+		let fracs = x.base_fractions();
+		let title = if json.ends.len() > 1 { format!("Base Composition (Read {})", end + 1) } else { "Base Composition".to_string() };
+		let mut chart = ChartBuilder::on(area)
+			.x_label_area_size(35)
+			.y_label_area_size(60)
+			.margin(5)
+			.caption(title, ("sans-serif", 22.0).into_font())
+			.build_ranged(0.0..(fracs.len().max(1) as f64), 0.0..1.0)?;
+
+		chart
+			.configure_mesh()
+			.line_style_1(&WHITE.mix(0.3))
+			.y_desc("Fraction")
+			.x_desc("Position in Read")
+			.y_label_formatter(&|y| format!("{:.1}", y))
+			.x_label_formatter(&|x| format!("{:.0}", x))
+			.axis_desc_style(("sans-serif", 15).into_font())
+			.draw()?;
+
+		for (ix, col) in colours.iter().enumerate() {
+			let col = *col;
+			chart.draw_series(LineSeries::new(fracs.iter().enumerate().map(|(i, f)| ((i + 1) as f64, f[ix])), Into::<ShapeStyle>::into(col).stroke_width(2)))?
+			.label(names[ix])
+			.legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], col));
+		}
+		chart.configure_series_labels().border_style(&BLACK).draw()?;
+	}
+	Ok(())
+}
+
+// Mapping and (if available) trimming and read QC statistics for a sample or dataset
+struct ReportStats<'a> {
+	map: &'a MapJson,
+	trim: Option<&'a TrimJson>,
+	qc: Option<&'a QcJson>,
+	genomes: Option<&'a GenomeJson>,
+	cram: Option<&'a CramStats>,
+}
+
+fn create_sample_body(project: &str, bc: &str, ds: &[&str], mapq_threshold: usize, dir: &Path, stats: &ReportStats, sample_report: bool) -> Result<HtmlElement, String> {
+	let json = stats.map;
 	let name = if sample_report { bc } else { ds[0] };
 	let mut img_dir = dir.to_owned();
 	img_dir.push("images");
@@ -414,6 +691,39 @@ fn create_sample_body(project: &str, bc: &str, ds: &[&str], mapq_threshold: usiz
 	body.push_element(HtmlElement::new("BR><BR><BR", None, false));
 	body.push_element(make_section("Bisulfite Conversion Rate"));
 	body.push(make_conversion_table(json));
+	if let Some(trim) = stats.trim {
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Read Trimming"));
+		body.push(make_trim_table(trim));
+	}
+	if let Some(qc) = stats.qc {
+		let mut bc_png = img_dir.clone();
+		bc_png.push(format!("{}_base_comp.png", name).as_str());
+		create_base_comp_plot(&bc_png, qc).map_err(|e| format!("{}", e))?;
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Read QC"));
+		body.push(make_qc_table(qc));
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push(make_base_comp_table(&bc_png));
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Base Quality Distribution"));
+		body.push(make_qual_dist_table(qc));
+		if qc.ends.iter().any(|x| !x.overrepresented.is_empty()) {
+			body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+			body.push_element(make_section("Overrepresented Sequences"));
+			body.push(make_overrep_table(qc));
+		}
+	}
+	if let Some(genomes) = stats.genomes {
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("Genome Assignment"));
+		body.push(make_genome_table(genomes));
+	}
+	if let Some(cram) = stats.cram {
+		body.push_element(HtmlElement::new("BR><BR><BR", None, false));
+		body.push_element(make_section("CRAM Storage"));
+		body.push(make_cram_table(cram, json));
+	}
 	let mut tp = img_dir;
 	tp.push(format!("{}_isize.png", name).as_str());
 	match json {
@@ -450,7 +760,7 @@ fn create_sample_body(project: &str, bc: &str, ds: &[&str], mapq_threshold: usiz
 	Ok(body)
 }
 
-fn create_sample_html(project: &str, bc: &str, ds: &[&str], mapq_threshold: usize, dir: &Path, json: &MapJson, sample_report: bool) -> Result<(), String> {
+fn create_sample_html(project: &str, bc: &str, ds: &[&str], mapq_threshold: usize, dir: &Path, stats: &ReportStats, sample_report: bool) -> Result<(), String> {
 	let l = ds.len();
 	if l == 0 { return Err("No datasets supplied for map report".to_string() )}
 	else if l > 1 && !sample_report { return Err("Multiple datasets supplied for dataset map report".to_string())}
@@ -463,13 +773,12 @@ fn create_sample_html(project: &str, bc: &str, ds: &[&str], mapq_threshold: usiz
 	style_element.push_str("<!--\n@import url(\"../../css/style.css\");\n-->");
 	head_element.push_element(style_element);
 	html.push_element(head_element);
-	html.push_element(create_sample_body(project, bc, ds, mapq_threshold, dir, json, sample_report)?);
+	html.push_element(create_sample_body(project, bc, ds, mapq_threshold, dir, stats, sample_report)?);
 	Ok(())
 }
 
-fn get_sample_sum(bc: &str, mapq_threshold: usize, mjson: &MapJson) -> SampleSummary {
-	let barcode = bc.to_owned();
-	let reads = match mjson {
+fn get_total_reads(mjson: &MapJson) -> usize {
+	match mjson {
 		MapJson::Paired(x) | MapJson::Unknown(x) => {
 			let rds = x.reads();
 			let total = rds.get_total();
@@ -480,7 +789,22 @@ fn get_sample_sum(bc: &str, mapq_threshold: usize, mjson: &MapJson) -> SampleSum
 			let total = rds.get_total();
 			total[0]
 		},
-	};
+	}
+}
+
+fn get_total_bases(mjson: &MapJson) -> usize {
+	match mjson {
+		MapJson::Paired(x) | MapJson::Unknown(x) => {
+			let total = x.base_counts().overall.get_total();
+			total[0] + total[1]
+		},
+		MapJson::Single(x) => x.base_counts().overall.get_total()[0],
+	}
+}
+
+fn get_sample_sum(bc: &str, mapq_threshold: usize, mjson: &MapJson) -> SampleSummary {
+	let barcode = bc.to_owned();
+	let reads = get_total_reads(mjson);
 	let (unique, fragments) = mjson.get_unique(mapq_threshold);
 	let (ct1, ct2) = mjson.get_conversion_counts();	
 	let conversion = call::calc_conversion(&ct1);
@@ -494,7 +818,23 @@ fn read_map_json(json_path: &Path) -> Result<MapJson, String> {
 	Ok(MapJson::from_reader(reader).map_err(|e| format!("Couldn't parse JSON file {}: {}", json_path.to_string_lossy(), e))?)
 }
 
-fn make_latex_sec(bc: &str, ds: Option<&str>, mapq_threshold: usize, json: &MapJson, sample_report: bool) -> Result<LatexSection, String> {
+fn read_trim_json(json_path: &Path) -> Result<TrimJson, String> {
+	let file = fs::File::open(json_path).map_err(|e| format!("Couldn't open {}: {}", json_path.to_string_lossy(), e))?;
+	TrimJson::from_reader(BufReader::new(file)).map_err(|e| format!("Couldn't parse JSON file {}: {}", json_path.to_string_lossy(), e))
+}
+
+fn read_qc_json(json_path: &Path) -> Result<QcJson, String> {
+	let file = fs::File::open(json_path).map_err(|e| format!("Couldn't open {}: {}", json_path.to_string_lossy(), e))?;
+	QcJson::from_reader(BufReader::new(file)).map_err(|e| format!("Couldn't parse JSON file {}: {}", json_path.to_string_lossy(), e))
+}
+
+fn read_genome_json(json_path: &Path) -> Result<GenomeJson, String> {
+	let file = fs::File::open(json_path).map_err(|e| format!("Couldn't open {}: {}", json_path.to_string_lossy(), e))?;
+	GenomeJson::from_reader(BufReader::new(file)).map_err(|e| format!("Couldn't parse JSON file {}: {}", json_path.to_string_lossy(), e))
+}
+
+fn make_latex_sec(bc: &str, ds: Option<&str>, mapq_threshold: usize, stats: &ReportStats, sample_report: bool) -> Result<LatexSection, String> {
+	let json = stats.map;
 	let mut img_dir = PathBuf::from_str(bc).expect("Couldn't get Path from barcode");
 	img_dir.push("images");
 	let (name, stype, desc) = if sample_report { (bc, "section", "Sample") } else { (ds.expect("No dataset name supplied"), "subsection", "Dataset") };
@@ -511,6 +851,29 @@ fn make_latex_sec(bc: &str, ds: Option<&str>, mapq_threshold: usize, json: &MapJ
 	sec.push(make_bases_latex_tab(json));
 	sec.push_string(format!("\\{}{{Bisulfite Conversion Rate}}", stype1));
 	sec.push(make_conversion_latex_tab(json));
+	if let Some(t) = stats.trim {
+		sec.push_string(format!("\\{}{{Read Trimming}}", stype1));
+		sec.push(make_trim_latex_tab(t));
+	}
+	if let Some(q) = stats.qc {
+		sec.push_string(format!("\\{}{{Read QC}}", stype1));
+		sec.push(make_qc_latex_tab(q));
+		sec.push_string(format!("\\includegraphics[width=12cm]{{{}}}", img_dir.join(format!("{}_base_comp", name).as_str()).display()));
+		sec.push_string(format!("\\{}{{Base Quality Distribution}}", stype1));
+		sec.push(make_qual_dist_latex_tab(q));
+		if q.ends.iter().any(|x| !x.overrepresented.is_empty()) {
+			sec.push_string(format!("\\{}{{Overrepresented Sequences}}", stype1));
+			sec.push(make_overrep_latex_tab(q));
+		}
+	}
+	if let Some(g) = stats.genomes {
+		sec.push_string(format!("\\{}{{Genome Assignment}}", stype1));
+		sec.push(make_genome_latex_tab(g));
+	}
+	if let Some(c) = stats.cram {
+		sec.push_string(format!("\\{}{{CRAM Storage}}", stype1));
+		sec.push(make_cram_latex_tab(c, json));
+	}
 	sec.push_string(format!("\\{}{{Mapping Quality Histogram}}", stype1));
 	sec.push_string(format!("\\includegraphics[width=12cm]{{{}}}", img_dir.join(format!("{}_mapq", name).as_str()).display()));
 	sec.push_string(format!("\\{}{{Read Lengths}}", stype1));
@@ -531,10 +894,47 @@ fn create_sample_report(job: ReportJob) -> Result<(), String> {
 			let mut dataset_secs = if v.datasets.len() > 1 { Some(SectionArray::new()) } else { None };
 			let mut mrg_json: Option<MapJson> = None;
 			let mut dsets: Vec<&str> = Vec::new();
+			let mut trim_json: Option<TrimJson> = None;
+			for (_, path) in v.trim_files.iter() {
+				let json = read_trim_json(path)?;
+				trim_json = match trim_json {
+					Some(j) => Some(j.merge(&json)),
+					None => Some(json),
+				};
+			}
+			let mut qc_json: Option<QcJson> = None;
+			for (_, path) in v.qc_files.iter() {
+				let json = read_qc_json(path)?;
+				qc_json = match qc_json {
+					Some(j) => Some(j.merge(&json)),
+					None => Some(json),
+				};
+			}
+			let mut genome_json: Option<GenomeJson> = None;
+			for (_, path) in v.genome_files.iter() {
+				let json = read_genome_json(path)?;
+				genome_json = match genome_json {
+					Some(j) => Some(j.merge(&json)),
+					None => Some(json),
+				};
+			}
 			for (ds, json_path) in v.datasets.iter() {
 				let json = read_map_json(&json_path)?;
 				if let Some(ref mut sa) = dataset_secs {
-					let ds_sec = make_latex_sec(&job.barcode, Some(ds), v.mapq_threshold, &json, false)?;
+					let ds_trim = match v.trim_files.iter().find(|(d, _)| d == ds) {
+						Some((_, p)) => Some(read_trim_json(p)?),
+						None => None,
+					};
+					let ds_qc = match v.qc_files.iter().find(|(d, _)| d == ds) {
+						Some((_, p)) => Some(read_qc_json(p)?),
+						None => None,
+					};
+					let ds_genomes = match v.genome_files.iter().find(|(d, _)| d == ds) {
+						Some((_, p)) => Some(read_genome_json(p)?),
+						None => None,
+					};
+					let ds_stats = ReportStats{map: &json, trim: ds_trim.as_ref(), qc: ds_qc.as_ref(), genomes: ds_genomes.as_ref(), cram: None};
+					let ds_sec = make_latex_sec(&job.barcode, Some(ds), v.mapq_threshold, &ds_stats, false)?;
 					sa.push(ds_sec); 
 				}
 				mrg_json = match mrg_json {
@@ -544,26 +944,44 @@ fn create_sample_report(job: ReportJob) -> Result<(), String> {
 				dsets.push(ds);
 			}
 			dsets.sort_unstable();
+			let cram = match &v.cram_file {
+				Some(p) => Some(read_cram_stats(p)?),
+				None => None,
+			};
 			match mrg_json {
 				Some(mjson) => {
 					let sample_sum = get_sample_sum(&job.barcode, v.mapq_threshold, &mjson);
 					if let Ok(mut sum_vec) = v.summary.lock() {
 						sum_vec.push(sample_sum);
 					} else { return Err("Couldn't obtain lock on sample summary".to_string()); }
-					let mut latex_sec = make_latex_sec(&job.barcode, None, v.mapq_threshold, &mjson, true)?;
+					let stats = ReportStats{map: &mjson, trim: trim_json.as_ref(), qc: qc_json.as_ref(), genomes: genome_json.as_ref(), cram: cram.as_ref()};
+					let mut latex_sec = make_latex_sec(&job.barcode, None, v.mapq_threshold, &stats, true)?;
 					if let Some(sa) = dataset_secs { latex_sec.push(LatexContent::SecArray(sa)); }
 					if let Ok(mut ldoc) = v.latex_doc.lock() { 
 						ldoc.push_section(latex_sec)?;
 					} else { return Err("Couldn't obtain lock on latex doc".to_string()); }
-					create_sample_html(&job.project, &job.barcode, &dsets, v.mapq_threshold, &job.bc_dir, &mjson, true)
+					create_sample_html(&job.project, &job.barcode, &dsets, v.mapq_threshold, &job.bc_dir, &stats, true)
 				},
 				None => Err(format!("No merged JSON structure for {}", &job.barcode))
 			}
 		},
 		RepJob::Dataset(v) => {
 			let json = read_map_json(&v.json_path)?;
+			let trim = match &v.trim_path {
+				Some(p) => Some(read_trim_json(p)?),
+				None => None,
+			};
+			let qc = match &v.qc_path {
+				Some(p) => Some(read_qc_json(p)?),
+				None => None,
+			};
+			let genomes = match &v.genome_path {
+				Some(p) => Some(read_genome_json(p)?),
+				None => None,
+			};
 			info!("Create dataset mapping report for {}/{}/{}", job.project, job.barcode, v.dataset);
-			create_sample_html(&job.project, &job.barcode, &[&v.dataset], v.mapq_threshold, &job.bc_dir, &json, false) 
+			let stats = ReportStats{map: &json, trim: trim.as_ref(), qc: qc.as_ref(), genomes: genomes.as_ref(), cram: None};
+			create_sample_html(&job.project, &job.barcode, &[&v.dataset], v.mapq_threshold, &job.bc_dir, &stats, false) 
 		},
 		_ => Err("Invalid command".to_string())
 	}
@@ -650,10 +1068,17 @@ fn prepare_jobs(svec: &[SampleJsonFiles], project: &str, mapq_threshold: usize,
 		// First push sample report job
 		let mut sjob = SampleJob::new(summary.clone(), latex_doc.clone(), mapq_threshold);
 		let l = hr.json_files.len();
+		for (ds, path) in hr.trim_files.iter() { sjob.add_trim_file(ds, path); }
+		for (ds, path) in hr.qc_files.iter() { sjob.add_qc_file(ds, path); }
+		for (ds, path) in hr.genome_files.iter() { sjob.add_genome_file(ds, path); }
+		sjob.cram_file = hr.cram_file.clone();
 		for(ds, path) in hr.json_files.iter() {
 			if l > 1 {
 				sjob.depend.push(v.len());
-				let djob = DatasetJob::new(ds, path, mapq_threshold);
+				let trim_path = hr.trim_files.iter().find(|(d, _)| d == ds).map(|(_, p)| p.as_path());
+				let qc_path = hr.qc_files.iter().find(|(d, _)| d == ds).map(|(_, p)| p.as_path());
+				let genome_path = hr.genome_files.iter().find(|(d, _)| d == ds).map(|(_, p)| p.as_path());
+				let djob = DatasetJob::new(ds, path, trim_path, qc_path, genome_path, mapq_threshold);
 				v.push(ReportJob::new(&hr.barcode, project, &hr.bc_dir, RepJob::Dataset(djob)));
 			}
 			sjob.add_dataset(ds, path);
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 
 use crate::common::json_call_stats::CallJson;
 use crate::common::latex_utils::LatexBare;
+use crate::common::defs::Sex;
 
 #[derive(PartialEq, Eq, Clone)]
 pub enum JobStatus {
@@ -17,18 +18,26 @@ pub enum JobStatus {
 pub struct DatasetJob {
 	pub dataset: String,
 	pub json_path: PathBuf,
+	pub trim_path: Option<PathBuf>,
+	pub qc_path: Option<PathBuf>,
+	pub genome_path: Option<PathBuf>,
 	pub mapq_threshold: usize,
 }
 
 impl DatasetJob {
-	pub fn new(dataset: &str, json_path: &Path, mapq_threshold: usize) -> Self {
-		DatasetJob{dataset: dataset.to_owned(), json_path: json_path.to_owned(), mapq_threshold }
+	pub fn new(dataset: &str, json_path: &Path, trim_path: Option<&Path>, qc_path: Option<&Path>, genome_path: Option<&Path>, mapq_threshold: usize) -> Self {
+		DatasetJob{dataset: dataset.to_owned(), json_path: json_path.to_owned(), trim_path: trim_path.map(|p| p.to_owned()),
+			qc_path: qc_path.map(|p| p.to_owned()), genome_path: genome_path.map(|p| p.to_owned()), mapq_threshold }
 	}
 }
 
 #[derive(Clone)]
 pub struct SampleJob {
 	pub datasets: Vec<(String, PathBuf)>,
+	pub trim_files: Vec<(String, PathBuf)>,
+	pub qc_files: Vec<(String, PathBuf)>,
+	pub genome_files: Vec<(String, PathBuf)>,
+	pub cram_file: Option<PathBuf>,
 	pub depend: Vec<usize>,
 	pub summary: Arc<Mutex<Vec<SampleSummary>>>,
 	pub latex_doc: Arc<Mutex<LatexBare>>,
@@ -37,12 +46,24 @@ pub struct SampleJob {
 
 impl SampleJob {
 	pub fn new(summary: Arc<Mutex<Vec<SampleSummary>>>, latex_doc: Arc<Mutex<LatexBare>>, mapq_threshold:usize) -> Self {
-		SampleJob{datasets: Vec::new(), depend: Vec::new(), summary, mapq_threshold, latex_doc}
+		SampleJob{datasets: Vec::new(), trim_files: Vec::new(), qc_files: Vec::new(), genome_files: Vec::new(), cram_file: None, depend: Vec::new(), summary, mapq_threshold, latex_doc}
 	}
 	pub fn add_dataset(&mut self, dataset: &str, path: &Path) -> &mut Self {
 		self.datasets.push((dataset.to_owned(), path.to_owned()));
 		self
 	}
+	pub fn add_trim_file(&mut self, dataset: &str, path: &Path) -> &mut Self {
+		self.trim_files.push((dataset.to_owned(), path.to_owned()));
+		self
+	}
+	pub fn add_qc_file(&mut self, dataset: &str, path: &Path) -> &mut Self {
+		self.qc_files.push((dataset.to_owned(), path.to_owned()));
+		self
+	}
+	pub fn add_genome_file(&mut self, dataset: &str, path: &Path) -> &mut Self {
+		self.genome_files.push((dataset.to_owned(), path.to_owned()));
+		self
+	}
 }
 
 #[derive(Clone)]
@@ -74,11 +95,12 @@ pub enum CallJob {
 	MappingReport,
 	MethylationReport,
 	VariantReport,
+	CopyNumberReport,
 }
 
 impl CallJob {
     pub fn iter() -> impl Iterator<Item = CallJob> {
-        static GRAPHS: [CallJob; 21] = [
+        static GRAPHS: [CallJob; 22] = [
 			CallJob::CoverageAll,
 			CallJob::CoverageNonRefCpg,
 			CallJob::CoverageNonRefCpgInf,
@@ -100,6 +122,7 @@ impl CallJob {
 			CallJob::MappingReport,
 			CallJob::MethylationReport,
 			CallJob::VariantReport,
+			CallJob::CopyNumberReport,
 		];
         GRAPHS.iter().copied()
     }
@@ -189,15 +212,37 @@ impl MethSummary {
 	pub fn new() -> Self {MethSummary{med_cpg_meth: 0.0, med_cpg_cov: 0, passed_cpgs: 0}}
 }
 
+#[derive(Debug)]
+pub struct CnvSummary {
+	pub altered_segments: usize,
+	pub aneuploid_contigs: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct SexSummary {
+	pub inferred: Option<Sex>,
+}
+
 #[derive(Debug)]
 pub struct CallSummary {
 	pub map: Option<MapSummary>,
 	pub var: Option<VarSummary>,
 	pub meth: Option<MethSummary>,
+	pub cnv: Option<CnvSummary>,
+	pub sex: Option<SexSummary>,
+	// Sex from the sample metadata
+	pub expected_sex: Option<Sex>,
 }
 
 impl CallSummary {
-	pub fn new() -> Self { CallSummary{map: None, var: None, meth: None }}
+	pub fn new(expected_sex: Option<Sex>) -> Self { CallSummary{map: None, var: None, meth: None, cnv: None, sex: None, expected_sex }}
+	// Returns true if the inferred sex is different from that given in the metadata
+	pub fn sex_mismatch(&self) -> bool { 
+		match (self.expected_sex, self.sex.as_ref().and_then(|s| s.inferred)) {
+			(Some(a), Some(b)) => a != b,
+			_ => false,
+		}
+	}
 }
 
 pub fn pct(a: usize, b: usize) -> f64 {
@@ -25,7 +25,7 @@ pub fn run_command(m: &ArgMatches, gem_bs: &mut GemBS) -> Result<(), String> {
 	let flock = utils::wait_for_lock(gem_bs.get_signal_clone(), &task_path)?; 
 	gem_bs.setup_assets_and_tasks(&flock)?;
 	let assets = collect_terminal_assets(gem_bs);
-	let com_set = [Command::Index, Command::Map, Command::MergeBams, Command::MergeCallJsons, Command::Call, Command::MergeBcfs, Command::Extract,
+	let com_set = [Command::Index, Command::Trim, Command::Map, Command::MergeBams, Command::MergeCallJsons, Command::Call, Command::MergeBcfs, Command::Extract,
 		Command::MD5SumMap, Command::MD5SumCall, Command::IndexBcf, Command::MapReport, Command::CallReport, Command::Report];
 	let task_list = gem_bs.get_required_tasks_from_asset_list(&assets, &com_set);
 	if gem_bs.execute_flag() { scheduler::schedule_jobs(gem_bs, &options, &task_list, &assets, &com_set, flock) }		
@@ -1,9 +1,13 @@
 pub mod defs;
 pub mod utils;
+pub mod provenance;
 pub mod assets;
 pub mod tasks;
 pub mod dry_run;
 pub mod json_call_stats;
 pub mod json_map_stats;
+pub mod json_trim_stats;
+pub mod json_qc_stats;
+pub mod json_genome_stats;
 pub mod html_utils;
 pub mod latex_utils;
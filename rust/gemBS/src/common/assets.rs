@@ -4,6 +4,8 @@ use std::rc::Rc;
 use std::fmt;
 use std::time::SystemTime;
 
+use crate::common::provenance::ProvenanceDb;
+
 // Supplied - user supplied files (reference or fastq files etc.) that gemBS can not generate
 // Derived - files that are (or can be) generated by gemBS
 // Temp - files that may be deleted after they have been used (i.e., pool BCF files)
@@ -42,6 +44,9 @@ pub struct Asset {
 	status: AssetStatus,
 	mod_time: Option<SystemTime>,
 	mod_time_ances: Option<SystemTime>,
+	prov_current: bool, // Provenance record shows asset is up to date
+	params_unknown: bool, // No record of the parameters used to create the asset
+	stale_reason: Option<String>,
 }
 
 fn get_status_time(path: &Path, asset_type: AssetType) -> (AssetStatus, Option<SystemTime>) {
@@ -63,7 +68,7 @@ impl Asset {
 	fn new(id_str: &str, path: &Path, idx: usize, asset_type: AssetType) -> Self {
 		let (status, mod_time) = get_status_time(path, asset_type);
 		let id = Rc::new(id_str.to_owned());		
-		Asset{id, path: path.to_owned(), idx, creator: None, parents: Vec::new(), asset_type, status, mod_time, mod_time_ances: mod_time}
+		Asset{id, path: path.to_owned(), idx, creator: None, parents: Vec::new(), asset_type, status, mod_time, mod_time_ances: mod_time, prov_current: false, params_unknown: false, stale_reason: None}
 	}
 	pub fn recheck_status(&mut self) {
 		let (status, mod_time) = get_status_time(&self.path, self.asset_type);
@@ -84,6 +89,15 @@ impl Asset {
 	pub fn mod_time_ances(&self) -> Option<SystemTime> { self.mod_time_ances }
 	pub fn parents(&self) -> &[usize] { &self.parents }
 	pub fn asset_type(&self) -> AssetType { self.asset_type }
+	pub fn prov_current(&self) -> bool { self.prov_current }
+	pub fn stale_reason(&self) -> Option<&str> { self.stale_reason.as_deref() }
+	pub fn params_unknown(&self) -> bool { self.params_unknown }
+	pub fn set_params_unknown(&mut self, x: bool) { self.params_unknown = x; }
+	pub fn set_outdated(&mut self, reason: String) {
+		self.status = AssetStatus::Outdated;
+		self.stale_reason = Some(reason);
+		self.prov_current = false;
+	}
 }
 
 pub fn make_ext_asset(id: &str, par: &Path, ext: &str) -> (String, PathBuf) {
@@ -154,6 +168,21 @@ impl AssetList {
 		
 	// Calculate most recent modification time of the ancestors for an asset
 	// If the asset is supplied (so has no ancestors) this is just the modification time of the file
+	fn topo_visit(&self, idx: usize, visited: &mut Vec<bool>, order: &mut Vec<usize>) {
+		if !visited[idx] {
+			visited[idx] = true;
+			for j in &self.assets[idx].parents { self.topo_visit(*j, visited, order); }
+			order.push(idx);
+		}
+	}
+	// Asset indices ordered so that every asset comes after all of its parents
+	pub fn topo_order(&self) -> Vec<usize> {
+		let len = self.assets.len();
+		let mut visited = vec!(false; len);
+		let mut order = Vec::with_capacity(len);
+		for ix in 0..len { self.topo_visit(ix, &mut visited, &mut order); }
+		order
+	}
 	fn calc_mta(&self, idx: usize, visited: &mut Vec<bool>, mtime: &mut Vec<Option<SystemTime>>) {
 		if !visited[idx] {
 			let asset = &self.assets[idx];
@@ -214,12 +243,68 @@ impl AssetList {
 			asset.mod_time_ances = mtime[ix];
 			if let AssetStatus::Present = asset.status {
 				if let (Some(m), Some(n)) = (asset.mod_time, asset.mod_time_ances) {
-					if n > m { asset.status = AssetStatus::Outdated; }
+					if n > m && !asset.prov_current { 
+						asset.status = AssetStatus::Outdated; 
+						asset.stale_reason = Some("an ancestor has a more recent modification time".to_string());
+					}
 				}
 			} 
 		}
 	}
 	
+	// Check assets against the provenance database.  Assets with a provenance record are marked as
+	// Outdated if their contents or the contents of any of their inputs have changed since they were
+	// created, and are otherwise exempt from the modification time checks
+	pub fn check_provenance(&mut self, db: &mut ProvenanceDb) {
+		let len = self.assets.len();
+		let mut stale = vec!(false; len);
+		let mut fprint = vec!(None; len);
+		// Parents must be checked before their children so that staleness is propagated
+		for ix in self.topo_order() {
+			let asset = &mut self.assets[ix];
+			asset.prov_current = false;
+			if asset.asset_type == AssetType::Log { continue; }
+			if asset.status == AssetStatus::Outdated {
+				asset.status = AssetStatus::Present;
+				asset.stale_reason = None;
+			}
+			if asset.status != AssetStatus::Present { continue; }
+			fprint[ix] = db.fingerprint(&asset.id, &asset.path);
+			if asset.asset_type == AssetType::Supplied { continue; }
+			let rec = match db.record(&asset.id) { 
+				Some(r) => r,
+				None => continue,
+			};
+			let asset = &self.assets[ix];
+			let mut reason = match &fprint[ix] {
+				Some(fp) if rec.fingerprint().matches(fp) => None,
+				_ => Some(format!("{} has been modified since it was created by task {}", asset.id, rec.task())),
+			};
+			if reason.is_none() {
+				for j in asset.parents.iter() {
+					let par = &self.assets[*j];
+					if par.asset_type == AssetType::Log || par.status == AssetStatus::Deleted { continue; }
+					reason = if stale[*j] { Some(format!("input {} is outdated", par.id)) }
+					else {
+						match (rec.input(&par.id), &fprint[*j]) {
+							(Some(a), Some(b)) => if a.matches(b) { None } else { Some(format!("input {} has changed", par.id)) },
+							(None, _) => Some(format!("input {} was not used when {} was created", par.id, asset.id)),
+							(Some(_), None) => None,
+						}
+					};
+					if reason.is_some() { break; }
+				}
+			}
+			let asset = &mut self.assets[ix];
+			if let Some(r) = reason {
+				trace!("check_provenance: asset {} is outdated: {}", asset.id, r);
+				asset.status = AssetStatus::Outdated;
+				asset.stale_reason = Some(r);
+				stale[ix] = true;
+			} else { asset.prov_current = true; }
+		}
+	}
+	
 	pub fn check_delete_status(&mut self) {
 		let len = self.assets.len();
 		let mut missing_desc = vec!(false; len);
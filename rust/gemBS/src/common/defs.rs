@@ -9,7 +9,7 @@ use super::latex_utils::PageSize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Section {
-	Default, Index, DbSnp, Mapping, Calling, Extract, Report, MD5Sum,
+	Default, Index, DbSnp, Mapping, Calling, Extract, Report, MD5Sum, Trimming,
 }
 
 impl FromStr for Section {
@@ -20,6 +20,7 @@ impl FromStr for Section {
             "default" => Ok(Section::Default),
             "index" => Ok(Section::Index),
             "dbsnp" | "dbsnp_index" | "dbsnpindex" => Ok(Section::DbSnp),
+            "trimming" => Ok(Section::Trimming),
             "mapping" => Ok(Section::Mapping),
             "calling" => Ok(Section::Calling),
             "extract" => Ok(Section::Extract),
@@ -32,7 +33,7 @@ impl FromStr for Section {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Metadata { SampleBarcode, SampleName, LibraryBarcode, Dataset, AltDataset, FileType,
-	FilePath, FilePath1, FilePath2, ReadEnd, Description, Centre, Platform,	Bisulfite,
+	FilePath, FilePath1, FilePath2, ReadEnd, Description, Centre, Platform,	Bisulfite, Sex, Group,
 }
 
 impl FromStr for Metadata {
@@ -55,6 +56,8 @@ impl FromStr for Metadata {
 			"centre" | "center" => Ok(Metadata::Centre),
 			"platform" => Ok(Metadata::Platform),
 			"bisulfite" | "bisulphite" | "bis" => Ok(Metadata::Bisulfite),
+			"sex" | "gender" => Ok(Metadata::Sex),
+			"group" | "samplegroup" => Ok(Metadata::Group),
             _ => Err("no match"),
         }
     }
@@ -89,6 +92,30 @@ impl fmt::Display for FileType {
 	}
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Sex { Male, Female }
+
+impl FromStr for Sex {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "m" | "male" | "xy" => Ok(Sex::Male),
+            "f" | "female" | "xx" => Ok(Sex::Female),
+            _ => Err("Sex: no match"),
+        }
+    }
+}
+
+impl fmt::Display for Sex {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Sex::Male => write!(f, "male"),
+			Sex::Female => write!(f, "female"),
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MemSize {
 	mem: usize,	
@@ -277,6 +304,27 @@ impl DataValue {
 	} 	
 }
 
+impl fmt::Display for DataValue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let join = |v: Vec<String>| v.join(",");
+		match self {
+			DataValue::String(x) => write!(f, "{}", x),
+			DataValue::StringVec(v) => write!(f, "{}", v.join(",")),
+			DataValue::ReadEnd(x) => write!(f, "{:?}", x),
+			DataValue::FileType(x) => write!(f, "{}", x),
+			DataValue::Bool(x) => write!(f, "{}", x),
+			DataValue::Int(x) => write!(f, "{}", x),
+			DataValue::IntVec(v) => write!(f, "{}", join(v.iter().map(|x| format!("{}", x)).collect())),
+			DataValue::Float(x) => write!(f, "{}", x),
+			DataValue::FloatVec(v) => write!(f, "{}", join(v.iter().map(|x| format!("{}", x)).collect())),
+			DataValue::JobLen(x) => write!(f, "{}", x),
+			DataValue::PageSize(x) => write!(f, "{:?}", x),
+			DataValue::MemSize(x) => write!(f, "{}", x),
+			DataValue::DbSnpFileType(x) => write!(f, "{}", x),
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum VarType {
 	String, StringVec, Bool, Int, IntVec, Float, FloatVec, ReadEnd, FileType, JobLen, PageSize, MemSize, DbSnpFileType,
@@ -284,7 +332,7 @@ pub enum VarType {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Command {
-	Index, Map, MergeBams, Call, MergeBcfs, Extract, MapReport, CallReport, Report, MD5SumMap, MD5SumCall,	IndexBcf, MergeCallJsons
+	Index, Trim, Map, MergeBams, Call, MergeBcfs, Extract, MapReport, CallReport, Report, MD5SumMap, MD5SumCall,	IndexBcf, MergeCallJsons
 }
 
 impl fmt::Display for Command {
@@ -292,6 +340,7 @@ impl fmt::Display for Command {
 		if f.alternate() {			
 			match *self {
 				Command::Index => write!(f, "index"),
+				Command::Trim => write!(f, "trim-map"),
 				Command::Map => write!(f, "map"),
 				Command::MergeBams => write!(f, "merge-bams"),
 				Command::Call => write!(f, "call"),
@@ -308,7 +357,7 @@ impl fmt::Display for Command {
 		} else {
 			match *self {
 				Command::Index => write!(f, "index"),
-				Command::Map => write!(f, "map"),
+				Command::Trim | Command::Map => write!(f, "map"),
 				Command::MergeBams => write!(f, "map --merge"),
 				Command::Call => write!(f, "call"),
 				Command::MergeBcfs => write!(f, "call --merge"),
@@ -325,6 +374,21 @@ impl fmt::Display for Command {
 	}
 }
 
+impl Command {
+	// Configuration section holding the parameters used by a command
+	pub fn section(&self) -> Section {
+		match *self {
+			Command::Index => Section::Index,
+			Command::Trim => Section::Trimming,
+			Command::Map | Command::MergeBams => Section::Mapping,
+			Command::Call | Command::MergeBcfs | Command::IndexBcf => Section::Calling,
+			Command::Extract => Section::Extract,
+			Command::MapReport | Command::CallReport | Command::Report | Command::MergeCallJsons => Section::Report,
+			Command::MD5SumMap | Command::MD5SumCall => Section::MD5Sum,
+		}
+	}
+}
+
 pub const SIGTERM: usize = signal_hook::SIGTERM as usize;
 pub const SIGINT: usize = signal_hook::SIGINT as usize;
 pub const SIGQUIT: usize = signal_hook::SIGQUIT as usize;		
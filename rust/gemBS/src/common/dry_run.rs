@@ -35,6 +35,8 @@ fn handle_dry_run(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, ta
 		let task = &gem_bs.get_tasks()[*ix];
 		if task.command() != Command::MergeCallJsons {
 			let arg_string = get_arg_string(task, options);
+			// Report why the task needs to be run (as comments so the output can still be used as a script)
+			for reason in gem_bs.stale_reasons(task) { println!("# {}", reason); }
 			println!("gemBS {} {}", task.command(), arg_string);
 		}
 	}	
@@ -3,6 +3,10 @@ use std::clone::Clone;
 use std::ops::{AddAssign, Add, Sub};
 use std::io::{Read, Write};
 use serde::{Deserialize, Serialize};
+use utils::sex_stats::SexCoverage;
+pub use utils::sex_stats::SexStats;
+
+use super::defs::Sex;
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -393,6 +397,38 @@ impl AddAssign for BasicStats {
 }
 
 
+// Callable (non N) reference bases, summed depth and covered sites, and passed heterozygous and homozygous alternate SNPs for a contig
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContigCoverage {
+	pub callable: usize,
+	pub depth: usize,
+	pub covered: usize,
+	#[serde(rename = "HetSNPs")]
+	pub het_snps: usize,
+	#[serde(rename = "HomAltSNPs")]
+	pub hom_alt_snps: usize,
+}
+
+impl ContigCoverage {
+	pub fn sex_coverage(&self) -> SexCoverage {
+		SexCoverage{callable: self.callable, depth: self.depth, het_snps: self.het_snps, hom_alt_snps: self.hom_alt_snps}
+	}
+}
+
+impl AddAssign for ContigCoverage {
+	// The callable bases come from the reference so are not summed
+	fn add_assign(&mut self, other: Self) {
+		*self = Self {
+			callable: self.callable.max(other.callable),
+			depth: self.depth + other.depth,
+			covered: self.covered + other.covered,
+			het_snps: self.het_snps + other.het_snps,
+			hom_alt_snps: self.hom_alt_snps + other.hom_alt_snps,
+		};
+	}
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 struct CSType { 
 	#[serde(flatten)]
@@ -403,11 +439,14 @@ struct CSType {
 	#[serde(rename = "dbSNPVariantSites")]
     #[serde(skip_serializing_if = "Option::is_none")]
 	dbsnp_variants: Option<Counts>,
+	#[serde(rename = "Coverage")]
+	#[serde(default)]
+	coverage: ContigCoverage,
 }
 
 impl CSType {
 	fn new() -> Self {
-		Self{basic_stats: BasicStats::new(), dbsnp_sites: None, dbsnp_variants: None }
+		Self{basic_stats: BasicStats::new(), dbsnp_sites: None, dbsnp_variants: None, coverage: ContigCoverage::default() }
 	}
 }
 impl AddAssign for CSType {
@@ -421,6 +460,7 @@ impl AddAssign for CSType {
 		self.basic_stats += other.basic_stats;		
 		self.dbsnp_sites = add_option_counts(self.dbsnp_sites, other.dbsnp_sites);
 		self.dbsnp_variants = add_option_counts(self.dbsnp_variants, other.dbsnp_variants);		
+		self.coverage += other.coverage;
 	}
 }
 
@@ -470,6 +510,111 @@ impl TSType {
 	}	
 }
 
+// Binned read depth for copy number estimation (see bs_call stats/cnv.rs)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CnvBins {
+	pub bin_size: usize,
+	#[serde(rename = "GC")]
+	pub gc: Vec<f32>,
+	pub callable: Vec<u32>,
+	pub depth: Vec<u64>,
+	pub covered: Vec<u32>,
+	#[serde(rename = "LowMapQ")]
+	pub low_mapq: Vec<u32>,
+	pub het: Vec<u32>,
+	pub hom_alt: Vec<u32>,
+}
+
+impl CnvBins {
+	// The reference fields (bin_size, gc, callable) should be the same for the same contig, so we only sum the counts
+	fn merge(&mut self, other: &Self) {
+		add_assign_vec(&mut self.depth, &other.depth, 0);
+		add_assign_vec(&mut self.covered, &other.covered, 0);
+		add_assign_vec(&mut self.low_mapq, &other.low_mapq, 0);
+		add_assign_vec(&mut self.het, &other.het, 0);
+		add_assign_vec(&mut self.hom_alt, &other.hom_alt, 0);
+	}
+}
+
+// Thresholds for sex inference.  Males should have an X coverage ratio of around 0.5 and a Y ratio of around 0.5 (a little
+// less due to the low mappability of much of the Y), while females have an X ratio of around 1 and a Y ratio close to 0.
+// If there are enough SNPs on X then the heterozygosity on X relative to the autosomes is also checked (males should have
+// very few heterozygous calls on X outside of the pseudoautosomal regions)
+const SEX_Y_MALE: f64 = 0.2;
+const SEX_Y_FEMALE: f64 = 0.1;
+const SEX_X_MALE: f64 = 0.7;
+const SEX_X_FEMALE: f64 = 0.8;
+const SEX_MIN_X_SNPS: usize = 100;
+const SEX_HET_RATIO: f64 = 0.3;
+
+// Returns None if sex can not be determined
+pub fn infer_sex(st: &SexStats) -> Option<Sex> {
+	let (x, y) = (st.x_ratio?, st.y_ratio.unwrap_or(0.0));
+	let het = if st.x_snps >= SEX_MIN_X_SNPS { 
+		st.x_het_rate.zip(st.autosome_het_rate).filter(|(_, a)| *a > 0.0).map(|(h, a)| h / a) 
+	} else { None };
+	if y >= SEX_Y_MALE && x <= SEX_X_MALE && het.map(|h| h < SEX_HET_RATIO).unwrap_or(true) { Some(Sex::Male) }
+	else if y <= SEX_Y_FEMALE && x >= SEX_X_FEMALE && het.map(|h| h >= SEX_HET_RATIO).unwrap_or(true) { Some(Sex::Female) }
+	else { None }
+}
+
+// Passed variants on the mitochondrial contig with the summed estimated fraction of the alternate allele(s)
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MitoVariant {
+	pub pos: usize,
+	#[serde(rename = "Ref")]
+	pub ref_allele: String,
+	pub alt: String,
+	pub alt_fraction: f32,
+	pub depth: usize,
+	pub qual: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MitoStats {
+	pub contig: String,
+	pub min_heteroplasmy: f64,
+	pub variants: Vec<MitoVariant>,
+}
+
+impl MitoStats {
+	// Variants where the alternate allele fraction is between min_heteroplasmy and 1 - min_heteroplasmy
+	pub fn is_heteroplasmic(&self, v: &MitoVariant) -> bool { 
+		let f = v.alt_fraction as f64;
+		f >= self.min_heteroplasmy && f <= 1.0 - self.min_heteroplasmy 
+	}
+}
+
+// Variant recalibration: minimum VQSLOD and counts of variants at or above each truth sensitivity tranche
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RecalTranche {
+	pub truth_sensitivity: f64,
+	#[serde(rename = "MinVQSLOD")]
+	pub min_vqslod: f64,
+	pub known: usize,
+	pub novel: usize,
+	pub novel_ti: usize,
+	pub novel_tv: usize,
+}
+
+impl RecalTranche {
+	pub fn novel_ti_tv(&self) -> Option<f64> { if self.novel_tv > 0 { Some((self.novel_ti as f64) / (self.novel_tv as f64)) } else { None } }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RecalStats {
+	pub pass_sensitivity: f64,
+	pub variants: usize,
+	pub known_training_sites: usize,
+	pub bad_training_sites: usize,
+	pub tranches: Vec<RecalTranche>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CallJson {
@@ -478,6 +623,18 @@ pub struct CallJson {
 	filter_stats: FSType,
 	contig_stats: HashMap<String, CSType>,
 	total_stats: TSType,
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	cnv: Option<HashMap<String, CnvBins>>,
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	sex_stats: Option<SexStats>,
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	mito: Option<MitoStats>,
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	recalibration: Option<RecalStats>,
 }
 
 impl CallJson {
@@ -498,6 +655,23 @@ impl CallJson {
 		
 		// Merge contig stats
 		for (ctg, ct) in other.contig_stats.iter() { *(self.contig_stats.entry(ctg.to_owned()).or_insert_with(CSType::new)) += *ct; }
+		self.sex_stats = SexStats::from_contig_coverage(self.contig_stats.iter().map(|(k, cs)| (k.as_str(), cs.coverage.sex_coverage())));
+		
+		// Merge copy number bins
+		if let Some(ocnv) = other.cnv.as_ref() {
+			let cnv = self.cnv.get_or_insert_with(HashMap::new);
+			for (ctg, bins) in ocnv.iter() {
+				if let Some(b) = cnv.get_mut(ctg) { b.merge(bins) } else { cnv.insert(ctg.to_owned(), bins.clone()); }
+			}
+		}
+		
+		// The mitochondrial contig is only processed in one contig pool, but we allow for it being split
+		if let Some(omito) = other.mito.as_ref() {
+			if let Some(mito) = self.mito.as_mut() { 
+				mito.variants.extend_from_slice(&omito.variants);
+				mito.variants.sort_by_key(|v| v.pos);
+			} else { self.mito = Some(omito.clone()) }
+		}
 	}
 	pub fn coverage(&self) -> &Coverage { &self.total_stats.coverage }
 	pub fn quality(&self) -> &Quality { &self.total_stats.quality }
@@ -507,4 +681,19 @@ impl CallJson {
 	pub fn basic_stats(&self) -> &BasicStats { &self.total_stats.basic_stats }
 	pub fn vcf_filter_stats(&self) -> &HashMap<String, QCCounts> { &self.total_stats.vcf_filter_stats }
 	pub fn mutations(&self) -> &HashMap<String, MutCounts> { &self.total_stats.mutations }
+	pub fn cnv(&self) -> Option<&HashMap<String, CnvBins>> { self.cnv.as_ref() }
+	pub fn sex_stats(&self) -> Option<&SexStats> { self.sex_stats.as_ref() }
+	pub fn mito(&self) -> Option<&MitoStats> { self.mito.as_ref() }
+	pub fn recalibration(&self) -> Option<&RecalStats> { self.recalibration.as_ref() }
+	// With multiple contig pools recalibration is performed on the merged BCF
+	pub fn set_recalibration(&mut self, recal: RecalStats) { self.recalibration = Some(recal) }
+	// Mean depth over callable bases of a contig
+	pub fn contig_depth(&self, ctg: &str) -> Option<f64> {
+		self.contig_stats.get(ctg).map(|c| c.coverage).filter(|c| c.callable > 0).map(|c| (c.depth as f64) / (c.callable as f64))
+	}
+	// mtDNA copies per cell estimated as twice the ratio of mitochondrial to autosomal depth
+	pub fn mito_copy_number(&self) -> Option<f64> {
+		let mito_depth = self.contig_depth(&self.mito.as_ref()?.contig)?;
+		self.sex_stats.as_ref().map(|s| 2.0 * mito_depth / s.autosome_depth)
+	}
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+// Read assignment to genomes from read_filter when extra references (spike-ins, xenograft hosts) are used
+use std::io::Read;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GenomeCount {
+	pub name: String,
+	pub reads: usize,
+	pub removed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GenomeJson {
+	pub genomes: Vec<GenomeCount>,
+	pub unmapped: usize,
+	pub cross_genome: usize,
+	pub removed: usize,
+}
+
+impl GenomeJson {
+	pub fn from_reader<T: Read>(rdr: T) -> Result<Self, String> {
+		serde_json::from_reader(rdr).map_err(|e| format!("Couldn't parse genome JSON file {}", e))
+	}
+	pub fn merge(mut self, other: &GenomeJson) -> Self {
+		for g in other.genomes.iter() {
+			match self.genomes.iter_mut().find(|x| x.name == g.name) {
+				Some(x) => {
+					x.reads += g.reads;
+					x.removed = x.removed || g.removed;
+				},
+				None => self.genomes.push(g.clone()),
+			}
+		}
+		self.unmapped += other.unmapped;
+		self.cross_genome += other.cross_genome;
+		self.removed += other.removed;
+		self
+	}
+	pub fn total(&self) -> usize { self.genomes.iter().fold(self.unmapped, |s, g| s + g.reads) }
+}
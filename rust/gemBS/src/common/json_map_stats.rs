@@ -2,13 +2,13 @@ use std::collections::HashMap;
 use std::clone::Clone;
 use std::convert::From;
 use std::ops::{Index, IndexMut, Add, AddAssign};
-use std::io::Read;
-use serde::{Deserialize};
+use std::io::{Read, BufRead};
+use serde::{Serialize, Deserialize};
 use super::json_call_stats::add_assign_vec;
 
 pub trait New { fn new() -> Self; }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Counts(pub [usize; 2]);
 
 impl AddAssign for Counts {
@@ -28,7 +28,7 @@ impl IndexMut<usize> for Counts {
 
 impl New for Counts { fn new() -> Self { Counts([0;2]) } }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Count(pub [usize; 1]);
 impl Index<usize> for Count {
     type Output = usize;
@@ -52,7 +52,7 @@ impl Add for Counts {
 
 impl New for Count { fn new() -> Self { Count([0; 1])} }
 
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Reads<T> {
 	pub general: T,
@@ -103,7 +103,7 @@ impl From<Reads<Count>> for Reads<Counts> {
 	}	
 }
 
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct NumReadsBS<T> {
 	pub c2t: T,
@@ -121,7 +121,7 @@ impl<T: Add<Output = T>> Add for NumReadsBS<T> {
     fn add(self, other: Self) -> Self { Self { c2t: self.c2t + other.c2t, g2a: self.g2a + other.g2a } }
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct BaseCounts<T> {
 	pub a: T,
@@ -162,7 +162,7 @@ impl From<BaseCounts<Count>> for BaseCounts<Counts> {
 	}	
 }
 
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct BaseCountStats<T> {
 	pub overall: BaseCounts<T>,
@@ -192,6 +192,13 @@ impl<T: AddAssign + Copy + Add<Output = T>> AddAssign for BaseCountStats<T> {
     }
 }
 
+impl<T> BaseCountStats<T> {
+	fn from_overall(overall: BaseCounts<T>) -> Self {
+		Self{overall, general_c2t: None, general_g2a: None, under_conversion_control_c2t: None, under_conversion_control_g2a: None, 
+			over_conversion_control_c2t: None, over_conversion_control_g2a: None}
+	}
+}
+
 impl From<BaseCountStats<Count>> for BaseCountStats<Counts> {
 	fn from(bc: BaseCountStats<Count>) -> Self {
 		Self{
@@ -206,7 +213,7 @@ impl From<BaseCountStats<Count>> for BaseCountStats<Counts> {
 	}	
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Paired {
 	read_group: Option<String>,
@@ -244,7 +251,7 @@ impl Paired {
 	pub fn template_len(&self) -> &HashMap<String, usize> { &self.hist_template_len }
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Single {
 	read_group: Option<String>,
@@ -274,10 +281,55 @@ impl Single {
 	pub fn mismatch(&self) -> &HashMap<String, usize> { &self.hist_mismatch[0] }
 }
 
+// Statistics parsed from the output of samtools stats, used for aligners that do not generate GEM3 style reports
+#[derive(Default)]
+pub struct SamStats {
+	mapped: usize,
+	unmapped: usize,
+	properly_paired: usize,
+	bases: [usize; 5],
+	hist_mapq: Vec<usize>,
+	hist_read_len: HashMap<String, usize>,
+	hist_template_len: HashMap<String, usize>,
+}
+
+impl SamStats {
+	pub fn from_reader<T: BufRead>(rdr: T) -> Result<Self, String> {
+		let mut st = SamStats::default();
+		for line in rdr.lines() {
+			let line = line.map_err(|e| format!("Error reading samtools stats output: {}", e))?;
+			let fd: Vec<&str> = line.split('\t').collect();
+			if fd.len() < 3 { continue; }
+			let get = |i: usize| fd.get(i).and_then(|s| s.trim().parse::<usize>().ok()).unwrap_or(0);
+			match fd[0] {
+				"SN" => match fd[1].trim_end_matches(':') {
+					"reads mapped" => st.mapped = get(2),
+					"reads unmapped" => st.unmapped = get(2),
+					"reads properly paired" => st.properly_paired = get(2),
+					_ => (),
+				},
+				"MAPQ" => {
+					let q = get(1);
+					if st.hist_mapq.len() <= q { st.hist_mapq.resize(q + 1, 0) }
+					st.hist_mapq[q] += get(2);
+				},
+				"RL" => *st.hist_read_len.entry(fd[1].to_owned()).or_insert(0) += get(2),
+				"FTC" | "LTC" => for (i, ct) in st.bases.iter_mut().enumerate() { *ct += get(i + 1) },
+				"IS" => {
+					let n = get(2);
+					if n > 0 { *st.hist_template_len.entry(fd[1].to_owned()).or_insert(0) += n }
+				},
+				_ => (),
+			}
+		}
+		Ok(st)
+	}
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum MapJsonType { Paired, Unknown, Single }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(tag = "MapperType", rename_all = "PascalCase")]
 pub enum MapJson {
 	Paired(Paired),
@@ -299,6 +351,29 @@ impl MapJson {
 	pub fn from_reader<T: Read>(rdr: T) -> Result<Self, String> {
 		serde_json::from_reader(rdr).map_err(|e| format!("Couldn't parse call JSON file {}", e))
 	}
+	// Make mapping report from samtools stats output.  For paired data the statistics for each read
+	// are collected separately (and each pair is counted once in the template length histograms of the two reads)
+	pub fn from_sam_stats(end1: &SamStats, end2: Option<&SamStats>) -> Self {
+		if let Some(e2) = end2 {
+			let bc = |i: usize| Counts([end1.bases[i], e2.bases[i]]);
+			let reads = Reads{general: Counts([end1.mapped, e2.mapped]), unmapped: Counts([end1.unmapped, e2.unmapped]), 
+				sequencing_control: None, under_conversion_control: None, over_conversion_control: None};
+			let mut hist_mapq = end1.hist_mapq.clone();
+			add_assign_vec(&mut hist_mapq, &e2.hist_mapq, 0);
+			let mut hist_template_len = end1.hist_template_len.clone();
+			for (key, ct) in e2.hist_template_len.iter() { *(hist_template_len.entry(key.to_owned()).or_insert(0)) += ct; }
+			MapJson::Paired(Paired{read_group: None, reads, num_reads_bs: None, correct_pairs: end1.properly_paired, 
+				base_counts: BaseCountStats::from_overall(BaseCounts{a: bc(0), c: bc(1), g: bc(2), t: bc(3), n: bc(4)}), hist_mapq, 
+				hist_read_len: [end1.hist_read_len.clone(), e2.hist_read_len.clone()], hist_mismatch: [HashMap::new(), HashMap::new()], hist_template_len})
+		} else {
+			let bc = |i: usize| Count([end1.bases[i]]);
+			let reads = Reads{general: Count([end1.mapped]), unmapped: Count([end1.unmapped]), 
+				sequencing_control: None, under_conversion_control: None, over_conversion_control: None};
+			MapJson::Single(Single{read_group: None, reads, num_reads_bs: None, 
+				base_counts: BaseCountStats::from_overall(BaseCounts{a: bc(0), c: bc(1), g: bc(2), t: bc(3), n: bc(4)}), hist_mapq: end1.hist_mapq.clone(), 
+				hist_read_len: [end1.hist_read_len.clone()], hist_mismatch: [HashMap::new()]})
+		}
+	}
 	pub fn get_conversion_counts(&self) -> (BaseCounts<Counts>, BaseCounts<Counts>) {
 		let mut ct1 = BaseCounts::<Counts>::new();
 		let mut ct2 = BaseCounts::<Counts>::new();
@@ -396,3 +471,36 @@ impl MapJson {
 		if let MapJson::Paired(s) = self { MapJson::Unknown(s) } else { panic!("Invalid conversion") }
 	} 
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sam_stats_from_reader() {
+		let input = "# Summary Numbers\n\
+			SN\treads mapped:\t90\n\
+			SN\treads unmapped:\t10\n\
+			SN\treads properly paired:\t80\t# proper-pair bit set\n\
+			SN\tsequences:\t100\n\
+			FTC\t10\t20\t30\t40\t1\n\
+			LTC\t1\t2\t3\t4\t0\n\
+			RL\t100\t60\n\
+			RL\t150\t30\n\
+			MAPQ\t0\t5\n\
+			MAPQ\t60\t85\n\
+			IS\t200\t0\t0\t0\t0\n\
+			IS\t300\t12\t6\t6\t0\n\
+			bad line\n";
+		let st = SamStats::from_reader(input.as_bytes()).unwrap();
+		assert_eq!((st.mapped, st.unmapped, st.properly_paired), (90, 10, 80));
+		assert_eq!(st.bases, [11, 22, 33, 44, 1]);
+		assert_eq!(st.hist_read_len.get("100"), Some(&60));
+		assert_eq!(st.hist_read_len.get("150"), Some(&30));
+		assert_eq!(st.hist_mapq.len(), 61);
+		assert_eq!((st.hist_mapq[0], st.hist_mapq[60], st.hist_mapq[30]), (5, 85, 0));
+		// Insert sizes with no pairs are skipped
+		assert_eq!(st.hist_template_len.get("200"), None);
+		assert_eq!(st.hist_template_len.get("300"), Some(&12));
+	}
+}
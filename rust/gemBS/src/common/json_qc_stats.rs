@@ -0,0 +1,84 @@
+// Read level QC metrics from read_trim (base composition, quality distribution and overrepresented sequences)
+use std::io::Read;
+use serde::{Serialize, Deserialize};
+use super::json_call_stats::add_assign_vec;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OverSeq {
+	pub sequence: String,
+	pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EndQc {
+	pub bases: usize,
+	pub min_length: usize,
+	pub max_length: usize,
+	pub base_counts: Vec<[usize; 5]>,
+	pub quality_sum: Vec<usize>,
+	pub quality_hist: Vec<usize>,
+	pub sampled: usize,
+	pub overrepresented: Vec<OverSeq>,
+}
+
+impl EndQc {
+	fn add(&mut self, other: &Self) {
+		if self.bases == 0 { self.min_length = other.min_length }
+		else if other.bases > 0 { self.min_length = self.min_length.min(other.min_length) }
+		self.max_length = self.max_length.max(other.max_length);
+		self.bases += other.bases;
+		if self.base_counts.len() < other.base_counts.len() { self.base_counts.resize(other.base_counts.len(), [0; 5]) }
+		for (a, b) in self.base_counts.iter_mut().zip(other.base_counts.iter()) {
+			for (x, y) in a.iter_mut().zip(b.iter()) { *x += *y }
+		}
+		add_assign_vec(&mut self.quality_sum, &other.quality_sum, 0);
+		add_assign_vec(&mut self.quality_hist, &other.quality_hist, 0);
+		self.sampled += other.sampled;
+		for s in other.overrepresented.iter() {
+			match self.overrepresented.iter_mut().find(|x| x.sequence == s.sequence) {
+				Some(x) => x.count += s.count,
+				None => self.overrepresented.push(s.clone()),
+			}
+		}
+		self.overrepresented.sort_unstable_by_key(|x| std::cmp::Reverse(x.count));
+	}
+
+	// Fraction of each base (A, C, G, T, N) at each position in the read
+	pub fn base_fractions(&self) -> Vec<[f64; 5]> {
+		self.base_counts.iter().map(|c| {
+			let tot = c.iter().sum::<usize>().max(1) as f64;
+			let mut f = [0.0; 5];
+			for (x, y) in f.iter_mut().zip(c.iter()) { *x = *y as f64 / tot }
+			f
+		}).collect()
+	}
+
+	pub fn mean_quality(&self) -> f64 {
+		let (s, n) = self.quality_hist.iter().enumerate().fold((0, 0), |(s, n), (q, c)| (s + q * c, n + c));
+		if n > 0 { s as f64 / n as f64 } else { 0.0 }
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct QcJson {
+	pub encoding: String,
+	pub paired: bool,
+	pub reads: usize,
+	pub ends: Vec<EndQc>,
+}
+
+impl QcJson {
+	pub fn from_reader<T: Read>(rdr: T) -> Result<Self, String> {
+		serde_json::from_reader(rdr).map_err(|e| format!("Couldn't parse QC JSON file {}", e))
+	}
+	pub fn merge(mut self, other: &QcJson) -> Self {
+		self.paired = self.paired || other.paired;
+		self.reads += other.reads;
+		if self.ends.len() < other.ends.len() { self.ends.resize(other.ends.len(), EndQc::default()) }
+		for (a, b) in self.ends.iter_mut().zip(other.ends.iter()) { a.add(b) }
+		self
+	}
+}
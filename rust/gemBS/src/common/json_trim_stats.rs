@@ -0,0 +1,57 @@
+// Statistics from read_trim for the trimming stage of the mapping pipeline
+use std::io::Read;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TrimReadStats {
+	pub bases_in: usize,
+	pub bases_out: usize,
+	pub adapter_reads: usize,
+	pub adapter_bases: usize,
+	pub quality_bases: usize,
+	pub poly_g_reads: usize,
+	pub poly_g_bases: usize,
+	pub clipped_bases: usize,
+}
+
+impl TrimReadStats {
+	fn add(&mut self, other: &Self) {
+		self.bases_in += other.bases_in;
+		self.bases_out += other.bases_out;
+		self.adapter_reads += other.adapter_reads;
+		self.adapter_bases += other.adapter_bases;
+		self.quality_bases += other.quality_bases;
+		self.poly_g_reads += other.poly_g_reads;
+		self.poly_g_bases += other.poly_g_bases;
+		self.clipped_bases += other.clipped_bases;
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TrimJson {
+	pub paired: bool,
+	pub adapters: Vec<String>,
+	pub reads_in: usize,
+	pub reads_out: usize,
+	pub too_short: usize,
+	pub reads: Vec<TrimReadStats>,
+}
+
+impl TrimJson {
+	pub fn from_reader<T: Read>(rdr: T) -> Result<Self, String> {
+		serde_json::from_reader(rdr).map_err(|e| format!("Couldn't parse trim JSON file {}", e))
+	}
+	// Datasets from the same sample can have different adapters or be a mixture of single and paired end
+	pub fn merge(mut self, other: &TrimJson) -> Self {
+		for ad in other.adapters.iter() { if !self.adapters.contains(ad) { self.adapters.push(ad.clone()) } }
+		self.paired = self.paired || other.paired;
+		self.reads_in += other.reads_in;
+		self.reads_out += other.reads_out;
+		self.too_short += other.too_short;
+		if self.reads.len() < other.reads.len() { self.reads.resize(other.reads.len(), TrimReadStats::default()) }
+		for (a, b) in self.reads.iter_mut().zip(other.reads.iter()) { a.add(b) }
+		self
+	}
+}
@@ -0,0 +1,188 @@
+// Provenance database for gemBS assets
+//
+// For every derived asset we record a fingerprint of the file (size, modification time, a digest of
+// the start and end of the file and optionally an MD5 digest of the complete contents) together with the
+// task and command that produced it, the parameters used and the fingerprints of the inputs.  This allows
+// up-to-date checks to be based on the contents of files rather than their modification times, so copying
+// or restoring a project does not make the outputs appear modified.
+//
+// Unless provenance_hash is set, the contents are only compared using the size and the digest of the first
+// and last SAMPLE_BLOCK bytes, so a change in the middle of a file that does not alter its size is not detected.
+//
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use serde::{Serialize, Deserialize};
+use md5::{Md5, Digest};
+
+use crate::common::utils::timed_wait_for_lock;
+use crate::config::ParamSet;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+	size: u64,
+	mtime: i64,
+	mtime_nsec: i64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	sample: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	md5: Option<String>,
+}
+
+// Size of the blocks at the start and end of a file used for the sample digest
+const SAMPLE_BLOCK: u64 = 1 << 16;
+
+fn md5_file(path: &Path) -> io::Result<String> {
+	let mut file = fs::File::open(path)?;
+	let mut hasher = Md5::new();
+	let mut buf = vec![0u8; 1 << 20];
+	loop {
+		match file.read(&mut buf)? {
+			0 => break,
+			n => hasher.update(&buf[..n]),
+		}
+	}
+	Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Cheap digest of the first and last blocks of a file
+fn sample_digest(path: &Path, size: u64) -> io::Result<String> {
+	let mut file = fs::File::open(path)?;
+	let mut buf = Vec::with_capacity(2 * SAMPLE_BLOCK as usize);
+	(&mut file).take(SAMPLE_BLOCK).read_to_end(&mut buf)?;
+	if size > SAMPLE_BLOCK {
+		file.seek(SeekFrom::Start(SAMPLE_BLOCK.max(size - SAMPLE_BLOCK)))?;
+		file.take(SAMPLE_BLOCK).read_to_end(&mut buf)?;
+	}
+	Ok(format!("{:x}", Md5::digest(&buf)))
+}
+
+impl Fingerprint {
+	fn from_metadata(md: &fs::Metadata) -> Self {
+		Fingerprint{size: md.size(), mtime: md.mtime(), mtime_nsec: md.mtime_nsec(), sample: None, md5: None}
+	}
+	fn same_file(&self, other: &Fingerprint) -> bool {
+		self.size == other.size && self.mtime == other.mtime && self.mtime_nsec == other.mtime_nsec
+	}
+	// Compare contents using the full digests if available, then the sample digests, and otherwise fall back
+	// to the file attributes
+	pub fn matches(&self, other: &Fingerprint) -> bool {
+		match (&self.md5, &other.md5, &self.sample, &other.sample) {
+			(Some(a), Some(b), _, _) | (_, _, Some(a), Some(b)) => self.size == other.size && a == b,
+			_ => self.same_file(other),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProvRecord {
+	fingerprint: Fingerprint,
+	task: String,
+	#[serde(default)]
+	command: String,
+	#[serde(default)]
+	params: ParamSet,
+	inputs: HashMap<String, Fingerprint>,
+}
+
+impl ProvRecord {
+	pub fn fingerprint(&self) -> &Fingerprint { &self.fingerprint }
+	pub fn task(&self) -> &str { &self.task }
+	pub fn input(&self, id: &str) -> Option<&Fingerprint> { self.inputs.get(id) }
+}
+
+// Information collected when a task is started, used to make the provenance records for
+// the outputs when the task completes successfully
+#[derive(Debug)]
+pub struct TaskProvenance {
+	task: String,
+	command: String,
+	params: ParamSet,
+	inputs: HashMap<String, Fingerprint>,
+	outputs: Vec<(String, std::path::PathBuf)>,
+}
+
+impl TaskProvenance {
+	pub fn new(task: &str, command: &str, params: ParamSet) -> Self {
+		TaskProvenance{task: task.to_owned(), command: command.to_owned(), params, inputs: HashMap::new(), outputs: Vec::new()}
+	}
+	pub fn add_input(&mut self, id: &str, fp: Fingerprint) { self.inputs.insert(id.to_owned(), fp); }
+	pub fn add_output(&mut self, id: &str, path: &Path) { self.outputs.push((id.to_owned(), path.to_owned())); }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProvenanceDb {
+	// Last fingerprint seen for each asset (avoids recalculating digests of unchanged files)
+	files: HashMap<String, Fingerprint>,
+	records: HashMap<String, ProvRecord>,
+	#[serde(skip)]
+	hash: bool,
+	#[serde(skip)]
+	changed: bool,
+}
+
+impl ProvenanceDb {
+	pub fn load(path: &Path, hash: bool) -> Result<Self, String> {
+		let mut db = if path.exists() {
+			let file = fs::File::open(path).map_err(|e| format!("Couldn't open provenance file {}: {}", path.display(), e))?;
+			serde_json::from_reader(io::BufReader::new(file)).map_err(|e| format!("Error reading provenance file {}: {}", path.display(), e))?
+		} else { ProvenanceDb::default() };
+		db.hash = hash;
+		Ok(db)
+	}
+
+	// Merge our changes into the database on disk (which may have been updated by other gemBS processes)
+	pub fn save(&mut self, path: &Path, sig: Arc<AtomicUsize>) -> Result<(), String> {
+		if !self.changed { return Ok(()) }
+		let lock = timed_wait_for_lock(sig, path)?;
+		let mut db = ProvenanceDb::load(path, self.hash)?;
+		for (id, fp) in self.files.iter() { db.files.insert(id.clone(), fp.clone()); }
+		for (id, rec) in self.records.iter() { db.records.insert(id.clone(), rec.clone()); }
+		let writer = lock.writer()?;
+		serde_json::to_writer_pretty(writer, &db).map_err(|e| format!("Error writing provenance file {}: {}", path.display(), e))?;
+		self.files = db.files;
+		self.records = db.records;
+		self.changed = false;
+		Ok(())
+	}
+
+	pub fn record(&self, id: &str) -> Option<&ProvRecord> { self.records.get(id) }
+
+	// Get current fingerprint for a file.  The digests are taken from the cache if the file attributes
+	// are unchanged, otherwise they are recalculated (the full MD5 digest only if hashing is enabled).
+	pub fn fingerprint(&mut self, id: &str, path: &Path) -> Option<Fingerprint> {
+		let md = path.metadata().ok()?;
+		let mut fp = Fingerprint::from_metadata(&md);
+		if let Some(cached) = self.files.get(id) {
+			if cached.same_file(&fp) && cached.sample.is_some() && (cached.md5.is_some() || !self.hash) { return Some(cached.clone()) }
+		}
+		match sample_digest(path, fp.size) {
+			Ok(s) => fp.sample = Some(s),
+			Err(e) => warn!("Couldn't calculate digest of {}: {}", path.display(), e),
+		}
+		if self.hash {
+			match md5_file(path) {
+				Ok(s) => fp.md5 = Some(s),
+				Err(e) => warn!("Couldn't calculate MD5 digest of {}: {}", path.display(), e),
+			}
+		}
+		self.files.insert(id.to_owned(), fp.clone());
+		self.changed = true;
+		Some(fp)
+	}
+
+	pub fn add_task(&mut self, tp: TaskProvenance) {
+		for (id, path) in tp.outputs.iter() {
+			if let Some(fingerprint) = self.fingerprint(id, path) {
+				let rec = ProvRecord{fingerprint, task: tp.task.clone(), command: tp.command.clone(), params: tp.params.clone(), inputs: tp.inputs.clone()};
+				self.records.insert(id.clone(), rec);
+				self.changed = true;
+			}
+		}
+	}
+}
@@ -3,7 +3,7 @@
 // Holds all of the information from the config files, JSON files, sqlite db etc.
 //
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, BTreeMap};
 use std::path::PathBuf;
 use std::{env,option_env};
 use std::sync::Arc;
@@ -19,6 +19,8 @@ use crate::common::defs::{Section, Metadata, DataValue, JobLen, MemSize, Command
 use crate::common::assets::{Asset, AssetList, AssetType, AssetStatus, GetAsset};
 use crate::common::tasks::{Task, TaskList, TaskStatus, RunningTask};
 use crate::common::utils::{FileLock, timed_wait_for_lock, get_phys_memory};
+use crate::common::provenance::{ProvenanceDb, TaskProvenance};
+use crate::commands::prepare::config_file::affects_output;
 
 use crate::config::contig::{Contig, ContigPool};
 use utils::{log_level::LogLevel, find_exec_path};
@@ -28,7 +30,7 @@ mod check_ref;
 mod check_map;
 mod check_report;
 mod check_call;
-mod check_extract;
+pub mod check_extract;
 mod md5_fasta;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,6 +39,63 @@ enum GemBSData {
 	SampleData(HashMap<String, HashMap<Metadata, DataValue>>),
 	Contigs(Vec<Contig>),
 	ContigPools(HashMap<Rc<String>, ContigPool>),
+	Params(ParamRecord),
+	SampleConfig(HashMap<String, HashMap<Section, HashMap<String, DataValue>>>),
+}
+
+pub type ParamSet = BTreeMap<String, String>;
+
+// Effective parameters for each config section, and the parameters that were used to create each derived asset
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ParamRecord {
+	section: HashMap<Section, ParamSet>,
+	asset: HashMap<String, ParamSet>,
+}
+
+// List differences between the parameters used to create an asset and the current parameters
+fn param_diffs(old: &ParamSet, new: &ParamSet) -> Vec<String> {
+	let mut v = Vec::new();
+	for (k, x) in new.iter() {
+		match old.get(k) {
+			Some(y) if y == x => (),
+			Some(y) => v.push(format!("{}: {} -> {}", k, y, x)),
+			None => v.push(format!("{}: (unset) -> {}", k, x)),
+		}
+	}
+	for (k, y) in old.iter() {
+		if !new.contains_key(k) { v.push(format!("{}: {} -> (unset)", k, y)) }
+	}
+	v
+}
+
+fn write_state(lock: &FileLock, var: &[GemBSData]) -> Result<(), String> {
+	let config_file = lock.path();
+	let mut writer = {
+		match find_exec_path("pigz").or_else(|| find_exec_path("gzip")) {
+			Some(p) => lock.pipe_writer(&p),
+			None => lock.writer(),
+		}.map_err(|e| format!("Error: Could not open gemBS config file {} for writing: {}", config_file.to_string_lossy(), e))
+	}?;
+	debug!("Writing out config file {}", config_file.display());
+	let now = Instant::now();
+	rmp_serde::encode::write(&mut writer, var).map_err(|e| format!("Error: failed to write RMP config file {}: {}", config_file.to_string_lossy(), e))?;		
+	debug!("Config file written out to {} in {}ms", config_file.display(), now.elapsed().as_millis());
+	Ok(())
+}
+
+fn read_state_from_lock(lock: &FileLock) -> Result<Vec<GemBSData>, String> {
+	let config_file = lock.path();
+	let reader = lock.reader().map_err(|e| format!("Error: Could not open JSON config file {} for reading: {}", config_file.to_string_lossy(), e))?;
+	debug!("Reading in config file {}", config_file.display());
+	let now = Instant::now();
+	let var = rmp_serde::decode::from_read(reader).map_err(|e| format!("Error: failed to read MP config file {}: {}", config_file.to_string_lossy(), e))?;
+	debug!("config file read from {} in {}ms", config_file.display(), now.elapsed().as_millis());
+	Ok(var)
+}
+
+fn read_state(sig: Arc<AtomicUsize>, config_file: &Path) -> Result<Vec<GemBSData>, String> {
+	let lock = timed_wait_for_lock(sig, config_file).map_err(|e| format!("Error: Could not obtain lock on JSON config file: {}", e))?;
+	read_state_from_lock(&lock)
 }
 
 struct GemBSFiles {
@@ -46,10 +105,12 @@ struct GemBSFiles {
 }
 
 pub struct GemBS {
-	var: [GemBSData; 4],
+	var: Vec<GemBSData>,
 	fs: Option<GemBSFiles>,
 	assets: AssetList,
 	tasks: TaskList,
+	provenance: Option<ProvenanceDb>,
+	pending_params: HashMap<String, ParamSet>,
 	total_mem: usize,
 	signal: Arc<AtomicUsize>,
 	ignore_times: bool,
@@ -66,16 +127,18 @@ pub struct GemBS {
 impl GemBS {
 	pub fn new() -> Self {
 		let total_mem = get_phys_memory().expect("Couldn't get total memory on system");
-		let var = [
+		let var = vec!(
 			GemBSData::Config(HashMap::new()),
 			GemBSData::SampleData(HashMap::new()),
 			GemBSData::Contigs(Vec::new()),
 			GemBSData::ContigPools(HashMap::new()),			
-		];
+			GemBSData::Params(ParamRecord::default()),			
+			GemBSData::SampleConfig(HashMap::new()),
+		);
 		let gem_bs = GemBS{var, fs: None, 
 			ignore_times: false, ignore_status: false, keep_logs: false, total_mem,
 			json_out: None, all: false, slurm: false, slurm_script: None, dry_run: false, verbose: LogLevel::from_str("error").unwrap(),
-			assets: AssetList::new(), tasks: TaskList::new(), signal: Arc::new(AtomicUsize::new(0))};
+			assets: AssetList::new(), tasks: TaskList::new(), provenance: None, pending_params: HashMap::new(), signal: Arc::new(AtomicUsize::new(0))};
 		gem_bs.mask_signals();
 		gem_bs
 	}
@@ -105,6 +168,13 @@ impl GemBS {
 			href.entry(section).or_insert_with(HashMap::new).insert(name.to_string(), val);
 		} else { panic!("Internal error!"); }
 	}
+	// Parameter overrides for a sample or group of samples (from a sample sheet or a scoped section of the config file)
+	pub fn set_sample_config(&mut self, scope: &str, section: Section, name: &str, val: DataValue) {
+		if let GemBSData::SampleConfig(href) = &mut self.var[5] {
+			debug!("Setting {:?}:{} to {:?} for {}", section, name, val, scope);
+			href.entry(scope.to_string()).or_insert_with(HashMap::new).entry(section).or_insert_with(HashMap::new).insert(name.to_string(), val);
+		} else { panic!("Internal error!"); }
+	}
 	pub fn set_sample_data(&mut self, dataset: &str, mt: Metadata, val: DataValue) {
 		if let GemBSData::SampleData(href) = &mut self.var[1] {
 			href.entry(dataset.to_string()).or_insert_with(HashMap::new).insert(mt, val);
@@ -122,6 +192,15 @@ impl GemBS {
 	fn get_contig_pool_hash_mut(&mut self) -> &mut HashMap<Rc<String>, ContigPool> {
 		if let GemBSData::ContigPools(href) = &mut self.var[3] { href } else { panic!("Internal error!"); }
 	}
+	fn get_params(&self) -> &ParamRecord {
+		if let GemBSData::Params(pref) = &self.var[4] { pref } else { panic!("Internal error!"); }
+	}
+	fn get_params_mut(&mut self) -> &mut ParamRecord {
+		if let GemBSData::Params(pref) = &mut self.var[4] { pref } else { panic!("Internal error!"); }
+	}
+	pub fn set_section_params(&mut self, section: Section, params: ParamSet) {
+		self.get_params_mut().section.insert(section, params);
+	}
 	pub fn set_contig_def(&mut self, ctg: contig::Contig) {
 		self.get_contigs_mut().push(ctg);
 	}
@@ -139,6 +218,54 @@ impl GemBS {
 		}
 		None
 	}
+	// As get_config(), but sample specific values take precedence, followed by values for the sample's group
+	pub fn get_sample_config(&self, barcode: &str, section: Section, name: &str) -> Option<&DataValue> {
+		for h in self.get_sample_overrides(barcode) {
+			if let Some(s) = h.get(&section).and_then(|h1| h1.get(name)) { return Some(s); }
+			if let Some(s) = h.get(&Section::Default).and_then(|h1| h1.get(name)) { return Some(s); }
+		}
+		self.get_config(section, name)
+	}
+	pub fn get_sample_config_bool(&self, barcode: &str, section: Section, name: &str) -> bool {
+		if let Some(DataValue::Bool(x)) = self.get_sample_config(barcode, section, name) { *x } else { false }
+	}
+	pub fn get_sample_config_str(&self, barcode: &str, section: Section, name: &str) -> Option<&str> {
+		if let Some(DataValue::String(x)) = self.get_sample_config(barcode, section, name) { Some(x) } else { None }
+	}
+	pub fn get_sample_group(&self, barcode: &str) -> Option<&str> {
+		self.get_sample_data_ref().values().filter(|h| matches!(h.get(&Metadata::SampleBarcode), Some(DataValue::String(bc)) if bc == barcode))
+			.find_map(|h| if let Some(DataValue::String(g)) = h.get(&Metadata::Group) { Some(g.as_str()) } else { None })
+	}
+	// Overrides for a sample in order of precedence (sample then group)
+	fn get_sample_overrides(&self, barcode: &str) -> Vec<&HashMap<Section, HashMap<String, DataValue>>> {
+		if let GemBSData::SampleConfig(href) = &self.var[5] {
+			[Some(barcode), self.get_sample_group(barcode)].iter().flatten().filter_map(|s| href.get(*s)).collect()
+		} else { Vec::new() }
+	}
+	// As get_config(), but values set for the sample or group (scope) take precedence
+	pub fn get_scope_config(&self, scope: Option<&str>, section: Section, name: &str) -> Option<&DataValue> {
+		if let (Some(sc), GemBSData::SampleConfig(href)) = (scope, &self.var[5]) {
+			if let Some(h) = href.get(sc) {
+				if let Some(s) = h.get(&section).and_then(|h1| h1.get(name)) { return Some(s); }
+				if let Some(s) = h.get(&Section::Default).and_then(|h1| h1.get(name)) { return Some(s); }
+			}
+		}
+		self.get_config(section, name)
+	}
+	pub fn get_sample_config_scopes(&self) -> Vec<&str> {
+		if let GemBSData::SampleConfig(href) = &self.var[5] { href.keys().map(|s| s.as_str()).collect() } else { Vec::new() }
+	}
+	// Warn about overrides that do not apply to any sample
+	pub fn check_sample_config(&self) {
+		if let GemBSData::SampleConfig(href) = &self.var[5] {
+			let samples = self.get_samples();
+			for scope in href.keys() {
+				if !samples.iter().any(|(bc, _)| bc == scope || self.get_sample_group(bc) == Some(scope.as_str())) {
+					warn!("Warning: parameters set for {} which does not match any sample barcode or group", scope);
+				}
+			}
+		}
+	}
 	pub fn get_config_strict(&self, section: Section, name: &str) -> Option<&DataValue> {
 		if let GemBSData::Config(href) = &self.var[0] {
 			if let Some(h) = href.get(&section) { 
@@ -153,18 +280,12 @@ impl GemBS {
 	pub fn get_config_int(&self, section: Section, name: &str) -> Option<isize> {
 		if let Some(DataValue::Int(x)) = self.get_config(section, name) { Some(*x) } else { None }
 	}	
-	pub fn get_config_intvec(&self, section: Section, name: &str) -> Option<&Vec<isize>> {
-		if let Some(DataValue::IntVec(x)) = self.get_config(section, name) { Some(x) } else { None }
-	}	
 	pub fn get_config_str(&self, section: Section, name: &str) -> Option<&str> {
 		if let Some(DataValue::String(x)) = self.get_config(section, name) { Some(x) } else { None }
 	}	
 	pub fn get_config_stringvec(&self, section: Section, name: &str) -> Option<&Vec<String>> {
 		if let Some(DataValue::StringVec(x)) = self.get_config(section, name) { Some(x) } else { None }
 	}	
-	pub fn get_config_float(&self, section: Section, name: &str) -> Option<f64> {
-		if let Some(DataValue::Float(x)) = self.get_config(section, name) { Some(*x) } else { None }
-	}	
 	pub fn get_config_joblen(&self, section: Section, name: &str) -> Option<JobLen> {
 		if let Some(DataValue::JobLen(x)) = self.get_config(section, name) { Some(*x) } else { None }
 	}	
@@ -200,37 +321,117 @@ impl GemBS {
 		self.check_signal()?;
 		let config_file = &self.fs.as_ref().unwrap().config_file;		
 		let lock = FileLock::new(config_file).map_err(|e| format!("Error: Could not obtain lock on gemBS config file: {}", e))?;
-		let mut writer = {
-			match find_exec_path("pigz").or_else(|| find_exec_path("gzip")) {
-				Some(p) => lock.pipe_writer(&p),
-				None => lock.writer(),
-			}.map_err(|e| format!("Error: Could not open gemBS config file {} for writing: {}", config_file.to_string_lossy(), e))
-		}?;
-		debug!("Writing out config file {}", config_file.display());
-		let now = Instant::now();
-		rmp_serde::encode::write(&mut writer, &self.var).map_err(|e| format!("Error: failed to write RMP config file {}: {}", config_file.to_string_lossy(), e))?;		
-		debug!("Config file written out to {} in {}ms", config_file.display(), now.elapsed().as_millis());
+		write_state(&lock, &self.var)
+	}
+	// Read the parameters used to create existing assets from the config file from a previous run of prepare
+	pub fn load_asset_params(&mut self) {
+		let config_file = &self.fs.as_ref().unwrap().config_file;
+		if !config_file.exists() { return }
+		match read_state(self.get_signal_clone(), config_file) {
+			Ok(mut var) => if let Some(GemBSData::Params(pref)) = var.get_mut(4) {
+				let asset = std::mem::take(&mut pref.asset);
+				self.get_params_mut().asset = asset;
+			},
+			Err(e) => debug!("Could not read asset parameters from previous config file: {}", e),
+		}
+	}
+	// Effective parameters for a task, including any sample specific overrides
+	fn task_params(&self, task: &Task) -> Option<ParamSet> {
+		let section = task.command().section();
+		let mut params = self.get_params().section.get(&section)?.clone();
+		if let Some(bc) = task.barcode() {
+			// Trimming is performed as part of the mapping pipeline, so the mapping parameters also apply
+			let secs = match section {
+				Section::Trimming => vec!(Section::Default, Section::Mapping, section),
+				Section::Calling => vec!(Section::Default, Section::DbSnp, section),
+				_ => vec!(Section::Default, section),
+			};
+			// Group overrides are applied first so that sample overrides take precedence
+			for h in self.get_sample_overrides(bc).iter().rev() {
+				for (k, v) in secs.iter().filter_map(|s| h.get(s)).flat_map(|h1| h1.iter()) {
+					if affects_output(k) { params.insert(k.clone(), format!("{}", v)); }
+				}
+			}
+		}
+		Some(params)
+	}
+	// Record the current parameters as those used to create the outputs of a task.  The config file is
+	// updated by save_task_params()
+	pub fn record_task_params(&mut self, task_idx: usize) {
+		let params = match self.task_params(&self.tasks[task_idx]) {
+			Some(p) => p,
+			None => return,
+		};
+		let ids: Vec<String> = self.tasks[task_idx].outputs().map(|x| self.assets.get_asset(*x).unwrap()).filter(|x| x.asset_type() != AssetType::Log).map(|x| x.id().to_owned()).collect();
+		for id in ids {
+			self.get_params_mut().asset.insert(id.clone(), params.clone());
+			self.pending_params.insert(id, params.clone());
+		}
+	}
+	// Write the parameters recorded since the last call to the config file
+	pub fn save_task_params(&mut self) -> Result<(), String> {
+		if self.pending_params.is_empty() { return Ok(()) }
+		let config_file = self.fs.as_ref().unwrap().config_file.clone();
+		let lock = timed_wait_for_lock(self.get_signal_clone(), &config_file).map_err(|e| format!("Error: Could not obtain lock on gemBS config file: {}", e))?;
+		let mut var = read_state_from_lock(&lock)?;
+		if let Some(GemBSData::Params(pref)) = var.get_mut(4) {
+			pref.asset.extend(self.pending_params.drain());
+			write_state(&lock, &var)?;
+		}
 		Ok(())
 	}
+	// Mark assets as outdated if the parameters used to create them differ from the current parameters.  Assets
+	// without a record (i.e., created by an older version of gemBS) are flagged as having unknown parameters
+	fn check_params(&mut self) {
+		let len = self.assets.len();
+		let mut stale = vec!(false; len);
+		for ix in self.assets.topo_order() {
+			self.assets.get_asset_mut(ix).unwrap().set_params_unknown(false);
+			let asset = self.assets.get_asset(ix).unwrap();
+			if asset.asset_type() == AssetType::Supplied || asset.asset_type() == AssetType::Log || asset.status() != AssetStatus::Present { continue; }
+			let mut unknown = false;
+			let reason = if let Some(j) = asset.parents().iter().find(|j| stale[**j]) {
+				Some(format!("input {} is outdated", self.assets.get_asset(*j).unwrap().id()))
+			} else {
+				let new_params = match asset.creator() {
+					Some(t) => self.task_params(&self.tasks[t]),
+					None => continue,
+				};
+				match (self.get_params().asset.get(asset.id()), new_params) {
+					(Some(old), Some(new)) => {
+						let diffs = param_diffs(old, &new);
+						if diffs.is_empty() { None } else { Some(format!("parameters changed ({})", diffs.join(", "))) }
+					},
+					(None, Some(_)) => {
+						unknown = true;
+						None
+					},
+					_ => None,
+				}
+			};
+			if let Some(r) = reason {
+				trace!("check_params: asset {} is outdated: {}", asset.id(), r);
+				stale[ix] = true;
+				self.assets.get_asset_mut(ix).unwrap().set_outdated(r);
+			} else if unknown {
+				trace!("check_params: parameters used to create asset {} are unknown", asset.id());
+				self.assets.get_asset_mut(ix).unwrap().set_params_unknown(true);
+			}
+		}
+	}
 	pub fn read_config(&mut self) -> Result<(), String> {
 		self.check_signal()?;
 		let config_file = &self.fs.as_ref().unwrap().config_file;
-		let lock = timed_wait_for_lock(self.get_signal_clone(), config_file).map_err(|e| format!("Error: Could not obtain lock on JSON config file: {}", e))?;
-		let reader = lock.reader().map_err(|e| format!("Error: Could not open JSON config file {} for reading: {}", config_file.to_string_lossy(), e))?;
-		debug!("Reading in config file {}", config_file.display());
-		let now = Instant::now();
-		self.var = rmp_serde::decode::from_read(reader).map_err(|e| format!("Error: failed to read MP config file {}: {}", config_file.to_string_lossy(), e))?;
-		debug!("config file read from {} in {}ms", config_file.display(), now.elapsed().as_millis());
+		self.var = read_state(self.get_signal_clone(), config_file)?;
+		// Config files from older versions of gemBS do not have the parameter record
+		if self.var.len() < 5 { self.var.push(GemBSData::Params(ParamRecord::default())) }
+		if self.var.len() < 6 { self.var.push(GemBSData::SampleConfig(HashMap::new())) }
 		self.check_signal()?;
 		Ok(())
 	}
 
-	pub fn setup_fs(&mut self, initial: bool) -> Result<(), String> {
-		self.check_signal()?;
-		let cdir = ".gemBS";
-		let config_file = if let Some(DataValue::String(x)) = self.get_config(Section::Default, "config_file") { PathBuf::from(x) } else { 
-			[cdir, "gemBS.mp"].iter().collect()
-		};
+	// Installation root from the command line, environment or compile time setting
+	pub fn find_gem_bs_root(&self) -> Result<PathBuf, String> {
 		let compile_root: Option<&'static str> = option_env!("GEMBS_INSTALL_ROOT");
 		let gem_bs_root = if let Some(DataValue::String(x)) = self.get_config(Section::Default, "gembs_root") { PathBuf::from(x) } 
 		else if let Ok(x) = env::var("GEMBS_ROOT") { PathBuf::from(x) } 
@@ -239,6 +440,15 @@ impl GemBS {
 		if !check_root(&gem_bs_root) {
 			return Err(format!("Could not find (installation) root directory for gemBS at {:?}.  Use root-dir option or set GEMBS_ROOT environment variable", gem_bs_root));
 		}
+		Ok(gem_bs_root)
+	}
+	pub fn setup_fs(&mut self, initial: bool) -> Result<(), String> {
+		self.check_signal()?;
+		let cdir = ".gemBS";
+		let config_file = if let Some(DataValue::String(x)) = self.get_config(Section::Default, "config_file") { PathBuf::from(x) } else { 
+			[cdir, "gemBS.mp"].iter().collect()
+		};
+		let gem_bs_root = self.find_gem_bs_root()?;
 		let config_dir = Path::new(cdir);	
 		if initial {
 			if config_dir.exists() {
@@ -281,6 +491,9 @@ impl GemBS {
 	pub fn get_task_file_path(&self) -> PathBuf {
 		[&self.fs.as_ref().unwrap().config_dir, Path::new("gemBS_tasks.json")].iter().collect()
 	}
+	pub fn get_provenance_path(&self) -> PathBuf {
+		[&self.fs.as_ref().unwrap().config_dir, Path::new("gemBS_provenance.json")].iter().collect()
+	}
 	pub fn get_config_script_path(&self) -> PathBuf {
 		let root = &self.fs.as_ref().unwrap().gem_bs_root;
 		[root, Path::new("etc"), Path::new("config_scripts")].iter().collect()
@@ -329,10 +542,21 @@ impl GemBS {
 				}
 			}
 		}
+		self.check_provenance()?;
+		self.check_params();
+		let unknown = self.assets.iter().filter(|x| x.params_unknown()).count();
+		if unknown > 0 { warn!("Parameters used to create {} existing file(s) are unknown, so parameter changes will not be detected for them", unknown) }
 		self.assets.calc_mod_time_ances();
 		self.assets.check_delete_status();
 		self.rescan_assets_and_tasks(lock)
 	}
+	// Remove partially written reference cache files (i.e., after an interrupted run)
+	pub fn clear_partial_ref_cache(&self) -> Result<(), String> {
+		match self.get_asset("contig_md5") {
+			Some(x) if md5_fasta::use_ref_cache(self) && x.path().exists() => md5_fasta::clear_partial_cache(self, x.path()),
+			_ => Ok(()),
+		}
+	}
 	pub fn rescan_assets_and_tasks(&mut self, lock: &FileLock) -> Result<(), String> {
 		trace!("Running rescan_assets_and_tasks()");
 		let running = get_running_tasks(lock)?;		
@@ -341,12 +565,59 @@ impl GemBS {
 			h
 		});
 		self.assets.recheck_status(&running_ids);
+		self.check_provenance()?;
+		self.check_params();
 		self.assets.calc_mod_time_ances();
 		self.assets.check_delete_status();		
 		self.handle_status(&running);
 		Ok(())				
 	}
 
+	// Load the provenance database (if enabled) and use it to check the status of the assets
+	fn check_provenance(&mut self) -> Result<(), String> {
+		if !self.get_config_bool(Section::Default, "provenance") || self.fs.is_none() { return Ok(()) }
+		let path = self.get_provenance_path();
+		if self.provenance.is_none() { 
+			self.provenance = Some(ProvenanceDb::load(&path, self.get_config_bool(Section::Default, "provenance_hash"))?);
+		}
+		let db = self.provenance.as_mut().unwrap();
+		self.assets.check_provenance(db);
+		db.save(&path, Arc::clone(&self.signal))
+	}
+	// Collect the input fingerprints, command and parameters for a task that is about to be run
+	pub fn start_task_provenance(&mut self, task_idx: usize, command: &str) -> Option<TaskProvenance> {
+		let params = self.task_params(&self.tasks[task_idx]).unwrap_or_default();
+		let db = self.provenance.as_mut()?;
+		let task = &self.tasks[task_idx];
+		let mut tp = TaskProvenance::new(task.id(), command, params);
+		for asset in task.inputs().map(|x| self.assets.get_asset(*x).unwrap()).filter(|x| x.asset_type() != AssetType::Log) {
+			if let Some(fp) = db.fingerprint(asset.id(), asset.path()) { tp.add_input(asset.id(), fp); }
+		}
+		for asset in task.outputs().map(|x| self.assets.get_asset(*x).unwrap()).filter(|x| x.asset_type() != AssetType::Log) {
+			tp.add_output(asset.id(), asset.path());
+		}
+		Some(tp)
+	}
+	// Record provenance of the outputs of a successfully completed task
+	pub fn record_task_provenance(&mut self, tp: TaskProvenance) {
+		let path = self.get_provenance_path();
+		if let Some(db) = self.provenance.as_mut() {
+			db.add_task(tp);
+			if let Err(e) = db.save(&path, Arc::clone(&self.signal)) { warn!("Could not update provenance database: {}", e); }
+		}
+	}
+	// Reasons why the outputs of a task are not up to date
+	pub fn stale_reasons(&self, task: &Task) -> Vec<String> {
+		task.outputs().map(|x| self.assets.get_asset(*x).unwrap()).filter(|x| x.asset_type() != AssetType::Log).fold(Vec::new(), |mut v, asset| {
+			match asset.status() {
+				AssetStatus::Absent => v.push(format!("{} is missing", asset.id())),
+				AssetStatus::Outdated => v.push(format!("{} is outdated: {}", asset.id(), asset.stale_reason().unwrap_or("unknown reason"))),
+				_ => (),
+			}
+			v
+		})
+	}
+
 	fn handle_status(&mut self, running: &[RunningTask]) {
 		self.tasks.iter_mut().for_each(|x| x.clear_status());
 		self.calc_task_statuses(running);	
@@ -390,8 +661,12 @@ impl GemBS {
 				if check(asset.status()) == 0 { outputs_ready = false; break; }
 			}
 		} else {
+			// If the provenance records show the outputs are current then modification times are not checked
+			let prov_current = task.outputs().map(|x| self.assets.get_asset(*x).unwrap()).any(|x| x.prov_current()) 
+				&& task.outputs().map(|x| self.assets.get_asset(*x).unwrap()).all(|x| x.prov_current() || x.asset_type() == AssetType::Log);
 			for asset in task.inputs().map(|x| self.assets.get_asset(*x).unwrap()) {
 				if asset.status() != AssetStatus::Present { inputs_ready = false; }
+				if prov_current { continue; }
 				latest_input_mod = match (latest_input_mod, asset.mod_time_ances()) {
 					(None, None) => None,
 					(Some(m), None) => Some(m),
@@ -508,7 +783,7 @@ impl GemBS {
 		if let Some(t) = self.tasks.find_task(format!("single_map_{}", barcode).as_str()) {
 			for i in self.tasks[t].outputs() {
 				let asset = self.get_asset(*i).expect("Couldn't get asset");
-				if asset.id().ends_with(".json") { json_files.push(asset.idx()) }
+				if asset.id().ends_with("_map.json") { json_files.push(asset.idx()) }
 			}		
 		} else if let Some(t) = self.tasks.find_task(format!("merge-bam_{}", barcode).as_str()) {
 			for ix in self.tasks[t].parents() {
@@ -516,7 +791,7 @@ impl GemBS {
 				if task.id().starts_with("map_") {
 					for i in task.outputs() {
 						let asset = self.get_asset(*i).expect("Couldn't get asset");
-						if asset.id().ends_with(".json") { json_files.push(asset.idx()) }
+						if asset.id().ends_with("_map.json") { json_files.push(asset.idx()) }
 					}		
 				}
 			}
@@ -551,7 +826,7 @@ impl GetAsset<&str> for GemBS {
 }
 
 fn check_root(path: &PathBuf) -> bool {
-	let apps = ["mextr", "snpxtr", "bs_call", "dbsnp_index", "read_filter", 
+	let apps = ["mextr", "snpxtr", "bs_call", "dbsnp_index", "read_filter", "read_trim",
 		"gem-indexer", "gem-mapper", "samtools", "bcftools", "bgzip"];
 	
 	trace!("Checking for gemBS root in {:?}", path);
@@ -25,6 +25,7 @@ pub fn check_call(gem_bs: &mut GemBS) -> Result<(), String> {
 		if let Some(x) = gem_bs.get_asset(*f) { common_inputs.push(x.idx()) } else { panic!("{} not found", f) };
 	}
 	if let Some(x) = gem_bs.get_asset("dbsnp_index") { common_inputs.push(x.idx()) } 
+	else if samples.iter().any(|(bc, _)| gem_bs.get_sample_config_bool(bc, Section::Calling, "recalibrate")) { return Err("Variant recalibration requires a dbSNP index (dbsnp_files or dbsnp_index)".to_string()) }
 	let handle_file = |gb: &mut GemBS, nm: String, id: Option<String>, p: &Path, st: AssetType| {
 		let tpath = Path::new(nm.as_str());
 		let path: PathBuf = [p, tpath].iter().collect();
@@ -44,6 +45,8 @@ pub fn check_call(gem_bs: &mut GemBS) -> Result<(), String> {
 		let tmp_bcf_dir = replace_meta_var(&bcf_dir);
 		let bcf_path = Path::new(&tmp_bcf_dir);	
 		if pools.len() > 1 {
+			// With multiple pools the variant sites from all pools are collected so that recalibration is trained once for the sample
+			let recal = gem_bs.get_sample_config_bool(bcode, Section::Calling, "recalibrate");
 			let mut out_bcfs = Vec::new();
 			let mut out_jsons = vec!(0); // Dummy value - will fill in later
 			for pool in pools.iter() {
@@ -55,14 +58,20 @@ pub fn check_call(gem_bs: &mut GemBS) -> Result<(), String> {
 				let out1 = handle_file(gem_bs, format!("{}_{}.json", bcode, pool), Some(format!("{}_{}_call.json", bcode, pool)), bcf_path, AssetType::Temp);
 				out_bcfs.push(out);
 				out_jsons.push(out1);
+				let mut outputs = vec!(out, out1);
+				if recal { 
+					let out2 = handle_file(gem_bs, format!("{}_{}_recal_sites.txt", bcode, pool), None, bcf_path, AssetType::Temp);
+					outputs.push(out2);
+					out_bcfs.push(out2);
+				}
 				let id = format!("bcf_call_{}_{}", bcode, pool);
 				let (lname, lpath) = assets::make_ext_asset(&id, bcf_path, "log");
 				let log_index = gem_bs.insert_asset(&lname, &lpath, AssetType::Log);				
 				let call_task = gem_bs.add_task(&id, format!("Call BCFs for pool {}, barcode {}", pool, bcode).as_str(),
 				 	Command::Call, format!("--barcode {} --pool {}", bcode, pool).as_str());
-				gem_bs.add_task_inputs(call_task, &in_vec).add_outputs(&[out, out1]).set_log(Some(log_index)).set_barcode(bcode)
+				gem_bs.add_task_inputs(call_task, &in_vec).add_outputs(&outputs).set_log(Some(log_index)).set_barcode(bcode)
 					.add_cores(cores).add_memory(memory).add_time(time);
-				[out, out1].iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(call_task, &in_vec));
+				outputs.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(call_task, &in_vec));
 			}
 			let out = handle_file(gem_bs, format!("{}.bcf", bcode), None, bcf_path, AssetType::Derived);
 			out_jsons[0] = out; // Add bcf file as input requirement for JSON merge so that we don't do the merge step until the merge BCF step is complete
@@ -70,11 +79,18 @@ pub fn check_call(gem_bs: &mut GemBS) -> Result<(), String> {
 			let id = format!("bcf_mergecall_{}", bcode);
 			let (lname, lpath) = assets::make_ext_asset(&id, bcf_path, "log");
 			let log_index = gem_bs.insert_asset(&lname, &lpath, AssetType::Log);				
+			let mut merge_outputs = vec!(out);
+			// Recalibration statistics from the merge step
+			if recal {
+				let out2 = handle_file(gem_bs, format!("{}_recal.json", bcode), None, bcf_path, AssetType::Temp);
+				merge_outputs.push(out2);
+				out_jsons.push(out2);
+			}
 			let merge_task = gem_bs.add_task(&id, format!("Merge BCFs for barcode {}", bcode).as_str(),
 				 	Command::MergeBcfs, format!("--barcode {} --no-md5 --no-index", bcode).as_str());
-			gem_bs.add_task_inputs(merge_task, &out_bcfs).add_outputs(&[out]).set_log(Some(log_index)).set_barcode(bcode)
+			gem_bs.add_task_inputs(merge_task, &out_bcfs).add_outputs(&merge_outputs).set_log(Some(log_index)).set_barcode(bcode)
 				.add_cores(merge_cores).add_memory(merge_memory).add_time(merge_time);
-			gem_bs.get_asset_mut(out).unwrap().set_creator(merge_task, &out_bcfs);
+			merge_outputs.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(merge_task, &out_bcfs));
 			let id = format!("merge_call_jsons_{}", bcode);
 			let merge_json_task = gem_bs.add_task(&id, format!("Merge JSONs for barcode {}", bcode).as_str(), Command::MergeCallJsons, "");
 			gem_bs.add_task_inputs(merge_json_task, &out_jsons).add_outputs(&[out1]).set_barcode(bcode);
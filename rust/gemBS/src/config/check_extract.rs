@@ -6,42 +6,124 @@ use crate::common::defs::{Section, DataValue, Command};
 use crate::common::assets::{AssetType, GetAsset};
 use crate::common::assets;
 use super::GemBS;
+use super::contig;
 
-pub fn check_extract(gem_bs: &mut GemBS) -> Result<(), String> {
-	let get_dir = |name: &str| { if let Some(DataValue::String(x)) = gem_bs.get_config(Section::Extract, name ) { x } else { "." } };
-	let extract_dir = get_dir("extract_dir").to_owned();
-	let cores = gem_bs.get_config_int(Section::Extract, "cores").map(|x| x as usize).or(Some(2));
-	let memory = gem_bs.get_config_memsize(Section::Extract, "memory");
-	let time = gem_bs.get_config_joblen(Section::Extract, "time").or_else(|| Some(3600.into()));
-	let bw_strand_specific = gem_bs.get_config_bool(Section::Extract, "bigwig_strand_specific");
-	let non_cpg = gem_bs.get_config_bool(Section::Extract, "make_non_cpg");
-	let snps = gem_bs.get_config_bool(Section::Extract, "make_snps");
-	let bedmethyl = gem_bs.get_config_bool(Section::Extract, "make_bedmethyl");
-	let cpg = gem_bs.get_config_bool(Section::Extract, "make_cpg")  || !(non_cpg || bedmethyl || snps);
-	let samples = gem_bs.get_samples();
-	let mut snpxtr_inputs = Vec::new();
-	if let Some(x) = gem_bs.get_asset("dbsnp_index") { snpxtr_inputs.push(x.idx()) } 
-	let mut mextr_suff: Vec<&str> = Vec::new();
+// Optional additional bigWig tracks from mextr (see mextr --bw-tracks)
+const BW_TRACKS: [(&str, [&str; 2]); 4] = [
+	("depth", ["depth.bw", "depth.bw.md5"]),
+	("nonconv", ["nonconv.bw", "nonconv.bw.md5"]),
+	("ci_low", ["ci_low.bw", "ci_low.bw.md5"]),
+	("ci_high", ["ci_high.bw", "ci_high.bw.md5"]),
+];
+
+/// Contexts for the context bigWig files for a sample (see mextr --bw-contexts).  Patterns are made from IUPAC
+/// codes with 2 or 3 bases, the first of which must match C.  Duplicates are removed
+pub fn get_bw_contexts(gem_bs: &GemBS, bc: &str) -> Result<Vec<String>, String> {
+	let s = gem_bs.get_sample_config_str(bc, Section::Extract, "context_bigwig_contexts").unwrap_or("CHG,CHH");
+	let mut v: Vec<String> = Vec::new();
+	for ctx in s.split(',').map(|x| x.trim().to_ascii_uppercase()).filter(|x| !x.is_empty()) {
+		let valid = (ctx.len() == 2 || ctx.len() == 3) && ctx.bytes().all(|c| b"ACGTRYSWKMBDHVN".contains(&c)) 
+			&& b"CYSMBHVN".contains(&ctx.as_bytes()[0]);
+		if !valid { return Err(format!("Illegal context pattern '{}' in context_bigwig_contexts", ctx)) }
+		if !v.contains(&ctx) { v.push(ctx) }
+	}
+	if v.is_empty() { Err(format!("No contexts specified in context_bigwig_contexts for sample {}", bc)) } else { Ok(v) }
+}
+
+// Outputs from mextr and snpxtr for a sample.  These can differ between samples as the extract
+// parameters can be set per sample or group
+struct ExtractOutputs {
+	mextr_suff: Vec<String>,
+	mextr_comm: String,
+	snpxtr_suff: Vec<&'static str>,
+	haplotype: bool,
+	from_pools: bool,
+}
+
+fn get_extract_outputs(gem_bs: &GemBS, bc: &str, multiple_pools: bool) -> Result<ExtractOutputs, String> {
+	let get_bool = |name: &str| gem_bs.get_sample_config_bool(bc, Section::Extract, name);
+	let bw_strand_specific = get_bool("bigwig_strand_specific");
+	let non_cpg = get_bool("make_non_cpg");
+	let snps = get_bool("make_snps");
+	let bedmethyl = get_bool("make_bedmethyl");
+	let haplotype = get_bool("make_haplotype");
+	let allele_report = get_bool("make_allele_report");
+	let context_summary = get_bool("make_context_summary");
+	let context_bw = get_bool("make_context_bigwig");
+	let cpg = get_bool("make_cpg")  || !(non_cpg || bedmethyl || snps || haplotype || allele_report || context_summary || context_bw);
+	// Read the per pool BCFs directly, skipping the BCF merge and index steps.  Extraction still starts only
+	// when all pools have been called.  Not possible for bigBed/bigWig output as the contigs would not be in sorted order
+	let from_pools = get_bool("extract_from_pools") && multiple_pools;
+	if from_pools && (bedmethyl || haplotype || context_bw) { warn!("Option extract_from_pools ignored for sample {} as incompatible with bedMethyl, haplotype and context bigWig output", bc) }
+	let from_pools = from_pools && !(bedmethyl || haplotype || context_bw);
+	// Pool BCFs are not recalibrated (recalibration is performed on the merged BCF)
+	let recal = gem_bs.get_sample_config_bool(bc, Section::Calling, "recalibrate");
+	if from_pools && recal { warn!("Option extract_from_pools ignored for sample {} as variant recalibration requires the merged BCF", bc) }
+	let from_pools = from_pools && !recal;
+	let mut mextr_suff: Vec<String> = Vec::new();
 	let mut mextr_comm = String::new();
 	if cpg { 
-		["cpg.txt.gz", "cpg.txt.gz.tbi", "cpg.txt.gz.md5"].iter().for_each(|x| mextr_suff.push(x));
+		["cpg.txt.gz", "cpg.txt.gz.tbi", "cpg.txt.gz.md5"].iter().for_each(|x| mextr_suff.push(x.to_string()));
 		mextr_comm.push_str(" --cpg");
 	}
 	if non_cpg {
-		["non_cpg.txt.gz", "non_cpg.txt.gz.tbi", "non_cpg.txt.gz.md5"].iter().for_each(|x| mextr_suff.push(x));
+		["non_cpg.txt.gz", "non_cpg.txt.gz.tbi", "non_cpg.txt.gz.md5"].iter().for_each(|x| mextr_suff.push(x.to_string()));
 		mextr_comm.push_str(" --non-cpg");
 	}
 	if bedmethyl { 
 		["cpg.bed.gz", "cpg.bed.gz.md5", "cpg.bb", "cpg.bb.md5", 
 		 "chg.bed.gz", "chg.bed.gz.md5", "chg.bb", "chg.bb.md5",
-		 "chh.bed.gz", "chh.bed.gz.md5", "chh.bb", "chh.bb.md5",].iter().for_each(|x| mextr_suff.push(x));
+		 "chh.bed.gz", "chh.bed.gz.md5", "chh.bb", "chh.bb.md5",].iter().for_each(|x| mextr_suff.push(x.to_string()));
 		if bw_strand_specific { 
-			["pos.bw", "pos.bw.md5", "neg.bw", "neg.bw.md5"].iter().for_each(|x| mextr_suff.push(x)); 
-		} else { [".bw", ".bw.md5"].iter().for_each(|x| mextr_suff.push(x)); }
+			["pos.bw", "pos.bw.md5", "neg.bw", "neg.bw.md5"].iter().for_each(|x| mextr_suff.push(x.to_string())); 
+		} else { [".bw", ".bw.md5"].iter().for_each(|x| mextr_suff.push(x.to_string())); }
+		if let Some(s) = gem_bs.get_sample_config_str(bc, Section::Extract, "bigwig_tracks") {
+			for tr in s.split(',').map(|x| x.trim().to_ascii_lowercase()).filter(|x| !x.is_empty()) {
+				match BW_TRACKS.iter().find(|(n, _)| *n == tr) {
+					Some((_, v)) => v.iter().for_each(|x| if !mextr_suff.iter().any(|y| y == x) { mextr_suff.push(x.to_string()) }),
+					None => return Err(format!("Unknown bigWig track '{}' in bigwig_tracks (possible values: depth, nonconv, ci_low, ci_high)", tr)),
+				}
+			}
+		}
 		mextr_comm.push_str(" --bed-methyl");
 	}
+	if haplotype {
+		["haplotype.txt.gz", "haplotype.txt.gz.tbi", "haplotype.txt.gz.md5", 
+		 "mhl.bw", "mhl.bw.md5", "pdr.bw", "pdr.bw.md5", 
+		 "epipoly.bw", "epipoly.bw.md5", "entropy.bw", "entropy.bw.md5"].iter().for_each(|x| mextr_suff.push(x.to_string()));
+		mextr_comm.push_str(" --haplotype");
+	}
+	if context_summary {
+		["context_summary.txt", "context_summary.txt.md5"].iter().for_each(|x| mextr_suff.push(x.to_string()));
+		mextr_comm.push_str(" --context-summary");
+	}
+	if context_bw {
+		for ctx in get_bw_contexts(gem_bs, bc)?.iter() {
+			mextr_suff.push(format!("context_{}.bw", ctx));
+			mextr_suff.push(format!("context_{}.bw.md5", ctx));
+		}
+		mextr_comm.push_str(" --context-bigwig");
+	}
+	if allele_report {
+		["allele_report.txt.gz", "allele_report.txt.gz.tbi", "allele_report.txt.gz.md5"].iter().for_each(|x| mextr_suff.push(x.to_string()));
+		mextr_comm.push_str(" --allele-report");
+	}
 	let mut snpxtr_suff: Vec<&str> = Vec::new();
 	if snps { ["snps.txt.gz", "snps.txt.gz.md5", "snps.txt.gz.tbi"].iter().for_each(|x| snpxtr_suff.push(x)) }
+	Ok(ExtractOutputs{mextr_suff, mextr_comm, snpxtr_suff, haplotype, from_pools})
+}
+
+pub fn check_extract(gem_bs: &mut GemBS) -> Result<(), String> {
+	let get_dir = |name: &str| { if let Some(DataValue::String(x)) = gem_bs.get_config(Section::Extract, name ) { x } else { "." } };
+	let extract_dir = get_dir("extract_dir").to_owned();
+	let cores = gem_bs.get_config_int(Section::Extract, "cores").map(|x| x as usize).or(Some(2));
+	let memory = gem_bs.get_config_memsize(Section::Extract, "memory");
+	let time = gem_bs.get_config_joblen(Section::Extract, "time").or_else(|| Some(3600.into()));
+	let bam_ext = if gem_bs.get_config_bool(Section::Mapping, "make_cram") { "cram" } else { "bam" };
+	let pools = contig::get_ordered_contig_pools(gem_bs);
+	let samples = gem_bs.get_samples();
+	let mut snpxtr_inputs = Vec::new();
+	if let Some(x) = gem_bs.get_asset("dbsnp_index") { snpxtr_inputs.push(x.idx()) } 
 	let snp_list = if let Some(DataValue::String(s)) = gem_bs.get_config(Section::Index, "snp_list") { Some(PathBuf::from(s)) } else { None };
 	if let Some(p) = snp_list { snpxtr_inputs.push(gem_bs.insert_asset("snp_list", &p, AssetType::Supplied)); }
 	let handle_file = |gb: &mut GemBS, nm: String, p: &Path| {
@@ -51,6 +133,7 @@ pub fn check_extract(gem_bs: &mut GemBS) -> Result<(), String> {
 	}; 
 
 	for (bc, name) in samples.iter() {
+		let ExtractOutputs{mextr_suff, mextr_comm, snpxtr_suff, haplotype, from_pools} = get_extract_outputs(gem_bs, bc, pools.len() > 1)?;
 		let bcf = if let Some(x) = gem_bs.get_asset(format!("{}.bcf", bc).as_str()) { x.idx() } 
 		else { panic!("alignment file {}.bcf not found", bc); };
 		let bcf_ix = if let Some(x) = gem_bs.get_asset(format!("{}.bcf.csi", bc).as_str()) { x.idx() } 
@@ -68,11 +151,22 @@ pub fn check_extract(gem_bs: &mut GemBS) -> Result<(), String> {
 			let id = format!("mextr_{}", bc);
 			let (lname, lpath) = assets::make_ext_asset(&id, extract_path, "log");
 			let log_index = gem_bs.insert_asset(&lname, &lpath, AssetType::Log);				
+			let mut in_vec = if from_pools {
+				pools.iter().map(|p| if let Some(x) = gem_bs.get_asset(format!("{}_{}.bcf", bc, p).as_str()) { x.idx() } 
+					else { panic!("BCF file {}_{}.bcf not found", bc, p) }).collect()
+			} else { vec!(bcf, bcf_ix, bcf_md5) };
+			let parents = if from_pools { in_vec.clone() } else { vec!(bcf) };
+			// Haplotype metrics require the aligned reads
+			if haplotype {
+				for f in &[format!("{}.{}", bc, bam_ext), format!("{}.{}.md5", bc, bam_ext)] {
+					if let Some(x) = gem_bs.get_asset(f.as_str()) { in_vec.push(x.idx()) } else { return Err(format!("Alignment file {} required for haplotype extraction not found", f)) }
+				}
+			}
 			let task = gem_bs.add_task(&id, format!("Extract methylation values for barcode {}", bc).as_str(),
 					Command::Extract, format!("{} --barcode {}", mextr_comm, bc).as_str());
-			gem_bs.add_task_inputs(task, &[bcf, bcf_ix, bcf_md5]).add_outputs(&out_vec).set_log(Some(log_index)).set_barcode(bc)
+			gem_bs.add_task_inputs(task, &in_vec).add_outputs(&out_vec).set_log(Some(log_index)).set_barcode(bc)
 				.add_cores(cores).add_memory(memory).add_time(time);
-			out_vec.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &[bcf]));
+			out_vec.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &parents));
 		}		
 		if !snpxtr_suff.is_empty() {
 			let mut out_vec = Vec::new();
@@ -10,6 +10,7 @@ use crate::common::defs::{Metadata, Section, DataValue, Command, FileType};
 use crate::common::assets;
 use crate::common::assets::{AssetType, GetAsset};
 use super::GemBS;
+use crate::scheduler::aligner::{get_aligner, check_trim_qc};
 
 struct TempSample<'a> {
 	name: Option<&'a str>,
@@ -81,12 +82,14 @@ pub fn check_map(gem_bs: &mut GemBS) -> Result<(), String> {
 	let time = gem_bs.get_config_joblen(Section::Mapping, "time").or_else(|| Some(21600.into()));
 	let merge_time = gem_bs.get_config_joblen(Section::Mapping, "merge_time").or(time);
 	let make_cram = gem_bs.get_config_bool(Section::Mapping, "make_cram");
+	let aligner = get_aligner(gem_bs)?;
 	let mut common_inputs = Vec::new();
 	if make_cram {
 			for f in &["gembs_reference", "gembs_reference_fai", "gembs_reference_gzi", "contig_md5"] {
 			if let Some(x) = gem_bs.get_asset(*f) { common_inputs.push(x.idx()) } else { panic!("{} not found", f) };
 		}
 	}
+	let genomes_id = gem_bs.get_asset("contig_genomes").map(|x| x.idx());
 	let index_id = gem_bs.get_asset("index").map(|x| x.idx());
 	let nonbs_index_id = gem_bs.get_asset("nonbs_index").map(|x| x.idx());
 	let (suffix, index_suff) = if make_cram { (".cram", ".cram.crai") } else { (".bam", ".bam.csi") };
@@ -110,6 +113,16 @@ pub fn check_map(gem_bs: &mut GemBS) -> Result<(), String> {
 		let sdir = replace_meta_var(&seq_dir);
 		let spath = Path::new(&sdir);
 		let mut bams = Vec::new();
+		// Trimming and QC can be set per sample
+		let trim = gem_bs.get_sample_config_bool(&sample.barcode, Section::Trimming, "trim");
+		// FASTQ QC is on by default (using read_trim in QC only mode if trimming is off) unless the aligner can not read from stdin
+		let qc_opt = if let Some(DataValue::Bool(x)) = gem_bs.get_sample_config(&sample.barcode, Section::Mapping, "fastq_qc") { Some(*x) } else { None };
+		let fastq_qc = qc_opt.unwrap_or_else(|| aligner.supports_stdin());
+		check_trim_qc(aligner.as_ref(), trim, qc_opt == Some(true))?;
+		// Per genome statistics (and disambiguation) require extra references
+		let genome_stats = gem_bs.get_sample_config_bool(&sample.barcode, Section::Mapping, "genome_stats") || gem_bs.get_sample_config_bool(&sample.barcode, Section::Mapping, "disambiguate");
+		if genome_stats && genomes_id.is_none() { warn!("Sample {}: genome statistics and disambiguation not available without extra_references", sample.barcode) }
+		let genome_stats = genome_stats && genomes_id.is_some();
 		for dat in sample.datasets.iter() {
 			let href = gem_bs.get_sample_data_ref();
 			let dvec: Vec<(Metadata, DataValue)> = if let Some(dr) = href.get(dat) {
@@ -131,6 +144,18 @@ pub fn check_map(gem_bs: &mut GemBS) -> Result<(), String> {
 					_ => (),
 				}
 			}
+			if let Some(ft) = file_type {
+				if !aligner.supports_input(*ft) { return Err(format!("Dataset {}: file type {} is not supported by the {} aligner", dat, ft, aligner.name())) }
+			}
+			// Trimming and QC require FASTQ input
+			let (trimmed, qc) = match file_type {
+				Some(ft @ FileType::BAM) | Some(ft @ FileType::Stream) => {
+					if trim || qc_opt == Some(true) { warn!("Dataset {}: trimming and QC not available for file type {}", dat, ft) }
+					(false, false)
+				},
+				_ => (trim, fastq_qc),
+			};
+			let (command, desc) = if trimmed { (Command::Trim, "Trim and map") } else { (Command::Map, "Map") };
 			// If no data files specified, look for files based on Dataset or AltDataset
 			if in_vec.is_empty() {
 				let mut thash = HashMap::new();
@@ -176,31 +201,41 @@ pub fn check_map(gem_bs: &mut GemBS) -> Result<(), String> {
 			if in_vec.is_empty() { return Err(format!("No datafiles for dataset {}", dat)); }
 			if bisulfite { in_vec.push(index_id.unwrap()); } else { in_vec.push(nonbs_index_id.unwrap()); }
 			common_inputs.iter().for_each(|x| in_vec.push(*x));
+			if genome_stats { in_vec.push(genomes_id.unwrap()) }
 			
 			if sample.datasets.len() > 1 {
-				let out1 = handle_file(gem_bs, dat, format!("{}.bam", dat).as_str(), ".bam", &bpath, AssetType::Temp); 
+				// Intermediate files per dataset are CRAM in CRAM mode so that they are written directly by the mapping pipeline
+				let out1 = handle_file(gem_bs, dat, format!("{}{}", dat, suffix).as_str(), suffix, &bpath, AssetType::Temp); 
 				let out2 = handle_file(gem_bs, dat, format!("{}.json", dat).as_str(), "_map.json", &bpath, AssetType::Derived);
+				let mut outs = vec!(out1, out2);
+				if trimmed { outs.push(handle_file(gem_bs, dat, format!("{}_trim.json", dat).as_str(), "_trim.json", bpath, AssetType::Derived)) }
+				if qc { outs.push(handle_file(gem_bs, dat, format!("{}_qc.json", dat).as_str(), "_qc.json", bpath, AssetType::Derived)) }
+				if genome_stats { outs.push(handle_file(gem_bs, dat, format!("{}_genomes.json", dat).as_str(), "_genomes.json", bpath, AssetType::Derived)) }
 				let id = format!("map_{}", dat);
 				let (lname, lpath) = assets::make_ext_asset(&id, &bpath, "log");
 				let log_index = gem_bs.insert_asset(&lname, &lpath, AssetType::Log);
-				let task = gem_bs.add_task(&id, format!("Map dataset {} for barcode {}", dat, sample.barcode).as_str(),
-					Command::Map, format!("--dataset {}", dat).as_str());
-				gem_bs.add_task_inputs(task, &in_vec).add_outputs(&[out1, out2]).set_log(Some(log_index)).set_barcode(&sample.barcode)
+				let task = gem_bs.add_task(&id, format!("{} dataset {} for barcode {}", desc, dat, sample.barcode).as_str(),
+					command, format!("--dataset {}", dat).as_str());
+				gem_bs.add_task_inputs(task, &in_vec).add_outputs(&outs).set_log(Some(log_index)).set_barcode(&sample.barcode)
 					.add_cores(cores).add_memory(memory).add_time(time);
-				[out1, out2].iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &in_vec));
+				outs.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &in_vec));
 				bams.push(out1);
 			} else {
 				let out1 = handle_file(gem_bs, &sample.barcode, format!("{}{}", sample.barcode, suffix).as_str(), suffix, &bpath, AssetType::Derived);
 				let out2 = handle_file(gem_bs, &sample.barcode, format!("{}.json", sample.barcode).as_str(), "_map.json", &bpath, AssetType::Derived);
 				let out3 = handle_file(gem_bs, &sample.barcode, format!("{}{}", sample.barcode, index_suff).as_str(), index_suff, &bpath, AssetType::Derived);
+				let mut outs = vec!(out1, out2, out3);
+				if trimmed { outs.push(handle_file(gem_bs, dat, format!("{}_trim.json", dat).as_str(), "_trim.json", bpath, AssetType::Derived)) }
+				if qc { outs.push(handle_file(gem_bs, dat, format!("{}_qc.json", dat).as_str(), "_qc.json", bpath, AssetType::Derived)) }
+				if genome_stats { outs.push(handle_file(gem_bs, dat, format!("{}_genomes.json", dat).as_str(), "_genomes.json", bpath, AssetType::Derived)) }
 				let id = format!("single_map_{}", sample.barcode);
 				let (lname, lpath) = assets::make_ext_asset(&id, &bpath, "log");
 				let log_index = gem_bs.insert_asset(&lname, &lpath, AssetType::Log);				
-				let task = gem_bs.add_task(&id, format!("Map dataset {} for barcode {}", dat, sample.barcode).as_str(),
-					Command::Map, format!("--barcode {} --no-md5", sample.barcode).as_str());
-				gem_bs.add_task_inputs(task, &in_vec).add_outputs(&[out1, out2, out3]).set_log(Some(log_index)).set_barcode(&sample.barcode)
+				let task = gem_bs.add_task(&id, format!("{} dataset {} for barcode {}", desc, dat, sample.barcode).as_str(),
+					command, format!("--barcode {} --no-md5", sample.barcode).as_str());
+				gem_bs.add_task_inputs(task, &in_vec).add_outputs(&outs).set_log(Some(log_index)).set_barcode(&sample.barcode)
 					.add_cores(cores).add_memory(memory).add_time(time);
-				[out1, out2, out3].iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &in_vec));
+				outs.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &in_vec));
 				let (md5_name, md5_path)  = assets::make_ext_asset(gem_bs.get_asset_mut(out1).unwrap().id(), &bpath, "md5");
 				let md5 = gem_bs.insert_asset(&md5_name, &md5_path, AssetType::Derived);
 				let md5_task = gem_bs.add_task(&md5_name, format!("Calc MD5 sum for {}", id).as_str(),
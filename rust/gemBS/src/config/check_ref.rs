@@ -9,41 +9,86 @@ use crate::common::assets;
 use utils::compress;
 use crate::common::assets::{AssetType, GetAsset};
 use super::md5_fasta;
+use crate::scheduler::aligner::get_aligner;
 
 use std::str::FromStr;
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::io::{BufRead, BufWriter, Write};
 use glob::glob;
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap};
 
-fn check_ref(gem_bs: &mut GemBS) -> Result<(), String> {
+// Extra references can be a single file (older configurations) or a list of files
+fn get_extra_references(gem_bs: &GemBS) -> Vec<String> {
+	match gem_bs.get_config(Section::Index, "extra_references") {
+		Some(DataValue::StringVec(v)) => v.clone(),
+		Some(DataValue::String(s)) => vec!(s.clone()),
+		_ => Vec::new(),
+	}
+}
+
+// Default genome name from FASTA file name (i.e., hg38.fa.gz -> hg38)
+fn genome_name(file: &str) -> String {
+	let mut name = Path::new(file).file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or_else(|| file.to_owned());
+	for ext in &[".gz", ".bgz", ".xz", ".bz2", ".fa", ".fasta", ".fna"] {
+		if let Some(x) = name.strip_suffix(ext) { name = x.to_owned() }
+	}
+	name
+}
+
+// Returns the list of contigs from the extra references with the genome name for each contig
+fn check_ref(gem_bs: &mut GemBS) -> Result<Vec<(String, String)>, String> {
 	// Check reference file
 	let reference = gem_bs.get_reference()?;
 	let tpath = PathBuf::from(reference);
 	if !tpath.exists() { return Err(format!("Reference file {} does not exist or is not accessible", reference)); }
 	debug!("Reference file {} found", reference);
+	let primary = gem_bs.get_config_str(Section::Index, "reference_name").map(|x| x.to_owned()).unwrap_or_else(|| genome_name(reference));
 	gem_bs.insert_asset("reference", &tpath, AssetType::Supplied);
-	// Check extra references - these are not required, but if specified in the config file, the file should be present
-	let extra_ref = gem_bs.get_config(Section::Index, "extra_references").cloned();
-	if let Some(DataValue::String(ref_file)) = extra_ref {
+	gem_bs.set_config(Section::Index, "reference_name", DataValue::String(primary.clone()));
+	// Check extra references - these are not required, but if specified in the config file, the files should be present
+	let extra_refs = get_extra_references(gem_bs);
+	let names = match gem_bs.get_config_stringvec(Section::Index, "extra_reference_names") {
+		Some(v) => {
+			if v.len() != extra_refs.len() { return Err(format!("Number of extra_reference_names ({}) does not match number of extra_references ({})", v.len(), extra_refs.len())) }
+			v.clone()
+		},
+		None => extra_refs.iter().map(|x| genome_name(x)).collect(),
+	};
+	for (ix, name) in names.iter().enumerate() {
+		if name == &primary || names[..ix].contains(name) { return Err(format!("Genome name {} used more than once", name)) }
+	}
+	let mut extra_ctgs = Vec::new();
+	let mut ctg_genome: HashMap<String, &str> = HashMap::new();
+	for (ix, (ref_file, name)) in extra_refs.iter().zip(names.iter()).enumerate() {
 		gem_bs.check_signal()?;
-		let tpath = Path::new(&ref_file);
+		let tpath = Path::new(ref_file);
 		if !tpath.exists() { return Err(format!("Extra references file {} does not exist or is not accessible", ref_file)); }
 		debug!("Extra references file {} found", ref_file);
-		gem_bs.insert_asset("extra_reference", tpath, AssetType::Supplied);
+		gem_bs.insert_asset(format!("extra_reference_{}", ix + 1), tpath, AssetType::Supplied);
 		trace!("Getting names of contigs in extra references file {}", ref_file);
 		let rdr = compress::open_bufreader(tpath).map_err(|x| format!("{}", x))?;
-		let mut omit_ctgs = Vec::new();
-		for line in rdr.lines() {
-			if let Ok(s) = line {
-				if s.starts_with('>') { omit_ctgs.push(s.trim_start_matches('>').to_string()) }
+		for s in rdr.lines().map_while(Result::ok) {
+			if let Some(ctg) = s.strip_prefix('>').and_then(|x| x.split_whitespace().next()) {
+				if let Some(g) = ctg_genome.insert(ctg.to_owned(), name) { return Err(format!("Contig {} found in genomes {} and {}", ctg, g, name)) }
+				extra_ctgs.push((ctg.to_owned(), name.clone()))
 			}
 		}
-		if !omit_ctgs.is_empty() { gem_bs.set_config(Section::Index, "omit_ctgs", DataValue::StringVec(omit_ctgs)); }
-	} 	
+	}
+	// Contigs from extra genomes are omitted from calling and extraction unless the genome is in call_genomes
+	let check_names = |var: &str| -> Result<Vec<String>, String> {
+		let v = gem_bs.get_config_stringvec(Section::Index, var).cloned().unwrap_or_default();
+		for g in v.iter() {
+			if g != &primary && !names.contains(g) { return Err(format!("Unknown genome {} in {} (known genomes: {}, {})", g, var, primary, names.join(", "))) }
+		}
+		Ok(v)
+	};
+	let call_genomes = check_names("call_genomes")?;
+	if check_names("xenograft_genomes")?.contains(&primary) { return Err(format!("Primary genome {} can not be a xenograft genome", primary)) }
+	let omit_ctgs: Vec<_> = extra_ctgs.iter().filter(|(_, g)| !call_genomes.contains(g)).map(|(c, _)| c.clone()).collect();
+	if !omit_ctgs.is_empty() { gem_bs.set_config(Section::Index, "omit_ctgs", DataValue::StringVec(omit_ctgs)); }
 	gem_bs.check_signal()?;
-	Ok(())
+	Ok(extra_ctgs)
 }
 
 fn check_index_requirements(gem_bs: &GemBS) -> (bool, bool) {
@@ -61,6 +106,7 @@ fn check_index_requirements(gem_bs: &GemBS) -> (bool, bool) {
 fn check_indices(gem_bs: &mut GemBS) -> Result<(), String> {	
 
 	let reference = gem_bs.get_reference()?;
+	let aligner = get_aligner(gem_bs)?;
 	let (need_bs_index, need_nonbs_index) = check_index_requirements(gem_bs);
 	
 	// Check index and indexdir.  One of these at least should exist and then the other can be inferred.
@@ -96,13 +142,13 @@ fn check_indices(gem_bs: &mut GemBS) -> Result<(), String> {
 	} 
 	if missing_index || missing_nonbs_index {			
 		if missing_index {
-			let tpath = Path::new(Path::new(reference).file_stem().unwrap()).with_extension("BS.gem");	
+			let tpath = Path::new(Path::new(reference).file_stem().unwrap()).with_extension(aligner.index_ext(true));	
 			let mut idx = idx_dir.clone();
 			idx.push(tpath);		
 			infer_idx = Some(idx.to_str().unwrap().to_string());
 		}
 		if missing_nonbs_index {
-			let tpath = Path::new(Path::new(reference).file_stem().unwrap()).with_extension("gem");	
+			let tpath = Path::new(Path::new(reference).file_stem().unwrap()).with_extension(aligner.index_ext(false));	
 			let mut idx = idx_dir;
 			idx.push(tpath);		
 			infer_nonbs_idx = Some(idx.to_str().unwrap().to_string());
@@ -221,15 +267,17 @@ fn make_gem_ref(gem_bs: &mut GemBS) -> Result<(), String> {
 	let tpath = Path::new(Path::new(reference).file_stem().unwrap()).with_extension("gemBS.contig_md5");
 	let mut ctg_md5 = PathBuf::from(index_dir);
 	ctg_md5.push(tpath);
-	let mut populate_cache = gem_bs.get_config_bool(Section::Index, "populate_cache");
+	let use_cache = md5_fasta::use_ref_cache(gem_bs);
+	let mut populate_cache = use_cache;
 	// Create gemBS reference if it does not already exist		
 	if !(gref.exists() && ctg_md5.exists()) {
 		gem_bs.check_signal()?;
 		info!("Creating gemBS compressed reference and calculating md5 sums of contigs");
 		let _ = fs::remove_file(&gref_fai);
 		let _ = fs::remove_file(&gref_gzi);
+		let extra_refs = get_extra_references(gem_bs);
 		let mut in_vec = vec!(reference);
-		if let Some(s) = gem_bs.get_config_str(Section::Index, "extra_references") { in_vec.push(s); }
+		extra_refs.iter().for_each(|s| in_vec.push(s));
 		if let Err(e) = md5_fasta::md5_fasta(gem_bs, &in_vec, &gref, &ctg_md5) {
 			debug!("Generation of gemBS compressed reference failed - removing output files");
 			let _ = fs::remove_file(&gref);
@@ -250,6 +298,8 @@ fn make_gem_ref(gem_bs: &mut GemBS) -> Result<(), String> {
 		pipeline.run(gem_bs.get_signal_clone())?;
 	}
 	if populate_cache { md5_fasta::check_reference_cache(gem_bs, &gref, &ctg_md5)?; }
+	// Child processes (samtools, bs_call etc.) find the reference for CRAM files through the cache
+	if use_cache { md5_fasta::setup_ref_cache_env(gem_bs) }
 	gem_bs.insert_asset("gembs_reference", &gref, AssetType::Derived);			
 	gem_bs.insert_asset("gembs_reference_fai", &gref_fai, AssetType::Derived);			
 	gem_bs.insert_asset("gembs_reference_gzi", &gref_gzi, AssetType::Derived);			
@@ -257,6 +307,22 @@ fn make_gem_ref(gem_bs: &mut GemBS) -> Result<(), String> {
 	gem_bs.check_signal()
 }
 
+// Write file with the genome for each contig from the extra references (used for per genome statistics and disambiguation).
+// The file is only rewritten if the contents change so that mapping tasks are not rerun unnecessarily
+fn make_genome_file(gem_bs: &mut GemBS, extra_ctgs: &[(String, String)]) -> Result<(), String> {
+	if extra_ctgs.is_empty() { return Ok(()) }
+	let reference = gem_bs.get_reference()?;
+	let index_dir = if let Some(DataValue::String(idx)) = gem_bs.get_config(Section::Index, "index_dir") { idx } else { panic!("Internal error - missing index_dir") }; 
+	let genome_file: PathBuf = [Path::new(index_dir), &Path::new(Path::new(reference).file_stem().unwrap()).with_extension("gemBS.genomes")].iter().collect();
+	let contents = extra_ctgs.iter().fold(String::new(), |mut s, (c, g)| { s.push_str(&format!("{}\t{}\n", c, g)); s });
+	if fs::read_to_string(&genome_file).map(|s| s != contents).unwrap_or(true) {
+		info!("Creating genome file {}", genome_file.to_string_lossy());
+		fs::write(&genome_file, contents).map_err(|e| format!("Couldn't write genome file {}: {}", genome_file.to_string_lossy(), e))?;
+	}
+	gem_bs.insert_asset("contig_genomes", &genome_file, AssetType::Derived);
+	gem_bs.check_signal()
+}
+
 fn add_make_index_task(gem_bs: &mut GemBS, idx_name: &str, desc: &str, command: &str) {
 	let gref = if let Some(x) = gem_bs.get_asset("gembs_reference") { x.idx() } else { panic!("gembs_reference not found")};
 	let index_asset = if let Some(x) = gem_bs.get_asset(idx_name) { x } else { panic!("{} not found", idx_name)};
@@ -274,15 +340,16 @@ fn add_make_index_task(gem_bs: &mut GemBS, idx_name: &str, desc: &str, command:
 }
 
 fn make_index_tasks(gem_bs: &mut GemBS) -> Result<(), String> {
+	let aligner = get_aligner(gem_bs)?;
 	match gem_bs.get_config(Section::Index, "need_bs_index") {
 		Some(DataValue::Bool(x)) => {
-			if *x { add_make_index_task(gem_bs, "index", "Make GEM3 bisulfite index", "--bs-index"); }			
+			if *x { add_make_index_task(gem_bs, "index", &format!("Make {} bisulfite index", aligner.name()), "--bs-index"); }			
 		},
 		_ => panic!("No value stored for need_bs_index"),
 	}
 	match gem_bs.get_config(Section::Index, "need_nonbs_index") {
 		Some(DataValue::Bool(x)) => {
-			if *x { add_make_index_task(gem_bs, "nonbs_index", "Make GEM3 non-bisulfite bisulfite index", "--nonbs-index"); }	
+			if *x { add_make_index_task(gem_bs, "nonbs_index", &format!("Make {} non-bisulfite index", aligner.name()), "--nonbs-index"); }	
 		},
 		_ => panic!("No value stored for need_nonbs_index"),
 	}
@@ -290,9 +357,10 @@ fn make_index_tasks(gem_bs: &mut GemBS) -> Result<(), String> {
 }
 
 pub fn check_ref_and_indices(gem_bs: &mut GemBS) -> Result<(), String> {
-	check_ref(gem_bs)?;
+	let extra_ctgs = check_ref(gem_bs)?;
 	check_indices(gem_bs)?;
 	make_gem_ref(gem_bs)?;
+	make_genome_file(gem_bs, &extra_ctgs)?;
 	add_index_assets(gem_bs)?;
 	check_dbsnp_ref(gem_bs)?;
 	make_index_tasks(gem_bs)
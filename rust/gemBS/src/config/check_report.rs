@@ -55,6 +55,30 @@ pub fn check_map_report(gem_bs: &mut GemBS) -> Result<(), String> {
 			out_vec.push(handle_file(gem_bs, format!("{}_isize.png", dset).as_str(), format!("{}_isize.png", dset).as_str(), &img_dir));
 		}
 	}	
+	// Trimming and genome assignment statistics are also included in the report if available
+	for (_, dset) in dsets.iter() {
+		if let Some(x) = gem_bs.get_asset(format!("{}_trim.json", dset).as_str()) { json_files.push(x.idx()) }
+		if let Some(x) = gem_bs.get_asset(format!("{}_genomes.json", dset).as_str()) { json_files.push(x.idx()) }
+	}
+	// In CRAM mode the storage statistics for the sample CRAM files are reported
+	for bc in bc_count.keys() {
+		if let Some(x) = gem_bs.get_asset(format!("{}.cram", bc).as_str()) { json_files.push(x.idx()) }
+	}
+	// As are read QC metrics, with base composition plots for each sample (and dataset if more than one per sample)
+	let mut qc_bcodes = Vec::new();
+	for (bc, dset) in dsets.iter() {
+		if let Some(x) = gem_bs.get_asset(format!("{}_qc.json", dset).as_str()).map(|x| x.idx()) {
+			json_files.push(x);
+			let img_dir: PathBuf = [&report_dir, Path::new(bc), Path::new("images")].iter().collect();
+			if *bc_count.get(bc).expect("No count found for barcode") > 1 {
+				out_vec.push(handle_file(gem_bs, format!("{}_base_comp.png", dset).as_str(), format!("{}_base_comp.png", dset).as_str(), &img_dir));
+			}
+			if !qc_bcodes.contains(&bc) {
+				out_vec.push(handle_file(gem_bs, format!("{}_base_comp.png", bc).as_str(), format!("{}_base_comp.png", bc).as_str(), &img_dir));
+				qc_bcodes.push(bc);
+			}
+		}
+	}
 	let task = gem_bs.add_task("map_report", "Generate mapping report", Command::MapReport, "");
 	gem_bs.add_task_inputs(task, &json_files).add_outputs(&out_vec).add_cores(cores).add_memory(memory).add_time(time);
 	out_vec.iter().for_each(|id| gem_bs.get_asset_mut(*id).unwrap().set_creator(task, &json_files));
@@ -144,3 +144,10 @@ pub fn get_contig_pools(gem_bs: &GemBS) -> Vec<Rc<String>> {
 	pools
 }
 
+// Contig pools ordered by the position of their first contig in the contig definitions
+pub fn get_ordered_contig_pools(gem_bs: &GemBS) -> Vec<Rc<String>> {
+	let hr = gem_bs.get_contig_pool_hash();
+	let mut pools: Vec<_> = hr.iter().map(|(key, pool)| (pool.contigs.iter().min().copied().unwrap_or(usize::MAX), key.clone())).collect();
+	pools.sort();
+	pools.drain(..).map(|(_, key)| key).collect()
+}
@@ -20,9 +20,10 @@ fn get_env_var<K: AsRef<OsStr>>(key: K) -> Option<String> {
 }
 
 // Follows the logic in htslib/cram/cram_io.c to find
-// the path for the reference cache
-fn get_cache_path() -> String {
-	if let Some(x) = get_env_var("REF_CACHE") { x }
+// the path for the reference cache unless ref_cache is set in the config
+fn get_cache_path(gem_bs: &GemBS) -> String {
+	if let Some(x) = gem_bs.get_config_str(Section::Index, "ref_cache") { format!("{}/%2s/%2s/%s", x.trim_end_matches('/')) }
+	else if let Some(x) = get_env_var("REF_CACHE") { x }
 	else {
 		let base = if let Some(x) = get_env_var("XDG_CACHE_HOME") { x }
 		else if let Some(mut x) = get_env_var("HOME") { x.push_str("/.cache"); x }
@@ -33,6 +34,52 @@ fn get_cache_path() -> String {
 	} 
 }
 
+// The reference cache is always used when making CRAM files
+pub fn use_ref_cache(gem_bs: &GemBS) -> bool {
+	gem_bs.get_config_bool(Section::Index, "populate_cache") || gem_bs.get_config_bool(Section::Mapping, "make_cram")
+}
+
+// Point htslib in all child processes at the reference cache so that CRAM files are encoded and decoded
+// against the project reference without remote lookups
+pub fn setup_ref_cache_env(gem_bs: &GemBS) {
+	let cp = get_cache_path(gem_bs);
+	let ref_path = match get_env_var("REF_PATH") {
+		Some(x) if !x.split(':').any(|s| s == cp) => format!("{}:{}", cp, x),
+		Some(x) => x,
+		None => cp.clone(),
+	};
+	debug!("Setting REF_CACHE={} REF_PATH={}", cp, ref_path);
+	env::set_var("REF_CACHE", &cp);
+	env::set_var("REF_PATH", &ref_path);
+}
+
+// Cache files are written to a temporary file and renamed so that interrupted writes do not leave truncated entries
+fn tmp_cache_name(fname: &Path) -> PathBuf {
+	let mut s = fname.as_os_str().to_owned();
+	s.push(".gemBS.tmp");
+	PathBuf::from(s)
+}
+
+fn write_cache_file(fname: &Path, buf: &[u8]) -> std::io::Result<()> {
+	// Make cache directories if required
+	if let Some(d) = &fname.parent() { fs::create_dir_all(d)? }
+	let tmp = tmp_cache_name(fname);
+	let res = compress::open_bufwriter(&tmp).and_then(|mut wrt| { wrt.write_all(buf)?; wrt.flush() }).and_then(|_| fs::rename(&tmp, fname));
+	if res.is_err() { let _ = fs::remove_file(&tmp); }
+	res
+}
+
+// Existing cache files are only accepted if their size matches the contig length
+fn cache_ok(fname: &Path, len: usize) -> bool {
+	match fs::metadata(fname) {
+		Ok(m) => if m.len() as usize == len { true } else {
+			warn!("Reference cache file {} has incorrect size - will be regenerated", fname.display());
+			false
+		},
+		Err(_) => false,
+	}
+}
+
 // For the MD5 calculation we skip everything with ascii value < 33 or > 126
 // and all letters should be uppercase.  The quickest way to do this is with
 // a lookup table
@@ -92,17 +139,12 @@ impl Md5Contig {
 	}
 	fn handle_cache(&self, cache_path: &str, cache_buf: Vec<u8>) -> std::io::Result<Vec<u8>> {
 		let fname = get_fname(cache_path, format!("{}", self.md5).as_str()).map_err(|e| Error::new(ErrorKind::Other, e))?;
-		if !fname.exists() {
-			// Make cache directories if required
-			if let Some(d) = &fname.parent() { fs::create_dir_all(d)? }
-			let mut wrt = compress::open_bufwriter(&fname)?;
-			wrt.write_all(&cache_buf)?;
-		}
+		if !cache_ok(&fname, cache_buf.len()) { write_cache_file(&fname, &cache_buf)?; }
 		Ok(cache_buf)
 	}
 	fn check_cache<T: AsRef<Path>>(&self, gem_bs: &GemBS, path_str: &str, gref: T) -> Result<(), String> {
 		let fname = get_fname(path_str, format!("{}", self.md5).as_str())?;
-		if !fname.exists() {
+		if !cache_ok(&fname, self.len) {
 			// Read in sequence information from gref
 			let faidx_args = vec!("faidx", gref.as_ref().to_str().unwrap(), &self.name);
 			let samtools_path = gem_bs.get_exec_path("samtools");
@@ -131,10 +173,8 @@ impl Md5Contig {
 					Err(e) => return Err(format!("Error reading from file {}: {}", gref.as_ref().display(), e)),
 				}
 			}
-			// Make cache directories if required
-			if let Some(d) = &fname.parent() { fs::create_dir_all(d).map_err(|e| format!("Couldn't create cache directories for {}: {}", &fname.display(), e))?; }
-			let mut wrt = compress::open_bufwriter(&fname).map_err(|e| format!("Couldn't open cache file {} for writing: {}", &fname.display(), e))?;
-			wrt.write_all(&buf).map_err(|e| format!("Error writing to cache file {}: {}", &fname.display(), e))?;			
+			if buf.len() != self.len { return Err(format!("Length of contig {} from {} ({}) does not match expected length ({})", self.name, gref.as_ref().display(), buf.len(), self.len)) }
+			write_cache_file(&fname, &buf).map_err(|e| format!("Error writing to cache file {}: {}", &fname.display(), e))?;
 		}
 		Ok(())	
 	}
@@ -197,8 +237,8 @@ struct Md5Data {
 // same naming logic as htslib
 pub fn md5_fasta<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(gem_bs: &GemBS, in_files: &[P], output_ref: Q, ctg_md5: R) -> Result<(), String> {
 
-	let cache_path = if gem_bs.get_config_bool(Section::Index, "populate_cache") { 
-		let cp = get_cache_path();
+	let cache_path = if use_ref_cache(gem_bs) { 
+		let cp = get_cache_path(gem_bs);
 		debug!("reference cache_path = {}", cp);
 		Some(cp)
 	} else { None };
@@ -252,6 +292,7 @@ pub fn md5_fasta<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(gem_bs: &GemBS,
 		if let Some(mut c) = current_ctg.take() {
 			c.md5.set(&md5_hasher.finalize_reset())?;
 			c.process_ctg(&cache_path, &min_contig_size, &mut md5_data).map_err(|e| format!("{}", e))?;
+			if let Some(buf) = &mut md5_data.cache_buf { buf.clear() }
 		}	
 		debug!("File processed in {}ms", now.elapsed().as_millis());
 		// Only apply contig size limit to first file (main reference file)
@@ -279,7 +320,7 @@ fn get_from_hex<T: AsRef<[u8]>>(v: T) -> Result<Vec<u8>, String> {
 pub fn check_reference_cache<P: AsRef<Path>, Q: AsRef<Path>>(gem_bs: &GemBS, gref: P, ctg_md5: Q) -> Result<(), String> {
 	let mut rdr = compress::open_bufreader(ctg_md5.as_ref()).map_err(|x| format!("{}",x))?;
 	debug!("Checking reference cache");
-	let cp = get_cache_path();
+	let cp = get_cache_path(gem_bs);
 	let now = Instant::now();
 	let mut line = String::with_capacity(1024);
 	loop {
@@ -289,17 +330,17 @@ pub fn check_reference_cache<P: AsRef<Path>, Q: AsRef<Path>>(gem_bs: &GemBS, gre
 			Ok(_) => {
 				let mut iter = line.split_ascii_whitespace();
 				let e = if let Some(name) = iter.next() {
+					let mut c = Md5Contig::new(&name);
 					let mut found = false;
 					for f in iter {
 						if f.starts_with("M5:") && f.len() >= 35 {
-							let md5 = get_from_hex(&f.as_bytes()[3..])?;
-							let mut c = Md5Contig::new(&name);
-							c.md5.set(&md5)?;
-							c.check_cache(gem_bs, &cp, &gref)?;
+							c.md5.set(&get_from_hex(&f.as_bytes()[3..])?)?;
 							found = true;
-							break;
+						} else if let Some(l) = f.strip_prefix("LN:") {
+							c.len = <usize>::from_str(l).map_err(|e| format!("Error reading from file {}: {}", ctg_md5.as_ref().display(), e))?;
 						}
 					}
+					if found { c.check_cache(gem_bs, &cp, &gref)?; }
 					!found
 				} else { true };
 				if e  { return Err(format!("Error reading from file {}", ctg_md5.as_ref().display())) }
@@ -311,4 +352,21 @@ pub fn check_reference_cache<P: AsRef<Path>, Q: AsRef<Path>>(gem_bs: &GemBS, gre
 	debug!("Reference cache checked in {}ms", now.elapsed().as_millis());		
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+// Remove temporary cache files left by interrupted writes for the contigs of the gemBS reference
+pub fn clear_partial_cache<P: AsRef<Path>>(gem_bs: &GemBS, ctg_md5: P) -> Result<(), String> {
+	let rdr = compress::open_bufreader(ctg_md5.as_ref()).map_err(|e| format!("Couldn't open {}: {}", ctg_md5.as_ref().display(), e))?;
+	let cp = get_cache_path(gem_bs);
+	for line in rdr.lines() {
+		let line = line.map_err(|e| format!("Error reading from file {}: {}", ctg_md5.as_ref().display(), e))?;
+		if let Some(md5) = line.split_ascii_whitespace().find_map(|f| f.strip_prefix("M5:")) {
+			let tmp = tmp_cache_name(&get_fname(&cp, md5)?);
+			if tmp.exists() {
+				info!("Removing incomplete reference cache file {}", tmp.display());
+				let _ = fs::remove_file(&tmp);
+			}
+		}
+	}
+	Ok(())
+}
@@ -17,9 +17,12 @@ use crate::common::utils::{Pipeline, FileLock};
 use crate::common::utils;
 use crate::common::latex_utils::PageSize;
 use crate::common::assets::{GetAsset};
+use crate::common::provenance::TaskProvenance;
 use crate::commands::report::{make_map_report, make_call_report, make_report};
 
 use report::{MergeJsonFiles, SampleJsonFiles, CallJsonFiles};
+use aligner::MapStatsJob;
+use call::CramCheckJob;
 
 mod map;
 mod index;
@@ -27,6 +30,7 @@ mod md5sum;
 mod extract;
 pub mod call;
 pub mod report;
+pub mod aligner;
 
 #[derive(Debug)]
 struct RunJob {
@@ -35,6 +39,7 @@ struct RunJob {
 	path: PathBuf,	
 	runlist: Rc<RefCell<Vec<usize>>>,
 	signal: Arc<AtomicUsize>,
+	prov: Option<TaskProvenance>,
 }
 
 impl RunJob {
@@ -127,7 +132,7 @@ fn get_merge_bam_req(gem_bs: &GemBS) -> (f64, usize) {
 pub fn get_command_req(gem_bs: &GemBS, com: Command) -> (f64, usize) {
 	match com {
 		Command::Index => get_requirements(gem_bs, Section::Index, true),
-		Command::Map => get_requirements(gem_bs, Section::Mapping, true),
+		Command::Map | Command::Trim => get_requirements(gem_bs, Section::Mapping, true),
 		Command::Call => get_requirements(gem_bs, Section::Calling, false),
 		Command::IndexBcf => get_requirements(gem_bs, Section::Calling, false),
 		Command::Extract => get_requirements(gem_bs, Section::Extract, false),
@@ -222,7 +227,7 @@ impl<'a> Scheduler<'a> {
 			self.add_task(gem_bs, x)?;
 			let task = &gem_bs.get_tasks()[x];
 			let runlist = Rc::clone(&self.running);			
-			let rj = RunJob{id: task.id().to_string(), task_idx: x, path: self.lock.as_ref().unwrap().path().to_owned(), runlist, signal: gem_bs.get_signal_clone() };
+			let rj = RunJob{id: task.id().to_string(), task_idx: x, path: self.lock.as_ref().unwrap().path().to_owned(), runlist, signal: gem_bs.get_signal_clone(), prov: None };
 			self.drop_lock();
 			Ok(rj) 
 		} else {
@@ -248,6 +253,8 @@ pub enum QPipeCom {
 	CallReport((Option<String>, PathBuf, usize, Vec<CallJsonFiles>)),
 	Report((Option<String>, PageSize, bool)),
 	MergeCallJsons(MergeJsonFiles),
+	MapStats(MapStatsJob),
+	CheckCram(CramCheckJob),
 }
 
 #[derive(Debug)]
@@ -265,11 +272,13 @@ pub struct QPipe {
 	output: Option<PathBuf>,
 	log: Option<PathBuf>,
 	remove_log: bool,
+	post: Option<QPipeCom>, // Internal command run after successful completion of an external pipeline
+	pre: Option<QPipeCom>, // Internal command (i.e., input validation) run before the pipeline is started
 	sig: Arc<AtomicUsize>,
 } 
 
 impl QPipe {
-	pub fn new(sig: Arc<AtomicUsize>) -> Self { QPipe{ stages: QPipeStage::None, remove: Vec::new(), outputs: Vec::new(), output: None, log: None, remove_log: true, sig} }
+	pub fn new(sig: Arc<AtomicUsize>) -> Self { QPipe{ stages: QPipeStage::None, remove: Vec::new(), outputs: Vec::new(), output: None, log: None, remove_log: true, post: None, pre: None, sig} }
 	pub fn add_stage(&mut self, path: &Path, args: &str) -> &mut Self {
 		let stage = (path.to_owned(), args.to_owned());
 		match &mut self.stages {
@@ -290,41 +299,68 @@ impl QPipe {
 	pub fn get_remove_iter(&self) -> std::slice::Iter<'_, PathBuf> {self.remove.iter() }
 	pub fn get_outputs_iter(&self) -> std::slice::Iter<'_, PathBuf> {self.outputs.iter() }
 	pub fn set_output(&mut self, out: Option<PathBuf>) { self.output = out; }
+	pub fn set_post_com(&mut self, com: QPipeCom) { self.post = Some(com); }
+	pub fn set_pre_com(&mut self, com: QPipeCom) { self.pre = Some(com); }
+	// Description of the pipeline used for the provenance records
+	pub fn signature(&self) -> String {
+		match &self.stages {
+			QPipeStage::External(v) => v.iter().map(|(path, args)| format!("{} {}", path.display(), args.replace('\x1e', " ").trim_end())).collect::<Vec<_>>().join(" | "),
+			QPipeStage::Internal(com) => format!("{:?}", com),
+			QPipeStage::None => String::new(),
+		}
+	}
+}
+
+// Record provenance and parameters for the outputs of a job that has completed successfully
+fn record_completion(gem_bs: &mut GemBS, jobs: &mut [(RunJob, isize)], x: isize) {
+	if let Some((job, _)) = jobs.iter_mut().find(|(_, ix)| *ix == x) {
+		if let Some(tp) = job.prov.take() { gem_bs.record_task_provenance(tp) }
+		gem_bs.record_task_params(job.task_idx);
+		if let Err(e) = gem_bs.save_task_params() { warn!("Could not record task parameters: {}", e) }
+	}
 }
 
-fn handle_job(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, job: usize) -> QPipe {
+fn handle_job(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, job: usize) -> Result<QPipe, String> {
 	let task = &gem_bs.get_tasks()[job];
 	for p in task.outputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get output asset").path()) {
 		if let Some(par) = p.parent() {
 			fs::create_dir_all(par).expect("Could not create required output directories for command");
 		}
 	}
-	match task.command() {
-		Command::Index => index::make_index_pipeline(gem_bs, options, job),
-		Command::Map => map::make_map_pipeline(gem_bs, options, job),
+	Ok(match task.command() {
+		Command::Index => index::make_index_pipeline(gem_bs, options, job)?,
+		Command::Map | Command::Trim => map::make_map_pipeline(gem_bs, options, job)?,
 		Command::MergeBams => map::make_merge_bams_pipeline(gem_bs, options, job),
 		Command::Call => call::make_call_pipeline(gem_bs, job),
 		Command::MergeBcfs => call::make_merge_bcfs_pipeline(gem_bs, options, job),
 		Command::IndexBcf => call::make_index_bcf_pipeline(gem_bs, job),
 		Command::MD5SumMap | Command::MD5SumCall => md5sum::make_md5sum_pipeline(gem_bs, job),
-		Command::Extract => extract::make_extract_pipeline(gem_bs, job),
+		Command::Extract => extract::make_extract_pipeline(gem_bs, job)?,
 		Command::MapReport => report::make_map_report_pipeline(gem_bs, job),
 		Command::CallReport => report::make_call_report_pipeline(gem_bs, job),
 		Command::Report => report::make_report_pipeline(gem_bs, job),
 		Command::MergeCallJsons => report::make_merge_call_jsons_pipeline(gem_bs, job),
-	}
+	})
 }
 
 fn worker_thread(tx: mpsc::Sender<isize>, rx: mpsc::Receiver<Option<QPipe>>, idx: isize) -> Result<(), String> {
 	loop {
 		match rx.recv() {
-			Ok(Some(qpipe)) => {
+			Ok(Some(mut qpipe)) => {
 				let rm_log = qpipe.get_remove_log();
 				let rm_list: Vec<_> = qpipe.get_remove_iter().cloned().collect();
 				let out_list: Vec<_> = qpipe.get_outputs_iter().cloned().collect();
 				debug!("Worker thread {} received job: {:?}", idx, qpipe);
 				let log = &qpipe.log.to_owned();
+				let post = qpipe.post.take();
+				let pre_res = match qpipe.pre.take() {
+					Some(QPipeCom::CheckCram(job)) => call::check_cram_reference(Arc::clone(&qpipe.sig), &job),
+					Some(com) => Err(format!("Unexpected pre pipeline command {:?}", com)),
+					None => Ok(()),
+				};
+				if let Err(e) = &pre_res { error!("{} - pipeline not started", e) }
 				let res = match qpipe.stages {
+					_ if pre_res.is_err() => pre_res,
 					QPipeStage::External(stages) => {
 						let mut pipeline = Pipeline::new();
 						for (path, s) in stages.iter() { pipeline.add_stage(path, Some(s.split_terminator('\x1e'))); }
@@ -333,9 +369,23 @@ fn worker_thread(tx: mpsc::Sender<isize>, rx: mpsc::Receiver<Option<QPipe>>, idx
 						let opath = &qpipe.output.to_owned();
 						if let Some(path) = opath { pipeline.out_filepath(&path); }
 						trace!("Launching external pipeline");
-						let res = pipeline.run(qpipe.sig);
+						let res = pipeline.run(Arc::clone(&qpipe.sig));
 						trace!("External pipeline ended");
-						res
+						match (res, post) {
+							(Ok(_), Some(QPipeCom::MapStats(job))) => {
+								let ret = aligner::make_map_stats(Arc::clone(&qpipe.sig), &job);
+								if ret.is_err() {
+									error!("Error returned from generation of mapping statistics");
+									for file in out_list.iter().filter(|x| x.exists()) {
+										warn!("Removing output file {}", file.to_string_lossy());
+										let _ = fs::remove_file(file);
+									}
+								}
+								ret
+							},
+							(Ok(_), Some(com)) => panic!("Unexpected post pipeline command {:?}", com),
+							(r, _) => r,
+						}
 					},
 					QPipeStage::Internal(com) => {
 						let ret = match com {
@@ -343,6 +393,8 @@ fn worker_thread(tx: mpsc::Sender<isize>, rx: mpsc::Receiver<Option<QPipe>>, idx
 							QPipeCom::MapReport((prj, cdir, thresh, nc, x)) => make_map_report::make_map_report(Arc::clone(&qpipe.sig), &qpipe.outputs, prj, &cdir, thresh, nc, x),
 							QPipeCom::CallReport((prj, cdir, nc, x)) => make_call_report::make_call_report(Arc::clone(&qpipe.sig), &qpipe.outputs, prj, &cdir, nc, x),					
 							QPipeCom::Report((prj, page_size, pdf)) => make_report::make_report(Arc::clone(&qpipe.sig), &qpipe.outputs, prj, page_size, pdf),
+							QPipeCom::MapStats(job) => aligner::make_map_stats(Arc::clone(&qpipe.sig), &job),
+							QPipeCom::CheckCram(job) => call::check_cram_reference(Arc::clone(&qpipe.sig), &job),
 						};
 						if ret.is_err() {
 							error!("Error returned from internal pipeline command");
@@ -457,8 +509,16 @@ pub fn schedule_jobs(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataVal
 				sched.set_lock(flock);
 			}	
 			match sched.get_task(gem_bs) {
-				Ok(job) => {
-					let qpipe = handle_job(gem_bs, options, job.task_idx);
+				Ok(mut job) => {
+					let qpipe = match handle_job(gem_bs, options, job.task_idx) {
+						Ok(q) => q,
+						Err(e) => {
+							error!("Couldn't set up pipeline for task {}: {}", job.id(), e);
+							sched.state = SchedState::Abort;
+							break;
+						},
+					};
+					job.prov = gem_bs.start_task_provenance(job.task_idx, &qpipe.signature());
 					jobs.push((job, idx));					
 					workers[idx as usize].tx.send(Some(qpipe)).expect("Error sending new command to worker thread");
 				},
@@ -484,6 +544,7 @@ pub fn schedule_jobs(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataVal
 			match ctr_rx.try_recv() {
 				Ok(x) if x >= 0 => {
 					debug!("Job completion by worker thread {}", x);
+					record_completion(gem_bs, &mut jobs, x);
 					jobs.retain(|(_, ix)| *ix != x);
 					avail.push(x);
 					sched.state = SchedState::Ready;
@@ -507,6 +568,7 @@ pub fn schedule_jobs(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataVal
 			match ctr_rx.recv_timeout(time::Duration::from_millis(1000)) {
 				Ok(x) if x >= 0 => {
 					debug!("Job completion by worker thread {}", x);
+					record_completion(gem_bs, &mut jobs, x);
 					jobs.retain(|(_, ix)| *ix != x);
 					avail.push(x);
 					sched.state = SchedState::Ready;
@@ -541,6 +603,7 @@ pub fn schedule_jobs(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataVal
 		match ctr_rx.recv_timeout(time::Duration::from_millis(1000)) {
 			Ok(x) if x >= 0 => {
 				debug!("Job completion by worker thread {}", x);
+				record_completion(gem_bs, &mut jobs, x);
 				jobs.retain(|(_, ix)| *ix != x);
 			},
 			Ok(x) => {
@@ -575,13 +638,20 @@ pub fn schedule_jobs(gem_bs: &mut GemBS, options: &HashMap<&'static str, DataVal
 }
 
 pub fn add_command_opts(gem_bs: &GemBS, args: &mut String, sec: Section, opt_list: &[(&'static str, &'static str, VarType)]) {
+	add_sample_command_opts(gem_bs, args, sec, None, opt_list)
+}
+
+// As add_command_opts(), but using any sample specific parameters
+pub fn add_sample_command_opts(gem_bs: &GemBS, args: &mut String, sec: Section, barcode: Option<&str>, opt_list: &[(&'static str, &'static str, VarType)]) {
+	let get = |x: &str| match barcode {
+		Some(bc) => gem_bs.get_sample_config(bc, sec, x),
+		None => gem_bs.get_config(sec, x),
+	};
 	for (x, y, t) in opt_list.iter() {
-		match t {
-			VarType::Bool => if gem_bs.get_config_bool(sec, x) { 
-				args.push_str(format!("--{}\x1e", y).as_str()) },
-			VarType::Int => if let Some(i) = gem_bs.get_config_int(sec, x) { 
-				args.push_str(format!("--{}\x1e{}\x1e", y, i).as_str()) },
-			VarType::IntVec => if let Some(v) = gem_bs.get_config_intvec(sec, x) { 
+		match (t, get(x)) {
+			(VarType::Bool, Some(DataValue::Bool(true))) => args.push_str(format!("--{}\x1e", y).as_str()),
+			(VarType::Int, Some(DataValue::Int(i))) => args.push_str(format!("--{}\x1e{}\x1e", y, i).as_str()),
+			(VarType::IntVec, Some(DataValue::IntVec(v))) => {
 				if v.len() == 1 { args.push_str(format!("--{}\x1e{}\x1e", y, v[0]).as_str()) }
 				else {
 					args.push_str(format!("--{}\x1e{}", y, v[0]).as_str());
@@ -589,10 +659,8 @@ pub fn add_command_opts(gem_bs: &GemBS, args: &mut String, sec: Section, opt_lis
 					args.push('\x1e');
 				}
 			},
-			VarType::String => if let Some(s) = gem_bs.get_config_str(sec, x) { 
-				args.push_str(format!("--{}\x1e{}\x1e", y, s).as_str()) },
-			VarType::Float => if let Some(z) = gem_bs.get_config_float(sec, x) { 
-				args.push_str(format!("--{}\x1e{}\x1e", y, z).as_str()) },
+			(VarType::String, Some(DataValue::String(s))) => args.push_str(format!("--{}\x1e{}\x1e", y, s).as_str()),
+			(VarType::Float, Some(DataValue::Float(z))) => args.push_str(format!("--{}\x1e{}\x1e", y, z).as_str()),
 			_ => (),
 		}
 	}
@@ -0,0 +1,82 @@
+// Read aligner backends
+//
+// Each backend provides the commands to build the index and map the reads, and (if the mapper
+// does not generate it directly) a way of producing the mapping statistics in the GEM3 JSON format
+// used by the reports and the caller.
+//
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+
+use crate::config::GemBS;
+use crate::common::defs::{Section, FileType};
+use super::{QPipe, QPipeCom};
+
+mod gem3;
+mod bwa_meth;
+
+// Information required to set up the mapping stage of a pipeline
+pub struct MapJob<'a> {
+	pub threads: Option<isize>,
+	pub index: &'a Path,
+	pub bisulfite: bool,
+	// Reads are taken from stdin (i.e., from the trimming stage) if files is empty
+	pub files: Vec<&'a Path>,
+	pub ftype: Option<FileType>,
+	pub paired: bool,
+	pub read_groups: String,
+	pub report: &'a Path,
+	pub output: &'a Path,
+	pub cram: bool,
+	// Sample barcode, used to look up any sample specific parameters
+	pub barcode: Option<&'a str>,
+}
+
+// Generation of JSON mapping report after the mapping pipeline has completed
+#[derive(Debug)]
+pub struct MapStatsJob {
+	samtools: PathBuf,
+	input: PathBuf,
+	report: PathBuf,
+	reference: Option<PathBuf>,
+	paired: bool,
+	threads: Option<isize>,
+}
+
+pub trait Aligner {
+	fn name(&self) -> &'static str;
+	// Extension used to generate the default index name from the reference
+	fn index_ext(&self, bisulfite: bool) -> &'static str;
+	fn supports_input(&self, _ftype: FileType) -> bool { true }
+	// Can the mapper read (possibly interleaved) FASTQ from stdin, as required for read trimming
+	fn supports_stdin(&self) -> bool { true }
+	fn make_index_pipeline(&self, gem_bs: &GemBS, job: usize, bisulfite: bool) -> Result<QPipe, String>;
+	// Add the mapping stages to the pipeline.  The last stage should write SAM to stdout
+	fn add_map_stages(&self, gem_bs: &GemBS, pipeline: &mut QPipe, job: &MapJob) -> Result<(), String>;
+	// Command to generate the JSON mapping report if this is not done by the mapper
+	fn map_stats_com(&self, _gem_bs: &GemBS, _job: &MapJob) -> Option<QPipeCom> { None }
+}
+
+// The aligner can be set in the index or mapping sections, but they must agree
+pub fn get_aligner(gem_bs: &GemBS) -> Result<Box<dyn Aligner>, String> {
+	let name = match (gem_bs.get_config_str(Section::Index, "aligner"), gem_bs.get_config_str(Section::Mapping, "aligner")) {
+		(Some(a), Some(b)) if !a.eq_ignore_ascii_case(b) => return Err(format!("Inconsistent aligners selected for indexing ({}) and mapping ({})", a, b)),
+		(Some(a), _) | (None, Some(a)) => a,
+		(None, None) => "gem3",
+	};
+	match name.to_lowercase().as_str() {
+		"gem3" | "gem" => Ok(Box::new(gem3::Gem3)),
+		"bwa-meth" | "bwameth" => Ok(Box::new(bwa_meth::BwaMeth)),
+		_ => Err(format!("Unknown aligner {} (options are gem3 or bwa-meth)", name)),
+	}
+}
+
+// Read trimming and FASTQ QC are run in front of the mapper, so require it to read from stdin
+pub fn check_trim_qc(aligner: &dyn Aligner, trim: bool, fastq_qc: bool) -> Result<(), String> {
+	if trim && !aligner.supports_stdin() { Err(format!("Read trimming is not supported by the {} aligner", aligner.name())) }
+	else if fastq_qc && !aligner.supports_stdin() { Err(format!("FASTQ QC is not supported by the {} aligner", aligner.name())) }
+	else { Ok(()) }
+}
+
+pub fn make_map_stats(sig: Arc<AtomicUsize>, job: &MapStatsJob) -> Result<(), String> { bwa_meth::make_map_json(sig, job) }
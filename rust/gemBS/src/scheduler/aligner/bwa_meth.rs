@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::{fs, io};
+
+use utils::find_exec_path;
+
+use crate::config::GemBS;
+use crate::common::assets::GetAsset;
+use crate::common::defs::{Section, FileType};
+use crate::common::json_map_stats::{MapJson, SamStats};
+use crate::common::utils::check_signal;
+use super::{Aligner, MapJob, MapStatsJob};
+use super::super::{QPipe, QPipeCom};
+
+// The bisulfite index is built by bwameth.py next to the reference, and the non-bisulfite index is a standard bwa index
+const BS_INDEX_EXT: &str = ".bwameth.c2t";
+const NONBS_INDEX_EXT: &str = ".bwt";
+
+pub struct BwaMeth;
+
+// Use the version on the PATH if available, otherwise look in the gemBS bin directory
+fn get_exec(gem_bs: &GemBS, name: &str) -> PathBuf { find_exec_path(name).unwrap_or_else(|| gem_bs.get_exec_path(name)) }
+
+fn index_base<'a>(index: &'a Path, ext: &str) -> Result<&'a str, String> {
+	let s = index.to_str().ok_or_else(|| format!("bwa-meth index path {} not valid UTF8", index.display()))?;
+	s.strip_suffix(ext).ok_or_else(|| format!("bwa-meth index {} does not end with {}", s, ext))
+}
+
+impl Aligner for BwaMeth {
+	fn name(&self) -> &'static str { "bwa-meth" }
+	fn index_ext(&self, bisulfite: bool) -> &'static str { if bisulfite { "gemBS.ref.bwameth.c2t" } else { "bwa.bwt" } }
+	fn supports_input(&self, ftype: FileType) -> bool { !matches!(ftype, FileType::BAM | FileType::Interleaved | FileType::Stream) }
+	fn supports_stdin(&self) -> bool { false }
+
+	fn make_index_pipeline(&self, gem_bs: &GemBS, job: usize, bisulfite: bool) -> Result<QPipe, String> {
+		let gembs_ref = gem_bs.get_asset("gembs_reference").expect("Couldn't find gemBS reference asset").path();
+		let index_name = if bisulfite { "index" } else { "nonbs_index" };
+		let index = gem_bs.get_asset(index_name).expect("Couldn't find gemBS index asset").path();
+		let mut pipeline = QPipe::new(gem_bs.get_signal_clone());
+		if bisulfite {
+			// bwameth.py puts the index files next to the reference, so if the index is not in the default location
+			// we link the gemBS reference to the index location
+			let reference = Path::new(index_base(index, BS_INDEX_EXT)?);
+			if reference != gembs_ref && !reference.exists() {
+				let target = gembs_ref.canonicalize().map_err(|e| format!("Couldn't get absolute path of gemBS reference {}: {}", gembs_ref.display(), e))?;
+				std::os::unix::fs::symlink(&target, reference).map_err(|e| format!("Couldn't link {} to {}: {}", reference.display(), target.display(), e))?;
+			}
+			pipeline.add_stage(&get_exec(gem_bs, "bwameth.py"), &format!("index\x1e{}", reference.display()));
+		} else {
+			pipeline.add_stage(&get_exec(gem_bs, "bwa"), &format!("index\x1e-p\x1e{}\x1e{}", index_base(index, NONBS_INDEX_EXT)?, gembs_ref.display()));
+		}
+		if let Some(x) = gem_bs.get_tasks()[job].log() { pipeline.log = Some(gem_bs.get_asset(x).expect("Couldn't get log file").path().to_owned()) }
+		if gem_bs.get_config_bool(Section::Index, "keep_logs") { pipeline.set_remove_log(false) }
+		for out in gem_bs.get_tasks()[job].outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get index output asset").path()); }
+		Ok(pipeline)
+	}
+
+	fn add_map_stages(&self, gem_bs: &GemBS, pipeline: &mut QPipe, job: &MapJob) -> Result<(), String> {
+		let (path, mut args) = if job.bisulfite {
+			let mut args = format!("--reference\x1e{}\x1e--read-group\x1e{}\x1e", index_base(job.index, BS_INDEX_EXT)?, job.read_groups);
+			if let Some(t) = job.threads { args.push_str(format!("--threads\x1e{}\x1e", t).as_str()) }
+			(get_exec(gem_bs, "bwameth.py"), args)
+		} else {
+			let mut args = format!("mem\x1e-R\x1e{}\x1e", job.read_groups);
+			if let Some(t) = job.threads { args.push_str(format!("-t\x1e{}\x1e", t).as_str()) }
+			args.push_str(format!("{}\x1e", index_base(job.index, NONBS_INDEX_EXT)?).as_str());
+			(get_exec(gem_bs, "bwa"), args)
+		};
+		let files: Vec<String> = job.files.iter().map(|f| format!("{}", f.display())).collect();
+		args.push_str(&files.join("\x1e"));
+		pipeline.add_stage(&path, &args);
+		Ok(())
+	}
+
+	fn map_stats_com(&self, gem_bs: &GemBS, job: &MapJob) -> Option<QPipeCom> {
+		let reference = if job.cram { Some(gem_bs.get_asset("gembs_reference").expect("Couldn't find gemBS reference asset").path().to_owned()) } else { None };
+		Some(QPipeCom::MapStats(MapStatsJob{samtools: gem_bs.get_exec_path("samtools"), input: job.output.to_owned(), report: job.report.to_owned(),
+			reference, paired: job.paired, threads: job.threads}))
+	}
+}
+
+fn samtools_stats(job: &MapStatsJob, flag: Option<&str>) -> Result<SamStats, String> {
+	let mut com = Command::new(&job.samtools);
+	com.arg("stats");
+	if let Some(t) = job.threads { com.arg("--threads").arg(format!("{}", t)); }
+	if let Some(r) = &job.reference { com.arg("--reference").arg(r); }
+	if let Some(f) = flag { com.arg("--required-flag").arg(f); }
+	com.arg(&job.input);
+	let output = com.output().map_err(|e| format!("Couldn't run {}: {}", job.samtools.display(), e))?;
+	if !output.status.success() { return Err(format!("samtools stats failed for {}: {}", job.input.display(), String::from_utf8_lossy(&output.stderr))) }
+	SamStats::from_reader(io::Cursor::new(output.stdout))
+}
+
+// Generate the JSON mapping report from the samtools stats output (collected separately for each read if paired)
+pub fn make_map_json(sig: Arc<AtomicUsize>, job: &MapStatsJob) -> Result<(), String> {
+	check_signal(Arc::clone(&sig))?;
+	let json = if job.paired {
+		let end1 = samtools_stats(job, Some("64"))?;
+		check_signal(Arc::clone(&sig))?;
+		let end2 = samtools_stats(job, Some("128"))?;
+		MapJson::from_sam_stats(&end1, Some(&end2))
+	} else { MapJson::from_sam_stats(&samtools_stats(job, None)?, None) };
+	let file = fs::File::create(&job.report).map_err(|e| format!("Couldn't open {}: {}", job.report.display(), e))?;
+	serde_json::to_writer_pretty(io::BufWriter::new(file), &json).map_err(|e| format!("Error writing JSON file {}: {}", job.report.display(), e))
+}
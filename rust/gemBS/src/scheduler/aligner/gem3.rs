@@ -0,0 +1,72 @@
+use lazy_static::lazy_static;
+
+use crate::config::GemBS;
+use crate::common::assets::GetAsset;
+use crate::common::defs::{Section, FileType, VarType};
+use super::{Aligner, MapJob};
+use super::super::QPipe;
+
+pub struct Gem3;
+
+impl Aligner for Gem3 {
+	fn name(&self) -> &'static str { "GEM3" }
+	fn index_ext(&self, bisulfite: bool) -> &'static str { if bisulfite { "BS.gem" } else { "gem" } }
+
+	fn make_index_pipeline(&self, gem_bs: &GemBS, job: usize, bisulfite: bool) -> Result<QPipe, String> {
+		let gembs_ref = gem_bs.get_asset("gembs_reference").expect("Couldn't find gemBS reference asset");
+		let index_name = if bisulfite { "index" } else { "nonbs_index" };
+		let index = gem_bs.get_asset(index_name).expect("Couldn't find gemBS index asset").path();
+		let index_base = index.to_string_lossy().clone();
+		let index_base = index_base.trim_end_matches(".gem");
+		let gem_indexer = gem_bs.get_exec_path("gem-indexer");
+		let mut args = format!("-i\x1e{}\x1e-o\x1e{}\x1e", gembs_ref.path().to_string_lossy(), index_base);
+		if bisulfite {args.push_str("--bisulfite-index\x1e")}
+		if let Some(x) = gem_bs.get_config_int(Section::Index, "sampling_rate") { args.push_str(format!("--text-sampling-rate\x1e{}\x1e", x).as_str())}
+		if let Some(x) = gem_bs.get_config_int(Section::Index, "threads") { args.push_str(format!("--threads\x1e{}\x1e", x).as_str())}
+		if let Some(x) = index.parent() {  args.push_str(format!("--tmp-folder\x1e{}", x.to_string_lossy()).as_str())}
+		let mut pipeline = QPipe::new(gem_bs.get_signal_clone());
+		if let Some(x) = gem_bs.get_tasks()[job].log() { pipeline.log = Some(gem_bs.get_asset(x).expect("Couldn't get log file").path().to_owned()) }
+		if gem_bs.get_config_bool(Section::Index, "keep_logs") { pipeline.set_remove_log(false) }
+		for out in gem_bs.get_tasks()[job].outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get index output asset").path()); }
+		pipeline.add_stage(&gem_indexer, &args);
+		Ok(pipeline)
+	}
+
+	fn add_map_stages(&self, gem_bs: &GemBS, pipeline: &mut QPipe, job: &MapJob) -> Result<(), String> {
+		lazy_static! {
+	    	static ref OPT_LIST: Vec<(&'static str, &'static str, VarType)> = {
+	        	let mut m = Vec::new();
+	        	m.push(("underconversion_sequence", "underconversion-sequence", VarType::String));
+	        	m.push(("overconversion_sequence", "overconversion-sequence", VarType::String));
+	        	m.push(("benchmark_mode", "benchmark-mode", VarType::Bool));
+				m
+			};
+		}
+		let mapper_path = gem_bs.get_exec_path("gem-mapper");
+		let mut mapper_args = if let Some(t) = job.threads { format!("--threads\x1e{}\x1e", t) } else { String::new() };
+		mapper_args.push_str(format!("-I\x1e{}\x1e", job.index.display()).as_str());
+		if job.files.len() == 2 {
+			mapper_args.push_str(format!("--i1\x1e{}\x1e--i2\x1e{}\x1e", job.files[0].display(), job.files[1].display()).as_str());
+		} else if let Some(FileType::BAM) = job.ftype {
+			let bam2fq = gem_bs.get_exec_path("samtools");
+			let args = if let Some(t) = job.threads { format!("bam2fq\x1e{}\x1e--threads\x1e{}", job.files[0].display(), t) }
+			else { format!("bam2fq {}", job.files[0].display()) };
+			pipeline.add_stage(&bam2fq, &args);
+		} else if let Some(f) = job.files.first() { mapper_args.push_str(format!("-i\x1e{}\x1e", f.display()).as_str()) }
+		if job.paired { mapper_args.push_str("--paired-end-alignment\x1e")}
+		let get_bool = |x| match job.barcode {
+			Some(bc) => gem_bs.get_sample_config_bool(bc, Section::Mapping, x),
+			None => gem_bs.get_config_bool(Section::Mapping, x),
+		};
+		if get_bool("non_stranded") { mapper_args.push_str("--bisulfite-conversion\x1enon-stranded\x1e") }
+		else if get_bool("reverse_conversion") { mapper_args.push_str("--bisulfite-conversion\x1einferred-G2A-C2T\x1e") }
+		else { mapper_args.push_str("--bisulfite-conversion\x1einferred-C2T-G2A\x1e") }
+
+		super::super::add_sample_command_opts(gem_bs, &mut mapper_args, Section::Mapping, job.barcode, &OPT_LIST);
+
+		mapper_args.push_str(format!("--report-file\x1e{}\x1e", job.report.display()).as_str());
+		mapper_args.push_str(format!("--sam-read-group-header\x1e{}", job.read_groups).as_str());
+		pipeline.add_stage(&mapper_path, &mapper_args);
+		Ok(())
+	}
+}
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
-use std::io::{BufWriter, Write, BufReader};
+use std::io::{BufWriter, Write, BufReader, BufRead};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
 use regex::Regex;
 use lazy_static::lazy_static;
 
@@ -10,7 +13,54 @@ use crate::common::assets::GetAsset;
 use crate::common::defs::{DataValue, Section, VarType};
 use crate::common::tasks::Task;
 use crate::common::json_map_stats::{MapJson, BaseCounts, Counts};
-use super::QPipe;
+use crate::common::utils::check_signal;
+use super::{QPipe, QPipeCom};
+
+// Check that the contigs in the header of a CRAM file match the gemBS reference (using the M5 tags) before calling
+#[derive(Debug)]
+pub struct CramCheckJob {
+	samtools: PathBuf,
+	input: PathBuf,
+	contig_md5: PathBuf,
+}
+
+fn read_contig_md5(path: &Path) -> Result<HashMap<String, String>, String> {
+	let rdr = BufReader::new(fs::File::open(path).map_err(|e| format!("Couldn't open {}: {}", path.display(), e))?);
+	let mut ctgs = HashMap::new();
+	for line in rdr.lines() {
+		let line = line.map_err(|e| format!("Error reading from {}: {}", path.display(), e))?;
+		let mut iter = line.split('\t');
+		if let Some(name) = iter.next() {
+			if let Some(md5) = iter.find_map(|f| f.strip_prefix("M5:")) { ctgs.insert(name.to_owned(), md5.to_owned()); }
+		}
+	}
+	Ok(ctgs)
+}
+
+pub fn check_cram_reference(sig: Arc<AtomicUsize>, job: &CramCheckJob) -> Result<(), String> {
+	check_signal(Arc::clone(&sig))?;
+	let ctgs = read_contig_md5(&job.contig_md5)?;
+	let output = Command::new(&job.samtools).arg("view").arg("-H").arg(&job.input).output()
+		.map_err(|e| format!("Couldn't run {}: {}", job.samtools.display(), e))?;
+	if !output.status.success() { return Err(format!("Couldn't read header of {}: {}", job.input.display(), String::from_utf8_lossy(&output.stderr))) }
+	let mut n = 0;
+	for line in String::from_utf8_lossy(&output.stdout).lines().filter(|l| l.starts_with("@SQ\t")) {
+		let (mut sn, mut m5) = (None, None);
+		for f in line.split('\t').skip(1) {
+			if let Some(x) = f.strip_prefix("SN:") { sn = Some(x) } else if let Some(x) = f.strip_prefix("M5:") { m5 = Some(x) }
+		}
+		let sn = sn.ok_or_else(|| format!("Missing SN tag in @SQ line from {}", job.input.display()))?;
+		let m5 = m5.ok_or_else(|| format!("Missing M5 tag for contig {} in {}", sn, job.input.display()))?;
+		match ctgs.get(sn) {
+			Some(x) if x.eq_ignore_ascii_case(m5) => n += 1,
+			Some(x) => return Err(format!("M5 tag for contig {} in {} ({}) does not match the gemBS reference ({})", sn, job.input.display(), m5, x)),
+			None => return Err(format!("Contig {} in {} not found in the gemBS reference", sn, job.input.display())),
+		}
+	}
+	if n == 0 { return Err(format!("No @SQ lines found in header of {}", job.input.display())) }
+	debug!("M5 tags for {} contigs in {} match the gemBS reference", n, job.input.display());
+	Ok(())
+}
 
 fn check_inputs<'a>(gem_bs: &'a GemBS, task: &'a Task) -> (usize, &'a str) {
 	lazy_static! { static ref RE: Regex = Regex::new(r"^(.+)\.(bam|cram)").unwrap(); }
@@ -76,7 +126,7 @@ pub fn calc_conversion(cts: &BaseCounts<Counts>) -> Option<f64> {
 	} else { None }
 }
 fn get_conversion_rate(gem_bs: &GemBS, barcode: &str) -> (f64, f64) {
-	let (mut under, mut over) = if gem_bs.get_config_bool(Section::Calling, "auto_conversion") {	
+	let (mut under, mut over) = if gem_bs.get_sample_config_bool(barcode, Section::Calling, "auto_conversion") {	
 		let json_files = gem_bs.get_mapping_json_files_for_barcode(barcode);
 		let mut counts = [BaseCounts::<Counts>::new(), BaseCounts::<Counts>::new()];
 		for f in json_files.iter() { add_conversion_counts(gem_bs, *f, &mut counts); }
@@ -94,7 +144,7 @@ fn get_conversion_rate(gem_bs: &GemBS, barcode: &str) -> (f64, f64) {
 		(under, over)
 	} else { (None, None) };
 	// Bring in config values if conversion rates not set
-	if let Some(DataValue::FloatVec(v)) = gem_bs.get_config(Section::Calling, "conversion") {
+	if let Some(DataValue::FloatVec(v)) = gem_bs.get_sample_config(barcode, Section::Calling, "conversion") {
 		if under.is_none() && !v.is_empty() { under = Some(v[0]) }
 		if over.is_none() && v.len() > 1 { over = Some(v[1]) }
 	}
@@ -119,6 +169,12 @@ pub fn make_call_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
 			m.push(("mapq_threshold", "mapq-threshold", VarType::Int));
 			m.push(("qual_threshold", "bq-threshold", VarType::Int));
 			m.push(("dbsnp_index", "dbsnp", VarType::String));
+			m.push(("cnv_bin_size", "cnv-bin-size", VarType::Int));
+			m.push(("mito_mode", "mito-mode", VarType::Bool));
+			m.push(("mito_contig", "mito-contig", VarType::String));
+			m.push(("mito_mapq_threshold", "mito-mapq-threshold", VarType::Int));
+			m.push(("min_heteroplasmy", "min-heteroplasmy", VarType::Float));
+			m.push(("recalibrate", "recalibrate", VarType::Bool));
 			m
 		};
 	}
@@ -145,9 +201,18 @@ pub fn make_call_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
 		pipeline.add_remove_file(&cp);
 	}
 	if let Some(t) = call_threads { args.push_str(format!("--threads\x1e{}\x1e", t).as_str()); }
+	// Variant sites for recalibration of the merged BCF
+	if let Some(x) = out_iter.next() { 
+		args.push_str(format!("--recal-sites\x1e{}\x1e", gem_bs.get_asset(*x).expect("Couldn't get asset").path().to_string_lossy()).as_str());
+	}
 	args.push_str(format!("--conversion\x1e{},{}\x1e", under, over).as_str());
-	super::add_command_opts(gem_bs, &mut args, Section::Calling, &OPT_LIST);
-	args.push_str(&gem_bs.get_asset(in_bam).unwrap().path().to_string_lossy());
+	super::add_sample_command_opts(gem_bs, &mut args, Section::Calling, Some(barcode), &OPT_LIST);
+	let input = gem_bs.get_asset(in_bam).unwrap().path();
+	args.push_str(&input.to_string_lossy());
+	if input.extension().map(|x| x == "cram").unwrap_or(false) {
+		let contig_md5 = gem_bs.get_asset("contig_md5").expect("Couldn't find contig md5 asset").path().to_owned();
+		pipeline.set_pre_com(QPipeCom::CheckCram(CramCheckJob{samtools: gem_bs.get_exec_path("samtools"), input: input.to_owned(), contig_md5}));
+	}
 
 	if let Some(x) = task.log() { pipeline.log = Some(gem_bs.get_asset(x).expect("Couldn't get log file").path().to_owned()) }
 	for out in task.outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get md5sum output asset").path()); }
@@ -163,20 +228,26 @@ pub fn make_merge_bcfs_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str,
 	let mut pipeline = QPipe::new(gem_bs.get_signal_clone());
 	let bcftools_path = gem_bs.get_exec_path("bcftools");
 	let task = &gem_bs.get_tasks()[job];
-	let output_bcf = gem_bs.get_asset(*task.outputs().next().expect("No output files for call step")).expect("Couldn't get asset").path();
+	let mut out_iter = task.outputs();
+	let output_bcf = gem_bs.get_asset(*out_iter.next().expect("No output files for call step")).expect("Couldn't get asset").path();
+	let recal_json = out_iter.next().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset").path());
 
 	// Setup arguments	
-	let mut args = format!("concat\x1e--output\x1e{}\x1e--output-type\x1eb\x1e--naive\x1e", output_bcf.to_string_lossy());
+	let mut args = if recal_json.is_some() { "concat\x1e--output-type\x1eb\x1e--naive\x1e".to_string() }
+	else { format!("concat\x1e--output\x1e{}\x1e--output-type\x1eb\x1e--naive\x1e", output_bcf.to_string_lossy()) };
 	if gem_bs.get_config_bool(Section::Calling, "benchmark_mode") { args.push_str("--no-version\x1e")}		
 	if let Some(t) = merge_threads { args.push_str(format!("--threads\x1e{}\x1e", t).as_str()); }
 
 	let remove_bcfs = if let Some(DataValue::Bool(x)) = options.get("remove") { *x } else { 
 	gem_bs.get_config_bool(Section::Calling, "remove_individual_bcfs") };	
+	// Pool BCFs read directly by the extract step (extract_from_pools) must be kept
+	let extract_inputs: Vec<usize> = task.barcode().and_then(|bc| gem_bs.get_tasks().find_task(format!("mextr_{}", bc).as_str()))
+		.map(|ix| gem_bs.get_tasks()[ix].inputs().copied().collect()).unwrap_or_default();
 	let mut v = Vec::new();
 	for asset in task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset")).filter(|x| x.id().ends_with(".bcf")) {
 		let s = asset.path().to_string_lossy();
 		v.push(s);
-		if remove_bcfs { pipeline.add_remove_file(&asset.path()); }
+		if remove_bcfs && !extract_inputs.contains(&asset.idx()) { pipeline.add_remove_file(&asset.path()); }
 	}
 	v.sort();
 	for s in v.iter() { args.push_str(format!("{}\x1e", s).as_str()) }
@@ -185,6 +256,18 @@ pub fn make_merge_bcfs_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str,
 	if gem_bs.get_config_bool(Section::Mapping, "keep_logs") { pipeline.set_remove_log(false) }
 
 	pipeline.add_stage(&bcftools_path, &args);
+	// If recalibration was requested the merged BCF is recalibrated using the variant sites from all pools
+	if let Some(rj) = recal_json {
+		let mut sites = Vec::new();
+		for asset in task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset")).filter(|x| x.id().ends_with("_recal_sites.txt")) {
+			sites.push(asset.path().to_string_lossy());
+			pipeline.add_remove_file(asset.path());
+		}
+		let mut args = format!("--loglevel\x1e{}\x1e--recalibrate-from\x1e{}\x1e--output\x1e{}\x1e--output-type\x1eb\x1e--report-file\x1e{}\x1e",
+			gem_bs.verbose(), sites.join(","), output_bcf.to_string_lossy(), rj.to_string_lossy());
+		if let Some(t) = merge_threads { args.push_str(format!("--threads\x1e{}\x1e", t).as_str()); }
+		pipeline.add_stage(&gem_bs.get_exec_path("bs_call"), &args);
+	}
 	pipeline
 }
 
@@ -27,19 +27,40 @@ fn make_contig_file(gem_bs: &GemBS, barcode: &str, output_dir: &Path) -> PathBuf
 	ctg_path
 }
 
-fn make_mextr_pipeline(gem_bs: &GemBS, job: usize, bc: &str) -> QPipe {
+fn make_mextr_pipeline(gem_bs: &GemBS, job: usize, bc: &str) -> Result<QPipe, String> {
 	let task = &gem_bs.get_tasks()[job];
 	let first_output = gem_bs.get_asset(*task.outputs().next().expect("No output files for extract step")).expect("Couldn't get asset").path();
-	let in_bcf = gem_bs.get_asset(*task.inputs().next().expect("No output files for extract step")).expect("Couldn't get asset").path();
+	// Input is either the merged BCF or the per pool BCFs (in pool order)
+	let in_bcfs: Vec<_> = task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset").path())
+		.filter(|p| p.extension().and_then(|s| s.to_str()) == Some("bcf")).collect();
+	let in_bcf = in_bcfs.first().expect("No input BCF for extract step");
 	let output_dir = first_output.parent().unwrap_or_else(|| Path::new("."));
-	let contig_file = make_contig_file(gem_bs, bc, output_dir);
 	let mextr_path = gem_bs.get_exec_path("mextr");
 	
 	// Set up arg list
-	let mut args = format!("--loglevel\x1e{}\x1e--compress\x1e--md5\x1e--regions-file\x1e{}\x1e", gem_bs.verbose(), contig_file.to_string_lossy());
-	let (mut cpg, mut noncpg, mut bedmethyl) = (false, false, false);
+	let mut args = format!("--loglevel\x1e{}\x1e--compress\x1e--md5\x1e", gem_bs.verbose());
+	// Pool BCFs are read sequentially by mextr (no index, so no region selection)
+	let contig_file = if in_bcfs.len() > 1 {
+		let v: Vec<_> = in_bcfs[1..].iter().map(|p| p.to_string_lossy()).collect();
+		args.push_str(format!("--concat\x1e{}\x1e", v.join(",")).as_str());
+		None
+	} else {
+		let contig_file = make_contig_file(gem_bs, bc, output_dir);
+		args.push_str(format!("--regions-file\x1e{}\x1e", contig_file.to_string_lossy()).as_str());
+		Some(contig_file)
+	};
+	let (mut cpg, mut noncpg, mut bedmethyl, mut haplotype, mut allele_report, mut context_summary) = (false, false, false, false, false, false);
+	// Context bigWig files are named <barcode>_context_<CONTEXT>.bw
+	let context_bw_prefix = format!("{}_context_", bc);
+	let mut bw_contexts = Vec::new();
 	for out in task.outputs() {
-		let oname = gem_bs.get_asset(*out).expect("Couldn't get output asset").path().to_string_lossy();
+		let opath = gem_bs.get_asset(*out).expect("Couldn't get output asset").path();
+		if let Some(ctx) = opath.file_name().and_then(|s| s.to_str())
+			.and_then(|s| s.strip_prefix(context_bw_prefix.as_str())).and_then(|s| s.strip_suffix(".bw")) {
+			bw_contexts.push(ctx.to_owned());
+			continue
+		}
+		let oname = opath.to_string_lossy();
 		if oname.ends_with("non_cpg.txt.gz") { 
 			noncpg = true;
 			args.push_str(format!("--noncpgfile\x1e{}\x1e", oname).as_str())
@@ -50,22 +71,58 @@ fn make_mextr_pipeline(gem_bs: &GemBS, job: usize, bc: &str) -> QPipe {
 			bedmethyl = true;
 			let outbase: PathBuf = [output_dir, Path::new(bc)].iter().collect();	
 			args.push_str(format!("--bed-methyl\x1e{}\x1e", outbase.to_string_lossy()).as_str())
+		} else if oname.ends_with("context_summary.txt") {
+			context_summary = true;
+			args.push_str(format!("--context-summary\x1e{}\x1e", oname).as_str())
+		} else if oname.ends_with("allele_report.txt.gz") {
+			allele_report = true;
+			args.push_str(format!("--allele-report\x1e{}\x1e", oname).as_str())
+		} else if oname.ends_with("haplotype.txt.gz") {
+			haplotype = true;
+			let outbase: PathBuf = [output_dir, Path::new(bc)].iter().collect();	
+			args.push_str(format!("--haplotype\x1e{}\x1e", outbase.to_string_lossy()).as_str())
 		}
 	}
+	if !bw_contexts.is_empty() {
+		let outbase: PathBuf = [output_dir, Path::new(format!("{}_context", bc).as_str())].iter().collect();
+		args.push_str(format!("--context-bigwig\x1e{}\x1e--bw-contexts\x1e{}\x1e", outbase.to_string_lossy(), bw_contexts.join(",")).as_str())
+	}
+	if haplotype {
+		let bam = task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset").path())
+			.find(|p| matches!(p.extension().and_then(|s| s.to_str()), Some("bam") | Some("cram")))
+			.ok_or_else(|| format!("No alignment file found for haplotype extraction for barcode {}", bc))?;
+		args.push_str(format!("--bam\x1e{}\x1e", bam.to_string_lossy()).as_str());
+	}
 	let mut opt_list = Vec::new();
 	opt_list.push(("threads", "threads", VarType::Int));
    	opt_list.push(("reference_bias", "reference-bias", VarType::Float));
    	opt_list.push(("qual_threshold", "bq-threshold", VarType::Int));
+	// The haplotype and allele report files are also tabix indexed
+	if cpg || noncpg || haplotype || allele_report { args.push_str("--tabix\x1e") }
 	if cpg || noncpg { 
-		args.push_str("--tabix\x1e");
 	  	opt_list.push(("phred_threshold", "threshold", VarType::Int));
 		opt_list.push(("min_inform", "inform", VarType::Int));
 		opt_list.push(("allow_het", "select het", VarType::Bool));
 	}
-	if noncpg { opt_list.push(("min_nc", "min-nc", VarType::Int)); }
-	if cpg { opt_list.push(("strand_specific", "mode\x1estrand-specific", VarType::Bool)); }
-	if bedmethyl { opt_list.push(("bigwig_strand_specific", "bw-mode\x1estrand-specific", VarType::Bool)); }
-	super::add_command_opts(gem_bs, &mut args, Section::Extract, &opt_list);
+	if noncpg {
+		opt_list.push(("min_nc", "min-nc", VarType::Int));
+		opt_list.push(("non_cpg_contexts", "context-filter", VarType::String));
+	}
+	if cpg {
+		opt_list.push(("strand_specific", "mode\x1estrand-specific", VarType::Bool));
+		opt_list.push(("cpg_annotation", "cpg-annotation", VarType::Bool));
+	}
+	if bedmethyl {
+		opt_list.push(("bigwig_strand_specific", "bw-mode\x1estrand-specific", VarType::Bool));
+		opt_list.push(("mask_allele_cpg", "mask-allele-cpg", VarType::Bool));
+		opt_list.push(("bigwig_tracks", "bw-tracks", VarType::String));
+	}
+	if context_summary { opt_list.push(("context_summary_regions", "summary-regions", VarType::String)) }
+	if haplotype {
+		opt_list.push(("haplotype_window", "hap-window", VarType::Int));
+		opt_list.push(("haplotype_min_reads", "hap-min-reads", VarType::Int));
+	}
+	super::add_sample_command_opts(gem_bs, &mut args, Section::Extract, Some(bc), &opt_list);
 	args.push_str(&in_bcf.to_string_lossy());
 
 	// Setup mextr pipeline
@@ -73,8 +130,9 @@ fn make_mextr_pipeline(gem_bs: &GemBS, job: usize, bc: &str) -> QPipe {
 	if let Some(x) = task.log() { pipeline.log = Some(gem_bs.get_asset(x).expect("Couldn't get log file").path().to_owned()) }
 	for out in task.outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get mextr output asset").path()); }
 	if gem_bs.keep_logs() || gem_bs.get_config_bool(Section::Extract, "keep_logs") { pipeline.set_remove_log(false) }
-	pipeline.add_stage(&mextr_path, &args).add_remove_file(&contig_file);
-	pipeline	
+	pipeline.add_stage(&mextr_path, &args);
+	if let Some(f) = contig_file { pipeline.add_remove_file(&f); }
+	Ok(pipeline)
 }
 
 fn make_snpxtr_pipeline(gem_bs: &GemBS, job: usize) -> QPipe {
@@ -89,7 +147,7 @@ fn make_snpxtr_pipeline(gem_bs: &GemBS, job: usize) -> QPipe {
 	opt_list.push(("threads", "threads", VarType::Int));
 	opt_list.push(("snp_list", "snps", VarType::String));
 	opt_list.push(("dbsnp_index", "dbsnp", VarType::String));
-	super::add_command_opts(gem_bs, &mut args, Section::Extract, &opt_list);
+	super::add_sample_command_opts(gem_bs, &mut args, Section::Extract, task.barcode().map(|x| x.as_str()), &opt_list);
 	args.push_str(&in_bcf.to_string_lossy());
 
 	// Setup snpxtr pipeline
@@ -108,11 +166,11 @@ fn get_command_and_barcode(id: &str) -> (&str, &str) {
 	} else { panic!("Couldn't parse extract task id") }
 }
 
-pub fn make_extract_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
+pub fn make_extract_pipeline(gem_bs: &GemBS, job: usize) -> Result<QPipe, String>
 {
 	match get_command_and_barcode(gem_bs.get_tasks()[job].id()) {
 		("mextr", bc) => make_mextr_pipeline(gem_bs, job, bc),
-		("snpxtr", _) => make_snpxtr_pipeline(gem_bs, job),
+		("snpxtr", _) => Ok(make_snpxtr_pipeline(gem_bs, job)),
 		_ => panic!("Couldn't parse extract task id"),
 	}
 }
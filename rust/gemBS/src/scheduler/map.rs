@@ -9,6 +9,7 @@ use crate::common::assets::{Asset, GetAsset};
 use crate::common::defs::{DataValue, Section, Metadata, FileType, VarType};
 use crate::common::tasks::Task;
 use super::QPipe;
+use super::aligner::{get_aligner, MapJob};
 
 fn check_inputs<'a>(gem_bs: &'a GemBS, task: &'a Task) -> (Vec<&'a Asset>, &'a str) {
 	lazy_static! {
@@ -43,15 +44,18 @@ fn check_inputs<'a>(gem_bs: &'a GemBS, task: &'a Task) -> (Vec<&'a Asset>, &'a s
 	(vfile, dataset)	
 }
 
-fn check_outputs<'a>(gem_bs: &'a GemBS, task: &'a Task) -> [Option<&'a Asset>; 3] {
+fn check_outputs<'a>(gem_bs: &'a GemBS, task: &'a Task) -> [Option<&'a Asset>; 6] {
 	lazy_static! {
        static ref REBAM: Regex = Regex::new(r"^.*\.(bam|cram)$").unwrap();
        static ref REJSON: Regex = Regex::new(r"^.*\.json$").unwrap();
 	}
-	let mut ofiles = [None, None, None];
+	let mut ofiles = [None, None, None, None, None, None];
 	for ix in task.outputs() {
 		let asset = gem_bs.get_asset(*ix).expect("Missing asset");
-		if let Some(cap) = REBAM.captures(asset.id()) {
+		if asset.id().ends_with("_trim.json") { ofiles[3] = Some(asset) }
+		else if asset.id().ends_with("_qc.json") { ofiles[4] = Some(asset) }
+		else if asset.id().ends_with("_genomes.json") { ofiles[5] = Some(asset) }
+		else if let Some(cap) = REBAM.captures(asset.id()) {
 			let x = match cap.get(1) {
 				Some(i) => { if i.as_str() == "bam" { 0 } else { 1 }},
 				None => panic!("Unexpected match"),
@@ -75,23 +79,44 @@ fn get_read_groups(dataset: &str, href: &HashMap<Metadata, DataValue>) -> String
 	read_groups
 }
 
-pub fn make_map_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, job: usize) -> QPipe
-{
+// If stats is None then no trimming is performed and read_trim is only used for input validation and QC
+fn make_trim_args(gem_bs: &GemBS, task: &Task, stats: Option<&Path>, qc: Option<&Path>, files: &[&Asset], interleaved: bool) -> String {
 	lazy_static! {
-    	static ref OPT_LIST: Vec<(&'static str, &'static str, VarType)> = {
-        	let mut m = Vec::new();
-        	m.push(("underconversion_sequence", "underconversion-sequence", VarType::String));
-        	m.push(("overconversion_sequence", "overconversion-sequence", VarType::String));
-        	m.push(("benchmark_mode", "benchmark-mode", VarType::Bool));
-			m
-		};
+		static ref OPT_LIST: Vec<(&'static str, &'static str, VarType)> = vec!(
+			("adapter1", "adapter", VarType::String),
+			("adapter2", "adapter2", VarType::String),
+			("adapter_min_overlap", "min-overlap", VarType::Int),
+			("adapter_error_rate", "error-rate", VarType::Float),
+			("trim_quality", "quality", VarType::Int),
+			("min_read_length", "min-length", VarType::Int),
+			("poly_g", "poly-g", VarType::Bool),
+			("poly_g_length", "poly-g-length", VarType::Int),
+			("clip_preset", "preset", VarType::String),
+			("clip_r1_5p", "clip-r1-5p", VarType::Int),
+			("clip_r1_3p", "clip-r1-3p", VarType::Int),
+			("clip_r2_5p", "clip-r2-5p", VarType::Int),
+			("clip_r2_3p", "clip-r2-3p", VarType::Int),
+		);
 	}
+	let mut args = String::new();
+	if interleaved { args.push_str("--interleaved\x1e") }
+	if let Some(x) = qc { args.push_str(format!("--qc\x1e{}\x1e", x.display()).as_str()) }
+	if let Some(x) = stats {
+		args.push_str(format!("--stats\x1e{}\x1e", x.display()).as_str());
+		super::add_sample_command_opts(gem_bs, &mut args, Section::Trimming, task.barcode().map(|x| x.as_str()), &OPT_LIST);
+	} else { args.push_str("--no-trim\x1e") }
+	let files: Vec<String> = files.iter().map(|f| format!("{}", f.path().display())).collect();
+	args.push_str(&files.join("\x1e"));
+	args
+}
+
+pub fn make_map_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, job: usize) -> Result<QPipe, String>
+{
+	let aligner = get_aligner(gem_bs)?;
 	let threads = gem_bs.get_config_int(Section::Mapping, "threads");
 	let mapping_threads = gem_bs.get_config_int(Section::Mapping, "mapping_threads").or(threads);
 	let sort_threads = gem_bs.get_config_int(Section::Mapping, "sort_threads").or(mapping_threads);
 	let mut pipeline = QPipe::new(gem_bs.get_signal_clone());
-	let mapper_path = gem_bs.get_exec_path("gem-mapper");
-	let mut mapper_args = if let Some(t) = mapping_threads { format!("--threads\x1e{}\x1e", t) } else { String::new() };
 	let task = &gem_bs.get_tasks()[job];
 	if let Some(x) = task.log() { pipeline.log = Some(gem_bs.get_asset(x).expect("Couldn't get log file").path().to_owned()) }
 	// Check type of mapping
@@ -113,33 +138,35 @@ pub fn make_map_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str, DataVal
 	// Set read_groups
 	let read_groups = get_read_groups(dataset, href);
 
-	// Setup rest of arguments for mapper
+	// Setup mapping stage(s)
 	let ftype = if let Some(DataValue::FileType(t)) = href.get(&Metadata::FileType) { Some(*t) } else { None };
 	let paired = if let Some(DataValue::Bool(x)) = options.get("paired") { *x } else { matches!(ftype, Some(FileType::Paired) | Some(FileType::Interleaved)) };
-
-	mapper_args.push_str(format!("-I\x1e{}\x1e", index.path().display()).as_str());
-	if vfile.len() == 2 {
-		mapper_args.push_str(format!("--i1\x1e{}\x1e--i2\x1e{}\x1e", vfile[0].path().display(), vfile[1].path().display()).as_str());
-	} else if let Some(FileType::BAM) = ftype {
-		let bam2fq = gem_bs.get_exec_path("samtools");
-		let args = if let Some(t) = mapping_threads { format!("bam2fq\x1e{}\x1e--threads\x1e{}", vfile[0].path().display(), t) } 
-		else { format!("bam2fq {}", vfile[0].path().display()) };
-		pipeline.add_stage(&bam2fq, &args);
-	} else { mapper_args.push_str(format!("-i\x1e{}\x1e", vfile[0].path().display()).as_str()) }
-	if paired { mapper_args.push_str("--paired-end-alignment\x1e")}
-	if gem_bs.get_config_bool(Section::Mapping, "non_stranded") { mapper_args.push_str("--bisulfite-conversion\x1enon-stranded\x1e") }
-	else if gem_bs.get_config_bool(Section::Mapping, "reverse_conversion") { mapper_args.push_str("--bisulfite-conversion\x1einferred-G2A-C2T\x1e") }
-	else { mapper_args.push_str("--bisulfite-conversion\x1einferred-C2T-G2A\x1e") }
 	
-	super::add_command_opts(gem_bs, &mut mapper_args, Section::Mapping, &OPT_LIST);
-
-	mapper_args.push_str(format!("--report-file\x1e{}\x1e", outs[2].unwrap().path().display()).as_str());
-	mapper_args.push_str(format!("--sam-read-group-header\x1e{}", read_groups).as_str());
+	// Setup trimming and/or QC stage if required.  Reads (interleaved if paired) are sent to the mapper on stdin
+	let files = if outs[3].is_some() || outs[4].is_some() {
+		let read_trim = gem_bs.get_exec_path("read_trim");
+		pipeline.add_stage(&read_trim, &make_trim_args(gem_bs, task, outs[3].map(|x| x.path()), outs[4].map(|x| x.path()), &vfile, paired && vfile.len() == 1));
+		Vec::new()
+	} else { vfile.iter().map(|x| x.path()).collect() };
+	let map_job = MapJob{threads: mapping_threads, index: index.path(), bisulfite: index.id() == "index", files,
+		ftype, paired, read_groups, report: outs[2].unwrap().path(), output: outfile.path(), cram, barcode: task.barcode().map(|x| x.as_str())};
+	aligner.add_map_stages(gem_bs, &mut pipeline, &map_job)?;
+	if let Some(com) = aligner.map_stats_com(gem_bs, &map_job) { pipeline.set_post_com(com) }
 	
 	// Setup read_filter stage
 	let read_filter = gem_bs.get_exec_path("read_filter");
 	let contig_md5 = gem_bs.get_asset("contig_md5").expect("Couldn't find contig md5 asset");
-	let read_filter_args = format!("{}", contig_md5.path().display());
+	let mut read_filter_args = format!("{}", contig_md5.path().display());
+	if let Some(x) = outs[5] {
+		let genomes = gem_bs.get_asset("contig_genomes").expect("Couldn't find contig genomes asset");
+		let primary = gem_bs.get_config_str(Section::Index, "reference_name").expect("No reference name");
+		read_filter_args.push_str(format!("\x1e--genomes\x1e{}\x1e--primary\x1e{}\x1e--stats\x1e{}", genomes.path().display(), primary, x.path().display()).as_str());
+		let remove = match task.barcode() {
+			Some(bc) if gem_bs.get_sample_config_bool(bc, Section::Mapping, "disambiguate") => gem_bs.get_config_stringvec(Section::Index, "xenograft_genomes"),
+			_ => None,
+		};
+		if let Some(v) = remove.filter(|v| !v.is_empty()) { read_filter_args.push_str(format!("\x1e--remove\x1e{}", v.join(",")).as_str()) }
+	}
 	
 	// Setup samtools stage
 	let samtools = gem_bs.get_exec_path("samtools");
@@ -148,19 +175,18 @@ pub fn make_map_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str, DataVal
 	if let Some(x) = gem_bs.get_config_str(Section::Mapping, "sort_memory") { samtools_args.push_str(format!("-m\x1e{}\x1e", x).as_str())}
 	if let Some(x) = sort_threads { samtools_args.push_str(format!("--threads\x1e{}\x1e", x).as_str())}
 	if single_bam { samtools_args.push_str("--write-index\x1e") }
-	if cram { samtools_args.push_str("-O\x1eCRAM\x1e") }
-	if gem_bs.get_config_bool(Section::Mapping, "benchmark_mode") { samtools_args.push_str("--no-PG\x1e") } else if cram {
+	if cram {
 		let gembs_ref = gem_bs.get_asset("gembs_reference").expect("Couldn't find gemBS reference asset");
-		samtools_args.push_str(format!("--reference\x1e{}\x1e", gembs_ref.path().display()).as_str());
+		samtools_args.push_str(format!("-O\x1eCRAM\x1e--reference\x1e{}\x1e", gembs_ref.path().display()).as_str());
 	}
+	if gem_bs.get_config_bool(Section::Mapping, "benchmark_mode") { samtools_args.push_str("--no-PG\x1e") }
 	samtools_args.push('-');
 	if gem_bs.get_config_bool(Section::Mapping, "keep_logs") { pipeline.set_remove_log(false) }
 	for out in task.outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get md5sum output asset").path()); }
 
-	pipeline.add_stage(&mapper_path, &mapper_args)
-			.add_stage(&read_filter, &read_filter_args)
+	pipeline.add_stage(&read_filter, &read_filter_args)
 			.add_stage(&samtools, &samtools_args);
-	pipeline
+	Ok(pipeline)
 }
 
 pub fn make_merge_bams_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str, DataValue>, job: usize) -> QPipe
@@ -174,15 +200,15 @@ pub fn make_merge_bams_pipeline(gem_bs: &GemBS, options: &HashMap<&'static str,
 	let task = &gem_bs.get_tasks()[job];
 	let output = gem_bs.get_asset(*task.outputs().next().expect("No output files for merge step")).expect("Couldn't get asset");
 	let cram = output.id().ends_with(".cram");
-	if cram { args.push_str("-O\x1ecram\x1e") }
-	if gem_bs.get_config_bool(Section::Mapping, "benchmark_mode") { args.push_str("--no-PG\x1e") } else if cram {
+	if cram {
 		let gembs_ref = gem_bs.get_asset("gembs_reference").expect("Couldn't find gemBS reference asset");
-		args.push_str(format!("--reference\x1e{}\x1e", gembs_ref.path().display()).as_str());
+		args.push_str(format!("-O\x1ecram\x1e--reference\x1e{}\x1e", gembs_ref.path().display()).as_str());
 	}
+	if gem_bs.get_config_bool(Section::Mapping, "benchmark_mode") { args.push_str("--no-PG\x1e") }
 	args.push_str(format!("-f\x1e{}\x1e", output.path().display()).as_str());
 	let remove_bams = if let Some(DataValue::Bool(x)) = options.get("remove") { *x } else { 
 		gem_bs.get_config_bool(Section::Mapping, "remove_individual_bams") };	
-	for asset in task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset")).filter(|x| x.id().ends_with(".bam")) {
+	for asset in task.inputs().map(|x| gem_bs.get_asset(*x).expect("Couldn't get asset")).filter(|x| x.id().ends_with(".bam") || x.id().ends_with(".cram")) {
 		args.push_str(format!("{}\x1e", asset.path().display()).as_str());
 		if remove_bams { pipeline.add_remove_file(&asset.path()); }
 	}	
@@ -4,9 +4,9 @@ use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
 use crate::config::GemBS;
-use crate::common::defs::{Section, Metadata, DataValue, Command};
+use crate::common::defs::{Section, Metadata, DataValue, Command, Sex};
 use crate::common::assets::GetAsset;
-use crate::common::json_call_stats::CallJson;
+use crate::common::json_call_stats::{CallJson, RecalStats};
 use super::{QPipe, QPipeCom};
 use utils::compress;
 use crate::common::utils::check_signal;
@@ -17,6 +17,10 @@ pub struct SampleJsonFiles {
 	pub barcode: String,
 	pub bc_dir: PathBuf,
 	pub json_files: Vec<(String, PathBuf)>,
+	pub trim_files: Vec<(String, PathBuf)>,
+	pub qc_files: Vec<(String, PathBuf)>,
+	pub genome_files: Vec<(String, PathBuf)>,
+	pub cram_file: Option<PathBuf>,
 }
 
 #[derive(Debug)]
@@ -24,12 +28,14 @@ pub struct CallJsonFiles {
 	pub barcode: String,
 	pub bc_dir: PathBuf,
 	pub json_file: PathBuf,
+	pub sex: Option<Sex>,
 }
 
 #[derive(Debug)]
 pub struct MergeJsonFiles {
 	pub barcode: String,
 	pub json_files: Vec<(String, PathBuf)>,
+	pub recal_stats: Option<PathBuf>,
 }
 
 pub fn make_map_report_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
@@ -64,7 +70,11 @@ pub fn make_map_report_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
 			}
 			v
 		};
-		json_files.push(SampleJsonFiles{barcode: bc.to_owned(), bc_dir, json_files: v});
+		let trim_files = dvec.iter().filter_map(|dat| gem_bs.get_asset(format!("{}_trim.json", dat).as_str()).map(|x| (dat.to_owned(), x.path().to_owned()))).collect();
+		let qc_files = dvec.iter().filter_map(|dat| gem_bs.get_asset(format!("{}_qc.json", dat).as_str()).map(|x| (dat.to_owned(), x.path().to_owned()))).collect();
+		let genome_files = dvec.iter().filter_map(|dat| gem_bs.get_asset(format!("{}_genomes.json", dat).as_str()).map(|x| (dat.to_owned(), x.path().to_owned()))).collect();
+		let cram_file = gem_bs.get_asset(format!("{}.cram", bc).as_str()).map(|x| x.path().to_owned());
+		json_files.push(SampleJsonFiles{barcode: bc.to_owned(), bc_dir, json_files: v, trim_files, qc_files, genome_files, cram_file});
 	}
 	let mut css_dir = gem_bs.get_css_path();
 	css_dir.push("style.css");
@@ -83,11 +93,18 @@ pub fn make_call_report_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
 	for out in task.outputs() { pipeline.add_outputs(gem_bs.get_asset(*out).expect("Couldn't get call-report output asset").path()); }
 	let mut json_files = Vec::new();
 	let samples = gem_bs.get_samples();
+	// Expected sex of samples from the metadata
+	let mut sample_sex = HashMap::new();
+	for href1 in gem_bs.get_sample_data_ref().values() {
+		if let (Some(DataValue::String(bc)), Some(DataValue::String(s))) = (href1.get(&Metadata::SampleBarcode), href1.get(&Metadata::Sex)) {
+			if let Ok(sex) = s.parse::<Sex>() { sample_sex.insert(bc.to_owned(), sex); }
+		}
+	}
 	for (bc, _) in samples.iter() { 
 		let bc_dir = gem_bs.get_asset(format!("{}_mapping_coverage.html", bc).as_str()).expect("Couldn't find call report asset")
 			.path().parent().expect("No parent dir found for call report file").to_owned();
 		let json_file = gem_bs.get_asset(format!("{}_call.json", bc).as_str()).expect("Couldn't find call JSON asset for call report").path().to_owned();
-		json_files.push(CallJsonFiles{barcode: bc.to_owned(), bc_dir, json_file});
+		json_files.push(CallJsonFiles{barcode: bc.to_owned(), bc_dir, json_file, sex: sample_sex.get(bc).copied()});
 	}
 	let mut css_dir = gem_bs.get_css_path();
 	css_dir.push("style.css");
@@ -117,12 +134,15 @@ pub fn make_merge_call_jsons_pipeline(gem_bs: &GemBS, job: usize) -> QPipe
 	let bc = task.barcode().expect("No barcode set for merge-call-jsons task");
 	let mut it = task.inputs();
 	let _ = it.next(); // Throw array first element as this is the BCF file
-	let json_files: Vec<_> = it.map(|x| {
-		let asset = gem_bs.get_asset(*x).expect("Couldn't find JSON file asset");
+	let mut recal_stats = None;
+	let mut json_files = Vec::new();
+	for asset in it.map(|x| gem_bs.get_asset(*x).expect("Couldn't find JSON file asset")) {
 		pipeline.add_remove_file(asset.path());
-		(asset.id().to_owned(), asset.path().to_owned())
-	}).collect();
-	let com = QPipeCom::MergeCallJsons(MergeJsonFiles{barcode: bc.to_owned(), json_files});
+		// Recalibration statistics for the sample from the BCF merge step
+		if asset.id().ends_with("_recal.json") { recal_stats = Some(asset.path().to_owned()) } 
+		else { json_files.push((asset.id().to_owned(), asset.path().to_owned())) }
+	}
+	let com = QPipeCom::MergeCallJsons(MergeJsonFiles{barcode: bc.to_owned(), json_files, recal_stats});
 	pipeline.add_com(com);
 	pipeline		
 }
@@ -139,7 +159,13 @@ pub fn merge_call_jsons(sig: Arc<AtomicUsize>, outputs: &[PathBuf], sfiles: &Mer
 		else { Some(jstats) }
 	}
 	check_signal(sig)?;
-	if let Some(st) = combined_stats {
+	if let Some(mut st) = combined_stats {
+		// The recalibration statistics are null if there were too few known variants for recalibration
+		if let Some(path) = sfiles.recal_stats.as_ref() {
+			let rdr = compress::open_bufreader(path).map_err(|e| format!("{}", e))?;
+			let recal: Option<RecalStats> = serde_json::from_reader(rdr).map_err(|e| format!("Couldn't parse recalibration JSON file {}: {}", path.display(), e))?;
+			if let Some(rs) = recal { st.set_recalibration(rs) }
+		}
 		let output = outputs.first().expect("No output file for merge JSON command");
 		let wrt = compress::open_bufwriter(&output).map_err(|e| format!("{}", e))?;
 		st.to_writer(wrt)?;
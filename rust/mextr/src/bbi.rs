@@ -5,6 +5,7 @@ use std::ffi::CString;
 use crossbeam_channel::Sender;
 use libc::{c_void, memcpy};
 use crate::config::{Mode, ConfHash};
+use crate::output::bw_tracks::parse_bw_tracks;
 
 pub mod bbi_file_struct;
 pub mod compress_bbi;
@@ -21,6 +22,16 @@ use bbi_utils::*;
 const BB_ITEMS_PER_SLOT: u32 = 512;
 const BW_ITEMS_PER_SLOT: u32 = 1024;
 
+/// Independent groups of bbi files, each with their own compress and write threads
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BbiSet {
+	BedMethyl,
+	Haplotype,
+	Context,
+}
+
+pub const N_BBI_SETS: usize = 3;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum BbiBlockType {
 	Bb(u8),
@@ -247,14 +258,29 @@ impl Bbi {
 			BbiFile::new(format!("{}_chg.bb", prefix.as_ref()), 1, bb_zoom_scales.clone(), true)?,
 			BbiFile::new(format!("{}_chh.bb", prefix.as_ref()), 2, bb_zoom_scales, true)?
 		);
-		let bw_files = if strand_specific { vec!( 
+		let mut bw_files = if strand_specific { vec!( 
 			BbiFile::new(format!("{}_pos.bw", prefix.as_ref()), 0, bw_zoom_scales.clone(), false)?,
-			BbiFile::new(format!("{}_neg.bw", prefix.as_ref()), 1, bw_zoom_scales, false)?
-		)} else { vec!(BbiFile::new(format!("{}.bw", prefix.as_ref()), 0, bw_zoom_scales, false)?)};
+			BbiFile::new(format!("{}_neg.bw", prefix.as_ref()), 1, bw_zoom_scales.clone(), false)?
+		)} else { vec!(BbiFile::new(format!("{}.bw", prefix.as_ref()), 0, bw_zoom_scales.clone(), false)?)};
 		
+		// Additional (optional) bigWig tracks
+		if let Some(s) = chash.get_str("bw_tracks") {
+			for tr in parse_bw_tracks(s)?.iter() {
+				let ix = bw_files.len();
+				bw_files.push(BbiFile::new(format!("{}_{}.bw", prefix.as_ref(), tr.name()), ix, bw_zoom_scales.clone(), false)?)
+			}
+		}
 		Ok(Bbi{bb_files, bw_files, sender: Some(sender), n_output_ctgs})
 
 	}
+	// Set of bigWig files with no associated bigBed files
+	pub fn init_bw<S: AsRef<str>>(names: &[S], sender: Sender<BbiMsg>, chash: &ConfHash) -> io::Result<Self> {
+		let bw_zoom_scales = Arc::new(make_zoom_scales().1);
+		let n_output_ctgs = chash.vcf_contigs().iter().filter(|x| x.out_ix().is_some()).count();
+		let mut bw_files = Vec::with_capacity(names.len());
+		for (ix, name) in names.iter().enumerate() { bw_files.push(BbiFile::new(name, ix, bw_zoom_scales.clone(), false)?) }
+		Ok(Bbi{bb_files: Vec::new(), bw_files, sender: Some(sender), n_output_ctgs})
+	}
 	pub fn drop_sender(&mut self) { 
 		self.sender = None;
 		trace!("Bbi drop_sender()");
@@ -5,7 +5,7 @@ use crate::config::ConfHash;
 use super::write_bbi::BbiWriter;
 use super::tree::RTree;
 use super::bbi_zoom::{ZOOM_LEVELS, ZoomHeader};
-use super::{BbiCtgBlock, BbiBlockType};
+use super::{BbiCtgBlock, BbiBlockType, BbiSet};
 use super::bbi_file_struct::*;
 
 /// Finish off writing of bbi file
@@ -21,12 +21,12 @@ use super::bbi_file_struct::*;
 ///  
 ///    Write complete header
 /// 
-pub fn bbi_finish(ch: Arc<ConfHash>, mut writer: BbiWriter) {
+pub fn bbi_finish(ch: Arc<ConfHash>, set: BbiSet, mut writer: BbiWriter) {
 	let bbi_type = writer.bbi_type();
 	debug!("bbi_finish starting for {:?}", bbi_type);
 
 	let (n_rec, n_zoom_rec, summary, zoom_scales) = {	
-		let bbi_ref = ch.bbi().read().unwrap();
+		let bbi_ref = ch.bbi(set).read().unwrap();
 		let bbi = bbi_ref.as_ref().expect("Bbi not set");
 		
 		let build = match bbi_type {
@@ -72,7 +72,7 @@ pub fn bbi_finish(ch: Arc<ConfHash>, mut writer: BbiWriter) {
 
 	// Write out headers
 	let header = &mut writer.header;
-	header.set_uncompress_buf_size(ch.max_uncomp_size(bbi_type).expect("No data!") as u32);
+	header.set_uncompress_buf_size(ch.max_uncomp_size(set, bbi_type).expect("No data!") as u32);
 	header.set_full_index_offset(writer.index_offset);
 	header.write(&mut writer.fp).expect("Error writing out main header");
 	for zhdr in zoom_hdr.iter() { zhdr.write(&mut writer.fp).expect("Error writing zoom headers"); }
@@ -30,7 +30,7 @@ fn update_max_size(hash: &mut HashMap<BbiBlockType, usize>, bbi_type: BbiBlockTy
 	*curr_val = (*curr_val).max(sz);
 }
 
-pub fn compress_bbi_thread(ch: Arc<ConfHash>, r: Receiver<BbiMsg>, ps: Sender<BbiMsg>) {
+pub fn compress_bbi_thread(ch: Arc<ConfHash>, set: BbiSet, r: Receiver<BbiMsg>, ps: Sender<BbiMsg>) {
 	info!("compress_bbi_thread starting up");
 	let mut max_uncomp_size = HashMap::new();
 	for msg in r.iter() {
@@ -50,6 +50,6 @@ pub fn compress_bbi_thread(ch: Arc<ConfHash>, r: Receiver<BbiMsg>, ps: Sender<Bb
 			BbiMsg::EndOfSection(_) => ps.send(msg).expect("Error sending compressed data block"),
 		}
 	}
-	for (bbi_type, sz) in max_uncomp_size.iter() {	ch.update_max_uncomp_size(*bbi_type, *sz) }
+	for (bbi_type, sz) in max_uncomp_size.iter() {	ch.update_max_uncomp_size(set, *bbi_type, *sz) }
 	info!("compress_bbi_thread shutting down");
 }
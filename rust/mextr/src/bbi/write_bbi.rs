@@ -47,9 +47,9 @@ impl BbiWriter {
 	}
 }
 
-fn init_writers(ch: &ConfHash) -> (Vec<BbiWriter>, Vec<BbiWriter>) {
+fn init_writers(ch: &ConfHash, set: BbiSet) -> (Vec<BbiWriter>, Vec<BbiWriter>) {
 	trace!("write_bbi_thread - initialize writers");
-	let bbi_ref = ch.bbi().read().unwrap();
+	let bbi_ref = ch.bbi(set).read().unwrap();
 	let bbi = bbi_ref.as_ref().expect("Bbi not set");
 	let n_ctgs = bbi.n_output_ctgs();
 	trace!("init bb writers");
@@ -108,9 +108,9 @@ fn add_block(ws: &mut WriterState, blk: &BbiBlock) {
 	blocks.push(BbiCtgBlock::new(blk, pos));
 }
 
-pub fn write_bbi_thread(ch: Arc<ConfHash>, r: Receiver<BbiMsg>) {
+pub fn write_bbi_thread(ch: Arc<ConfHash>, set: BbiSet, r: Receiver<BbiMsg>) {
 	info!("write_bbi_thread starting up");
-	let (mut bb_writers, mut bw_writers) = init_writers(&ch);
+	let (mut bb_writers, mut bw_writers) = init_writers(&ch, set);
 	let mut state = HashMap::new();
 	
 	for (ix, w) in bb_writers.iter_mut().enumerate() { state.insert(BbiBlockType::Bb(ix as u8), WriterState::new(w)); }
@@ -184,7 +184,7 @@ pub fn write_bbi_thread(ch: Arc<ConfHash>, r: Receiver<BbiMsg>) {
 	let mut threads = Vec::with_capacity(5);
 	for w in bb_writers.drain(..).chain(bw_writers.drain(..)) { 
 		let chc = ch.clone();
-		let th = thread::spawn(move || bbi_finish(chc, w));
+		let th = thread::spawn(move || bbi_finish(chc, set, w));
 		threads.push(th);
 	}	
 	
@@ -5,11 +5,11 @@ use clap::App;
 mod options;
 pub mod cli_utils;
 
-use r_htslib::BcfSrs;
 use crate::config::ConfHash;
+use crate::read_vcf::VcfInput;
 
 
-pub fn process_cli() -> io::Result<(ConfHash, BcfSrs)> {
+pub fn process_cli() -> io::Result<(ConfHash, VcfInput)> {
 	let yaml = load_yaml!("cli/cli.yml");
     let app = App::from_yaml(yaml).version(crate_version!());
 	
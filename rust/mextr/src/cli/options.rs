@@ -1,15 +1,33 @@
 use std::io;
+use std::str::FromStr;
 use std::collections::HashMap;
 use clap::ArgMatches;
 
 use r_htslib::*;
 use super::cli_utils;
 use crate::config::*;
+use crate::output::context::ContextMask;
+use crate::output::bw_tracks::parse_bw_tracks;
+use crate::read_vcf::{VcfInput, StreamInput, has_index};
 
-pub const OPTS: [(&str, ConfVar);21] = [
+pub const OPTS: [(&str, ConfVar);35] = [
 	("cpgfile", ConfVar::String(None)),
 	("noncpgfile", ConfVar::String(None)),
 	("bed_methyl", ConfVar::String(None)),
+	("haplotype", ConfVar::String(None)),
+	("bam", ConfVar::String(None)),
+	("hap_window", ConfVar::Int(4)),
+	("hap_min_reads", ConfVar::Int(10)),
+	("hap_mapq", ConfVar::Int(20)),
+	("allele_report", ConfVar::String(None)),
+	("cpg_annotation", ConfVar::Bool(false)),
+	("mask_allele_cpg", ConfVar::Bool(false)),
+	("context_summary", ConfVar::String(None)),
+	("summary_regions", ConfVar::String(None)),
+	("context_bw", ConfVar::String(None)),
+	("bw_contexts", ConfVar::String(None)),
+	("context_filter", ConfVar::String(None)),
+	("bw_tracks", ConfVar::String(None)),
 	("bed_track_line", ConfVar::String(None)),
 	("report_file", ConfVar::String(None)),
 	("no_header", ConfVar::Bool(false)),
@@ -30,9 +48,7 @@ pub const OPTS: [(&str, ConfVar);21] = [
 	("select", ConfVar::Select(Select::Hom)),
 ];
 
-fn read_header(infile: &str) -> io::Result<(usize, Vec<VcfContig>)> {
-	let fp = HtsFile::new(infile, "rz")?;
-	let hdr = VcfHeader::read(fp)?;
+fn header_info(hdr: &bcf_hdr_t, infile: &str) -> io::Result<(usize, Vec<VcfContig>)> {
 	let ns = hdr.nsamples();
 	if ns == 0 { Err(new_err(format!("No samples in input file {}", infile)))}
 	else {
@@ -49,6 +65,20 @@ fn read_header(infile: &str) -> io::Result<(usize, Vec<VcfContig>)> {
 	}
 }
 
+fn read_header(infile: &str) -> io::Result<(usize, Vec<VcfContig>)> {
+	let fp = HtsFile::new(infile, "rz")?;
+	let hdr = VcfHeader::read(fp)?;
+	header_info(&hdr, infile)
+}
+
+// Set output indexes (used for bbi files) for all contigs sorted by name.  This is used when reading 
+// sequentially so the regions from the synced reader are not available
+fn set_all_contig_out_ix(chash: &mut ConfHash) {
+	let mut ctgs: Vec<_> = chash.vcf_contigs().iter().enumerate().map(|(i, c)| (c.name().to_owned(), i)).collect();
+	ctgs.sort_unstable();
+	for (ix, (_, rid)) in ctgs.iter().enumerate() { chash.set_contig_out_ix(*rid, ix) }
+}
+
 ///
 /// Set regions to iterate over
 /// If no regions specified on command line, use all contigs found in the VCF header
@@ -131,7 +161,7 @@ fn setup_bed_methyl_structs(chash: &mut ConfHash, hdr: &bcf_hdr_t, infile: &str,
 	Ok(())	
 }
 
-pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, BcfSrs)> {
+pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, VcfInput)> {
 	
 	let mut conf_hash: HashMap<&'static str, ConfVar> = HashMap::new();
 	// Handle simple options
@@ -150,7 +180,18 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, BcfSrs)> {
 	let prop = if let Some(x) = cli_utils::get_f64(m, "prop", 0.0, 1.0)? { x } else { 0.0 };
 
 	let infile = m.value_of("input").expect("No input filename"); // This should not be allowed by Clap	
-	let (ns, vcf_contigs) = read_header(infile)?;
+	// Additional input files (i.e., per pool BCFs) to be read after the main input file 
+	let mut infiles = vec!(infile);
+	if let Some(v) = m.values_of("concat") { v.for_each(|f| infiles.push(f)) }
+	
+	// If we have multiple input files, input from stdin or no index we read the input sequentially
+	let mut stream = if infiles.len() > 1 || !has_index(infile) {
+		if m.is_present("region_list") || m.is_present("regions") || m.is_present("regions_file") {
+			return Err(new_err("Region selection requires a single indexed input file".to_string()))
+		}
+		Some(StreamInput::new(&infiles)?)
+	} else { None };
+	let (ns, vcf_contigs) = if let Some(s) = stream.as_ref() { header_info(s.header(), infile)? } else { read_header(infile)? };
 	
 	let mut chash = ConfHash::new(conf_hash, vcf_contigs);
 	
@@ -160,9 +201,22 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, BcfSrs)> {
 	// Check threshold
 	if chash.get_int("threshold") > 255 { chash.set("threshold", ConfVar::Int(255)) }
 	
+	// Check haplotype window size (patterns are stored as bit vectors)
+	let k = chash.get_int("hap_window");
+	if !(1..=16).contains(&k) { return Err(new_err(format!("Haplotype window size {} out of range (1-16)", k))) }
+	
+	// Check context patterns
+	if chash.get_str("bw_contexts").is_none() { chash.set("bw_contexts", ConfVar::String(Some("CHG,CHH".to_owned()))) }
+	for var in &["context_filter", "bw_contexts"] {
+		if let Some(s) = chash.get_str(var) { ContextMask::from_str(s)?; }
+	}
+	
+	// Check extra bigWig tracks
+	if let Some(s) = chash.get_str("bw_tracks") { parse_bw_tracks(s)?; }
+	
 	// Check output names for .gz
 	// If so, strip suffix and set compress option
-	for var in &["cpgfile", "noncpgfile", "bed_methyl"] {
+	for var in &["cpgfile", "noncpgfile", "bed_methyl", "haplotype", "allele_report"] {
 		let tmp = chash.get_str(var).and_then(|s| s.strip_suffix(".gz")).map(|s| s.to_owned());
 		if let Some(s) = tmp { 
 			chash.set(var, ConfVar::String(Some(s)));
@@ -176,15 +230,21 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, BcfSrs)> {
 		chash.set("tabix", ConfVar::Bool(false)) 
 	}
 	
-	// Set up synced reader
-	let mut sr = BcfSrs::new()?;
-	// And regions
-	handle_regions(m, &mut chash, &mut sr)?;
-	// And input file
-	sr.add_reader(infile)?;
+	let mut input = if let Some(s) = stream.take() { 
+		set_all_contig_out_ix(&mut chash);
+		VcfInput::Stream(s) 
+	} else {
+		// Set up synced reader
+		let mut sr = BcfSrs::new()?;
+		// And regions
+		handle_regions(m, &mut chash, &mut sr)?;
+		// And input file
+		sr.add_reader(infile)?;
+		VcfInput::Synced(sr)
+	};
 	
 	// Get VCF header from input file
-	let hdr = sr.get_reader_hdr(0)?;
+	let hdr = input.header()?;
 	
 	// Check minimum sample numer
 	let mn = chash.get_int("number").min(ns);
@@ -192,6 +252,10 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<(ConfHash, BcfSrs)> {
 	chash.set("number", ConfVar::Int(mn));
 	
 	setup_bed_methyl_structs(&mut chash, hdr, infile, m.is_present("bed_methyl"))?;
+	for var in &["context_summary", "context_bw"] {
+		if m.is_present(var) && ns > 1 { return Err(new_err(format!("Input file {} has {} samples: {} output incompatible with multi-sample files", infile, ns, var))) }
+	}
+	if m.is_present("haplotype") && ns > 1 { return Err(new_err(format!("Input file {} has {} samples: haplotype output incompatible with multi-sample files", infile, ns))) }
 
-	Ok((chash, sr))
+	Ok((chash, input))
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::io::{self, Error, ErrorKind};
 use std::sync::{RwLock, Arc};
 
-use crate::bbi::{Bbi, BbiBlockType};
+use crate::bbi::{Bbi, BbiBlockType, BbiSet, N_BBI_SETS};
 
 pub fn new_err(s: String) -> io::Error {
 	Error::new(ErrorKind::Other, s)	
@@ -48,14 +48,14 @@ pub struct ConfHash {
 	out_files: RwLock<Vec<(String, bool)>>,
 	vcf_contigs: Vec<VcfContig>,
 	vcf_contig_hash: HashMap<Arc<Box<str>>, usize>,
-	bbi: RwLock<Option<Bbi>>,
-	max_uncomp_size: RwLock<HashMap<BbiBlockType, usize>>,
+	bbi: [RwLock<Option<Bbi>>; N_BBI_SETS],
+	max_uncomp_size: RwLock<HashMap<(BbiSet, BbiBlockType), usize>>,
 }
 
 impl ConfHash {
 	pub fn new(hash: HashMap<&'static str, ConfVar>, vcf_contigs: Vec<VcfContig>) -> Self { 
 		let vcf_contig_hash = vcf_contigs.iter().enumerate().fold(HashMap::new(), |mut h, (ix, ctg)| {h.insert(ctg.name.clone(), ix); h} );
-		ConfHash {hash, vcf_contigs, vcf_contig_hash, out_files: RwLock::new(Vec::new()), bbi: RwLock::new(None), max_uncomp_size: RwLock::new(Default::default()) } 
+		ConfHash {hash, vcf_contigs, vcf_contig_hash, out_files: RwLock::new(Vec::new()), bbi: Default::default(), max_uncomp_size: RwLock::new(Default::default()) } 
 	}
 	pub fn vcf_contigs(&self) -> &[VcfContig] { &self.vcf_contigs }	
 	pub fn contig_rid<S: AsRef<str>>(&self, ctg: S) -> Option<usize> { self.vcf_contig_hash.get(&(Box::<str>::from(ctg.as_ref()))).copied() }
@@ -95,25 +95,25 @@ impl ConfHash {
 	pub fn add_file<S: AsRef<str>>(&self, fname: S, tabix_flag: bool) {
 		self.out_files.write().unwrap().push((fname.as_ref().to_owned(), tabix_flag));
 	}
-	pub fn set_bbi(&self, bbi: Bbi) { 
+	pub fn set_bbi(&self, set: BbiSet, bbi: Bbi) { 
 		trace!("set_bbi()");
-		let mut p = self.bbi.write().unwrap();
+		let mut p = self.bbi[set as usize].write().unwrap();
 		*p = Some(bbi);
 		trace!("set_bbi() done");
 	}
-	pub fn drop_sender(&self) { 
+	pub fn drop_sender(&self, set: BbiSet) { 
 		trace!("config drop_sender()");
-		let mut p = self.bbi.write().unwrap();
+		let mut p = self.bbi[set as usize].write().unwrap();
 		if let Some(bbi) = (*p).as_mut() { bbi.drop_sender() }
 		trace!("drop_sender done()");
 	}
-	pub fn bbi(&self) -> &RwLock<Option<Bbi>> { &self.bbi }
-	pub fn update_max_uncomp_size(&self, bbi_type: BbiBlockType, sz: usize) {
+	pub fn bbi(&self, set: BbiSet) -> &RwLock<Option<Bbi>> { &self.bbi[set as usize] }
+	pub fn update_max_uncomp_size(&self, set: BbiSet, bbi_type: BbiBlockType, sz: usize) {
 		let mut hash = self.max_uncomp_size.write().unwrap();
-		let curr_val = hash.entry(bbi_type).or_insert(0);
+		let curr_val = hash.entry((set, bbi_type)).or_insert(0);
 		*curr_val = (*curr_val).max(sz);
 	}
-	pub fn max_uncomp_size(&self, bbi_type: BbiBlockType) -> Option<usize> { self.max_uncomp_size.read().unwrap().get(&bbi_type).copied() }
+	pub fn max_uncomp_size(&self, set: BbiSet, bbi_type: BbiBlockType) -> Option<usize> { self.max_uncomp_size.read().unwrap().get(&(set, bbi_type)).copied() }
 }
 
 
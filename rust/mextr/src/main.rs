@@ -11,8 +11,8 @@ pub mod output;
 pub mod bbi;
 
 fn main() -> Result<(), String> {
-	let (chash, sr) = cli::process_cli().map_err(|e| format!("mextr_index initialization failed with error: {}", e))?;
-	match process::process(chash, sr) {
+	let (chash, input) = cli::process_cli().map_err(|e| format!("mextr_index initialization failed with error: {}", e))?;
+	match process::process(chash, input) {
 		Ok(_) => Ok(()),
 		Err(e) => {
 			error!("mextr failed with error: {}", e);
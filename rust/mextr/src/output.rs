@@ -3,16 +3,16 @@ use std::io::{self, Write};
 use std::sync::Arc;
 use std::collections::HashMap;
 use std::ops::Deref;
-use std::thread;
+use std::thread::{self, JoinHandle};
 
 use r_htslib::{HtsFile, VcfHeader};
 use libc::c_int;
-use crossbeam_channel::bounded;
+use crossbeam_channel::{bounded, Sender};
 
 use super::config::*;
 use super::read_vcf::unpack::{Strand, RecordBlock, RecordBlockElem};
 use super::process::{Recv, TPool};
-use super::bbi::Bbi;
+use super::bbi::{Bbi, BbiSet, BbiMsg};
 use super::bbi::compress_bbi::compress_bbi_thread;
 use super::bbi::write_bbi::write_bbi_thread;
 
@@ -22,6 +22,15 @@ pub mod output_noncpg;
 pub use output_noncpg::*;
 mod output_bed_methyl;
 use output_bed_methyl::*;
+mod output_haplotype;
+use output_haplotype::*;
+pub mod context;
+pub mod bw_tracks;
+use context::ContextMask;
+mod output_context;
+use output_context::*;
+mod output_allele;
+use output_allele::*;
 pub mod md5;
 pub mod tabix;
 
@@ -67,13 +76,15 @@ pub struct Record {
 	pos: u32,
 	cx: [u8; 5],
 	gt_strand: Option<(u8, Strand)>,
+	id: Option<Box<str>>,
 }
 
 impl Record {
-	pub fn new(rid: u32, pos: u32, cx: [u8; 5], gt_strand: Option<(u8, Strand)>) -> Self {
-		Self{rid, pos, cx, gt_strand}
+	pub fn new(rid: u32, pos: u32, cx: [u8; 5], gt_strand: Option<(u8, Strand)>, id: Option<Box<str>>) -> Self {
+		Self{rid, pos, cx, gt_strand, id}
 	}	
 	pub fn cx(&self) -> &[u8] { &self.cx }
+	pub fn id(&self) -> Option<&str> { self.id.as_deref() }
 	pub fn rid(&self) -> u32 { self.rid }
 	pub fn pos(&self) -> u32 { self.pos }
 	pub fn gt(&self) -> Option<u8> { self.gt_strand.map(|(gt, _)| gt) }
@@ -102,6 +113,9 @@ pub struct OutputOpts<'a> {
 	bw_mode: Mode,
 	select: Select,	
 	threshold: u8,
+	context_filter: Option<ContextMask>,
+	cpg_annotation: bool,
+	mask_allele_cpg: bool,
 }
 
 impl <'a>OutputOpts<'a> {
@@ -114,7 +128,10 @@ impl <'a>OutputOpts<'a> {
 			mode: chash.get_mode("mode"),
 			bw_mode: chash.get_mode("bw_mode"),
 			select: chash.get_select("select"),
-			threshold: chash.get_int("threshold") as u8
+			threshold: chash.get_int("threshold") as u8,
+			context_filter: chash.get_str("context_filter").map(|s| s.parse().expect("Illegal context filter")),
+			cpg_annotation: chash.get_bool("cpg_annotation"),
+			mask_allele_cpg: chash.get_bool("mask_allele_cpg"),
 		}
 	}
 	pub fn min_inform(&self) -> c_int { self.min_inform as c_int }
@@ -125,6 +142,12 @@ impl <'a>OutputOpts<'a> {
 	pub fn select(&self) -> Select { self.select }
 	pub fn threshold(&self) -> u8 { self.threshold }
 	pub fn sample_desc(&self) -> Option<&str> { self.sample_desc }
+	pub fn context_filter(&self) -> Option<ContextMask> { self.context_filter }
+	pub fn cpg_annotation(&self) -> bool { self.cpg_annotation }
+	// Should sites where the sample CpG status differs from the reference be masked from bigWig files?
+	pub fn mask_bw_site(&self, rec: &Record, mrec: &MethRec) -> bool {
+		self.mask_allele_cpg && site_cpg_status(rec, mrec).map(|s| s.is_variant()).unwrap_or(false)
+	}
 }
 
 fn open_output_file(name: &str, chash: &ConfHash, tp: TPool) -> HtsFile {
@@ -164,7 +187,6 @@ pub fn get_prob_dist(prb: &mut [f64]) {
 } 
 
 type PrintHeader = fn(&mut HtsFile, &VcfHeader, &ConfHash) -> io::Result<()>;
-type OutputBlock = fn(&mut [HtsFile], &RecordBlock, Option<RecordBlockElem>, &ConfHash, &VcfHeader) -> io::Result<()>;
 
 fn print_bed_methyl_header(f: &mut HtsFile, _hdr: &VcfHeader, chash: &ConfHash) -> io::Result<()> {
 	if let Some(track_line) = chash.get_str("bed_track_line") {
@@ -176,17 +198,30 @@ fn print_bed_methyl_header(f: &mut HtsFile, _hdr: &VcfHeader, chash: &ConfHash)
 	}
 }
 
-// Print header for CpG and NonCpG tab separated variable files
-fn print_tsv_header(f: &mut HtsFile, hdr: &VcfHeader, _chash: &ConfHash) -> io::Result<()> {
+// Print column headers for CpG and NonCpG tab separated variable files (without line terminator)
+fn print_tsv_columns(f: &mut HtsFile, hdr: &VcfHeader) -> io::Result<()> {
 	write!(f, "Contig\tPos0\tPos1\tRef")?;
 	for i in 0..hdr.nsamples() {
 		let name = hdr.sample_name(i)?;
 		write!(f, "\t{}:Call\t{}:Flags\t{}:Meth\t{}:non_conv\t{}:conv\t{}:support_call\t{}:total", name, name, name, name, name, name, name)?;
-	}	
+	}
+	Ok(())
+}
+
+// Print header for CpG and NonCpG tab separated variable files
+fn print_tsv_header(f: &mut HtsFile, hdr: &VcfHeader, _chash: &ConfHash) -> io::Result<()> {
+	print_tsv_columns(f, hdr)?;
 	writeln!(f)
 }
 
-pub fn output_handler(chash: &ConfHash, hdr: &VcfHeader, r: Recv, outfiles: &mut [HtsFile], ph: PrintHeader, ob: OutputBlock) {
+// Print header for CpG file, adding the annotation columns if required
+fn print_cpg_header(f: &mut HtsFile, hdr: &VcfHeader, chash: &ConfHash) -> io::Result<()> {
+	print_tsv_columns(f, hdr)?;
+	if chash.get_bool("cpg_annotation") { writeln!(f, "\tCpG_status\tID") } else { writeln!(f) }
+}
+
+pub fn output_handler<F>(chash: &ConfHash, hdr: &VcfHeader, r: Recv, outfiles: &mut [HtsFile], ph: PrintHeader, mut ob: F) 
+	where F: FnMut(&mut [HtsFile], &RecordBlock, Option<RecordBlockElem>, &ConfHash, &VcfHeader) -> io::Result<()> {
 	if !chash.get_bool("no_header") { for mut outfile in outfiles.iter_mut() { ph(&mut outfile, hdr, chash).expect("Error writing header") } }
 	let mut blk_store: HashMap<usize, Arc<RecordBlock>> = HashMap::new();
 	let mut curr_ix = 0;	
@@ -212,7 +247,7 @@ pub fn output_cpg_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Recv, tp:
 	let output = chash.get_str("cpgfile").expect("CpG output filename is missing");
 	let outfile = open_output_file(output, &chash, tp);
 	debug!("output_cpg_thread starting up");
-	output_handler(&chash, &hdr, r, &mut[outfile], print_tsv_header, output_cpg);
+	output_handler(&chash, &hdr, r, &mut[outfile], print_cpg_header, output_cpg);
 	debug!("output_cpg_thread closing down")
 }
 
@@ -231,11 +266,69 @@ pub fn output_bed_methyl_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Re
 	let mut outfiles: Vec<_> = ["cpg", "chg", "chh"].iter().map(|s| open_output_file(format!("{}_{}.bed", prefix, s).as_str(), &chash, tp.clone())).collect();
 	
 	// Prepare bbi files (BigBed and BigWig)
+	let (threads, wrt_send) = start_bbi_threads(&chash, BbiSet::BedMethyl, |s| Bbi::init(prefix, s, &chash));
+	
+	debug!("output_bed_methyl_thread thread starting up");
+	output_handler(&chash, &hdr, r, &mut outfiles, print_bed_methyl_header, output_bed_methyl);
+
+	finish_bbi_threads(&chash, BbiSet::BedMethyl, threads, wrt_send);
+	debug!("output_bed_methyl_thread closing down")	
+}
+
+pub fn output_haplotype_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Recv, tp: TPool) {
+	let prefix = chash.get_str("haplotype").expect("Haplotype prefix is missing").trim_end_matches(".txt").trim_end_matches("_haplotype");
+	let outfile = open_output_file(format!("{}_haplotype.txt", prefix).as_str(), &chash, tp);
+	let bw_names: Vec<_> = HAP_BW_TRACKS.iter().map(|s| format!("{}_{}.bw", prefix, s)).collect();
+	let (threads, wrt_send) = start_bbi_threads(&chash, BbiSet::Haplotype, |s| Bbi::init_bw(&bw_names, s, &chash));
+	let mut hap = HapState::new(&chash).unwrap_or_else(|e| panic!("Error setting up haplotype output: {}", e));
+
+	debug!("output_haplotype_thread starting up");
+	let mut outfiles = [outfile];
+	output_handler(&chash, &hdr, r, &mut outfiles, print_haplotype_header, |f, blk, prev, ch, hd| hap.add_block(f, blk, prev, ch, hd));
+	hap.finish(&mut outfiles, &chash, &hdr).expect("Error writing file");
+
+	finish_bbi_threads(&chash, BbiSet::Haplotype, threads, wrt_send);
+	debug!("output_haplotype_thread closing down")
+}
+
+pub fn output_allele_report_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Recv, tp: TPool) {
+	let output = chash.get_str("allele_report").expect("Allele report output filename is missing");
+	let outfile = open_output_file(output, &chash, tp);
+	debug!("output_allele_report_thread starting up");
+	output_handler(&chash, &hdr, r, &mut[outfile], print_allele_report_header, output_allele_report);
+	debug!("output_allele_report_thread closing down")
+}
+
+pub fn output_context_summary_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Recv, _tp: TPool) {
+	let output = chash.get_str("context_summary").expect("Context summary output filename is missing");
+	let mut summ = ContextSummary::new(&chash).unwrap_or_else(|e| panic!("Error setting up context summary: {}", e));
+	debug!("output_context_summary_thread starting up");
+	output_handler(&chash, &hdr, r, &mut [], print_tsv_header, |_, blk, _, ch, _| summ.add_block(blk, ch));
+	summ.write(output, &chash).unwrap_or_else(|e| panic!("Error writing context summary file {}: {}", output, e));
+	debug!("output_context_summary_thread closing down")
+}
+
+pub fn output_context_bw_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Recv, _tp: TPool) {
+	let prefix = chash.get_str("context_bw").expect("Context bigWig prefix is missing").trim_end_matches(".bw");
+	let patterns = context_bw_patterns(&chash);
+	let bw_names: Vec<_> = patterns.iter().map(|s| format!("{}_{}.bw", prefix, s)).collect();
+	// Patterns have already been checked when the options were processed
+	let masks: Vec<ContextMask> = patterns.iter().map(|s| s.parse().expect("Illegal context pattern")).collect();
+	let (threads, wrt_send) = start_bbi_threads(&chash, BbiSet::Context, |s| Bbi::init_bw(&bw_names, s, &chash));
+	debug!("output_context_bw_thread starting up");
+	output_handler(&chash, &hdr, r, &mut [], print_tsv_header, |_, blk, prev, ch, _| output_context_bw(blk, prev, ch, &masks));
+	finish_bbi_threads(&chash, BbiSet::Context, threads, wrt_send);
+	debug!("output_context_bw_thread closing down")
+}
+
+// Create bbi structure for set and start up the associated compress and write threads
+fn start_bbi_threads<F>(chash: &Arc<ConfHash>, set: BbiSet, init: F) -> (Vec<JoinHandle<()>>, Sender<BbiMsg>) 
+	where F: FnOnce(Sender<BbiMsg>) -> io::Result<Bbi> {
 	let nt = chash.get_int("threads");
 	let (comp_send, comp_recv) = bounded(nt * 10);
 	let (wrt_send, wrt_recv) = bounded(nt * 10);
-	let bbi = Bbi::init(&prefix, comp_send, &chash).unwrap_or_else(|e| panic!("Error creating BigBed / BigWig files: {}", e));
-	chash.set_bbi(bbi);
+	let bbi = init(comp_send).unwrap_or_else(|e| panic!("Error creating BigBed / BigWig files: {}", e));
+	chash.set_bbi(set, bbi);
 	
 	// setup compress threads
 	let mut threads = Vec::with_capacity(nt + 1);
@@ -243,30 +336,28 @@ pub fn output_bed_methyl_thread(chash: Arc<ConfHash>, hdr: Arc<VcfHeader>, r: Re
 		let ch = chash.clone();
 		let cr = comp_recv.clone();
 		let ps = wrt_send.clone();
-		let th = thread::spawn(move || compress_bbi_thread(ch, cr, ps));
+		let th = thread::spawn(move || compress_bbi_thread(ch, set, cr, ps));
 		threads.push(th);
 	}
 	
 	// setup write thread
 	let ch = chash.clone();
-	threads.push(thread::spawn(move || write_bbi_thread(ch, wrt_recv)));
-	
-	debug!("output_bed_methyl_thread thread starting up");
-	output_handler(&chash, &hdr, r, &mut outfiles, print_bed_methyl_header, output_bed_methyl);
+	threads.push(thread::spawn(move || write_bbi_thread(ch, set, wrt_recv)));
+	(threads, wrt_send)
+}
 
+fn finish_bbi_threads(chash: &ConfHash, set: BbiSet, mut threads: Vec<JoinHandle<()>>, wrt_send: Sender<BbiMsg>) {
 	// Finish sending last bbi blocks
-	let bbi_ref = chash.bbi().read().unwrap();
+	let bbi_ref = chash.bbi(set).read().unwrap();
 	bbi_ref.as_ref().expect("Bbi not set").finish();
 	drop(bbi_ref);
 	
 	// Drop sender from the Bbi structure to trigger the compress threads to quit
-	chash.drop_sender();	
+	chash.drop_sender(set);	
 	// Drop write sender to trigger the write thread to finish up and exit
 	drop(wrt_send);
 	
 	debug!("wait for compress and write threads");
 	// Wait for compress and write threads
 	for th in threads.drain(..) { th.join().unwrap() }
-	
-	debug!("output_bed_methyl_thread closing down")	
 }
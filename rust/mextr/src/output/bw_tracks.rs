@@ -0,0 +1,116 @@
+use std::io;
+use std::str::FromStr;
+
+use libc::c_double;
+
+use crate::config::new_err;
+
+#[link(name = "m")]
+extern "C" {
+	fn lgamma(x: c_double) -> c_double;
+}
+
+// Tail probability for each side of the 95% credible interval
+const CI_TAIL: f64 = 0.025;
+
+/// Additional bigWig tracks that can be generated with the bedMethyl output
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BwTrack { Depth, NonConv, CiLow, CiHigh }
+
+const BW_TRACKS: [BwTrack; 4] = [BwTrack::Depth, BwTrack::NonConv, BwTrack::CiLow, BwTrack::CiHigh];
+
+impl BwTrack {
+	pub fn name(&self) -> &'static str {
+		match self {
+			BwTrack::Depth => "depth",
+			BwTrack::NonConv => "nonconv",
+			BwTrack::CiLow => "ci_low",
+			BwTrack::CiHigh => "ci_high",
+		}
+	}
+
+	pub fn is_ci(&self) -> bool { matches!(self, BwTrack::CiLow | BwTrack::CiHigh) }
+
+	/// Value for track at a site with `a` non-converted and `b` converted reads, and credible interval `ci`
+	pub fn value(&self, a: u32, b: u32, ci: (f64, f64)) -> f32 {
+		match self {
+			BwTrack::Depth => (a + b) as f32,
+			BwTrack::NonConv => a as f32,
+			BwTrack::CiLow => ci.0 as f32,
+			BwTrack::CiHigh => ci.1 as f32,
+		}
+	}
+}
+
+impl FromStr for BwTrack {
+	type Err = io::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim().to_ascii_lowercase();
+		BW_TRACKS.iter().find(|t| t.name() == s).copied().ok_or_else(|| new_err(format!("Unknown bigWig track '{}' (possible values: depth, nonconv, ci_low, ci_high)", s)))
+	}
+}
+
+/// Parse comma separated list of tracks.  Tracks are returned in a fixed order without duplicates
+pub fn parse_bw_tracks(s: &str) -> io::Result<Vec<BwTrack>> {
+	let mut v = Vec::new();
+	for t in s.split(',').filter(|x| !x.trim().is_empty()) { v.push(t.parse::<BwTrack>()?) }
+	v.sort_unstable();
+	v.dedup();
+	Ok(v)
+}
+
+/// 95% credible interval for methylation at a site with `a` non-converted and `b` converted reads, and model
+/// estimate of methylation `m`.  The posterior is taken as a Beta distribution with mode m and a + b observations
+/// (i.e., Beta(m(a + b) + 1, (1 - m)(a + b) + 1)), so with no conversion errors this is the posterior under a
+/// uniform prior
+pub fn credible_interval(a: u32, b: u32, m: f64) -> (f64, f64) {
+	let n = (a + b) as f64;
+	let m = m.clamp(0.0, 1.0);
+	let (p, q) = (m * n + 1.0, (1.0 - m) * n + 1.0);
+	(beta_quantile(CI_TAIL, p, q), beta_quantile(1.0 - CI_TAIL, p, q))
+}
+
+// Continued fraction for the incomplete beta function (modified Lentz's method)
+fn beta_cf(x: f64, p: f64, q: f64) -> f64 {
+	const TINY: f64 = 1.0e-300;
+	let fix = |z: f64| if z.abs() < TINY { TINY } else { z };
+	let mut c = 1.0;
+	let mut d = 1.0 / fix(1.0 - (p + q) * x / (p + 1.0));
+	let mut h = d;
+	for k in 1..200 {
+		let k = k as f64;
+		let k2 = 2.0 * k;
+		let aa = k * (q - k) * x / ((p - 1.0 + k2) * (p + k2));
+		d = 1.0 / fix(1.0 + aa * d);
+		c = fix(1.0 + aa / c);
+		h *= d * c;
+		let aa = -(p + k) * (p + q + k) * x / ((p + k2) * (p + 1.0 + k2));
+		d = 1.0 / fix(1.0 + aa * d);
+		c = fix(1.0 + aa / c);
+		let del = d * c;
+		h *= del;
+		if (del - 1.0).abs() < 1.0e-10 { break }
+	}
+	h
+}
+
+// Regularized incomplete beta function I_x(p, q).  lbeta is ln(B(p, q))
+fn inc_beta(x: f64, p: f64, q: f64, lbeta: f64) -> f64 {
+	if x <= 0.0 { return 0.0 }
+	if x >= 1.0 { return 1.0 }
+	let z = (p * x.ln() + q * (1.0 - x).ln() - lbeta).exp();
+	if x < (p + 1.0) / (p + q + 2.0) { z * beta_cf(x, p, q) / p }
+	else { 1.0 - z * beta_cf(1.0 - x, q, p) / q }
+}
+
+// Quantile of the Beta(p, q) distribution (by bisection)
+fn beta_quantile(prob: f64, p: f64, q: f64) -> f64 {
+	let lbeta = unsafe { lgamma(p) + lgamma(q) - lgamma(p + q) };
+	let (mut lo, mut hi) = (0.0, 1.0);
+	for _ in 0..32 {
+		let mid = 0.5 * (lo + hi);
+		if inc_beta(mid, p, q, lbeta) < prob { lo = mid } else { hi = mid }
+	}
+	0.5 * (lo + hi)
+}
@@ -0,0 +1,106 @@
+use std::io;
+use std::str::FromStr;
+
+use crate::config::new_err;
+use crate::read_vcf::unpack::Strand;
+
+/// Number of trinucleotide contexts (CAA, CAC, ..., CTT)
+pub const N_CONTEXTS: usize = 16;
+
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ContextGroup { CG, CHG, CHH }
+
+impl ContextGroup {
+	pub fn name(&self) -> &'static str {
+		match self {
+			ContextGroup::CG => "CG",
+			ContextGroup::CHG => "CHG",
+			ContextGroup::CHH => "CHH",
+		}
+	}
+}
+
+fn base_code(b: u8) -> Option<usize> {
+	match b.to_ascii_uppercase() {
+		b'A' => Some(0),
+		b'C' => Some(1),
+		b'G' => Some(2),
+		b'T' => Some(3),
+		_ => None,
+	}
+}
+
+/// Get the strand and trinucleotide context index from the 5 base context centred on a site
+/// The context is read 5' -> 3' from the strand of the cytosine
+pub fn get_context(cx: &[u8]) -> Option<(Strand, usize)> {
+	match cx[2].to_ascii_uppercase() {
+		b'C' => Some((Strand::C, (base_code(cx[3])? << 2) | base_code(cx[4])?)),
+		b'G' => Some((Strand::G, ((3 - base_code(cx[1])?) << 2) | (3 - base_code(cx[0])?))),
+		_ => None,
+	}
+}
+
+pub fn context_name(ix: usize) -> String { format!("C{}{}", BASES[(ix >> 2) & 3], BASES[ix & 3]) }
+
+pub fn context_group(ix: usize) -> ContextGroup {
+	if (ix >> 2) == 2 { ContextGroup::CG }
+	else if (ix & 3) == 2 { ContextGroup::CHG }
+	else { ContextGroup::CHH }
+}
+
+// Bit mask of bases (A = 1, C = 2, G = 4, T = 8) matched by an IUPAC code
+fn iupac_mask(c: u8) -> Option<u8> {
+	match c.to_ascii_uppercase() {
+		b'A' => Some(1),
+		b'C' => Some(2),
+		b'G' => Some(4),
+		b'T' => Some(8),
+		b'R' => Some(5),
+		b'Y' => Some(10),
+		b'S' => Some(6),
+		b'W' => Some(9),
+		b'K' => Some(12),
+		b'M' => Some(3),
+		b'B' => Some(14),
+		b'D' => Some(13),
+		b'H' => Some(11),
+		b'V' => Some(7),
+		b'N' => Some(15),
+		_ => None,
+	}
+}
+
+/// Set of trinucleotide contexts, specified as a comma separated list of patterns
+/// using IUPAC codes (i.e., CHG,CAG,CWG).  Two base patterns (i.e., CG) match
+/// any base in the third position
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ContextMask(u16);
+
+impl ContextMask {
+	pub fn contains(&self, ix: usize) -> bool { (self.0 & (1 << ix)) != 0 }
+
+	fn from_pattern(pat: &str) -> io::Result<Self> {
+		let p = pat.as_bytes();
+		let err = || new_err(format!("Illegal context pattern '{}'", pat));
+		if !(p.len() == 2 || p.len() == 3) || (iupac_mask(p[0]).ok_or_else(err)? & 2) == 0 { return Err(err()) }
+		let m1 = iupac_mask(p[1]).ok_or_else(err)?;
+		let m2 = if p.len() == 3 { iupac_mask(p[2]).ok_or_else(err)? } else { 15 };
+		let mut mask = 0;
+		for ix in 0..N_CONTEXTS {
+			if (m1 & (1 << (ix >> 2))) != 0 && (m2 & (1 << (ix & 3))) != 0 { mask |= 1 << ix }
+		}
+		Ok(Self(mask))
+	}
+}
+
+impl FromStr for ContextMask {
+	type Err = io::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut mask = 0;
+		for pat in s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) { mask |= Self::from_pattern(pat)?.0 }
+		if mask == 0 { Err(new_err(format!("No contexts specified in '{}'", s))) } else { Ok(Self(mask)) }
+	}
+}
@@ -0,0 +1,109 @@
+use std::io::{self, Write};
+use std::str::from_utf8;
+use std::slice;
+
+use r_htslib::{HtsFile, VcfHeader};
+
+use crate::config::*;
+use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem, Strand};
+use super::{calc_phred, Record, MethRec, GT_IUPAC};
+
+// Alleles for each genotype (AA, AC, AG, AT, CC, CG, CT, GG, GT, TT) coded as A = 0, C = 1, G = 2, T = 3
+const GT_ALLELES: [[u8; 2]; 10] = [[0, 0], [0, 1], [0, 2], [0, 3], [1, 1], [1, 2], [1, 3], [2, 2], [2, 3], [3, 3]];
+
+/// Status of a site with respect to CpG dinucleotides in the reference and in the sample
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CpgStatus { NonCpG, Ref, Het, Lost, Gained }
+
+impl CpgStatus {
+	pub fn name(&self) -> &'static str {
+		match self {
+			CpgStatus::NonCpG => "NON_CPG",
+			CpgStatus::Ref => "REF",
+			CpgStatus::Het => "HET",
+			CpgStatus::Lost => "LOST",
+			CpgStatus::Gained => "GAINED",
+		}
+	}
+	/// True if the CpG status of the sample differs (completely or for one allele) from the reference
+	pub fn is_variant(&self) -> bool { matches!(self, CpgStatus::Het | CpgStatus::Lost | CpgStatus::Gained) }
+}
+
+/// CpG status of a site considered as the C (Strand::C) or the G (Strand::G) of a CpG dinucleotide.
+/// The called genotype is used for the site itself and the sample context for the neighbouring base.
+/// Returns None if there is no genotype call for the sample
+pub fn cpg_status(rec: &Record, mrec: &MethRec, strand: Strand) -> Option<CpgStatus> {
+	let gt = mrec.max_gt()? as usize;
+	let (rcx, scx) = (rec.cx(), mrec.cx());
+	let up = |c: u8| c.to_ascii_uppercase();
+	let (ref_cpg, allele, nb_ok) = match strand {
+		Strand::G => (up(rcx[1]) == b'C' && up(rcx[2]) == b'G', 2, up(scx[1]) == b'C'),
+		_ => (up(rcx[2]) == b'C' && up(rcx[3]) == b'G', 1, up(scx[3]) == b'G'),
+	};
+	let n = if nb_ok { GT_ALLELES[gt].iter().filter(|a| **a == allele).count() } else { 0 };
+	Some(match (ref_cpg, n) {
+		(true, 2) => CpgStatus::Ref,
+		(true, 1) => CpgStatus::Het,
+		(true, _) => CpgStatus::Lost,
+		(false, 0) => CpgStatus::NonCpG,
+		_ => CpgStatus::Gained,
+	})
+}
+
+/// CpG status of a site considering it as both the C and the G of a CpG.  A variant status on either side takes precedence
+pub fn site_cpg_status(rec: &Record, mrec: &MethRec) -> Option<CpgStatus> {
+	let s1 = cpg_status(rec, mrec, Strand::C)?;
+	let s2 = cpg_status(rec, mrec, Strand::G)?;
+	Some(if s1.is_variant() { s1 } else if s2.is_variant() || matches!(s1, CpgStatus::NonCpG) { s2 } else { s1 })
+}
+
+/// Write annotation columns for CpG output file: comma separated list of per sample CpG status followed by the ID
+pub fn output_cpg_annotation<W: Write>(f: &mut W, rec: &Record, mrecs: &[MethRec], strand: Strand, id: Option<&str>) -> io::Result<()> {
+	let mut sep = '\t';
+	for m in mrecs.iter() {
+		write!(f, "{}{}", sep, cpg_status(rec, m, strand).map(|s| s.name()).unwrap_or("."))?;
+		sep = ',';
+	}
+	write!(f, "\t{}", id.unwrap_or("."))
+}
+
+pub fn print_allele_report_header(f: &mut HtsFile, hdr: &VcfHeader, _chash: &ConfHash) -> io::Result<()> {
+	write!(f, "Contig\tPos0\tPos1\tRef\tID")?;
+	for i in 0..hdr.nsamples() {
+		let name = hdr.sample_name(i)?;
+		write!(f, "\t{}:Status\t{}:Call\t{}:Context\t{}:GQ\t{}:Meth\t{}:non_conv\t{}:conv", name, name, name, name, name, name, name)?;
+	}
+	writeln!(f)
+}
+
+// Report CpGs lost or gained (on one or both alleles) in any sample.  Sites are reported
+// at the position of the C (on the + strand) of the reference or sample CpG, with one
+// set of columns per sample so that there is a single line per site
+fn output_report_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec: &Record, mrecs: &[MethRec]) -> io::Result<()> {
+	let status: Vec<_> = mrecs.iter().map(|m| cpg_status(rec, m, Strand::C)).collect();
+	if !status.iter().any(|s| s.map(|x| x.is_variant()).unwrap_or(false)) { return Ok(()) }
+	write!(f, "{}\t{}\t{}\t{}\t{}", hdr.ctg_name(rec.rid() as usize).unwrap(), rec.pos(), rec.pos() + 2,
+		from_utf8(&rec.cx()[2..4]).unwrap(), rec.id().unwrap_or("."))?;
+	for (m, st) in mrecs.iter().zip(status.iter()) {
+		if let Some(st) = st {
+			// Safe to unwrap because cpg_status() returns None if there is no genotype call
+			let gt = m.max_gt().unwrap() as usize;
+			let gq = calc_phred(1.0 - m.gt_probs()[gt].exp());
+			write!(f, "\t{}\t{}\t{}\t{}", st.name(), GT_IUPAC[gt] as char, from_utf8(&m.cx()[2..4]).unwrap(), gq)?;
+			// Methylation estimate for the C allele
+			if let Some(x) = m.get_meth(Strand::C) { write!(f, "\t{:.3}", x)? } else { write!(f, "\t.")? }
+			write!(f, "\t{}\t{}", m.counts()[5], m.counts()[7])?;
+		} else { write!(f, "\t.\t.\t.\t.\t.\t.\t.")? }
+	}
+	writeln!(f)
+}
+
+pub fn output_allele_report(outfiles: &mut [HtsFile], rec_blk: &RecordBlock, _prev: Option<RecordBlockElem>, _chash: &ConfHash, hdr: &VcfHeader) -> io::Result<()> {
+	assert_eq!(outfiles.len(), 1);
+	let outfile = &mut outfiles[0];
+	match rec_blk {
+		RecordBlock::Single(svec) => for (rec, meth_rec) in svec.iter() { output_report_rec(outfile, hdr, rec, slice::from_ref(meth_rec))? },
+		RecordBlock::Multi(mvec) => for (rec, mv) in mvec.iter() { output_report_rec(outfile, hdr, rec, mv)? },
+	}
+	Ok(())
+}
@@ -6,9 +6,11 @@ use lazy_static::lazy_static;
 use r_htslib::{HtsFile, VcfHeader};
 
 use crate::config::*;
-use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem};
+use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem, Strand};
+use crate::bbi::BbiSet;
 
 use super::{OutputOpts, calc_phred, Record, MethRec};
+use super::bw_tracks::{parse_bw_tracks, credible_interval};
 
 lazy_static! {
 	static ref TRANS_BASE: [u8; 256] = {
@@ -52,18 +54,24 @@ fn strand_and_context(rf: &[u8], call: &[u8]) -> Option<(char, [u8; 3], [u8; 3])
 fn output_bed_methyl_rec<W: Write>(files: &mut[W], chash: &ConfHash, hdr: &VcfHeader, opts: &OutputOpts, srec: &[(Record, MethRec)], mut prev_ctg: Option<u32>) -> io::Result<()> {
 	assert_eq!(files.len(), 3);
 	let sample_desc = opts.sample_desc().expect("Sample description not set");
-	let bbi_ref = chash.bbi().read().unwrap();
+	let bbi_ref = chash.bbi(BbiSet::BedMethyl).read().unwrap();
 	let bbi = bbi_ref.as_ref().expect("Bbi not set");
 	let sender = bbi.sender().expect("Bbi sender not set");
 
 	let bw_strand_specific = matches!(chash.get_mode("bw_mode"), Mode::StrandSpecific);
+	let n_meth_bw = if bw_strand_specific { 2 } else { 1 };
+	let tracks = match chash.get_str("bw_tracks") {
+		Some(s) => parse_bw_tracks(s)?,
+		None => Vec::new(),
+	};
+	let need_ci = tracks.iter().any(|t| t.is_ci());
 	let mut bb_builders = Vec::new();
 
 	for f in bbi.bb_files().iter().map(|f| f.build().write().unwrap()) { bb_builders.push(f) }
 	let mut bw_builders = Vec::new();
 	for f in bbi.bw_files().iter().map(|f| f.build().write().unwrap()) { bw_builders.push(f) }
 	if bb_builders.len() != 3 { panic!("Unexpected number of bigBed files")}
-	if bw_builders.len() != if bw_strand_specific { 2 } else { 1 } { panic!("Unexpected number of bigWig files")}
+	if bw_builders.len() != n_meth_bw + tracks.len() { panic!("Unexpected number of bigWig files")}
 	
 	for (rec, meth_rec) in srec {
 		// For bbi files - handle new ctg
@@ -122,10 +130,24 @@ fn output_bed_methyl_rec<W: Write>(files: &mut[W], chash: &ConfHash, hdr: &VcfHe
 				let bb_build = &mut bb_builders[bm_type];
 				bb_build.add_bb_rec(out_ix as u32, rec.pos, &sbuf, sender);
 				bb_build.add_zoom_obs(out_ix as u32, rec.pos, m as f32, sender);
-				// Handle bw record
-				let bw_build = &mut bw_builders[ if bw_strand_specific && strand == '-' { 1 } else { 0 } ];
-				bw_build.add_bw_rec(out_ix as u32, rec.pos, m as f32, sender);
-				bw_build.add_zoom_obs(out_ix as u32, rec.pos, m as f32, sender);
+				// Handle bw record (unless masked because of a SNP affecting a CpG)
+				if !opts.mask_bw_site(rec, meth_rec) {
+					let bw_build = &mut bw_builders[ if bw_strand_specific && strand == '-' { 1 } else { 0 } ];
+					bw_build.add_bw_rec(out_ix as u32, rec.pos, m as f32, sender);
+					bw_build.add_zoom_obs(out_ix as u32, rec.pos, m as f32, sender);
+					// Additional tracks.  For the interval we use the model estimate of methylation if available
+					if !tracks.is_empty() {
+						let ci = if need_ci {
+							let m_est = meth_rec.get_meth(if strand == '+' { Strand::C } else { Strand::G }).unwrap_or(m);
+							credible_interval(a as u32, b as u32, m_est)
+						} else { (0.0, 1.0) };
+						for (tr, bw_build) in tracks.iter().zip(bw_builders[n_meth_bw..].iter_mut()) {
+							let x = tr.value(a as u32, b as u32, ci);
+							bw_build.add_bw_rec(out_ix as u32, rec.pos, x, sender);
+							bw_build.add_zoom_obs(out_ix as u32, rec.pos, x, sender);
+						}
+					}
+				}
 			}
 		}
 	} 
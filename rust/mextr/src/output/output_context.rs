@@ -0,0 +1,215 @@
+use std::io::{self, Write, BufRead, BufReader, BufWriter};
+use std::fs::File;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::config::*;
+use crate::bbi::BbiSet;
+use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem, Strand};
+use super::{OutputOpts, calc_phred, MethRec, calc_site_prob};
+use super::context::*;
+
+// Get site context, non-converted and converted counts and check site passes thresholds.
+// Uses the context from the sample call so that sites affected by SNPs are correctly classified
+fn get_site(meth_rec: &MethRec, opts: &OutputOpts) -> Option<(usize, u64, u64)> {
+	meth_rec.max_gt()?;
+	let (strand, ix) = get_context(meth_rec.cx())?;
+	let phred = calc_phred(1.0 - calc_site_prob(meth_rec, strand, opts, 0));
+	if opts.min_n() > 0 && phred < opts.threshold() { return None }
+	let ct = meth_rec.counts();
+	let (a, b) = if matches!(strand, Strand::C) { (ct[5], ct[7]) } else { (ct[6], ct[4]) };
+	if a + b > 0 { Some((ix, a as u64, b as u64)) } else { None }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct CtxCounts {
+	sites: u64,
+	non_conv: u64,
+	conv: u64,
+	sum_meth: f64,
+}
+
+impl CtxCounts {
+	fn add_site(&mut self, a: u64, b: u64) {
+		self.sites += 1;
+		self.non_conv += a;
+		self.conv += b;
+		self.sum_meth += (a as f64) / ((a + b) as f64);
+	}
+	fn add(&mut self, c: &CtxCounts) {
+		self.sites += c.sites;
+		self.non_conv += c.non_conv;
+		self.conv += c.conv;
+		self.sum_meth += c.sum_meth;
+	}
+	fn write<W: Write>(&self, w: &mut W, region: &str, context: &str) -> io::Result<()> {
+		write!(w, "{}\t{}\t{}\t{}\t{}", region, context, self.sites, self.non_conv, self.conv)?;
+		if self.sites > 0 {
+			writeln!(w, "\t{:.4}\t{:.4}", (self.non_conv as f64) / ((self.non_conv + self.conv) as f64), self.sum_meth / (self.sites as f64))
+		} else { writeln!(w, "\t.\t.") }
+	}
+}
+
+struct RegionSummary {
+	name: String,
+	counts: [CtxCounts; N_CONTEXTS],
+}
+
+impl RegionSummary {
+	fn new<S: AsRef<str>>(name: S) -> Self { Self{name: name.as_ref().to_owned(), counts: Default::default()} }
+	fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		let mut groups = [CtxCounts::default(); 3];
+		for (ix, c) in self.counts.iter().enumerate() {
+			c.write(w, &self.name, &context_name(ix))?;
+			groups[context_group(ix) as usize].add(c);
+		}
+		for (grp, c) in [ContextGroup::CG, ContextGroup::CHG, ContextGroup::CHH].iter().zip(groups.iter()) { c.write(w, &self.name, grp.name())? }
+		Ok(())
+	}
+}
+
+// Regions from BED file for a contig, sorted on start position
+// max_end[i] is the maximum end position for regions 0..=i, which allows us to stop searching early
+#[derive(Default)]
+struct CtgRegions {
+	regions: Vec<(u32, u32, usize)>,
+	max_end: Vec<u32>,
+}
+
+impl CtgRegions {
+	fn finish(&mut self) {
+		self.regions.sort_unstable();
+		let mut mx = 0;
+		self.max_end = self.regions.iter().map(|(_, e, _)| { mx = mx.max(*e); mx }).collect();
+	}
+	fn overlaps(&self, pos: u32, v: &mut Vec<usize>) {
+		v.clear();
+		let mut i = self.regions.partition_point(|(s, _, _)| *s <= pos);
+		while i > 0 && self.max_end[i - 1] > pos {
+			i -= 1;
+			let (_, e, ix) = self.regions[i];
+			if e > pos { v.push(ix) }
+		}
+	}
+}
+
+fn read_regions(name: &str, chash: &ConfHash) -> io::Result<(Vec<RegionSummary>, HashMap<u32, CtgRegions>)> {
+	let rdr = BufReader::new(File::open(name).map_err(|e| new_err(format!("Couldn't open regions file {}: {}", name, e)))?);
+	let mut summ = Vec::new();
+	let mut ctg_regions: HashMap<u32, CtgRegions> = HashMap::new();
+	for (line_no, line) in rdr.lines().enumerate() {
+		let line = line?;
+		if line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") || line.trim().is_empty() { continue }
+		let fields: Vec<_> = line.trim_end().split('\t').collect();
+		let err = || new_err(format!("Error parsing regions file {} at line {}", name, line_no + 1));
+		if fields.len() < 3 { return Err(err()) }
+		let start = <u32>::from_str(fields[1]).map_err(|_| err())?;
+		let end = <u32>::from_str(fields[2]).map_err(|_| err())?;
+		if let Some(rid) = chash.contig_rid(fields[0]) {
+			let reg_name = if fields.len() > 3 && !fields[3].is_empty() { fields[3].to_owned() } else { format!("{}:{}-{}", fields[0], start + 1, end) };
+			ctg_regions.entry(rid as u32).or_default().regions.push((start, end, summ.len()));
+			summ.push(RegionSummary::new(reg_name));
+		} else { warn!("Contig {} from regions file {} not found in input", fields[0], name) }
+	}
+	for r in ctg_regions.values_mut() { r.finish() }
+	Ok((summ, ctg_regions))
+}
+
+/// Summary of methylation levels by trinucleotide context, genome wide, per contig and
+/// (optionally) for regions in a BED file
+pub struct ContextSummary {
+	total: RegionSummary,
+	contigs: Vec<Option<RegionSummary>>,
+	ctg_order: Vec<u32>,
+	regions: Vec<RegionSummary>,
+	ctg_regions: HashMap<u32, CtgRegions>,
+	tvec: Vec<usize>,
+}
+
+impl ContextSummary {
+	pub fn new(chash: &ConfHash) -> io::Result<Self> {
+		let (regions, ctg_regions) = if let Some(s) = chash.get_str("summary_regions") { read_regions(s, chash)? } else { (Vec::new(), HashMap::new()) };
+		let contigs = chash.vcf_contigs().iter().map(|_| None).collect();
+		Ok(Self{total: RegionSummary::new("all"), contigs, ctg_order: Vec::new(), regions, ctg_regions, tvec: Vec::new()})
+	}
+
+	pub fn add_block(&mut self, rec_blk: &RecordBlock, chash: &ConfHash) -> io::Result<()> {
+		let opts = OutputOpts::new(chash);
+		let svec = match rec_blk {
+			RecordBlock::Single(svec) => svec,
+			RecordBlock::Multi(_) => panic!("Multi sample files not compatible with context summary"),
+		};
+		for (rec, meth_rec) in svec.iter() {
+			if let Some((ix, a, b)) = get_site(meth_rec, &opts) {
+				self.total.counts[ix].add_site(a, b);
+				let rid = rec.rid();
+				if self.contigs[rid as usize].is_none() {
+					self.contigs[rid as usize] = Some(RegionSummary::new(chash.vcf_contigs()[rid as usize].name()));
+					self.ctg_order.push(rid);
+				}
+				self.contigs[rid as usize].as_mut().unwrap().counts[ix].add_site(a, b);
+				if let Some(creg) = self.ctg_regions.get(&rid) {
+					creg.overlaps(rec.pos(), &mut self.tvec);
+					for i in self.tvec.iter() { self.regions[*i].counts[ix].add_site(a, b) }
+				}
+			}
+		}
+		Ok(())
+	}
+
+	pub fn write(&self, name: &str, chash: &ConfHash) -> io::Result<()> {
+		let mut wrt = BufWriter::new(File::create(name)?);
+		if !chash.get_bool("no_header") { writeln!(wrt, "Region\tContext\tSites\tNonConv\tConv\tWeightedMeth\tMeanMeth")? }
+		self.total.write(&mut wrt)?;
+		for rid in self.ctg_order.iter() { self.contigs[*rid as usize].as_ref().unwrap().write(&mut wrt)? }
+		for reg in self.regions.iter() { reg.write(&mut wrt)? }
+		wrt.flush()?;
+		// Trigger MD5 generation
+		chash.add_file(name, false);
+		Ok(())
+	}
+}
+
+/// Context patterns for bigWig files
+pub fn context_bw_patterns(chash: &ConfHash) -> Vec<String> {
+	chash.get_str("bw_contexts").expect("Contexts for bigWig files not set")
+		.split(',').map(|s| s.trim().to_ascii_uppercase()).filter(|s| !s.is_empty()).collect()
+}
+
+/// Output sites to the bigWig file for each pattern (in masks) that matches the site context.  Patterns can overlap,
+/// in which case a site is written to more than one file
+pub fn output_context_bw(rec_blk: &RecordBlock, prev: Option<RecordBlockElem>, chash: &ConfHash, masks: &[ContextMask]) -> io::Result<()> {
+	let opts = OutputOpts::new(chash);
+	let svec = match rec_blk {
+		RecordBlock::Single(svec) => svec,
+		RecordBlock::Multi(_) => panic!("Multi sample files not compatible with context bigWig output"),
+	};
+	let bbi_ref = chash.bbi(BbiSet::Context).read().unwrap();
+	let bbi = bbi_ref.as_ref().expect("Bbi not set");
+	let sender = bbi.sender().expect("Bbi sender not set");
+	let mut bw_builders: Vec<_> = bbi.bw_files().iter().map(|f| f.build().write().unwrap()).collect();
+	if bw_builders.len() != masks.len() { panic!("Unexpected number of bigWig files") }
+	let mut prev_ctg = prev.map(|x| x.record().rid());
+	for (rec, meth_rec) in svec.iter() {
+		if let Some(old_rid) = prev_ctg {
+			if old_rid != rec.rid() {
+				for build in bw_builders.iter_mut() {
+					build.finish(sender);
+					build.clear_counts();
+				}
+			}
+		}
+		prev_ctg = Some(rec.rid());
+		if let Some((ix, a, b)) = get_site(meth_rec, &opts).filter(|_| !opts.mask_bw_site(rec, meth_rec)) {
+			let out_ix = chash.vcf_contigs()[rec.rid() as usize].out_ix().expect("Missing out index for contig") as u32;
+			let m = (a as f64) / ((a + b) as f64);
+			for (mask, build) in masks.iter().zip(bw_builders.iter_mut()) {
+				if mask.contains(ix) {
+					build.add_bw_rec(out_ix, rec.pos(), m as f32, sender);
+					build.add_zoom_obs(out_ix, rec.pos(), m as f32, sender);
+				}
+			}
+		}
+	}
+	Ok(())
+}
@@ -1,4 +1,5 @@
 use std::io::{self, Write};
+use std::slice;
 
 use libc::c_int;
 use r_htslib::{HtsFile, VcfHeader};
@@ -6,11 +7,12 @@ use r_htslib::{HtsFile, VcfHeader};
 use crate::config::*;
 use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem, Strand};
 use super::{OutputOpts, calc_phred, Record, MethRec, GT_IUPAC, GT_MASK, output_mrec, get_prob_dist};
+use super::output_allele::output_cpg_annotation;
 
 const HET_C: [bool; 10] = [false, true, false, false, true, true, true, false, false, false];
 const HET_G: [bool; 10] = [false, false, true, false, false, true, false, true, true, false];
 
-fn calc_prob(mrec1: &MethRec, mrec2: &MethRec, opts: &OutputOpts) -> (bool, f64) {
+pub fn calc_prob(mrec1: &MethRec, mrec2: &MethRec, opts: &OutputOpts) -> (bool, f64) {
 	if mrec1.max_gt().is_some() && mrec2.max_gt().is_some() {
 		let ct1 = mrec1.counts();
 		let p1 = mrec1.gt_probs();
@@ -70,27 +72,31 @@ fn output_combined_mrec<W: Write>(f: &mut W, mrec1: &MethRec, mrec2: &MethRec) -
 	}	
 }
 
-fn output_separate_single_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec: &Record, meth_rec: &MethRec, strand: Strand) -> io::Result<()> {
+fn output_separate_single_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec: &Record, meth_rec: &MethRec, strand: Strand, annot: bool) -> io::Result<()> {
 	write!(f, "{}\t{}\t{}\t{}", hdr.ctg_name(rec.rid as usize).unwrap(), rec.pos, rec.pos + 1, rec.cx[2] as char)?;
 	output_mrec(f, meth_rec, strand, false)?;
+	if annot { output_cpg_annotation(f, rec, slice::from_ref(meth_rec), strand, rec.id())? }
 	writeln!(f)
 }
 
-fn output_separate_multi_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec: &Record, mvec: &[MethRec], strand: Strand) -> io::Result<()> {
+fn output_separate_multi_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec: &Record, mvec: &[MethRec], strand: Strand, annot: bool) -> io::Result<()> {
 	write!(f, "{}\t{}\t{}\t{}", hdr.ctg_name(rec.rid as usize).unwrap(), rec.pos, rec.pos + 1, rec.cx[2] as char)?;
 	for meth_rec in mvec.iter() { output_mrec(f, meth_rec, strand, false)? }
+	if annot { output_cpg_annotation(f, rec, mvec, strand, rec.id())? }
 	writeln!(f)
 }
 
-fn output_combined_single_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec1: &Record, rec2: &Record, meth_rec1: &MethRec, meth_rec2: &MethRec) -> io::Result<()> {
+fn output_combined_single_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec1: &Record, rec2: &Record, meth_rec1: &MethRec, meth_rec2: &MethRec, annot: bool) -> io::Result<()> {
 	write!(f, "{}\t{}\t{}\t{}{}", hdr.ctg_name(rec1.rid as usize).unwrap(), rec1.pos, rec2.pos + 1, rec1.cx[2] as char, rec1.cx[3] as char)?;
 	output_combined_mrec(f, meth_rec1, meth_rec2)?;
+	if annot { output_cpg_annotation(f, rec1, slice::from_ref(meth_rec1), Strand::C, rec1.id().or_else(|| rec2.id()))? }
 	writeln!(f)
 }
 
-fn output_combined_multi_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec1: &Record, rec2: &Record, mvec1: &[MethRec], mvec2: &[MethRec]) -> io::Result<()> {
+fn output_combined_multi_rec<W: Write>(f: &mut W, hdr: &VcfHeader, rec1: &Record, rec2: &Record, mvec1: &[MethRec], mvec2: &[MethRec], annot: bool) -> io::Result<()> {
 	write!(f, "{}\t{}\t{}\t{}{}", hdr.ctg_name(rec1.rid as usize).unwrap(), rec1.pos, rec2.pos + 1, rec1.cx[2] as char, rec1.cx[3] as char)?;
 	for (meth_rec1, meth_rec2) in mvec1.iter().zip(mvec2.iter()) { output_combined_mrec(f, meth_rec1, meth_rec2)? }
+	if annot { output_cpg_annotation(f, rec1, mvec1, Strand::C, rec1.id().or_else(|| rec2.id()))? }
 	writeln!(f)
 }
 
@@ -102,10 +108,10 @@ fn output_single_rec<'a, W: Write>(f: &mut W, hdr: &VcfHeader, opts: &OutputOpts
 				if pass { 
 					let phred = calc_phred(1.0 - q);
 					if opts.min_n() < 1 || phred >= opts.threshold {
-						if matches!(opts.mode, Mode::Combined) { output_combined_single_rec(f, hdr, rec1, rec2, meth_rec1, meth_rec2)? }
+						if matches!(opts.mode, Mode::Combined) { output_combined_single_rec(f, hdr, rec1, rec2, meth_rec1, meth_rec2, opts.cpg_annotation())? }
 						else {
-							output_separate_single_rec(f, hdr, rec1, meth_rec1, Strand::C)?; 
-							output_separate_single_rec(f, hdr, rec2, meth_rec2, Strand::G)?; 
+							output_separate_single_rec(f, hdr, rec1, meth_rec1, Strand::C, opts.cpg_annotation())?; 
+							output_separate_single_rec(f, hdr, rec2, meth_rec2, Strand::G, opts.cpg_annotation())?; 
 						}
 					}
 				}
@@ -127,10 +133,10 @@ fn output_multi_rec<'a, W: Write>(f: &mut W, hdr: &VcfHeader, opts: &OutputOpts,
 				get_prob_dist(&mut qvec);
 				let phred = calc_phred(qvec[1..opts.min_n].iter().fold(qvec[0], |s, q| s + *q)); 
 				if opts.min_n == 0 || phred >= opts.threshold() {
-					if matches!(opts.mode, Mode::Combined) { output_combined_multi_rec(f, hdr, rec1, rec2, mvec1, mvec2)? }
+					if matches!(opts.mode, Mode::Combined) { output_combined_multi_rec(f, hdr, rec1, rec2, mvec1, mvec2, opts.cpg_annotation())? }
 					else {
-						output_separate_multi_rec(f, hdr, rec1, mvec1, Strand::C)?; 
-						output_separate_multi_rec(f, hdr, rec2, mvec2, Strand::G)?; 
+						output_separate_multi_rec(f, hdr, rec1, mvec1, Strand::C, opts.cpg_annotation())?; 
+						output_separate_multi_rec(f, hdr, rec2, mvec2, Strand::G, opts.cpg_annotation())?; 
 					}
 				}
 			}
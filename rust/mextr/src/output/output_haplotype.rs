@@ -0,0 +1,295 @@
+use std::io::{self, Write};
+use std::collections::HashMap;
+
+use r_htslib::*;
+
+use crate::config::*;
+use crate::bbi::BbiSet;
+use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem};
+use super::{OutputOpts, calc_phred, calc_prob};
+
+/// Suffixes for the bigWig tracks generated from the haplotype metrics (in the same order as the output columns)
+pub const HAP_BW_TRACKS: [&str; 4] = ["mhl", "pdr", "epipoly", "entropy"];
+
+// Number of windows processed per BAM query
+const CHUNK_SIZE: usize = 4096;
+const READ_FILTER: u16 = BAM_FUNMAP | BAM_FSECONDARY | BAM_FQCFAIL | BAM_FDUP | BAM_FSUPPLEMENTARY;
+
+// Methylation calls (CpG index, state) for each read name
+type ReadStates = HashMap<Box<str>, Vec<(u32, u8)>>;
+
+struct BamInput {
+	file: HtsFile,
+	hdr: SamHeader,
+	index: HtsIndex,
+	brec: BamRec,
+}
+
+impl BamInput {
+	fn new(name: &str) -> io::Result<Self> {
+		let mut file = HtsFile::new(name, "r")?;
+		let index = file.sam_index_load()?;
+		let hdr = SamHeader::read(&mut file)?;
+		let brec = BamRec::new()?;
+		Ok(Self{file, hdr, index, brec})
+	}
+
+	// Collect the methylation state of each CpG covered by each read (or read pair) overlapping the CpGs in cpgs
+	fn collect_states(&mut self, tid: usize, cpgs: &[u32], min_mapq: u8, bq_threshold: u8) -> io::Result<ReadStates> {
+		let start = cpgs[0] as usize;
+		let end = *cpgs.last().unwrap() as usize + 2;
+		let mut itr = self.index.sam_itr_queryi(tid as isize, start, end)?;
+		let mut reads = HashMap::new();
+		let mut states = Vec::new();
+		loop {
+			match itr.sam_itr_next(&mut self.file, &mut self.brec) {
+				SamReadResult::Ok => (),
+				SamReadResult::EOF => break,
+				_ => return Err(new_err("Error reading from BAM input file".to_string())),
+			}
+			let b = &self.brec;
+			if (b.flag() & READ_FILTER) != 0 || b.qual() < min_mapq { continue }
+			states.clear();
+			get_read_states(b, cpgs, bq_threshold, &mut states);
+			if !states.is_empty() { reads.entry(Box::from(b.qname())).or_insert_with(Vec::new).extend_from_slice(&states) }
+		}
+		Ok(reads)
+	}
+}
+
+// Get methylation calls (1 = methylated, 0 = unmethylated) for each CpG covered by the read
+// For C2T reads we look at the C on the top strand, and for G2A reads the G on the bottom strand
+fn get_read_states(b: &BamRec, cpgs: &[u32], bq_threshold: u8, out: &mut Vec<(u32, u8)>) {
+	let (off, meth_base, unmeth_base) = match get_bs_strand(b).0 {
+		BSStrand::StrandC2T => (0, 1, 3),
+		BSStrand::StrandG2A => (1, 2, 0),
+		_ => return,
+	};
+	let (pos, cigar) = match (b.pos(), b.cigar()) {
+		(Some(p), Some(c)) => (p, c),
+		_ => return,
+	};
+	let sq = match b.get_seq_qual() {
+		Ok(s) => s,
+		Err(_) => return,
+	};
+	let (mut rpos, mut qpos) = (pos, 0);
+	for elem in cigar.iter() {
+		let (tp, l) = (elem.op_type(), elem.op_len() as usize);
+		if tp == 3 {
+			let mut i = cpgs.partition_point(|x| (*x as usize) + off < rpos);
+			while i < cpgs.len() && (cpgs[i] as usize) + off < rpos + l {
+				let x = sq[qpos + (cpgs[i] as usize) + off - rpos];
+				if (x >> 2) >= bq_threshold {
+					let base = x & 3;
+					if base == meth_base { out.push((i as u32, 1)) } else if base == unmeth_base { out.push((i as u32, 0)) }
+				}
+				i += 1;
+			}
+		}
+		if (tp & 1) != 0 { qpos += l }
+		if (tp & 2) != 0 { rpos += l }
+	}
+}
+
+fn add_run_patterns(start: usize, run: &[u8], k: usize, windows: &mut [HashMap<u32, u32>]) {
+	for (x, w) in run.windows(k).enumerate() {
+		if let Some(ct) = windows.get_mut(start + x) {
+			let pat = w.iter().fold(0, |p, s| (p << 1) | (*s as u32));
+			*ct.entry(pat).or_insert(0) += 1;
+		}
+	}
+}
+
+// Add the patterns from all windows fully covered by a read.  Calls from overlapping mates are merged,
+// and CpGs with conflicting calls are treated as missing.  The calls in v should be sorted.
+fn add_read_patterns(v: &[(u32, u8)], k: usize, windows: &mut [HashMap<u32, u32>]) {
+	let mut run_start = 0;
+	let mut run: Vec<u8> = Vec::with_capacity(v.len());
+	let mut i = 0;
+	while i < v.len() {
+		let (ix, s) = v[i];
+		let mut conflict = false;
+		i += 1;
+		while i < v.len() && v[i].0 == ix {
+			if v[i].1 != s { conflict = true }
+			i += 1;
+		}
+		if conflict || (!run.is_empty() && run_start + run.len() != ix as usize) {
+			add_run_patterns(run_start, &run, k, windows);
+			run.clear();
+		}
+		if !conflict {
+			if run.is_empty() { run_start = ix as usize }
+			run.push(s);
+		}
+	}
+	add_run_patterns(run_start, &run, k, windows);
+}
+
+struct HapMetrics {
+	n: u32,
+	mhl: f64,
+	pdr: f64,
+	epipoly: f64,
+	entropy: f64,
+}
+
+impl HapMetrics {
+	// Calculate metrics from the pattern counts for a window of k CpGs
+	//
+	// MHL: methylation haplotype load - weighted average (by length) of the fraction of fully methylated substrings
+	// PDR: proportion of discordant reads (reads with a mixture of methylated and unmethylated CpGs)
+	// Epipolymorphism: probability that two reads drawn at random have different patterns
+	// Entropy: Shannon entropy (base 2) of the pattern distribution, normalized by the window size
+	fn new(pats: &HashMap<u32, u32>, k: usize) -> Self {
+		let n: u32 = pats.values().sum();
+		let fn_ = n as f64;
+		let full = (1u32 << k) - 1;
+		let mut disc = 0;
+		let mut ssq = 0.0;
+		let mut ent = 0.0;
+		let mut meth_ss = vec![0u64; k];
+		for (pat, c) in pats.iter() {
+			if *pat != 0 && *pat != full { disc += c }
+			let p = (*c as f64) / fn_;
+			ssq += p * p;
+			ent -= p * p.log2();
+			// Count fully methylated substrings by length
+			let mut r = 0;
+			for i in 0..=k {
+				if i < k && ((pat >> i) & 1) == 1 { r += 1 }
+				else {
+					for l in 1..=r { meth_ss[l - 1] += (*c as u64) * ((r - l + 1) as u64) }
+					r = 0;
+				}
+			}
+		}
+		let (mut num, mut den) = (0.0, 0.0);
+		for (l, ct) in meth_ss.iter().enumerate().map(|(i, c)| (i + 1, *c)) {
+			num += (l as f64) * (ct as f64) / (fn_ * ((k + 1 - l) as f64));
+			den += l as f64;
+		}
+		Self{n, mhl: num / den, pdr: (disc as f64) / fn_, epipoly: 1.0 - ssq, entropy: ent / (k as f64)}
+	}
+
+	fn values(&self) -> [f64; 4] { [self.mhl, self.pdr, self.epipoly, self.entropy] }
+}
+
+pub struct HapState {
+	input: BamInput,
+	rid: Option<u32>,
+	bw_rid: Option<u32>,
+	cpgs: Vec<u32>,
+	k: usize,
+	min_reads: u32,
+	min_mapq: u8,
+	bq_threshold: u8,
+}
+
+impl HapState {
+	pub fn new(chash: &ConfHash) -> io::Result<Self> {
+		let name = chash.get_str("bam").expect("BAM input file is missing");
+		let input = BamInput::new(name)?;
+		let k = chash.get_int("hap_window");
+		Ok(Self{input, rid: None, bw_rid: None, cpgs: Vec::with_capacity(CHUNK_SIZE + k), k,
+			min_reads: chash.get_int("hap_min_reads").max(1) as u32,
+			min_mapq: chash.get_int("hap_mapq").min(255) as u8,
+			bq_threshold: chash.get_int("bq_threshold").clamp(1, 255) as u8})
+	}
+
+	pub fn add_block(&mut self, outfiles: &mut [HtsFile], rec_blk: &RecordBlock, prev: Option<RecordBlockElem>, chash: &ConfHash, hdr: &VcfHeader) -> io::Result<()> {
+		assert_eq!(outfiles.len(), 1);
+		let opts = OutputOpts::new(chash);
+		let svec = match rec_blk {
+			RecordBlock::Single(svec) => svec,
+			RecordBlock::Multi(_) => panic!("Multi sample files not compatible with haplotype output"),
+		};
+		let mut prev = if let Some(RecordBlockElem::Single((r, m))) = prev { Some((r, m)) } else { None };
+		for (rec2, meth_rec2) in svec.iter() {
+			if let Some((rec1, meth_rec1)) = prev {
+				if rec1.gt().is_some() && rec2.gt().is_some() && rec1.rid() == rec2.rid() && rec1.pos() + 1 == rec2.pos() {
+					let (pass, q) = calc_prob(meth_rec1, meth_rec2, &opts);
+					if pass && (opts.min_n() < 1 || calc_phred(1.0 - q) >= opts.threshold()) { self.add_cpg(outfiles, chash, hdr, rec1.rid(), rec1.pos())? }
+				}
+			}
+			prev = Some((rec2, meth_rec2));
+		}
+		Ok(())
+	}
+
+	pub fn finish(&mut self, outfiles: &mut [HtsFile], chash: &ConfHash, hdr: &VcfHeader) -> io::Result<()> { self.flush(outfiles, chash, hdr, true) }
+
+	fn add_cpg(&mut self, outfiles: &mut [HtsFile], chash: &ConfHash, hdr: &VcfHeader, rid: u32, pos: u32) -> io::Result<()> {
+		if self.rid != Some(rid) {
+			self.flush(outfiles, chash, hdr, true)?;
+			self.rid = Some(rid);
+		}
+		self.cpgs.push(pos);
+		if self.cpgs.len() >= CHUNK_SIZE + self.k - 1 { self.flush(outfiles, chash, hdr, false) } else { Ok(()) }
+	}
+
+	// Process all complete windows in the buffer.  Unless we are at the end of a contig, keep the last k - 1 CpGs
+	// as these are required for the next window
+	fn flush(&mut self, outfiles: &mut [HtsFile], chash: &ConfHash, hdr: &VcfHeader, end_of_ctg: bool) -> io::Result<()> {
+		if let Some(rid) = self.rid {
+			if self.cpgs.len() >= self.k { self.process_chunk(&mut outfiles[0], chash, hdr, rid)? }
+			let l = self.cpgs.len();
+			let keep = if end_of_ctg { 0 } else { (self.k - 1).min(l) };
+			self.cpgs.drain(..l - keep);
+		}
+		Ok(())
+	}
+
+	fn process_chunk(&mut self, f: &mut HtsFile, chash: &ConfHash, hdr: &VcfHeader, rid: u32) -> io::Result<()> {
+		let ctg = hdr.ctg_name(rid as usize)?;
+		let tid = match self.input.hdr.name2tid(ctg) {
+			Some(t) => t,
+			None => {
+				warn!("Contig {} not found in BAM file - skipping haplotype output", ctg);
+				return Ok(())
+			},
+		};
+		let k = self.k;
+		let mut windows: Vec<HashMap<u32, u32>> = vec![HashMap::new(); self.cpgs.len() + 1 - k];
+		let mut reads = self.input.collect_states(tid, &self.cpgs, self.min_mapq, self.bq_threshold)?;
+		for (_, v) in reads.iter_mut() {
+			v.sort_unstable();
+			add_read_patterns(v, k, &mut windows);
+		}
+		drop(reads);
+
+		// Set up bigWig builders
+		let bbi_ref = chash.bbi(BbiSet::Haplotype).read().unwrap();
+		let bbi = bbi_ref.as_ref().expect("Bbi not set");
+		let sender = bbi.sender().expect("Bbi sender not set");
+		let mut bw_builders: Vec<_> = bbi.bw_files().iter().map(|f| f.build().write().unwrap()).collect();
+		if bw_builders.len() != HAP_BW_TRACKS.len() { panic!("Unexpected number of bigWig files") }
+		if let Some(old_rid) = self.bw_rid {
+			if old_rid != rid {
+				for build in bw_builders.iter_mut() {
+					build.finish(sender);
+					build.clear_counts();
+				}
+			}
+		}
+		self.bw_rid = Some(rid);
+		let out_ix = chash.vcf_contigs()[rid as usize].out_ix().expect("Missing out index for contig") as u32;
+
+		for (ix, pats) in windows.iter().enumerate().filter(|(_, p)| !p.is_empty()) {
+			let m = HapMetrics::new(pats, k);
+			if m.n < self.min_reads { continue }
+			let (start, end) = (self.cpgs[ix], self.cpgs[ix + k - 1] + 2);
+			writeln!(f, "{}\t{}\t{}\t{}\t{:.4}\t{:.4}\t{:.4}\t{:.4}", ctg, start, end, m.n, m.mhl, m.pdr, m.epipoly, m.entropy)?;
+			for (build, val) in bw_builders.iter_mut().zip(m.values().iter()) {
+				build.add_bw_rec(out_ix, start, *val as f32, sender);
+				build.add_zoom_obs(out_ix, start, *val as f32, sender);
+			}
+		}
+		Ok(())
+	}
+}
+
+pub fn print_haplotype_header(f: &mut HtsFile, _hdr: &VcfHeader, _chash: &ConfHash) -> io::Result<()> {
+	writeln!(f, "Contig\tStart\tEnd\tReads\tMHL\tPDR\tEpipolymorphism\tEntropy")
+}
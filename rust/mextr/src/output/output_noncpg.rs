@@ -1,24 +1,35 @@
 use std::io::{self, Write};
 use std::str::from_utf8;
 
+use libc::c_int;
 use r_htslib::{HtsFile, VcfHeader};
 
 use crate::config::*;
 use crate::read_vcf::unpack::{RecordBlock, RecordBlockElem, Strand};
 use super::{OutputOpts, calc_phred, Record, MethRec, GT_IUPAC, GT_MASK, get_prob_dist};
+use super::context::get_context;
+
+// Check site against the (optional) context filter.  We use the reference context here as the
+// record can be shared between multiple samples
+fn pass_context(rec: &Record, opts: &OutputOpts) -> bool {
+	match opts.context_filter() {
+		Some(mask) => get_context(&rec.cx).map(|(_, ix)| mask.contains(ix)).unwrap_or(false),
+		None => true,
+	}
+}
 
 // Prob. that sample has the required genotype depending on strand:
 //  for Select::Hom CC or GG, for Select::Het (AC CC CG CT) or (AG, CG, GG, GT)
-fn calc_prob(mrec: &MethRec, strand: Strand, opts: &OutputOpts) -> f64 {
+pub fn calc_site_prob(mrec: &MethRec, strand: Strand, opts: &OutputOpts, min_nc: c_int) -> f64 {
 	if mrec.max_gt().is_some() {
 		let ct = mrec.counts();
 		let p = mrec.gt_probs();
 		if let Strand::C = strand {
-			if ct[5] >= opts.min_nc() && (ct[5] + ct[7]) >= opts.min_inform() {
+			if ct[5] >= min_nc && (ct[5] + ct[7]) >= opts.min_inform() {
 				if let Select::Hom = opts.select { p[4].exp() }
 				else { p[1].exp() + p[4].exp() + p[5].exp() + p[6].exp() }
 			} else { 0.0 }
-		} else if ct[6] >= opts.min_nc() && (ct[6] + ct[4]) >= opts.min_inform() {
+		} else if ct[6] >= min_nc && (ct[6] + ct[4]) >= opts.min_inform() {
 			if let Select::Hom = opts.select { p[7].exp() }
 			else { p[2].exp() + p[5].exp() + p[7].exp() + p[8].exp() }
 		} else { 0.0 }			
@@ -55,10 +66,10 @@ pub fn output_mrec<W: Write>(f: &mut W, mrec: &MethRec, strand: Strand, output_c
 
 fn output_single_rec<W: Write>(f: &mut W, hdr: &VcfHeader, opts: &OutputOpts, srec: &[(Record, MethRec)]) -> io::Result<()> {
 	for (rec, meth_rec) in srec {
-		if rec.gt().is_some() {
+		if rec.gt().is_some() && pass_context(rec, opts) {
 			// Safe to unwrap because of previous line
 			let strand = rec.strand().unwrap();
-			let phred = calc_phred(1.0 - calc_prob(meth_rec, strand, opts));
+			let phred = calc_phred(1.0 - calc_site_prob(meth_rec, strand, opts, opts.min_nc()));
 			if opts.min_n == 0 || phred >= opts.threshold() {
 				write!(f, "{}\t{}\t{}\t{}", hdr.ctg_name(rec.rid as usize).unwrap(), rec.pos, rec.pos + 1, rec.cx[2] as char)?;
 				output_mrec(f, meth_rec, strand, true)?;
@@ -73,11 +84,11 @@ fn output_multi_rec<W: Write>(f: &mut W, hdr: &VcfHeader, opts: &OutputOpts, sre
 	let ns = hdr.nsamples();
 	let mut qvec = Vec::with_capacity(ns);
 	for (rec, mvec) in srec {
-		if rec.gt().is_some() {
+		if rec.gt().is_some() && pass_context(rec, opts) {
 			// Safe to unwrap because of previous line
 			let strand = rec.strand().unwrap();
 			qvec.clear();
-			mvec.iter().for_each(|m| qvec.push(calc_prob(m, strand, opts)));
+			mvec.iter().for_each(|m| qvec.push(calc_site_prob(m, strand, opts, opts.min_nc())));
 			get_prob_dist(&mut qvec);
 			let phred = calc_phred(qvec[1..opts.min_n].iter().fold(qvec[0], |s, q| s + *q)); 
 			if opts.min_n == 0 || phred >= opts.threshold() {
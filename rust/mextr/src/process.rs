@@ -4,9 +4,9 @@ use std::thread;
 
 use crossbeam_channel::{bounded, unbounded, Receiver};
 
-use r_htslib::{VcfHeader, BcfSrs, htsThreadPool};
+use r_htslib::{VcfHeader, htsThreadPool};
 use crate::config::ConfHash;
-use super::read_vcf::read_vcf;
+use super::read_vcf::{read_vcf, VcfInput};
 use super::read_vcf::unpack::RecordBlock;
 use super::output::*;
 use super::output::md5::md5_thread;
@@ -18,16 +18,20 @@ pub type TPool = Arc<Option<htsThreadPool>>;
 type ExtraFn = fn(Arc<ConfHash>, Receiver<bool>);
 type OutputFn = fn(Arc<ConfHash>, Arc<VcfHeader>, Recv, TPool);
 
-const OUTPUTS: [(&str, OutputFn); 3] = [
+const OUTPUTS: [(&str, OutputFn); 7] = [
 	("cpgfile", output_cpg_thread),
 	("noncpgfile", output_noncpg_thread),
 	("bed_methyl", output_bed_methyl_thread),
+	("haplotype", output_haplotype_thread),
+	("allele_report", output_allele_report_thread),
+	("context_summary", output_context_summary_thread),
+	("context_bw", output_context_bw_thread),
 ];
 
 const EXTRAS: [(&str, ExtraFn); 2] = [("md5", md5_thread), ("tabix", tabix_thread)];
 
-pub fn process(chash: ConfHash, mut sr: BcfSrs) -> io::Result<()> {
-	let hdr = Arc::new(sr.get_reader_hdr(0)?.dup());
+pub fn process(chash: ConfHash, mut input: VcfInput) -> io::Result<()> {
+	let hdr = Arc::new(input.header()?.dup());
 	let chash = Arc::new(chash);
 	let mut out_threads = Vec::new();
 	let mut out_channels = Vec::new();
@@ -35,7 +39,7 @@ pub fn process(chash: ConfHash, mut sr: BcfSrs) -> io::Result<()> {
 	// Set up thread pool for htsFiles (both reading and writing)
 	let nt = chash.get_int("threads");
 	let mut thread_pool = if nt > 0 { htsThreadPool::init(nt.min(4)) } else { None };
-	if let Some(tp) = thread_pool.as_mut() { sr.get_reader(0)?.file().set_thread_pool(tp); }
+	if let Some(tp) = thread_pool.as_mut() { input.set_thread_pool(tp)?; }
 	
 	// We'll be sharing the pool with the output threads, so wrap it in an Arc.
 	let thread_pool = Arc::new(thread_pool);
@@ -60,7 +64,7 @@ pub fn process(chash: ConfHash, mut sr: BcfSrs) -> io::Result<()> {
 			(th, s)							
 		}).collect();
 
-		read_vcf(chash, sr, hdr, 3, out_channels)?;
+		read_vcf(chash, input, hdr, 3, out_channels, thread_pool.clone())?;
 		for th in out_threads.drain(..) {
 			th.join().unwrap();
 		}
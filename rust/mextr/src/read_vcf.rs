@@ -1,10 +1,13 @@
 use std::io;
 use std::sync::Arc;
 use std::thread;
+use std::path::Path;
+use std::ops::Deref;
 use crossbeam_channel::{unbounded, Sender};
 
 use r_htslib::*;
 use crate::config::*;
+use crate::process::TPool;
 
 pub mod model;
 pub mod unpack;
@@ -27,7 +30,113 @@ impl BrecBlock {
 	pub fn buf(&mut self) -> &mut [BcfRec] { &mut self.buf }	
 }
 
-pub fn read_vcf(chash: Arc<ConfHash>, mut sr: BcfSrs, hdr: Arc<VcfHeader>, n_slaves: usize, channel_vec: Vec<Sender<(usize, Arc<RecordBlock>)>>) -> io::Result<()> {
+// FORMAT/INFO tags used by unpack_vcf(), which must have the same IDs in all input files 
+const REQ_TAGS: [&str; 5] = ["CX", "MC8", "MQ", "AMQ", "AQ"];
+
+/// Returns true if an index (CSI or TBI) exists for the file.
+pub fn has_index(fname: &str) -> bool {
+	fname != "-" && ["csi", "tbi"].iter().any(|ext| Path::new(&format!("{}.{}", fname, ext)).exists())
+}
+
+/// Reader for one or more BCF/VCF files that are read in turn without requiring an index.
+/// This allows reading from stdin ("-") and the processing of the per-pool BCFs from bs_call
+/// without merging.  All files must have the same samples, contigs and tags. 
+pub struct StreamInput {
+	files: Vec<String>,
+	ix: usize,
+	fp: HtsFile,
+	hdr: VcfHeader,
+	curr_hdr: Option<VcfHeader>, // Header of current file if not the first
+}
+
+impl StreamInput {
+	pub fn new<S: AsRef<str>>(files: &[S]) -> io::Result<Self> {
+		assert!(!files.is_empty());
+		let files: Vec<String> = files.iter().map(|s| s.as_ref().to_owned()).collect();
+		let (fp, hdr) = Self::open(&files[0])?;
+		Ok(Self{files, ix: 0, fp, hdr, curr_hdr: None})
+	}
+	
+	fn open(name: &str) -> io::Result<(HtsFile, VcfHeader)> {
+		let mut fp = HtsFile::new(name, "r")?;
+		let hdr = VcfHeader::read(&mut fp).map_err(|e| new_err(format!("Couldn't read header from {}: {}", name, e)))?;
+		Ok((fp, hdr))
+	}
+	
+	pub fn header(&self) -> &VcfHeader { &self.hdr }
+	pub fn file(&mut self) -> &mut HtsFile { &mut self.fp }
+	
+	// Check that the header of the current file is compatible with the header of the first file
+	fn check_header(&self, hdr: &VcfHeader) -> io::Result<()> {
+		let name = &self.files[self.ix];
+		let err = |s: &str| Err(new_err(format!("Header of input file {} incompatible with that of {}: {}", name, self.files[0], s)));
+		let ns = hdr.nsamples();
+		if ns != self.hdr.nsamples() { return err("different number of samples") }
+		for i in 0..ns { if hdr.sample_name(i)? != self.hdr.sample_name(i)? { return err("different samples") } }
+		let nc = hdr.nctgs();
+		if nc != self.hdr.nctgs() { return err("different number of contigs") }
+		for i in 0..nc { if hdr.ctg_name_len(i)? != self.hdr.ctg_name_len(i)? { return err("different contigs") } }
+		for tag in REQ_TAGS.iter() { if hdr.id2int(BCF_DT_ID as usize, tag) != self.hdr.id2int(BCF_DT_ID as usize, tag) { return err("different tag definitions") } }
+		Ok(())
+	}
+	
+	// Read next record, moving on to the next file when required.  Returns false when all files are finished
+	fn next_rec(&mut self, brec: &mut BcfRec, tp: &TPool) -> io::Result<bool> {
+		loop {
+			let hdr = self.curr_hdr.as_ref().unwrap_or(&self.hdr);
+			match brec.read(&mut self.fp, hdr) {
+				BcfReadResult::Ok => return Ok(true),
+				BcfReadResult::Error => return Err(new_err(format!("Error reading from input file {}", self.files[self.ix]))),
+				BcfReadResult::EOF => {
+					if self.ix + 1 == self.files.len() { return Ok(false) }
+					self.ix += 1;
+					let (mut fp, hdr) = Self::open(&self.files[self.ix])?;
+					self.check_header(&hdr)?;
+					if let Some(tpool) = tp.deref() { fp.set_thread_pool(tpool); }
+					debug!("Switching input to {}", self.files[self.ix]);
+					self.fp = fp;
+					self.curr_hdr = Some(hdr);
+				},
+			}
+		}
+	}
+}
+
+/// Source of BCF records for processing
+pub enum VcfInput {
+	// Single indexed file read through the synced reader (allows region selection)
+	Synced(BcfSrs),
+	// One or more files read sequentially
+	Stream(StreamInput),
+}
+
+impl VcfInput {
+	pub fn header(&mut self) -> io::Result<&bcf_hdr_t> {
+		match self {
+			VcfInput::Synced(sr) => sr.get_reader_hdr(0),
+			VcfInput::Stream(s) => Ok(s.header().as_ref()),
+		}
+	}
+	pub fn set_thread_pool(&mut self, tp: &htsThreadPool) -> io::Result<()> {
+		match self {
+			VcfInput::Synced(sr) => { sr.get_reader(0)?.file().set_thread_pool(tp); },
+			VcfInput::Stream(s) => { s.file().set_thread_pool(tp); },
+		}
+		Ok(())
+	}
+	pub fn is_stream(&self) -> bool { matches!(self, VcfInput::Stream(_)) }
+	fn next_rec(&mut self, brec: &mut BcfRec, tp: &TPool) -> io::Result<bool> {
+		match self {
+			VcfInput::Synced(sr) => if sr.next_line() > 0 { 
+				sr.swap_line(0, brec)?;
+				Ok(true)
+			} else { Ok(false) },
+			VcfInput::Stream(s) => s.next_rec(brec, tp),
+		}
+	}
+}
+
+pub fn read_vcf(chash: Arc<ConfHash>, mut input: VcfInput, hdr: Arc<VcfHeader>, n_slaves: usize, channel_vec: Vec<Sender<(usize, Arc<RecordBlock>)>>, tp: TPool) -> io::Result<()> {
 	let ns = hdr.nsamples();
 	assert!(ns > 0);	
 	let n_slaves = n_slaves.max(1);
@@ -49,10 +158,14 @@ pub fn read_vcf(chash: Arc<ConfHash>, mut sr: BcfSrs, hdr: Arc<VcfHeader>, n_sla
 		let th = thread::spawn(move || unpack_vcf_slave(ch, hd, cv, es, fr));
 		slaves.push(th);
 	}
-	// Read input file
+	// bigBed/bigWig files require contigs to be in the output (sorted) order.  This is guaranteed by the region
+	// sorting for synced input, but we have to check if reading sequentially
+	let check_order = input.is_stream() && ["bed_methyl", "context_bw", "haplotype"].iter().any(|s| chash.get_str(s).is_some());
+	let mut prev_ctg: Option<(usize, usize)> = None;
+	// Read input file(s)
 	let mut idx = 0;
 	let mut curr_blk = None;
-	while sr.next_line() > 0 {
+	loop {
 		// Get empty block if required
 		if curr_blk.is_none() {
 			if let Ok(mut blk) = empty_r.recv() {
@@ -70,12 +183,28 @@ pub fn read_vcf(chash: Arc<ConfHash>, mut sr: BcfSrs, hdr: Arc<VcfHeader>, n_sla
 		// All elements are initialized so we can do this
 		let i = cb.buf.len();
 		unsafe { cb.buf.set_len(i + 1) }
-		sr.swap_line(0, &mut cb.buf[i])?;
+		if !input.next_rec(&mut cb.buf[i], &tp)? {
+			unsafe { cb.buf.set_len(i) }
+			break
+		}
+		if check_order {
+			let rid = cb.buf[i].rid();
+			if prev_ctg.map(|(r, _)| r != rid).unwrap_or(true) {
+				let out_ix = chash.vcf_contigs()[rid].out_ix().ok_or_else(|| new_err(format!("Unexpected contig {}", chash.vcf_contigs()[rid].name())))?;
+				if let Some((prid, pix)) = prev_ctg {
+					if out_ix < pix { return Err(new_err(format!("Contig {} found after {} in input: bigBed/bigWig output requires contigs sorted by name (or an indexed input file)",
+						chash.vcf_contigs()[rid].name(), chash.vcf_contigs()[prid].name()))) }
+				}
+				prev_ctg = Some((rid, out_ix));
+			}
+		}
 		if i + 1 == cb.buf.capacity() {
 			full_s.send(curr_blk.take().unwrap()).map_err(|e| new_err(format!("Error sending to full queue: {}",e)))?;
 		}
 	}
-	if let Some(cb) = curr_blk.take() { full_s.send(cb).map_err(|e| new_err(format!("Error sending to full queue: {}",e)))?}
+	if let Some(cb) = curr_blk.take() { 
+		if !cb.buf.is_empty() { full_s.send(cb).map_err(|e| new_err(format!("Error sending to full queue: {}",e)))? }
+	}
 	drop(full_s);
 	for th in slaves.drain(..) { th.join().unwrap() }
 	Ok(())
@@ -230,7 +230,12 @@ pub fn unpack_vcf(brec: &mut BcfRec, hdr: &VcfHeader, udata: &mut UnpackData) {
 	let gt_strand = if udata.ns > 1 { calc_common_gt(&mut mrec_vec, udata.common_gt) } 
 	else { mrec_vec[0].max_gt().map(|x| (x, find_strand(&mrec_vec[0].gt_probs(), x as usize))) };
 	// Store record
-	let rec = Record::new(brec.rid() as u32, brec.pos() as u32, cx, gt_strand);
+	// Only store the ID (i.e., dbSNP id) if present
+	let id = match brec.id() {
+		"" | "." => None,
+		s => Some(s.to_owned().into_boxed_str()),
+	};
+	let rec = Record::new(brec.rid() as u32, brec.pos() as u32, cx, gt_strand, id);
 	let mut rec_blk = udata.rec_blk.as_mut().unwrap();
 	match &mut rec_blk {
 		RecordBlock::Single(rb) => rb.push((rec, mrec_vec.pop().unwrap())),
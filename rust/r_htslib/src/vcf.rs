@@ -190,10 +190,13 @@ extern "C" {
 	fn bcf_destroy(bcf: *mut bcf1_t);
 	fn bcf_clear(bcf: *mut bcf1_t);
 	fn bcf_write(hfile: *mut htsFile, hdr: *mut bcf_hdr_t, brec: *mut bcf1_t) -> c_int;
+	fn bcf_read(hfile: *mut htsFile, hdr: *const bcf_hdr_t, brec: *mut bcf1_t) -> c_int;
 	fn bcf_unpack(b: *mut bcf1_t, which: c_int);
 	fn bcf_get_format_values(hdr: *const bcf_hdr_t, line: *mut bcf1_t, tag: *const c_char, dst: *mut *mut c_void, ndst: *mut c_int, _type: c_int) -> c_int;
 	fn bcf_get_info_values(hdr: *const bcf_hdr_t, line: *mut bcf1_t, tag: *const c_char, dst: *mut *mut c_void, ndst: *mut c_int, _type: c_int) -> c_int;
 	fn bcf_hrec_find_key(hrec: *const bcf_hrec_t, key: *const c_char) -> c_int;
+	fn bcf_add_filter(hdr: *const bcf_hdr_t, line: *mut bcf1_t, flt_id: c_int) -> c_int;
+	fn bcf_update_info(hdr: *const bcf_hdr_t, line: *mut bcf1_t, key: *const c_char, values: *const c_void, n: c_int, _type: c_int) -> c_int;
 }
 
 pub struct VcfHeader {
@@ -370,9 +373,18 @@ impl bcf1_t {
 	pub fn pos(&self) -> usize { self.pos as usize }
 	pub fn set_rlen(&mut self, rlen: usize) { self.rlen = rlen as HtsPos }
 	pub fn set_qual(&mut self, qual: f32) { self.qual = qual }
+	// Missing values are returned as None
+	pub fn qual(&self) -> Option<f32> { if self.qual.is_nan() { None } else { Some(self.qual) } }
 	pub fn write<H: AsMut<bcf_hdr_t>, F: AsMut<htsFile>>(&mut self, mut file: F, mut hdr: H) -> io::Result<()> {
 		if unsafe { bcf_write(file.as_mut(), hdr.as_mut(), self) } < 0 { Err(hts_err("Error writing out VCF record".to_string())) } else { Ok(()) }
 	}
+	pub fn read<H: AsRef<bcf_hdr_t>, F: AsMut<htsFile>>(&mut self, mut file: F, hdr: H) -> BcfReadResult {
+		match unsafe { bcf_read(file.as_mut(), hdr.as_ref(), self) } {
+			0..=c_int::MAX => BcfReadResult::Ok,
+			-1 => BcfReadResult::EOF,
+			_ => BcfReadResult::Error,
+		}
+	}
 	pub fn unpack(&mut self, which: usize) { unsafe{bcf_unpack(self, which as c_int)} }
 	pub fn id(&mut self) -> &str {
 		self.unpack(BCF_UN_STR);
@@ -386,6 +398,12 @@ impl bcf1_t {
 		for i in 0..(d.n_flt as usize) { if unsafe{*d.flt.add(i)} == 0 { return true }}
 		false
 	}
+	// True if any filter other than PASS is set (records with missing filters are not considered to have failed)
+	pub fn failed_filters(&mut self) -> bool {
+		self.unpack(BCF_UN_FLT);
+		let d = &self.d;
+		(0..(d.n_flt as usize)).any(|i| unsafe{*d.flt.add(i)} != 0)
+	}
 	pub fn alleles(&mut self) -> Vec<&str> {
 		self.unpack(BCF_UN_STR);
 		let n_all = self.n_allele() as usize;
@@ -410,6 +428,16 @@ impl bcf1_t {
 		ret_format_res(p, len, cap, buf)
 	}
 	pub fn get_info_u8(&mut self, hdr: &VcfHeader, tag: &str, buf: &mut MallocDataBlock<u8>) -> Option<usize> { self.get_info_values(hdr, tag, buf, BCF_HT_STR)}	
+	// Adds a filter to the FILTER field, removing PASS if present
+	pub fn add_filter<H: AsRef<bcf_hdr_t>>(&mut self, hdr: H, flt_id: c_int) -> io::Result<()> {
+		if unsafe { bcf_add_filter(hdr.as_ref(), self, flt_id) } < 0 { Err(hts_err("Error adding VCF filter".to_string())) } else { Ok(()) }
+	}
+	pub fn update_info_f32<H: AsRef<bcf_hdr_t>>(&mut self, hdr: H, tag: &str, vals: &[f32]) -> io::Result<()> {
+		let tag = CString::new(tag).unwrap();
+		if unsafe { bcf_update_info(hdr.as_ref(), self, tag.as_ptr(), vals.as_ptr() as *const c_void, vals.len() as c_int, BCF_HT_REAL) } < 0 { 
+			Err(hts_err(format!("Error updating VCF INFO tag {}", tag.to_string_lossy()))) 
+		} else { Ok(()) }
+	}
 }
 
 pub struct BcfRec {
@@ -467,3 +495,8 @@ impl Drop for BcfRec {
 	fn drop(&mut self) { unsafe{ bcf_destroy(self.as_mut()) } }
 }
 
+pub enum BcfReadResult {
+	Ok,
+	EOF,
+	Error,
+}
@@ -0,0 +1,83 @@
+// Assignment of reads to genomes when the reference is a combination of genomes (i.e., host + xenograft or spike-in controls)
+// Reads mapping to genomes in the remove list (and their mates) can be dropped from the output
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Error, ErrorKind};
+use serde::Serialize;
+
+use utils::compress;
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GenomeCount {
+	name: String,
+	reads: usize,
+	removed: bool,
+}
+
+#[derive(Default, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GenomeStats {
+	genomes: Vec<GenomeCount>,
+	unmapped: usize,
+	cross_genome: usize,
+	removed: usize,
+}
+
+pub struct Genomes {
+	ctg_genome: HashMap<Vec<u8>, usize>,
+	stats: GenomeStats,
+}
+
+impl Genomes {
+	// Genome file has one line per contig from the extra genomes: contig name <TAB> genome name.
+	// Contigs not in the file belong to the primary genome
+	pub fn from_file(fname: &str, primary: &str, remove: &[String]) -> io::Result<Self> {
+		let rdr = compress::open_bufreader(fname)?;
+		let mut ctg_genome = HashMap::new();
+		let mut stats = GenomeStats::default();
+		stats.genomes.push(GenomeCount{name: primary.to_owned(), reads: 0, removed: remove.iter().any(|s| s == primary)});
+		for line in rdr.lines() {
+			let line = line?;
+			let mut iter = line.trim_end().split('\t');
+			if let (Some(ctg), Some(genome)) = (iter.next(), iter.next()) {
+				let ix = match stats.genomes.iter().position(|g| g.name == genome) {
+					Some(i) => i,
+					None => {
+						stats.genomes.push(GenomeCount{name: genome.to_owned(), reads: 0, removed: remove.iter().any(|s| s == genome)});
+						stats.genomes.len() - 1
+					},
+				};
+				ctg_genome.insert(ctg.as_bytes().to_vec(), ix);
+			}
+		}
+		for s in remove.iter() {
+			if !stats.genomes.iter().any(|g| &g.name == s) { return Err(Error::new(ErrorKind::Other, format!("Genome {} not found in genome file {}", s, fname))) }
+		}
+		Ok(Genomes{ctg_genome, stats})
+	}
+
+	// Count primary alignments per genome.  Returns false if the record should be removed
+	pub fn check_record(&mut self, rec: &[u8]) -> bool {
+		let mut iter = rec.split(|c| *c == b'\t');
+		let flag = iter.nth(1).and_then(|s| std::str::from_utf8(s).ok()).and_then(|s| s.parse::<u16>().ok()).unwrap_or(0);
+		let rname = iter.next().unwrap_or(b"*");
+		let rnext = iter.nth(3).unwrap_or(b"*");
+		let get_genome = |s: &[u8]| if s == b"*" { None } else { Some(self.ctg_genome.get(s).copied().unwrap_or(0)) };
+		let genome = get_genome(rname);
+		let mate_genome = if rnext == b"=" { genome } else { get_genome(rnext) };
+		let keep = !(genome.map(|i| self.stats.genomes[i].removed).unwrap_or(false) || mate_genome.map(|i| self.stats.genomes[i].removed).unwrap_or(false));
+		if (flag & 0x900) == 0 {
+			if (flag & 4) != 0 { self.stats.unmapped += 1 }
+			else if let Some(i) = genome { self.stats.genomes[i].reads += 1 }
+			if (flag & 0xd) == 1 && genome != mate_genome { self.stats.cross_genome += 1 }
+			if !keep { self.stats.removed += 1 }
+		}
+		keep
+	}
+
+	pub fn write_stats(&self, fname: &str) -> io::Result<()> {
+		let wrt = BufWriter::new(fs::File::create(fname)?);
+		serde_json::to_writer_pretty(wrt, &self.stats).map_err(|e| Error::new(ErrorKind::Other, format!("Error writing genome stats to {}: {}", fname, e)))
+	}
+}
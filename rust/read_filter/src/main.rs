@@ -5,6 +5,35 @@ use lazy_static::lazy_static;
 
 use utils::compress;
 
+mod genomes;
+use genomes::Genomes;
+
+// Command line: read_filter [contig_md5 file] [--genomes genome_file --primary name --stats stats_file [--remove genome1,genome2...]]
+#[derive(Default)]
+struct Config {
+	contig_file: Option<String>,
+	genome_file: Option<String>,
+	primary: Option<String>,
+	stats_file: Option<String>,
+	remove: Vec<String>,
+}
+
+fn get_config() -> io::Result<Config> {
+	let mut conf = Config::default();
+	let mut args = env::args().skip(1);
+	let missing = |opt: &str| Error::new(ErrorKind::Other, format!("Missing argument for option {}", opt));
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--genomes" => conf.genome_file = Some(args.next().ok_or_else(|| missing(&arg))?),
+			"--primary" => conf.primary = Some(args.next().ok_or_else(|| missing(&arg))?),
+			"--stats" => conf.stats_file = Some(args.next().ok_or_else(|| missing(&arg))?),
+			"--remove" => conf.remove = args.next().ok_or_else(|| missing(&arg))?.split(',').map(|x| x.to_owned()).collect(),
+			_ => if arg.starts_with("--") { return Err(Error::new(ErrorKind::Other, format!("Unknown option {}", arg))) } else { conf.contig_file = Some(arg) },
+		}
+	}
+	Ok(conf)
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum TagType { SN, AS, M5, SP, LN }
 
@@ -53,10 +82,9 @@ fn read_contig_file(fname: &str, href: &mut HashMap<String, Contig>) -> io::Resu
 	}	
 	Ok(())
 }
-fn handle_header(rd_handle: &mut StdinLock, wr_handle: &mut StdoutLock) -> io::Result<Vec<u8>> {
-	let args: Vec<String> = env::args().collect();
+fn handle_header(contig_file: Option<&str>, rd_handle: &mut StdinLock, wr_handle: &mut StdoutLock) -> io::Result<Vec<u8>> {
 	let mut contig_hash = HashMap::new();
-	if args.len() > 1 { read_contig_file(&args[1], &mut contig_hash)? }
+	if let Some(f) = contig_file { read_contig_file(f, &mut contig_hash)? }
     let mut buffer = String::new();
 	loop {
 		match rd_handle.read_line(&mut buffer) {
@@ -122,15 +150,21 @@ fn clean_readname(s: &mut [u8]) {
 
 
 fn main() -> io::Result<()> {
+	let conf = get_config()?;
+	let mut genomes = match &conf.genome_file {
+		Some(f) => Some(Genomes::from_file(f, conf.primary.as_deref().unwrap_or("primary"), &conf.remove)?),
+		None => None,
+	};
     let stdin = io::stdin();
     let mut rd_handle = stdin.lock();
     let stdout = io::stdout();
     let mut wr_handle = stdout.lock();
-	let mut buffer = handle_header(&mut rd_handle, &mut wr_handle)?;
-	if buffer.is_empty() { return Ok(()) }
-	loop {
-		clean_readname(&mut buffer);
-		wr_handle.write_all(&buffer)?;
+	let mut buffer = handle_header(conf.contig_file.as_deref(), &mut rd_handle, &mut wr_handle)?;
+	while !buffer.is_empty() {
+		if genomes.as_mut().map(|g| g.check_record(&buffer)).unwrap_or(true) {
+			clean_readname(&mut buffer);
+			wr_handle.write_all(&buffer)?;
+		}
 		buffer.clear();
 		match rd_handle.read_until(b'\n', &mut buffer) {
 			Ok(0) => { break; },
@@ -138,6 +172,7 @@ fn main() -> io::Result<()> {
 			Err(e) => return Err(e),
 		}		
 	}
+	if let (Some(g), Some(f)) = (&genomes, &conf.stats_file) { g.write_stats(f)? }
 
     Ok(())
 }
@@ -0,0 +1,50 @@
+use std::io;
+use std::str::FromStr;
+use clap::{ArgMatches, ErrorKind};
+
+use crate::config::{Config, TrimParam, new_err, preset_clip};
+
+fn get_opt<T: FromStr>(m: &ArgMatches, name: &str) -> io::Result<Option<T>> {
+	match value_t!(m, name, T) {
+		Ok(x) => Ok(Some(x)),
+		Err(e) if e.kind == ErrorKind::ArgumentNotFound => Ok(None),
+		Err(e) => Err(new_err(format!("Error parsing option {}: {}", name, e))),
+	}
+}
+
+fn check_adapter(s: &str) -> io::Result<Vec<u8>> {
+	let v: Vec<u8> = s.bytes().map(|c| c.to_ascii_uppercase()).collect();
+	if v.is_empty() || v.iter().any(|c| !matches!(c, b'A' | b'C' | b'G' | b'T' | b'N')) { Err(new_err(format!("Invalid adapter sequence {}", s))) }
+	else { Ok(v) }
+}
+
+pub fn handle_options(m: &ArgMatches) -> io::Result<Config> {
+	let mut param = TrimParam::default();
+	if let Some(s) = m.value_of("adapter1") { param.adapter[0] = Some(check_adapter(s)?) }
+	if let Some(s) = m.value_of("adapter2") { param.adapter[1] = Some(check_adapter(s)?) }
+	if let Some(x) = get_opt(m, "detect_reads")? { param.detect_reads = x }
+	if let Some(x) = get_opt(m, "min_overlap")? {
+		if x == 0 { return Err(new_err("Minimum adapter overlap must be greater than zero".to_string())) }
+		param.min_overlap = x
+	}
+	if let Some(x) = get_opt::<f64>(m, "error_rate")? {
+		if !(0.0..1.0).contains(&x) { return Err(new_err(format!("Invalid error rate {}", x))) }
+		param.error_rate = x
+	}
+	if let Some(x) = get_opt(m, "quality")? { param.quality = x }
+	if let Some(x) = get_opt(m, "qual_offset")? { param.qual_offset = x }
+	if m.is_present("poly_g") { param.poly_g = Some(get_opt(m, "poly_g_length")?.unwrap_or(10)) }
+	if let Some(s) = m.value_of("preset") { param.clip = preset_clip(s).ok_or_else(|| new_err(format!("Unknown preset {}", s)))? }
+	for (ix, name) in ["clip_r1_5p", "clip_r1_3p", "clip_r2_5p", "clip_r2_3p"].iter().enumerate() {
+		if let Some(x) = get_opt(m, name)? { param.clip[ix] = x }
+	}
+	if let Some(x) = get_opt(m, "min_length")? { param.min_length = x }
+	let input: Vec<String> = m.values_of("input").map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or_default();
+	let interleaved = m.is_present("interleaved");
+	if interleaved && input.len() > 1 { return Err(new_err("Only one input file allowed for interleaved input".to_string())) }
+	let output = m.value_of("output").map(|s| s.to_owned());
+	let stats = m.value_of("stats").map(|s| s.to_owned());
+	let qc = m.value_of("qc").map(|s| s.to_owned());
+	let no_trim = m.is_present("no_trim");
+	Ok(Config{param, input, interleaved, output, stats, qc, no_trim})
+}
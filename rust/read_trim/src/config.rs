@@ -0,0 +1,55 @@
+use std::io::{self, Error, ErrorKind};
+
+pub fn new_err(s: String) -> io::Error {
+	Error::new(ErrorKind::Other, s)
+}
+
+// Common adapters used for auto detection (the first is used if none are found)
+pub const KNOWN_ADAPTERS: [(&str, &str); 3] = [
+	("Illumina", "AGATCGGAAGAGC"),
+	("Nextera", "CTGTCTCTTATA"),
+	("smallRNA", "TGGAATTCTCGG"),
+];
+
+// Fixed clipping (r1 5', r1 3', r2 5', r2 3') recommended for library kits
+pub fn preset_clip(kit: &str) -> Option<[usize; 4]> {
+	match kit.to_lowercase().as_str() {
+		"swift" | "accel" => Some([10, 10, 15, 10]),
+		"epignome" | "truseq" => Some([8, 8, 8, 8]),
+		_ => None,
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct TrimParam {
+	pub adapter: [Option<Vec<u8>>; 2],
+	pub detect_reads: usize,
+	pub min_overlap: usize,
+	pub error_rate: f64,
+	pub quality: u8,
+	pub qual_offset: u8,
+	pub poly_g: Option<usize>,
+	pub clip: [usize; 4],
+	pub min_length: usize,
+}
+
+impl Default for TrimParam {
+	fn default() -> Self {
+		TrimParam{adapter: [None, None], detect_reads: 100000, min_overlap: 1, error_rate: 0.1, quality: 20, qual_offset: 33,
+			poly_g: None, clip: [0; 4], min_length: 20}
+	}
+}
+
+pub struct Config {
+	pub param: TrimParam,
+	pub input: Vec<String>,
+	pub interleaved: bool,
+	pub output: Option<String>,
+	pub stats: Option<String>,
+	pub qc: Option<String>,
+	pub no_trim: bool,
+}
+
+impl Config {
+	pub fn paired(&self) -> bool { self.interleaved || self.input.len() == 2 }
+}
@@ -0,0 +1,106 @@
+use std::io::{self, BufRead, Write};
+use std::process::Child;
+
+use crate::config::new_err;
+
+#[derive(Debug, Default, Clone)]
+pub struct FastqRecord {
+	pub id: Vec<u8>,
+	pub seq: Vec<u8>,
+	pub qual: Vec<u8>,
+}
+
+impl FastqRecord {
+	// Keep the bases in the range [start, end)
+	pub fn trim(&mut self, start: usize, end: usize) {
+		self.seq.truncate(end);
+		self.qual.truncate(end);
+		self.seq.drain(..start.min(end));
+		self.qual.drain(..start.min(end));
+	}
+	// Read name up to the first white space with any /1 or /2 suffix removed
+	pub fn name(&self) -> &[u8] {
+		let s = self.id.split(|c| c.is_ascii_whitespace()).next().unwrap_or(&[]);
+		if s.len() > 2 && s[s.len() - 2] == b'/' && matches!(s[s.len() - 1], b'1' | b'2') { &s[..s.len() - 2] } else { s }
+	}
+	pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		w.write_all(b"@")?;
+		w.write_all(&self.id)?;
+		w.write_all(b"\n")?;
+		w.write_all(&self.seq)?;
+		w.write_all(b"\n+\n")?;
+		w.write_all(&self.qual)?;
+		w.write_all(b"\n")
+	}
+}
+
+pub struct FastqReader {
+	rdr: Box<dyn BufRead>,
+	child: Option<Child>,
+	name: String,
+	line: usize,
+	qual_offset: u8,
+	buf: Vec<u8>,
+}
+
+fn chomp(v: &mut Vec<u8>) {
+	while let Some(c) = v.last() {
+		if *c == b'\n' || *c == b'\r' { v.pop(); } else { break }
+	}
+}
+
+impl FastqReader {
+	pub fn new(rdr: Box<dyn BufRead>, child: Option<Child>, name: &str, qual_offset: u8) -> Self {
+		FastqReader{rdr, child, name: name.to_owned(), line: 0, qual_offset, buf: Vec::with_capacity(256)}
+	}
+	pub fn name(&self) -> &str { &self.name }
+
+	fn read_line(&mut self) -> io::Result<usize> {
+		self.buf.clear();
+		let l = self.rdr.read_until(b'\n', &mut self.buf)?;
+		chomp(&mut self.buf);
+		self.line += 1;
+		Ok(l)
+	}
+
+	fn err(&self, s: &str) -> io::Error { new_err(format!("{}:{}: {}", self.name, self.line, s)) }
+
+	// At EOF check that the decompression process (if any) completed successfully, as otherwise the
+	// input is probably truncated
+	fn finish(&mut self) -> io::Result<()> {
+		if let Some(mut child) = self.child.take() {
+			let status = child.wait()?;
+			if !status.success() { return Err(new_err(format!("{}: decompression failed ({}) - file truncated or corrupt?", self.name, status))) }
+		}
+		Ok(())
+	}
+
+	// Read next record into rec.  Returns false at EOF
+	pub fn next_record(&mut self, rec: &mut FastqRecord) -> io::Result<bool> {
+		loop {
+			if self.read_line()? == 0 {
+				self.finish()?;
+				return Ok(false)
+			}
+			if !self.buf.is_empty() { break }
+		}
+		if self.buf[0] != b'@' { return Err(self.err("Expected FASTQ header line")) }
+		rec.id.clear();
+		rec.id.extend_from_slice(&self.buf[1..]);
+		if self.read_line()? == 0 { return Err(self.err("Unexpected end of file")) }
+		rec.seq.clear();
+		rec.seq.extend_from_slice(&self.buf);
+		if self.read_line()? == 0 || self.buf.first() != Some(&b'+') { return Err(self.err("Expected FASTQ separator line")) }
+		if self.read_line()? == 0 { return Err(self.err("Unexpected end of file")) }
+		rec.qual.clear();
+		rec.qual.extend_from_slice(&self.buf);
+		if rec.qual.len() != rec.seq.len() { return Err(self.err("Sequence and quality lengths differ (truncated file?)")) }
+		if let Some(c) = rec.seq.iter().find(|c| !matches!(c.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'N' | b'.')) {
+			return Err(self.err(format!("Invalid character '{}' in sequence", *c as char).as_str()))
+		}
+		if let Some(c) = rec.qual.iter().find(|c| **c < self.qual_offset || **c > b'~') {
+			return Err(self.err(format!("Invalid quality character '{}' for offset {}", *c as char, self.qual_offset).as_str()))
+		}
+		Ok(true)
+	}
+}
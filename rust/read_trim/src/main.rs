@@ -0,0 +1,22 @@
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate clap;
+
+mod cli;
+pub mod config;
+pub mod fastq;
+pub mod trim;
+pub mod qc;
+pub mod process;
+
+fn main() -> Result<(), String> {
+	let conf = cli::process_cli().map_err(|e| format!("read_trim initialization failed with error: {}", e))?;
+	match process::process(conf) {
+		Ok(_) => Ok(()),
+		Err(e) => {
+			error!("read_trim failed with error: {}", e);
+			Err("Failed".to_string())
+		}
+	}
+}
@@ -0,0 +1,127 @@
+use std::io::{self, Write, BufRead, BufReader, BufWriter};
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+
+use utils::compress;
+
+use crate::config::{Config, new_err};
+use crate::fastq::{FastqReader, FastqRecord};
+use crate::trim::{TrimStats, trim_read, detect_adapter};
+use crate::qc::QcStats;
+
+// Input can be a single file, a pair of files or an interleaved file (or stdin)
+struct Input {
+	rdr: FastqReader,
+	rdr2: Option<FastqReader>,
+	interleaved: bool,
+	count: u64,
+}
+
+impl Input {
+	fn new(conf: &Config) -> io::Result<Self> {
+		let offset = conf.param.qual_offset;
+		let open = |s: Option<&String>| -> io::Result<FastqReader> {
+			match s {
+				Some(name) => {
+					let (rdr, child) = compress::open_bufreader_child(Path::new(name)).map_err(|e| new_err(format!("Couldn't open {}: {}", name, e)))?;
+					Ok(FastqReader::new(rdr, child, name, offset))
+				},
+				None => {
+					let rdr: Box<dyn BufRead> = Box::new(BufReader::new(io::stdin()));
+					Ok(FastqReader::new(rdr, None, "<stdin>", offset))
+				},
+			}
+		};
+		let rdr = open(conf.input.first())?;
+		let rdr2 = if conf.input.len() > 1 { Some(open(conf.input.get(1))?) } else { None };
+		Ok(Input{rdr, rdr2, interleaved: conf.interleaved, count: 0})
+	}
+
+	// Read next record or pair of records.  Returns false at EOF
+	fn next(&mut self, recs: &mut [FastqRecord]) -> io::Result<bool> {
+		let eof = !self.rdr.next_record(&mut recs[0])?;
+		if recs.len() == 1 { return Ok(!eof) }
+		let eof2 = if self.interleaved { !self.rdr.next_record(&mut recs[1])? }
+		else { !self.rdr2.as_mut().expect("No second input").next_record(&mut recs[1])? };
+		let name2 = if self.interleaved { self.rdr.name() } else { self.rdr2.as_ref().map(|r| r.name()).unwrap_or("") };
+		match (eof, eof2) {
+			(true, true) => Ok(false),
+			(false, false) => {
+				self.count += 1;
+				if recs[0].name() != recs[1].name() {
+					Err(new_err(format!("Read names do not match for pair {} ({} and {}) in {} and {}", self.count,
+						String::from_utf8_lossy(recs[0].name()), String::from_utf8_lossy(recs[1].name()), self.rdr.name(), name2)))
+				} else { Ok(true) }
+			},
+			(true, false) => Err(new_err(format!("Unequal number of reads in read 1 and read 2: {} has fewer reads than {}", self.rdr.name(), name2))),
+			(false, true) => Err(new_err(format!("Unequal number of reads in read 1 and read 2: {} has fewer reads than {}", name2, self.rdr.name()))),
+		}
+	}
+}
+
+fn write_json<T: Serialize>(s: &str, x: &T) -> io::Result<()> {
+	let file = fs::File::create(s).map_err(|e| new_err(format!("Couldn't open {}: {}", s, e)))?;
+	serde_json::to_writer_pretty(BufWriter::new(file), x).map_err(|e| new_err(format!("Error writing JSON file {}: {}", s, e)))
+}
+
+pub fn process(conf: Config) -> io::Result<()> {
+	let paired = conf.paired();
+	let n_ends = if paired { 2 } else { 1 };
+	let mut input = Input::new(&conf)?;
+	let mut param = conf.param.clone();
+	let mut stats = TrimStats::new(paired);
+	// QC metrics are always collected as they are also used to check the quality encoding
+	let mut qc = QcStats::new(paired, param.qual_offset);
+
+	// Buffer the start of the input for adapter detection if required
+	let mut buffer: Vec<Vec<FastqRecord>> = Vec::new();
+	if param.adapter[0].is_none() && !conf.no_trim {
+		loop {
+			let mut recs = vec!(FastqRecord::default(); n_ends);
+			if buffer.len() >= param.detect_reads || !input.next(&mut recs)? { break }
+			buffer.push(recs);
+		}
+		let reads: Vec<FastqRecord> = buffer.iter().map(|v| v[0].clone()).collect();
+		let (name, ad) = detect_adapter(&reads);
+		info!("Using {} adapter {}", name, String::from_utf8_lossy(&ad));
+		param.adapter[0] = Some(ad);
+	}
+	if param.adapter[1].is_none() { param.adapter[1] = param.adapter[0].clone() }
+	let adapters: Vec<Vec<u8>> = param.adapter.iter().take(n_ends).map(|x| x.clone().unwrap_or_default()).collect();
+	stats.adapters = adapters.iter().map(|x| String::from_utf8_lossy(x).to_string()).collect();
+
+	let mut wrt: Box<dyn Write> = match &conf.output {
+		Some(s) => compress::open_bufwriter(s)?,
+		None => Box::new(BufWriter::new(io::stdout())),
+	};
+	let mut handle = |recs: &mut [FastqRecord]| -> io::Result<()> {
+		stats.reads_in += 1;
+		qc.add(recs)?;
+		let mut keep = true;
+		if conf.no_trim {
+			for (end, rec) in recs.iter().enumerate() { stats.reads[end].bases_in += rec.seq.len() as u64 }
+		} else {
+			for (end, rec) in recs.iter_mut().enumerate() {
+				if trim_read(rec, &adapters[end], end, &param, &mut stats.reads[end]) < param.min_length { keep = false }
+			}
+		}
+		if keep {
+			stats.reads_out += 1;
+			for (end, rec) in recs.iter().enumerate() {
+				stats.reads[end].bases_out += rec.seq.len() as u64;
+				rec.write(&mut wrt)?;
+			}
+		} else { stats.too_short += 1 }
+		Ok(())
+	};
+	for mut recs in buffer.drain(..) { handle(&mut recs)? }
+	let mut recs = vec!(FastqRecord::default(); n_ends);
+	while input.next(&mut recs)? { handle(&mut recs)? }
+	wrt.flush()?;
+	info!("Processed {} reads: {} written, {} too short", stats.reads_in, stats.reads_out, stats.too_short);
+	if let Some(s) = &conf.stats { write_json(s, &stats)? }
+	qc.finish();
+	if let Some(s) = &conf.qc { write_json(s, &qc)? }
+	Ok(())
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::io;
+use serde::Serialize;
+
+use crate::config::new_err;
+use crate::fastq::FastqRecord;
+
+// Number of reads used to look for overrepresented sequences
+const SAMPLE_READS: u64 = 200000;
+// Length of prefix used for overrepresented sequences
+const OVER_LEN: usize = 50;
+// Minimum fraction of sampled reads for a sequence to be reported as overrepresented
+const OVER_FRAC: f64 = 0.001;
+const MAX_OVER: usize = 20;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct OverSeq {
+	pub sequence: String,
+	pub count: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct EndQc {
+	pub bases: u64,
+	pub min_length: usize,
+	pub max_length: usize,
+	// Per position counts of A, C, G, T, N
+	pub base_counts: Vec<[u64; 5]>,
+	pub quality_sum: Vec<u64>,
+	pub quality_hist: Vec<u64>,
+	pub sampled: u64,
+	pub overrepresented: Vec<OverSeq>,
+	#[serde(skip)]
+	seq_counts: HashMap<Vec<u8>, u64>,
+}
+
+impl EndQc {
+	fn add(&mut self, rec: &FastqRecord, offset: u8, sample: bool) {
+		let l = rec.seq.len();
+		if self.bases == 0 && self.min_length == 0 { self.min_length = l }
+		self.min_length = self.min_length.min(l);
+		self.max_length = self.max_length.max(l);
+		self.bases += l as u64;
+		if self.base_counts.len() < l {
+			self.base_counts.resize(l, [0; 5]);
+			self.quality_sum.resize(l, 0);
+		}
+		for (i, (c, q)) in rec.seq.iter().zip(rec.qual.iter()).enumerate() {
+			let ix = match c.to_ascii_uppercase() { b'A' => 0, b'C' => 1, b'G' => 2, b'T' => 3, _ => 4 };
+			self.base_counts[i][ix] += 1;
+			let q = (q - offset) as usize;
+			self.quality_sum[i] += q as u64;
+			if self.quality_hist.len() <= q { self.quality_hist.resize(q + 1, 0) }
+			self.quality_hist[q] += 1;
+		}
+		if sample {
+			self.sampled += 1;
+			*self.seq_counts.entry(rec.seq[..l.min(OVER_LEN)].to_vec()).or_insert(0) += 1;
+		}
+	}
+
+	fn finish(&mut self) {
+		let thresh = ((self.sampled as f64) * OVER_FRAC).max(1.0) as u64;
+		let mut v: Vec<OverSeq> = self.seq_counts.drain().filter(|(_, c)| *c > thresh)
+			.map(|(s, count)| OverSeq{sequence: String::from_utf8_lossy(&s).to_string(), count}).collect();
+		v.sort_unstable_by_key(|x| std::cmp::Reverse(x.count));
+		v.truncate(MAX_OVER);
+		self.overrepresented = v;
+	}
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct QcStats {
+	pub encoding: String,
+	pub paired: bool,
+	pub reads: u64,
+	pub ends: Vec<EndQc>,
+	// Set once a quality value below the Phred+64 range has been seen
+	#[serde(skip)]
+	low_qual: bool,
+	#[serde(skip)]
+	offset: u8,
+}
+
+impl QcStats {
+	pub fn new(paired: bool, offset: u8) -> Self {
+		let ends = (0..if paired { 2 } else { 1 }).map(|_| EndQc::default()).collect();
+		QcStats{encoding: format!("Phred+{}", offset), paired, ends, offset, ..Default::default()}
+	}
+
+	// Check that the quality values are consistent with the quality offset.  Phred+64 data read with an
+	// offset of 33 will not fail the basic validation of the quality characters, but will have no values
+	// below 59 (';') and many values above 75 ('K'), so we fail on the first value above 75 unless a value
+	// below 59 has already been seen
+	fn check_encoding(&mut self, qual: &[u8]) -> io::Result<()> {
+		if self.offset != 33 || self.low_qual { return Ok(()) }
+		for q in qual.iter() {
+			if *q < 59 {
+				self.low_qual = true;
+				break
+			} else if *q > 75 {
+				return Err(new_err(format!("Quality value '{}' in read {} suggests Phred+64 encoding (set quality offset to 64)", *q as char, self.reads)))
+			}
+		}
+		Ok(())
+	}
+
+	pub fn add(&mut self, recs: &[FastqRecord]) -> io::Result<()> {
+		let sample = self.reads < SAMPLE_READS;
+		self.reads += 1;
+		for (end, rec) in recs.iter().enumerate() {
+			self.check_encoding(&rec.qual)?;
+			self.ends[end].add(rec, self.offset, sample);
+		}
+		Ok(())
+	}
+
+	pub fn finish(&mut self) {
+		for e in self.ends.iter_mut() { e.finish() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mk_rec(qual: &[u8]) -> FastqRecord {
+		FastqRecord{id: b"read".to_vec(), seq: vec![b'A'; qual.len()], qual: qual.to_vec()}
+	}
+
+	#[test]
+	fn phred64_fails_on_first_read() {
+		let mut qc = QcStats::new(false, 33);
+		assert!(qc.add(&[mk_rec(b"hhhhgggfff")]).is_err());
+	}
+
+	#[test]
+	fn phred33_high_values_after_low_values() {
+		let mut qc = QcStats::new(false, 33);
+		assert!(qc.add(&[mk_rec(b"IIII#")]).is_ok());
+		assert!(qc.add(&[mk_rec(b"hhhh")]).is_ok());
+		let mut qc = QcStats::new(false, 64);
+		assert!(qc.add(&[mk_rec(b"hhhhgggfff")]).is_ok());
+	}
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+
+use crate::config::{TrimParam, KNOWN_ADAPTERS};
+use crate::fastq::FastqRecord;
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReadStats {
+	pub bases_in: u64,
+	pub bases_out: u64,
+	pub adapter_reads: u64,
+	pub adapter_bases: u64,
+	pub quality_bases: u64,
+	pub poly_g_reads: u64,
+	pub poly_g_bases: u64,
+	pub clipped_bases: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct TrimStats {
+	pub paired: bool,
+	pub adapters: Vec<String>,
+	pub reads_in: u64,
+	pub reads_out: u64,
+	pub too_short: u64,
+	pub reads: Vec<ReadStats>,
+}
+
+impl TrimStats {
+	pub fn new(paired: bool) -> Self {
+		TrimStats{paired, reads: vec!(ReadStats::default(); if paired { 2 } else { 1 }), ..Default::default()}
+	}
+}
+
+// Position of a trailing poly-G run of at least min_len bases, allowing 1 mismatch per 8 bases
+fn poly_g_pos(seq: &[u8], min_len: usize) -> Option<usize> {
+	let mut mm = 0;
+	let mut pos = seq.len();
+	for (k, i) in (0..seq.len()).rev().enumerate() {
+		if seq[i] == b'G' { pos = i }
+		else {
+			mm += 1;
+			if mm > (k + 1) / 8 { break }
+		}
+	}
+	if seq.len() - pos >= min_len { Some(pos) } else { None }
+}
+
+// BWA style quality trimming from the 3' end
+fn quality_pos(qual: &[u8], cutoff: u8, offset: u8) -> usize {
+	let mut s = 0;
+	let mut max = 0;
+	let mut pos = qual.len();
+	for i in (0..qual.len()).rev() {
+		s += cutoff as isize - (qual[i].saturating_sub(offset)) as isize;
+		if s < 0 { break }
+		if s > max {
+			max = s;
+			pos = i;
+		}
+	}
+	pos
+}
+
+// Find leftmost match of the adapter (or a prefix of the adapter at the 3' end of the read).
+// N's in either sequence match anything
+fn adapter_pos(seq: &[u8], adapter: &[u8], min_overlap: usize, error_rate: f64) -> Option<usize> {
+	for i in 0..seq.len() {
+		let overlap = (seq.len() - i).min(adapter.len());
+		if overlap < min_overlap { break }
+		let max_err = (overlap as f64 * error_rate) as usize;
+		let mut err = 0;
+		for (a, b) in seq[i..i + overlap].iter().zip(adapter.iter()) {
+			if a != b && *a != b'N' && *b != b'N' {
+				err += 1;
+				if err > max_err { break }
+			}
+		}
+		if err <= max_err { return Some(i) }
+	}
+	None
+}
+
+// Pick the most frequently found of the known adapters
+pub fn detect_adapter(reads: &[FastqRecord]) -> (&'static str, Vec<u8>) {
+	let mut counts = [0usize; KNOWN_ADAPTERS.len()];
+	for rec in reads.iter() {
+		for (ix, (_, ad)) in KNOWN_ADAPTERS.iter().enumerate() {
+			if rec.seq.windows(ad.len()).any(|w| w == ad.as_bytes()) { counts[ix] += 1 }
+		}
+	}
+	for (ix, (name, _)) in KNOWN_ADAPTERS.iter().enumerate() { debug!("Adapter {}: found in {} of {} reads", name, counts[ix], reads.len()) }
+	let best = counts.iter().enumerate().fold(0, |b, (ix, c)| if *c > counts[b] { ix } else { b });
+	let (name, ad) = KNOWN_ADAPTERS[best];
+	(name, ad.as_bytes().to_vec())
+}
+
+// Trim a read, returning the number of bases remaining
+pub fn trim_read(rec: &mut FastqRecord, adapter: &[u8], end: usize, param: &TrimParam, stats: &mut ReadStats) -> usize {
+	let l = rec.seq.len();
+	stats.bases_in += l as u64;
+	let mut len = l;
+	if let Some(min_len) = param.poly_g {
+		if let Some(x) = poly_g_pos(&rec.seq, min_len) {
+			stats.poly_g_reads += 1;
+			stats.poly_g_bases += (len - x) as u64;
+			len = x;
+		}
+	}
+	if param.quality > 0 {
+		let x = quality_pos(&rec.qual[..len], param.quality, param.qual_offset);
+		stats.quality_bases += (len - x) as u64;
+		len = x;
+	}
+	if let Some(x) = adapter_pos(&rec.seq[..len], adapter, param.min_overlap, param.error_rate) {
+		stats.adapter_reads += 1;
+		stats.adapter_bases += (len - x) as u64;
+		len = x;
+	}
+	let (clip5, clip3) = (param.clip[end << 1], param.clip[(end << 1) + 1]);
+	let end_pos = len.saturating_sub(clip3);
+	let start = clip5.min(end_pos);
+	stats.clipped_bases += (len - end_pos + start) as u64;
+	rec.trim(start, end_pos);
+	end_pos - start
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ADAPTER: &[u8] = b"AGATCGGAAGAGC";
+
+	fn mk_rec(seq: &[u8], qual: &[u8]) -> FastqRecord {
+		FastqRecord{id: b"read".to_vec(), seq: seq.to_vec(), qual: qual.to_vec()}
+	}
+
+	#[test]
+	fn adapter_full_match() {
+		assert_eq!(adapter_pos(b"ACGTACGTACAGATCGGAAGAGCTTTT", ADAPTER, 3, 0.1), Some(10));
+		// One mismatch is allowed for a 13 base overlap with an error rate of 0.1
+		assert_eq!(adapter_pos(b"ACGTACGTACAGATCGTAAGAGCTTTT", ADAPTER, 3, 0.1), Some(10));
+		// N matches anything
+		assert_eq!(adapter_pos(b"ACGTAGNTCGGAAGAGC", ADAPTER, 3, 0.0), Some(4));
+	}
+
+	#[test]
+	fn adapter_partial_match() {
+		assert_eq!(adapter_pos(b"ACGTACGTACAGATC", ADAPTER, 3, 0.1), Some(10));
+		// Overlap shorter than min_overlap
+		assert_eq!(adapter_pos(b"ACGTACGTACAG", ADAPTER, 3, 0.1), None);
+		assert_eq!(adapter_pos(b"ACGTACGTACGTACGT", ADAPTER, 3, 0.1), None);
+	}
+
+	#[test]
+	fn adapter_short_and_empty_reads() {
+		assert_eq!(adapter_pos(b"", ADAPTER, 1, 0.1), None);
+		assert_eq!(adapter_pos(b"AGATCGG", ADAPTER, 3, 0.1), Some(0));
+		assert_eq!(adapter_pos(b"TTT", ADAPTER, 3, 0.1), None);
+		assert_eq!(adapter_pos(b"AG", ADAPTER, 3, 0.1), None);
+	}
+
+	#[test]
+	fn quality_trimming() {
+		assert_eq!(quality_pos(b"IIIIIIII", 20, 33), 8);
+		assert_eq!(quality_pos(b"IIIII###", 20, 33), 5);
+		// All low quality
+		assert_eq!(quality_pos(b"########", 20, 33), 0);
+		assert_eq!(quality_pos(b"", 20, 33), 0);
+	}
+
+	#[test]
+	fn poly_g_trimming() {
+		assert_eq!(poly_g_pos(b"ACATCAGGGGGGGGGG", 8), Some(6));
+		// Single mismatch within run
+		assert_eq!(poly_g_pos(b"ACATCAGGGGAGGGGGGGGGGG", 8), Some(6));
+		// Run too short
+		assert_eq!(poly_g_pos(b"ACATCAGGGG", 8), None);
+		assert_eq!(poly_g_pos(b"GGGGGGGGGG", 8), Some(0));
+		assert_eq!(poly_g_pos(b"", 8), None);
+	}
+
+	#[test]
+	fn trim_read_stats() {
+		let param = TrimParam{min_overlap: 3, ..Default::default()};
+		let mut stats = ReadStats::default();
+		let mut rec = mk_rec(b"ACGTACGTACAGATCGGAAGAGC", &[b'I'; 23]);
+		assert_eq!(trim_read(&mut rec, ADAPTER, 0, &param, &mut stats), 10);
+		assert_eq!(rec.seq, b"ACGTACGTAC");
+		assert_eq!(rec.qual.len(), 10);
+		assert_eq!((stats.adapter_reads, stats.adapter_bases, stats.quality_bases), (1, 13, 0));
+
+		let mut rec = mk_rec(b"ACGTACGT", b"########");
+		assert_eq!(trim_read(&mut rec, ADAPTER, 0, &param, &mut stats), 0);
+		assert!(rec.seq.is_empty());
+		assert_eq!(stats.quality_bases, 8);
+
+		let mut rec = mk_rec(b"", b"");
+		assert_eq!(trim_read(&mut rec, ADAPTER, 0, &param, &mut stats), 0);
+		assert_eq!(stats.bases_in, 31);
+	}
+
+	#[test]
+	fn trim_read_poly_g_and_clip() {
+		let param = TrimParam{poly_g: Some(8), clip: [2, 1, 0, 0], min_overlap: 3, ..Default::default()};
+		let mut stats = ReadStats::default();
+		let mut rec = mk_rec(b"ACATCAGGGGGGGGGG", &[b'I'; 16]);
+		assert_eq!(trim_read(&mut rec, ADAPTER, 0, &param, &mut stats), 3);
+		assert_eq!(rec.seq, b"ATC");
+		assert_eq!((stats.poly_g_reads, stats.poly_g_bases, stats.clipped_bases), (1, 10, 3));
+	}
+}
@@ -1,13 +1,12 @@
-use std::io;
-use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::collections::{HashSet, HashMap};
 use clap::{ArgMatches, ErrorKind};
 
 use utils::compress;
 use r_htslib::*;
 
 // use super::cli_utils::*;
-use crate::config::{Config, OutputOpt, new_err};
-use crate::dbsnp;
+use crate::config::{Config, OutputOpt, OutputFormat, new_err};
 
 fn read_select_file(s: &str) -> io::Result<HashSet<String>> {
 	let mut sel_set = HashSet::new();
@@ -27,13 +26,41 @@ fn read_select_file(s: &str) -> io::Result<HashSet<String>> {
 	Ok(sel_set)	
 }
 
+// Two columns: input sample name and expected truth sample name
+fn read_sample_map(s: &str) -> io::Result<HashMap<String, String>> {
+	let mut map = HashMap::new();
+	let rdr = compress::open_bufreader(s)?;
+	for line in rdr.lines() {
+		let line = line?;
+		if line.starts_with('#') { continue }
+		let v: Vec<&str> = line.split_ascii_whitespace().collect();
+		match v.len() {
+			0 => (),
+			2 => { map.insert(v[0].to_owned(), v[1].to_owned()); },
+			_ => return Err(new_err(format!("Unexpected line in sample map file {}: {}", s, line))),
+		}
+	}
+	Ok(map)
+}
+
 pub fn handle_options(m: &ArgMatches) -> io::Result<Config> {
 	
 	let mut output_opt = OutputOpt::new();
+	let format = match m.value_of("format").map(|s| s.to_lowercase()).as_deref() {
+		None | Some("text") => OutputFormat::Text,
+		Some("vcf") => OutputFormat::Vcf,
+		Some("plink") => OutputFormat::Plink,
+		Some("illumina") => OutputFormat::Illumina,
+		Some(s) => return Err(new_err(format!("Unknown output format {}", s))),
+	};
+	let truth = m.is_present("truth");
+	if truth && m.is_present("format") { return Err(new_err("Output format can not be set when comparing with truth genotypes".to_string())) }
+	if truth && m.is_present("tabix") { warn!("Tabix index not available for concordance report") }
+	if format == OutputFormat::Plink && m.value_of("output").is_none() { return Err(new_err("PLINK output requires an output file prefix (--output)".to_string())) }
 	match m.value_of("output") {
 		Some(s) => output_opt.set_filename(s),
 		None => &mut output_opt,
-	}.set_compress(m.is_present("compress")).set_compute_md5(m.is_present("md5")).set_compute_tbx(m.is_present("tabix")).fix_opts();
+	}.set_format(format).set_compress(m.is_present("compress")).set_compute_md5(m.is_present("md5")).set_compute_tbx(m.is_present("tabix") && !truth).fix_opts();
 	let mut sr = BcfSrs::new()?;
 	let infile = m.value_of("input").expect("No input filename"); // This should not be allowed by Clap	
 	let regions = {			
@@ -59,10 +86,18 @@ pub fn handle_options(m: &ArgMatches) -> io::Result<Config> {
 	let mut conf = Config::new(output_opt, sr);
 	if let Some(n) = nt { conf.set_threads(n); } 
 	if let Some(s) = m.value_of("dbsnp") { 
-		let dbsnp_index = dbsnp::DBSnpIndex::new(s)?;
-		conf.set_dbsnp_file(dbsnp::DBSnpFile::open(dbsnp_index)?); 
+		conf.set_dbsnp_index(dbsnp::DBSnpIndex::new(s)?); 
 	}
 	if let Some(s) = m.value_of("selected") { conf.set_selected_hash(read_select_file(s)?); }
-
+	match value_t!(m, "min_gq", i32) {
+		Ok(x) => { conf.set_min_gq(x); },
+		Err(e) if e.kind == ErrorKind::ArgumentNotFound => (),
+		Err(e) => return Err(new_err(format!("Error parsing option: {}", e))),
+	}
+	if let Some(s) = m.value_of("truth") {
+		conf.set_truth(s);
+		if let Some(f) = m.value_of("sample_map") { conf.set_sample_map(read_sample_map(f)?); }
+		if let Some(f) = m.value_of("mismatches") { conf.set_mismatch_file(f); }
+	}
 	Ok(conf)
 }
@@ -0,0 +1,250 @@
+use std::io::{self, Write};
+use std::fmt;
+
+use r_htslib::{HtsFile, VcfHeader};
+use utils::compress::open_bufwriter;
+use crate::config::Config;
+use crate::output::{Site, SnpWriter};
+
+mod truth;
+use truth::TruthSet;
+
+// Minimum number of sites compared for an identity check, and minimum concordance with the expected
+// truth sample for the check to pass
+const MIN_IDENTITY_SITES: u32 = 20;
+const IDENTITY_THRESHOLD: f64 = 0.9;
+
+const GT_CLASSES: [&str; 4] = ["HomRef", "Het", "HomAlt", "Missing"];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Verdict { Pass, Fail, Inconclusive, NotInTruth }
+
+impl fmt::Display for Verdict {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Verdict::Pass => "PASS",
+			Verdict::Fail => "FAIL",
+			Verdict::Inconclusive => "INCONCLUSIVE",
+			Verdict::NotInTruth => "NOT_IN_TRUTH",
+		};
+		write!(f, "{}", s)
+	}
+}
+
+// Genotype class (index into GT_CLASSES)
+fn gt_class(gt: Option<[u8; 2]>, ref_base: u8) -> usize {
+	match gt {
+		Some(g) => g.iter().filter(|c| **c != ref_base).count(),
+		None => 3,
+	}
+}
+
+fn fmt_gt(gt: Option<[u8; 2]>) -> String {
+	match gt {
+		Some([a, b]) => format!("{}/{}", a as char, b as char),
+		None => "./.".to_string(),
+	}
+}
+
+fn fmt_ratio(a: u64, b: u64) -> String { if b > 0 { format!("{:.4}", (a as f64) / (b as f64)) } else { "-".to_string() } }
+
+#[derive(Default)]
+struct SampleStats {
+	// Rows are the truth genotype class and columns the called genotype class
+	confusion: [[u64; 4]; 4],
+	compared: u64,
+	concordant: u64,
+}
+
+impl SampleStats {
+	// Non-reference sensitivity: fraction of truth non-reference genotypes that are called as non-reference
+	fn nrs(&self) -> (u64, u64) {
+		let c = &self.confusion;
+		(c[1][1] + c[1][2] + c[2][1] + c[2][2], c[1][..3].iter().chain(c[2][..3].iter()).sum())
+	}
+	// Non-reference discrepancy: discordant genotypes excluding sites that are homozygous reference in both
+	fn nrd(&self) -> (u64, u64) { (self.compared - self.concordant, self.compared - self.confusion[0][0]) }
+}
+
+#[derive(Default)]
+struct SiteCounts {
+	sites: u64,
+	by_id: u64,
+	by_pos: u64,
+	not_found: u64,
+	incompatible: u64,
+	flipped: u64,
+}
+
+/// Compare genotypes with truth genotypes from an external VCF/BCF or PLINK file.  Sites are matched
+/// by ID or, failing that, by position.  Genotypes are compared as pairs of bases, and the truth genotypes
+/// are complemented if the truth alleles are on the opposite strand.  Each input sample is compared with
+/// the truth sample of the same name (or that given in the sample map), and with all truth samples for the
+/// identity check.  A report is written to the output file on completion
+pub struct ConcordanceWriter {
+	out: HtsFile,
+	truth: TruthSet,
+	samples: Vec<String>,
+	// Expected truth sample for each input sample
+	expected: Vec<Option<usize>>,
+	stats: Vec<SampleStats>,
+	// Sites compared and concordant for all pairs of input and truth samples
+	identity: Vec<(u32, u32)>,
+	mismatches: Option<Box<dyn Write>>,
+	counts: SiteCounts,
+}
+
+impl ConcordanceWriter {
+	pub fn new(out: HtsFile, conf: &Config, hdr: &VcfHeader) -> io::Result<Self> {
+		let truth = TruthSet::read(conf.truth().expect("No truth file"))?;
+		let ns = hdr.nsamples();
+		let samples = (0..ns).map(|i| hdr.sample_name(i).map(|s| s.to_owned())).collect::<io::Result<Vec<_>>>()?;
+		let expected: Vec<Option<usize>> = samples.iter().map(|s| {
+			let name = conf.sample_map().get(s).map(|x| x.as_str()).unwrap_or(s);
+			let ix = truth.sample_index(name);
+			if ix.is_none() { warn!("Expected truth sample {} for sample {} not found in {}", name, s, truth.name()) }
+			ix
+		}).collect();
+		let mismatches = match conf.mismatch_file() {
+			Some(f) => {
+				let mut w = open_bufwriter(f)?;
+				writeln!(w, "Sample\tTruth_sample\tContig\tPosition\tID\tREF\tCall\tTruth\tGQ")?;
+				Some(w)
+			},
+			None => None,
+		};
+		let stats = (0..ns).map(|_| SampleStats::default()).collect();
+		let identity = vec!((0, 0); ns * truth.samples().len());
+		Ok(Self{out, truth, samples, expected, stats, identity, mismatches, counts: SiteCounts::default()})
+	}
+
+	// Best matching truth sample (highest concordance) with at least MIN_IDENTITY_SITES compared
+	fn best_match(&self, ix: usize) -> Option<(usize, f64)> {
+		let nt = self.truth.samples().len();
+		self.identity[ix * nt..(ix + 1) * nt].iter().enumerate().filter(|(_, (n, _))| *n >= MIN_IDENTITY_SITES)
+			.map(|(t, (n, k))| (t, (*k as f64) / (*n as f64))).fold(None, |b, (t, z)| match b {
+				Some((_, bz)) if bz >= z => b,
+				_ => Some((t, z)),
+			})
+	}
+
+	fn verdict(&self, ix: usize) -> Verdict {
+		match self.expected[ix] {
+			None => Verdict::NotInTruth,
+			Some(t) => {
+				let (n, k) = self.identity[ix * self.truth.samples().len() + t];
+				let z = (k as f64) / (n as f64);
+				if n < MIN_IDENTITY_SITES { Verdict::Inconclusive }
+				else if z >= IDENTITY_THRESHOLD && self.best_match(ix).map(|(_, bz)| bz <= z).unwrap_or(true) { Verdict::Pass }
+				else { Verdict::Fail }
+			},
+		}
+	}
+
+	fn write_report(&mut self) -> io::Result<()> {
+		let nt = self.truth.samples().len();
+		let mut buffer = Vec::with_capacity(4096);
+		let c = &self.counts;
+		writeln!(buffer, "# snpxtr_v{} genotype concordance report", env!("CARGO_PKG_VERSION"))?;
+		writeln!(buffer, "# Truth: {} ({} samples, {} sites)", self.truth.name(), nt, self.truth.n_sites())?;
+		writeln!(buffer, "# Sites: {} input, {} matched by ID, {} matched by position, {} not in truth, {} with incompatible alleles, {} strand flipped",
+			c.sites, c.by_id, c.by_pos, c.not_found, c.incompatible, c.flipped)?;
+		writeln!(buffer, "#\n# Sample summary\nSample\tTruth_sample\tCompared\tConcordant\tConcordance\tNRS\tNRD\tVerdict\tBest_match\tBest_concordance")?;
+		for (ix, s) in self.samples.iter().enumerate() {
+			let st = &self.stats[ix];
+			let tname = self.expected[ix].map(|t| self.truth.samples()[t].as_str()).unwrap_or("-");
+			let (a, b) = st.nrs();
+			let (x, y) = st.nrd();
+			let verdict = self.verdict(ix);
+			let (best, bz) = match self.best_match(ix) {
+				Some((t, z)) => (self.truth.samples()[t].as_str(), format!("{:.4}", z)),
+				None => ("-", "-".to_string()),
+			};
+			writeln!(buffer, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", s, tname, st.compared, st.concordant, fmt_ratio(st.concordant, st.compared),
+				fmt_ratio(a, b), fmt_ratio(x, y), verdict, best, bz)?;
+			match verdict {
+				Verdict::Pass => info!("Identity check for sample {}: {}", s, verdict),
+				_ => warn!("Identity check for sample {}: {} (best match {} with concordance {})", s, verdict, best, bz),
+			}
+		}
+		writeln!(buffer, "#\n# Genotype confusion matrices (rows - truth genotypes, columns - called genotypes)\nSample\tTruth\t{}", GT_CLASSES.join("\t"))?;
+		for (ix, s) in self.samples.iter().enumerate() {
+			if self.expected[ix].is_none() { continue }
+			for (cl, row) in GT_CLASSES.iter().zip(self.stats[ix].confusion.iter()) {
+				writeln!(buffer, "{}\t{}\t{}\t{}\t{}\t{}", s, cl, row[0], row[1], row[2], row[3])?;
+			}
+		}
+		write!(buffer, "#\n# Identity matrix (concordance/sites compared)\nSample")?;
+		for t in self.truth.samples() { write!(buffer, "\t{}", t)? }
+		buffer.push(b'\n');
+		for (ix, s) in self.samples.iter().enumerate() {
+			write!(buffer, "{}", s)?;
+			for (n, k) in self.identity[ix * nt..(ix + 1) * nt].iter() { write!(buffer, "\t{}/{}", fmt_ratio(*k as u64, *n as u64), n)? }
+			buffer.push(b'\n');
+		}
+		self.out.write_all(&buffer)
+	}
+}
+
+impl SnpWriter for ConcordanceWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()> {
+		self.counts.sites += 1;
+		let (tix, by_id) = match self.truth.find(site.ctg, site.pos, site.id) {
+			Some(x) => x,
+			None => {
+				self.counts.not_found += 1;
+				return Ok(())
+			},
+		};
+		if by_id { self.counts.by_id += 1 } else { self.counts.by_pos += 1 }
+		let ref_base = site.alleles[0];
+		let flip = match self.truth.strand(tix, ref_base) {
+			Some(f) => f,
+			None => {
+				self.counts.incompatible += 1;
+				return Ok(())
+			},
+		};
+		if flip { self.counts.flipped += 1 }
+		let nt = self.truth.samples().len();
+		for (ix, gt) in site.sample_gts().enumerate() {
+			let call = {
+				let a = site.allele(gt[0]);
+				let b = if gt.len() > 1 && gt[1] != i32::MIN + 1 { site.allele(gt[1]) } else { a };
+				match (a, b) {
+					(Some(a), Some(b)) => Some([a.min(b), a.max(b)]),
+					_ => None,
+				}
+			};
+			if let Some(g) = call {
+				for (t, x) in self.identity[ix * nt..(ix + 1) * nt].iter_mut().enumerate() {
+					if let Some(tg) = self.truth.genotype(tix, t, flip) {
+						x.0 += 1;
+						if tg == g { x.1 += 1 }
+					}
+				}
+			}
+			if let Some(t) = self.expected[ix] {
+				let tgt = self.truth.genotype(tix, t, flip);
+				let st = &mut self.stats[ix];
+				st.confusion[gt_class(tgt, ref_base)][gt_class(call, ref_base)] += 1;
+				if let (Some(g), Some(tg)) = (call, tgt) {
+					st.compared += 1;
+					if g == tg { st.concordant += 1 }
+					else if let Some(w) = self.mismatches.as_mut() {
+						let gq = site.calls[ix].gq.map(|q| q.to_string()).unwrap_or_else(|| ".".to_string());
+						writeln!(w, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", self.samples[ix], self.truth.samples()[t], site.ctg, site.pos + 1, site.id,
+							ref_base as char, fmt_gt(call), fmt_gt(tgt), gq)?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+	fn finish(mut self: Box<Self>) -> io::Result<()> {
+		self.write_report()?;
+		if let Some(w) = self.mismatches.as_mut() { w.flush()? }
+		info!("Compared {} sites with {}", self.counts.by_id + self.counts.by_pos - self.counts.incompatible, self.truth.name());
+		Ok(())
+	}
+}
@@ -0,0 +1,185 @@
+use std::io::{self, Read, BufRead};
+use std::collections::HashMap;
+use std::path::Path;
+use std::fs::File;
+
+use r_htslib::*;
+use utils::compress::open_bufreader;
+use crate::config::new_err;
+
+// Contig names are compared without any chr prefix, and the PLINK numeric codes for the sex and mitochondrial
+// chromosomes are converted to names (the pseudo-autosomal XY code is treated as X)
+fn norm_ctg(s: &str) -> &str {
+	let s = s.strip_prefix("chr").unwrap_or(s);
+	match s {
+		"23" | "25" => "X",
+		"24" => "Y",
+		"26" | "M" => "MT",
+		_ => s,
+	}
+}
+
+fn complement(c: u8) -> u8 {
+	match c {
+		b'A' => b'T',
+		b'C' => b'G',
+		b'G' => b'C',
+		b'T' => b'A',
+		_ => c,
+	}
+}
+
+/// Truth genotypes read from a VCF/BCF file or a PLINK binary fileset.  Only SNPs are kept.  Genotypes are
+/// stored as pairs of bases (haploid genotypes are treated as homozygous) with 0 for missing
+pub struct TruthSet {
+	name: String,
+	samples: Vec<String>,
+	alleles: Vec<Box<[u8]>>,
+	gts: Vec<u8>,
+	by_id: HashMap<String, usize>,
+	by_pos: HashMap<String, HashMap<usize, usize>>,
+}
+
+impl TruthSet {
+	fn new(name: &str, samples: Vec<String>) -> Self {
+		Self{name: name.to_owned(), samples, alleles: Vec::new(), gts: Vec::new(), by_id: HashMap::new(), by_pos: HashMap::new()}
+	}
+
+	/// Read from a PLINK fileset if name is a prefix (or the name of one of the component files) of a
+	/// .bed/.bim/.fam fileset, otherwise from a VCF/BCF file
+	pub fn read(name: &str) -> io::Result<Self> {
+		let prefix = name.strip_suffix(".bed").or_else(|| name.strip_suffix(".bim")).or_else(|| name.strip_suffix(".fam")).unwrap_or(name);
+		let ts = if ["bed", "bim", "fam"].iter().all(|s| Path::new(&format!("{}.{}", prefix, s)).exists()) { Self::read_plink(prefix)? }
+		else { Self::read_vcf(name)? };
+		info!("Read in {} truth sites for {} samples from {}", ts.n_sites(), ts.samples.len(), name);
+		Ok(ts)
+	}
+
+	fn add_site(&mut self, ctg: &str, pos: usize, id: &str, alleles: Box<[u8]>, gts: &[u8]) {
+		let ix = self.alleles.len();
+		self.alleles.push(alleles);
+		self.gts.extend_from_slice(gts);
+		if !id.is_empty() && id != "." { self.by_id.entry(id.to_owned()).or_insert(ix); }
+		self.by_pos.entry(norm_ctg(ctg).to_owned()).or_default().entry(pos).or_insert(ix);
+	}
+
+	fn read_vcf(name: &str) -> io::Result<Self> {
+		let mut fp = HtsFile::new(name, "r")?;
+		let hdr = VcfHeader::read(&mut fp).map_err(|e| new_err(format!("Couldn't read header from {}: {}", name, e)))?;
+		let nt = hdr.nsamples();
+		if nt == 0 { return Err(new_err(format!("No samples in truth file {}", name))) }
+		let samples = (0..nt).map(|i| hdr.sample_name(i).map(|s| s.to_owned())).collect::<io::Result<Vec<_>>>()?;
+		let mut ts = Self::new(name, samples);
+		let mut brec = BcfRec::new()?;
+		let mut gtb = MallocDataBlock::<i32>::new();
+		let mut gts = vec!(0; nt * 2);
+		loop {
+			match brec.read(&mut fp, &hdr) {
+				BcfReadResult::Ok => (),
+				BcfReadResult::EOF => break,
+				BcfReadResult::Error => return Err(new_err(format!("Error reading from truth file {}", name))),
+			}
+			if brec.failed_filters() { continue }
+			let alleles: Box<[u8]> = {
+				let v = brec.alleles();
+				if v.iter().any(|a| a.len() != 1) { continue }
+				v.iter().map(|a| a.as_bytes()[0].to_ascii_uppercase()).collect()
+			};
+			if brec.get_genotypes(&hdr, &mut gtb).is_none() { continue }
+			let ploidy = gtb.len() / nt;
+			if ploidy == 0 { continue }
+			let get_base = |x: i32| if x >> 1 > 0 { alleles.get(((x >> 1) - 1) as usize).copied().unwrap_or(0) } else { 0 };
+			for (gt, g) in gtb.chunks(ploidy).zip(gts.chunks_mut(2)) {
+				let a = get_base(gt[0]);
+				let b = if ploidy > 1 && gt[1] != i32::MIN + 1 { get_base(gt[1]) } else { a };
+				if a == 0 || b == 0 { g[0] = 0; g[1] = 0 } else { g[0] = a.min(b); g[1] = a.max(b) }
+			}
+			let ctg = hdr.ctg_name(brec.rid())?.to_owned();
+			let pos = brec.pos();
+			let id = brec.id().to_owned();
+			ts.add_site(&ctg, pos, &id, alleles, &gts);
+		}
+		Ok(ts)
+	}
+
+	// Sample names are taken from the individual IDs in the .fam file
+	fn read_plink(prefix: &str) -> io::Result<Self> {
+		let fam = format!("{}.fam", prefix);
+		let mut samples = Vec::new();
+		for line in open_bufreader(&fam)?.lines() {
+			let line = line?;
+			if let Some(s) = line.split_ascii_whitespace().nth(1) { samples.push(s.to_owned()) }
+		}
+		if samples.is_empty() { return Err(new_err(format!("No samples in truth file {}", fam))) }
+		let nt = samples.len();
+		let mut ts = Self::new(prefix, samples);
+		let bed = format!("{}.bed", prefix);
+		let mut bed_rdr = io::BufReader::new(File::open(&bed).map_err(|e| new_err(format!("Couldn't open truth file {}: {}", bed, e)))?);
+		let mut buf = [0u8; 3];
+		bed_rdr.read_exact(&mut buf)?;
+		if buf != [0x6c, 0x1b, 0x01] { return Err(new_err(format!("Truth file {} is not a SNP major PLINK bed file", bed))) }
+		let mut buf = vec!(0u8; (nt + 3) >> 2);
+		let mut gts = vec!(0; nt * 2);
+		let bim = format!("{}.bim", prefix);
+		for line in open_bufreader(&bim)?.lines() {
+			let line = line?;
+			let v: Vec<&str> = line.split_ascii_whitespace().collect();
+			if v.len() < 6 { return Err(new_err(format!("Unexpected line in truth file {}: {}", bim, line))) }
+			bed_rdr.read_exact(&mut buf).map_err(|e| new_err(format!("Error reading from truth file {}: {}", bed, e)))?;
+			let pos = match v[3].parse::<usize>() {
+				Ok(x) if x > 0 => x - 1,
+				_ => continue,
+			};
+			// A1 and A2 alleles ('0' if not known).  Non SNP alleles are skipped
+			let base = |s: &str| if s.len() == 1 { Some(s.as_bytes()[0].to_ascii_uppercase()) } else { None };
+			let (a1, a2) = match (base(v[4]), base(v[5])) {
+				(Some(a), Some(b)) if [a, b].iter().all(|c| b"ACGT0".contains(c)) => (a, b),
+				_ => continue,
+			};
+			// 00 - homozygous A1, 01 - missing, 10 - heterozygous, 11 - homozygous A2
+			for (ix, g) in gts.chunks_mut(2).enumerate() {
+				let (a, b) = match (buf[ix >> 2] >> ((ix & 3) << 1)) & 3 {
+					0 => (a1, a1),
+					2 => (a1, a2),
+					3 => (a2, a2),
+					_ => (0, 0),
+				};
+				if a == b'0' || b == b'0' || a == 0 { g[0] = 0; g[1] = 0 } else { g[0] = a.min(b); g[1] = a.max(b) }
+			}
+			let alleles: Box<[u8]> = [a2, a1].iter().copied().filter(|c| *c != b'0').collect();
+			ts.add_site(v[0], pos, v[1], alleles, &gts);
+		}
+		Ok(ts)
+	}
+
+	pub fn name(&self) -> &str { &self.name }
+	pub fn samples(&self) -> &[String] { &self.samples }
+	pub fn n_sites(&self) -> usize { self.alleles.len() }
+	pub fn sample_index(&self, name: &str) -> Option<usize> { self.samples.iter().position(|s| s == name) }
+
+	/// Find truth site, first by ID and then by position.  Returns the site index and whether it was found by ID
+	pub fn find(&self, ctg: &str, pos: usize, id: &str) -> Option<(usize, bool)> {
+		if let Some(ix) = self.by_id.get(id) { Some((*ix, true)) }
+		else { self.by_pos.get(norm_ctg(ctg)).and_then(|h| h.get(&pos)).map(|ix| (*ix, false)) }
+	}
+
+	/// Check strand of truth site against the reference base.  Returns None if the truth alleles are not
+	/// compatible with the reference, otherwise whether the truth alleles should be complemented
+	pub fn strand(&self, ix: usize, ref_base: u8) -> Option<bool> {
+		let alleles = &self.alleles[ix];
+		if alleles.contains(&ref_base) { Some(false) }
+		else if alleles.iter().any(|c| complement(*c) == ref_base) { Some(true) }
+		else { None }
+	}
+
+	/// Genotype (sorted pair of bases) for truth sample t at site ix
+	pub fn genotype(&self, ix: usize, t: usize, flip: bool) -> Option<[u8; 2]> {
+		let k = (ix * self.samples.len() + t) * 2;
+		let (a, b) = (self.gts[k], self.gts[k + 1]);
+		if a == 0 { None }
+		else if flip {
+			let (a, b) = (complement(a), complement(b));
+			Some([a.min(b), a.max(b)])
+		} else { Some([a, b]) }
+	}
+}
@@ -1,17 +1,20 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap};
 use std::io::{self, Error, ErrorKind};
 
 use r_htslib::BcfSrs;
 
-use crate::dbsnp;
 
 pub fn new_err(s: String) -> io::Error {
 	Error::new(ErrorKind::Other, s)	
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum OutputFormat { #[default] Text, Vcf, Plink, Illumina }
+
 #[derive(Default)]
 pub struct OutputOpt {
 	filename: Option<String>,
+	format: OutputFormat,
 	compress: bool,
 	compute_md5: bool,
 	compute_tbx: bool,
@@ -24,6 +27,8 @@ impl OutputOpt {
 		self
 	}
 	pub fn filename(&self) -> Option<&str> { self.filename.as_deref() }
+	pub fn set_format(&mut self, format: OutputFormat) -> &mut Self { self.format = format; self }
+	pub fn format(&self) -> OutputFormat { self.format }
 	pub fn set_compress(&mut self, b: bool) -> &mut Self { self.compress = b; self }
 	pub fn set_compute_md5(&mut self, b: bool) -> &mut Self { self.compute_md5 = b; self }
 	pub fn set_compute_tbx(&mut self, b: bool) -> &mut Self { self.compute_tbx = b; self }
@@ -34,8 +39,27 @@ impl OutputOpt {
 	// If no filename is set, set compute_md5 and compute_tbx to false, and if compress
 	// is set, we check if stdout is a terminal and, if so, the compress option is set to false
 	//
+	// PLINK output is written to a set of files with the filename as prefix, so compression, md5 and tabix are
+	// not available, and tabix is not available for Illumina format output.
+	//
 	// fix_opts() should be (obviously) run last
 	pub fn fix_opts(&mut self) {
+		match self.format {
+			OutputFormat::Plink => {
+				if self.compress || self.compute_md5 || self.compute_tbx { warn!("Compression, md5 and tabix options are not available for PLINK output") }
+				self.compress = false;
+				self.compute_md5 = false;
+				self.compute_tbx = false;
+				if let Some(fname) = &mut self.filename {
+					if let Some(s) = fname.strip_suffix(".bed") { *fname = s.to_owned() }
+				}
+			},
+			OutputFormat::Illumina if self.compute_tbx => {
+				warn!("Tabix index not available for Illumina output");
+				self.compute_tbx = false;
+			},
+			_ => (),
+		}
 		if let Some(fname) = &mut self.filename {
 			if self.compute_tbx { self.compress = true }
 			if self.compress && !fname.ends_with(".gz") { fname.push_str(".gz") }
@@ -55,17 +79,33 @@ pub struct Config {
 	threads: usize,
 	output: OutputOpt,
 	selected_hash: Option<HashSet<String>>,
-	dbsnp_file: Option<dbsnp::DBSnpFile>,	
+	dbsnp_index: Option<dbsnp::DBSnpIndex>,	
+	min_gq: Option<i32>,
+	truth: Option<String>,
+	sample_map: HashMap<String, String>,
+	mismatch_file: Option<String>,
 }
 
 impl Config {
-	pub fn new(output_opt: OutputOpt, sr: BcfSrs) -> Self { Self {threads: 1, output: output_opt, synced_reader: Some(sr), selected_hash: None, dbsnp_file: None }}
+	pub fn new(output_opt: OutputOpt, sr: BcfSrs) -> Self { Self {threads: 1, output: output_opt, synced_reader: Some(sr), selected_hash: None, dbsnp_index: None, min_gq: None,
+		truth: None, sample_map: HashMap::new(), mismatch_file: None }}
 	pub fn set_threads(&mut self, threads: usize) -> &mut Self { self.threads = threads; self }
 	pub fn threads(&self) -> usize { self.threads }
 	pub fn output(&self) -> &OutputOpt { &self.output } 
-	pub fn set_dbsnp_file(&mut self, dbsnp_file: dbsnp::DBSnpFile) -> &mut Self { self.dbsnp_file = Some(dbsnp_file); self}
-	pub fn dbsnp_file(&mut self) -> Option<dbsnp::DBSnpFile> { self.dbsnp_file.take() }
+	pub fn set_dbsnp_index(&mut self, dbsnp_index: dbsnp::DBSnpIndex) -> &mut Self { self.dbsnp_index = Some(dbsnp_index); self}
+	pub fn dbsnp_index(&mut self) -> Option<dbsnp::DBSnpIndex> { self.dbsnp_index.take() }
 	pub fn set_selected_hash(&mut self, selected_hash: HashSet<String>) -> &mut Self { self.selected_hash = Some(selected_hash); self}
 	pub fn selected_hash(&mut self) -> Option<HashSet<String>> { self.selected_hash.take() }
+	// Genotypes with GQ below this are set to missing
+	pub fn set_min_gq(&mut self, min_gq: i32) -> &mut Self { self.min_gq = Some(min_gq); self }
+	pub fn min_gq(&self) -> Option<i32> { self.min_gq }
+	// Truth genotype file for concordance mode, with the expected truth sample for each input sample (if different)
+	// and an optional file for the list of discordant sites
+	pub fn set_truth<S: AsRef<str>>(&mut self, truth: S) -> &mut Self { self.truth = Some(truth.as_ref().to_owned()); self }
+	pub fn truth(&self) -> Option<&str> { self.truth.as_deref() }
+	pub fn set_sample_map(&mut self, sample_map: HashMap<String, String>) -> &mut Self { self.sample_map = sample_map; self }
+	pub fn sample_map(&self) -> &HashMap<String, String> { &self.sample_map }
+	pub fn set_mismatch_file<S: AsRef<str>>(&mut self, name: S) -> &mut Self { self.mismatch_file = Some(name.as_ref().to_owned()); self }
+	pub fn mismatch_file(&self) -> Option<&str> { self.mismatch_file.as_deref() }
 	pub fn synced_reader(&mut self) -> Option<BcfSrs> { self.synced_reader.take() }
 }
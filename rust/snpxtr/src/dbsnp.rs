@@ -1 +0,0 @@
-../../bs_call/src/dbsnp.rs
\ No newline at end of file
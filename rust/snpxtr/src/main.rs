@@ -5,10 +5,11 @@ extern crate clap;
 
 mod cli;
 pub mod config;
-pub mod dbsnp;
 pub mod process;
 pub mod md5;
 pub mod tabix;
+pub mod output;
+pub mod concordance;
 
 fn main()  -> Result<(), String> {
 	let conf = cli::process_cli().map_err(|e| format!("snpxtr_index initialization failed with error: {}", e))?;
@@ -0,0 +1,90 @@
+use std::io;
+use std::fmt;
+
+use r_htslib::{HtsFile, VcfHeader};
+use crate::config::*;
+use crate::concordance::ConcordanceWriter;
+
+mod text;
+mod vcf;
+mod plink;
+mod illumina;
+
+pub struct Baf {
+	a: i32,
+	b: i32,
+}
+
+impl Baf {
+	pub fn new(a: i32, b:i32) -> Self { Self{a, b} }
+	pub fn baf(&self) -> Option<f64> {
+		if self.a + self.b > 0 { Some((self.a as f64) / ((self.a + self.b) as f64)) } else { None }
+	}
+}
+
+impl fmt::Display for Baf {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}\t{}\t", self.a, self.b)?;
+		if let Some(z) = self.baf() {
+			write!(f, "{:.3}", z)
+		} else {
+			write!(f, "-")
+		}
+	}
+}
+
+/// Call for one sample.  For the BAF calculation the genotype is split into the A and B alleles
+/// (ab_alleles gives the allele indices with the reference as 0) where the B allele count is baf.a
+pub struct SampleCall {
+	pub baf: Baf,
+	pub ab_alleles: Option<(usize, usize)>,
+	pub gq: Option<i32>,
+}
+
+/// Site to be output
+pub struct Site<'a> {
+	pub ctg: &'a str,
+	// Position (0 offset)
+	pub pos: usize,
+	pub id: &'a str,
+	// Reference allele followed by the alternate alleles
+	pub alleles: &'a [u8],
+	pub qual: Option<f32>,
+	pub ploidy: usize,
+	// BCF encoded genotypes (ploidy entries per sample)
+	pub gts: &'a [i32],
+	pub calls: &'a [SampleCall],
+}
+
+impl <'a> Site<'a> {
+	// Allele index (reference = 0) from BCF encoded genotype
+	pub fn allele_idx(x: i32) -> Option<usize> {
+		let i = (x >> 1) - 1;
+		if i >= 0 { Some(i as usize) } else { None }
+	}
+	pub fn allele(&self, x: i32) -> Option<u8> { Site::allele_idx(x).and_then(|i| self.alleles.get(i).copied()) }
+	pub fn sample_gts(&self) -> std::slice::Chunks<'_, i32> { self.gts.chunks(self.ploidy) }
+}
+
+pub trait SnpWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()>;
+	fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+fn open_hts_output(conf: &Config) -> io::Result<HtsFile> {
+	let output_name = conf.output().filename().unwrap_or("-");
+	let output_mode = if conf.output().compress() { "wz" } else { "w" };
+	let mut out = HtsFile::new(output_name, output_mode)?;
+	if conf.threads() > 0 { out.set_threads(conf.threads())? }
+	Ok(out)
+}
+
+pub fn make_writer(conf: &Config, hdr: &VcfHeader) -> io::Result<Box<dyn SnpWriter>> {
+	if conf.truth().is_some() { return Ok(Box::new(ConcordanceWriter::new(open_hts_output(conf)?, conf, hdr)?)) }
+	Ok(match conf.output().format() {
+		OutputFormat::Text => Box::new(text::TextWriter::new(open_hts_output(conf)?)),
+		OutputFormat::Vcf => Box::new(vcf::VcfWriter::new(open_hts_output(conf)?, hdr)?),
+		OutputFormat::Illumina => Box::new(illumina::IlluminaWriter::new(open_hts_output(conf)?, hdr)?),
+		OutputFormat::Plink => Box::new(plink::PlinkWriter::new(conf.output().filename().expect("No output prefix"), hdr)?),
+	})
+}
@@ -0,0 +1,47 @@
+use std::io::{self, Write};
+
+use r_htslib::{HtsFile, VcfHeader};
+use super::{Site, SnpWriter};
+
+/// Illumina GenomeStudio style final report with one line per site per sample (sites in the outer loop).
+/// The GC Score is the probability that the genotype call is correct, calculated from GQ
+pub struct IlluminaWriter {
+	out: HtsFile,
+	samples: Vec<String>,
+	buffer: Vec<u8>,
+}
+
+impl IlluminaWriter {
+	pub fn new(mut out: HtsFile, hdr: &VcfHeader) -> io::Result<Self> {
+		let ns = hdr.nsamples();
+		let mut samples = Vec::with_capacity(ns);
+		for ix in 0..ns { samples.push(hdr.sample_name(ix)?.to_owned()) }
+		let mut buffer = Vec::with_capacity(1024);
+		writeln!(buffer, "[Header]\nContent\tsnpxtr_v{}\nNum Samples\t{}\nTotal Samples\t{}", env!("CARGO_PKG_VERSION"), ns, ns)?;
+		writeln!(buffer, "[Data]\nSNP Name\tSample ID\tChr\tPosition\tAllele1 - Forward\tAllele2 - Forward\tGC Score\tB Allele Freq")?;
+		out.write_all(&buffer)?;
+		Ok(Self{out, samples, buffer})
+	}
+}
+
+impl SnpWriter for IlluminaWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()> {
+		let buffer = &mut self.buffer;
+		buffer.clear();
+		for ((gt, call), name) in site.sample_gts().zip(site.calls.iter()).zip(self.samples.iter()) {
+			let get_all = |i: usize| gt.get(i).and_then(|x| site.allele(*x)).map(|c| c as char).unwrap_or('-');
+			let (a1, a2) = (get_all(0), if site.ploidy > 1 { get_all(1) } else { get_all(0) });
+			write!(buffer, "{}\t{}\t{}\t{}\t{}\t{}", site.id, name, site.ctg, site.pos + 1, a1, a2)?;
+			match call.gq {
+				Some(q) if a1 != '-' => write!(buffer, "\t{:.4}", 1.0 - 10f64.powf(-0.1 * (q as f64)))?,
+				_ => write!(buffer, "\tNaN")?,
+			}
+			match call.baf.baf() {
+				Some(z) => writeln!(buffer, "\t{:.4}", z)?,
+				None => writeln!(buffer, "\tNaN")?,
+			}
+		}
+		self.out.write_all(buffer)
+	}
+	fn finish(self: Box<Self>) -> io::Result<()> { Ok(()) }
+}
@@ -0,0 +1,74 @@
+use std::io::{self, Write, BufWriter};
+use std::fs::File;
+
+use r_htslib::VcfHeader;
+use super::{Site, SnpWriter};
+
+/// PLINK 1.9 binary output (.bed, .bim and .fam files).  Only bi-allelic (or monomorphic) sites with
+/// diploid genotypes are written.  The alternate allele is A1 and the reference allele is A2
+pub struct PlinkWriter {
+	bed: BufWriter<File>,
+	bim: BufWriter<File>,
+	prefix: String,
+	buffer: Vec<u8>,
+	n_sites: usize,
+	n_skipped: usize,
+}
+
+fn create_file(prefix: &str, suffix: &str) -> io::Result<BufWriter<File>> {
+	let name = format!("{}.{}", prefix, suffix);
+	match File::create(&name) {
+		Ok(f) => Ok(BufWriter::new(f)),
+		Err(e) => Err(io::Error::new(e.kind(), format!("Couldn't open output file {}: {}", name, e))),
+	}
+}
+
+impl PlinkWriter {
+	pub fn new(prefix: &str, hdr: &VcfHeader) -> io::Result<Self> {
+		let mut fam = create_file(prefix, "fam")?;
+		// Family and individual IDs are both set to the sample name.  Sex and phenotype are unknown
+		for ix in 0..hdr.nsamples() {
+			let name = hdr.sample_name(ix)?;
+			writeln!(fam, "{}\t{}\t0\t0\t0\t-9", name, name)?;
+		}
+		fam.flush()?;
+		let mut bed = create_file(prefix, "bed")?;
+		// Magic number and SNP major mode
+		bed.write_all(&[0x6c, 0x1b, 0x01])?;
+		let bim = create_file(prefix, "bim")?;
+		let buffer = vec!(0; (hdr.nsamples() + 3) >> 2);
+		Ok(Self{bed, bim, prefix: prefix.to_owned(), buffer, n_sites: 0, n_skipped: 0})
+	}
+}
+
+impl SnpWriter for PlinkWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()> {
+		if site.ploidy != 2 || site.alleles.len() > 2 || site.gts.iter().any(|x| Site::allele_idx(*x).map(|i| i > 1).unwrap_or(false)) {
+			self.n_skipped += 1;
+			return Ok(())
+		}
+		for x in self.buffer.iter_mut() { *x = 0 }
+		for (ix, gt) in site.sample_gts().enumerate() {
+			// 00 - homozygous A1, 01 - missing, 10 - heterozygous, 11 - homozygous A2
+			let code = match (Site::allele_idx(gt[0]), Site::allele_idx(gt[1])) {
+				(Some(1), Some(1)) => 0,
+				(Some(0), Some(0)) => 3,
+				(Some(_), Some(_)) => 2,
+				_ => 1,
+			};
+			self.buffer[ix >> 2] |= code << ((ix & 3) << 1);
+		}
+		self.bed.write_all(&self.buffer)?;
+		let a1 = site.alleles.get(1).map(|c| *c as char).unwrap_or('0');
+		writeln!(self.bim, "{}\t{}\t0\t{}\t{}\t{}", site.ctg, site.id, site.pos + 1, a1, site.alleles[0] as char)?;
+		self.n_sites += 1;
+		Ok(())
+	}
+	fn finish(mut self: Box<Self>) -> io::Result<()> {
+		self.bed.flush()?;
+		self.bim.flush()?;
+		if self.n_skipped > 0 { warn!("{} multi-allelic or non-diploid sites not written to PLINK output", self.n_skipped) }
+		info!("Wrote {} sites to {}.bed", self.n_sites, self.prefix);
+		Ok(())
+	}
+}
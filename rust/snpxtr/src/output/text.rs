@@ -0,0 +1,35 @@
+use std::io::{self, Write};
+
+use r_htslib::HtsFile;
+use super::{Site, SnpWriter};
+
+/// Original snpxtr format: contig, position and ID followed by the genotype and BAF information for each sample
+pub struct TextWriter {
+	out: HtsFile,
+	buffer: Vec<u8>,
+}
+
+impl TextWriter {
+	pub fn new(out: HtsFile) -> Self { Self{out, buffer: Vec::with_capacity(80)} }
+}
+
+impl SnpWriter for TextWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()> {
+		let get_gt = |x: i32| {
+			let i = x >> 1;
+			if i == 0 { b'0' }
+			else { site.allele(x).unwrap_or(b'.') }
+		};
+		let buffer = &mut self.buffer;
+		buffer.clear();
+		write!(buffer, "{}\t{}\t{}", site.ctg, site.pos + 1, site.id)?;
+		for (gt, call) in site.sample_gts().zip(site.calls.iter()) {
+			buffer.push(b'\t');
+			for i in gt { buffer.push(get_gt(*i)) }
+			write!(buffer, "\t{}", call.baf)?;
+		}
+		buffer.push(b'\n');
+		self.out.write_all(buffer)
+	}
+	fn finish(self: Box<Self>) -> io::Result<()> { Ok(()) }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, Write};
+
+use r_htslib::{HtsFile, VcfHeader};
+use super::{Site, SnpWriter};
+
+/// Multi-sample VCF with GT, GQ, AD and BAF FORMAT fields
+pub struct VcfWriter {
+	out: HtsFile,
+	buffer: Vec<u8>,
+}
+
+impl VcfWriter {
+	pub fn new(mut out: HtsFile, hdr: &VcfHeader) -> io::Result<Self> {
+		let mut buffer = Vec::with_capacity(1024);
+		writeln!(buffer, "##fileformat=VCFv4.2\n##source=snpxtr_v{}", env!("CARGO_PKG_VERSION"))?;
+		for rid in 0..hdr.nctgs() {
+			let (name, len) = hdr.ctg_name_len(rid)?;
+			writeln!(buffer, "##contig=<ID={},length={}>", name, len)?;
+		}
+		writeln!(buffer, "##FILTER=<ID=PASS,Description=\"All filters passed\">")?;
+		writeln!(buffer, "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">")?;
+		writeln!(buffer, "##FORMAT=<ID=GQ,Number=1,Type=Integer,Description=\"Phred scaled genotype quality\">")?;
+		writeln!(buffer, "##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allelic depths for the called alleles (bisulfite informative bases only)\">")?;
+		writeln!(buffer, "##FORMAT=<ID=BAF,Number=1,Type=Float,Description=\"B allele frequency\">")?;
+		write!(buffer, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT")?;
+		for ix in 0..hdr.nsamples() { write!(buffer, "\t{}", hdr.sample_name(ix)?)? }
+		buffer.push(b'\n');
+		out.write_all(&buffer)?;
+		Ok(Self{out, buffer})
+	}
+}
+
+impl SnpWriter for VcfWriter {
+	fn write_site(&mut self, site: &Site) -> io::Result<()> {
+		let buffer = &mut self.buffer;
+		buffer.clear();
+		write!(buffer, "{}\t{}\t{}\t{}\t", site.ctg, site.pos + 1, site.id, site.alleles[0] as char)?;
+		if site.alleles.len() > 1 {
+			for (i, a) in site.alleles[1..].iter().enumerate() {
+				if i > 0 { buffer.push(b',') }
+				buffer.push(*a);
+			}
+		} else { buffer.push(b'.') }
+		match site.qual {
+			Some(q) => write!(buffer, "\t{}", q)?,
+			None => write!(buffer, "\t.")?,
+		}
+		write!(buffer, "\tPASS\t.\tGT:GQ:AD:BAF")?;
+		let n_all = site.alleles.len();
+		for (gt, call) in site.sample_gts().zip(site.calls.iter()) {
+			buffer.push(b'\t');
+			for (i, x) in gt.iter().enumerate() {
+				// Vector end marker for samples with lower ploidy
+				if *x == i32::MIN + 1 { break }
+				if i > 0 { buffer.push(if (x & 1) != 0 { b'|' } else { b'/' }) }
+				match Site::allele_idx(*x) {
+					Some(k) => write!(buffer, "{}", k)?,
+					None => buffer.push(b'.'),
+				}
+			}
+			match call.gq {
+				Some(q) => write!(buffer, ":{}", q)?,
+				None => write!(buffer, ":.")?,
+			}
+			match call.ab_alleles {
+				Some((ia, ib)) if ia < n_all && ib < n_all => {
+					let mut ad = vec!(0; n_all);
+					ad[ia] += call.baf.b;
+					ad[ib] += call.baf.a;
+					for (i, d) in ad.iter().enumerate() { write!(buffer, "{}{}", if i == 0 { ':' } else { ',' }, d)? }
+				},
+				_ => write!(buffer, ":.")?,
+			}
+			match call.baf.baf() {
+				Some(z) => write!(buffer, ":{:.3}", z)?,
+				None => write!(buffer, ":.")?,
+			}
+		}
+		buffer.push(b'\n');
+		self.out.write_all(buffer)
+	}
+	fn finish(self: Box<Self>) -> io::Result<()> { Ok(()) }
+}
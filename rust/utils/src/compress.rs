@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{BufReader, BufRead, BufWriter, Error, ErrorKind, Result, stdin};
-use std::process::{Command, Stdio, ChildStdout, ChildStdin};
+use std::process::{Command, Stdio, Child, ChildStdout, ChildStdin};
 use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use super::find_exec_path;
@@ -141,6 +141,22 @@ pub fn open_bufreader<P: AsRef<Path>>(name: P) -> Result<Box<dyn BufRead>> {
 	}
 }
 
+// As open_bufreader, but also returns the decompression process (if any) so that the caller can check its exit status
+pub fn open_bufreader_child<P: AsRef<Path>>(name: P) -> Result<(Box<dyn BufRead>, Option<Child>)> {
+	let ctype = get_compress_type(name.as_ref())?;
+	let f = test_open_file(name.as_ref())?;
+	match ctype {
+		CompressType::UNCOMPRESSED => Ok((Box::new(BufReader::new(f)), None)),
+		_ => {
+			let path = ctype.get_exec_path()?;
+			let mut child = Command::new(path).arg("-d").stdin(Stdio::from(f)).stdout(Stdio::piped()).spawn()
+				.map_err(|e| Error::new(ErrorKind::Other, format!("Error executing pipe command '{} -d': {}", path.display(), e)))?;
+			let stdout = child.stdout.take().expect("pipe problem");
+			Ok((Box::new(BufReader::new(stdout)), Some(child)))
+		},
+	}
+}
+
 pub fn get_reader<P: AsRef<Path>>(name: Option<P>) -> Result<Box<dyn BufRead>> {
     match name {
         Some(file) => open_bufreader(file),
@@ -10,6 +10,7 @@ use std::env;
 
 pub mod compress;
 pub mod log_level;
+pub mod sex_stats;
 
 fn access(p: &Path) -> Result<bool, String> {
 	let cstr = CString::new(p.as_os_str().as_bytes()).map_err(|e| format!("access(): error converting {}: {}", p.display(), e))?;
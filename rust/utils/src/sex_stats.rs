@@ -0,0 +1,70 @@
+use serde::{Serialize, Deserialize};
+
+// Contig names with or without a chr prefix.  Only contigs with numeric names are taken as autosomes,
+// so spike-ins, unplaced and random contigs are excluded
+pub fn strip_chr(ctg: &str) -> &str { ctg.strip_prefix("chr").unwrap_or(ctg) }
+pub fn is_autosome(ctg: &str) -> bool { strip_chr(ctg).parse::<usize>().is_ok() }
+
+// Per contig counts used to calculate the sex inference statistics
+#[derive(Clone, Copy, Default)]
+pub struct SexCoverage {
+	pub callable: usize,
+	pub depth: usize,
+	pub het_snps: usize,
+	pub hom_alt_snps: usize,
+}
+
+impl SexCoverage {
+	fn add(&mut self, other: &Self) {
+		self.callable += other.callable;
+		self.depth += other.depth;
+		self.het_snps += other.het_snps;
+		self.hom_alt_snps += other.hom_alt_snps;
+	}
+	fn depth(&self) -> Option<f64> { if self.callable > 0 { Some((self.depth as f64) / (self.callable as f64)) } else { None } }
+	fn het_rate(&self) -> Option<f64> { 
+		let n = self.het_snps + self.hom_alt_snps;
+		if n > 0 { Some((self.het_snps as f64) / (n as f64)) } else { None }
+	}
+}
+
+// Coverage of the X and Y chromosomes relative to the autosomes, and heterozygosity on X and the autosomes.  
+// Calculated by bs_call and used by gemBS to infer the sex of the sample
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SexStats {
+	pub autosome_depth: f64,
+	#[serde(rename = "XCoverageRatio")]
+	pub x_ratio: Option<f64>,
+	#[serde(rename = "YCoverageRatio")]
+	pub y_ratio: Option<f64>,
+	pub autosome_het_rate: Option<f64>,
+	#[serde(rename = "XHetRate")]
+	pub x_het_rate: Option<f64>,
+	#[serde(rename = "XSNPs")]
+	pub x_snps: usize,
+}
+
+impl SexStats {
+	// Returns None if there is no coverage information for the autosomes
+	pub fn from_contig_coverage<'a, I: IntoIterator<Item = (&'a str, SexCoverage)>>(cov: I) -> Option<Self> {
+		let (mut auto, mut x, mut y) = (SexCoverage::default(), SexCoverage::default(), SexCoverage::default());
+		for (ctg, c) in cov {
+			match strip_chr(ctg) {
+				"X" => x.add(&c),
+				"Y" => y.add(&c),
+				s if is_autosome(s) => auto.add(&c),
+				_ => (),
+			}
+		}
+		let autosome_depth = auto.depth().filter(|z| *z > 0.0)?;
+		Some(Self {
+			autosome_depth, 
+			x_ratio: x.depth().map(|z| z / autosome_depth),
+			y_ratio: y.depth().map(|z| z / autosome_depth),
+			autosome_het_rate: auto.het_rate(),
+			x_het_rate: x.het_rate(),
+			x_snps: x.het_snps + x.hom_alt_snps,
+		})
+	}
+}